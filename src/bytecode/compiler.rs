@@ -0,0 +1,1083 @@
+//! Compiles checked programs into bytecode.
+//!
+//! Every expression leaves exactly one value on the stack, `void` ones included, and statements
+//! leave the stack as they found it. The compiler tracks the height of the stack so that `break`
+//! and `continue` can pop the temporaries of the expressions they leave.
+
+use super::{CompileError, Function, Instr, Location, Module, NumTy, ScalarTy};
+use crate::{
+    checker::typed::{
+        Arm, Block, Builtin, Closure, Expr, ExprKind, Fn, IntTy, Item, Literal, Loop, Pattern,
+        Program, Statement, TraitMethod, Type,
+    },
+    interp::round,
+    lexer::token::Operator,
+    parser::item::UnaryOperator,
+    util::{Lines, Span, Spanned},
+};
+use std::{collections::HashMap, rc::Rc};
+
+/// Compiles the checked `program` read from `path` into a module.
+pub fn compile(
+    program: &Program,
+    source: &str,
+    path: &str,
+) -> Result<Module, Spanned<CompileError>> {
+    let mut compiler = Compiler {
+        module: Module {
+            path: path.to_owned(),
+            strings: Vec::new(),
+            functions: Vec::new(),
+            vtables: Vec::new(),
+            main: 0,
+        },
+        fns: HashMap::new(),
+        fields: HashMap::new(),
+        variants: HashMap::new(),
+        traits: HashMap::new(),
+        strings: HashMap::new(),
+        vtables: HashMap::new(),
+        lines: Lines::new(source),
+    };
+
+    // Functions are numbered before any of them is compiled, so calls can refer to later ones.
+    let mut fns = Vec::new();
+    for item in program.items.iter() {
+        let methods = match &item.value {
+            Item::Fn(f) => {
+                compiler.declare(f);
+                fns.push(f);
+                continue;
+            }
+            Item::Struct(s) => {
+                compiler.fields.insert(&s.ident.value, &s.fields);
+                &s.methods
+            }
+            Item::Enum(e) => {
+                compiler.variants.insert(&e.ident.value, &e.variants);
+                &e.methods
+            }
+            Item::Trait(t) => {
+                compiler.traits.insert(&t.ident.value, &t.methods);
+                continue;
+            }
+            Item::Extern(e) => {
+                let error = CompileError::Extern(e.ident.value.clone());
+                return Err(error.span(e.ident.range()));
+            }
+        };
+
+        for method in methods.iter() {
+            compiler.declare(method);
+            fns.push(method);
+        }
+    }
+
+    compiler.module.main = compiler.fns["main"];
+    for f in fns {
+        let index = compiler.fns[f.ident.value.as_str()];
+        compiler.module.functions[index as usize] = compiler.compile_fn(f, &[]);
+    }
+
+    Ok(compiler.module)
+}
+
+struct Compiler<'a> {
+    module: Module,
+    /// Indices of the top-level functions and methods by their path.
+    fns: HashMap<&'a str, u32>,
+    /// Fields of every struct in declaration order.
+    fields: HashMap<&'a str, &'a [(String, Type)]>,
+    /// Variants of every enum with the types of their payloads.
+    variants: HashMap<&'a str, &'a [(String, Vec<Type>)]>,
+    traits: HashMap<&'a str, &'a [TraitMethod]>,
+    /// Indices of the string literals already in the module.
+    strings: HashMap<&'a str, u32>,
+    /// Indices of the vtables by their trait and type.
+    vtables: HashMap<(&'a str, String), u32>,
+    lines: Lines,
+}
+
+/// The code of the function being compiled.
+#[derive(Default)]
+struct Body<'a> {
+    code: Vec<Instr>,
+    /// Slots of the locals, which are unique within a function.
+    slots: HashMap<&'a str, u32>,
+    locals: u32,
+    /// Number of temporaries on the stack above the locals.
+    height: i64,
+    loops: Vec<Labels>,
+}
+
+/// The jumps leaving a loop which are patched once its end is known.
+struct Labels {
+    /// Height of the stack in the loop, which `break` and `continue` pop the stack down to.
+    height: i64,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+impl<'a> Body<'a> {
+    /// Returns the slot of the local `ident`, declaring it if it's new.
+    fn slot(&mut self, ident: &'a str) -> u32 {
+        if let Some(slot) = self.slots.get(ident) {
+            return *slot;
+        }
+
+        let slot = self.temp();
+        self.slots.insert(ident, slot);
+        slot
+    }
+
+    /// Returns a new slot for a value the compiler stores.
+    fn temp(&mut self) -> u32 {
+        self.locals += 1;
+        self.locals - 1
+    }
+
+    /// Points the jump at `index` to the next instruction.
+    fn patch(&mut self, index: usize) {
+        let target = self.code.len() as u32;
+        match &mut self.code[index] {
+            Instr::Jump(to)
+            | Instr::JumpIfFalse(to)
+            | Instr::JumpIfTrue(to)
+            | Instr::JumpIfNone(to) => *to = target,
+            _ => unreachable!("Only jumps are patched."),
+        }
+    }
+}
+
+impl<'a> Compiler<'a> {
+    /// Numbers the function `f`, whose code is compiled later.
+    fn declare(&mut self, f: &'a Fn) {
+        let arity = (f.args.len() + f.receiver.is_some() as usize) as u32;
+        self.fns
+            .insert(&f.ident.value, self.module.functions.len() as u32);
+        self.module.functions.push(Function {
+            name: f.ident.value.clone(),
+            arity,
+            locals: arity,
+            code: Vec::new(),
+        });
+    }
+
+    /// Compiles `f`, the values of `captures` follow its arguments if it's a closure.
+    fn compile_fn(&mut self, f: &'a Fn, captures: &'a [(String, Type)]) -> Function {
+        let mut body = Body::default();
+        if f.receiver.is_some() {
+            body.temp();
+        }
+
+        for (ident, _) in f.args.iter().chain(captures) {
+            body.slot(ident);
+        }
+
+        self.block(&f.block, &mut body);
+        self.emit(Instr::Return, &mut body);
+        Function {
+            name: f.ident.value.clone(),
+            arity: (f.args.len() + f.receiver.is_some() as usize) as u32,
+            locals: body.locals,
+            code: body.code,
+        }
+    }
+
+    /// Appends `instr` and keeps track of the height of the stack, returns its index.
+    fn emit(&self, instr: Instr, body: &mut Body<'a>) -> usize {
+        let effect = match instr {
+            Instr::Void
+            | Instr::Bool(_)
+            | Instr::Int(_)
+            | Instr::Float(_)
+            | Instr::String(_)
+            | Instr::None
+            | Instr::Dup
+            | Instr::GetLocal(_)
+            | Instr::LocalAddr(_)
+            | Instr::Function(_)
+            | Instr::ReadLine => 1,
+            Instr::Pop
+            | Instr::SetLocal(_)
+            | Instr::IndexAddr(_)
+            | Instr::ListAddr(_)
+            | Instr::Index(_)
+            | Instr::Add(_)
+            | Instr::Sub(_)
+            | Instr::Mul(_)
+            | Instr::Div(_)
+            | Instr::Rem(_)
+            | Instr::Equal
+            | Instr::NotEqual
+            | Instr::Less
+            | Instr::LessEqual
+            | Instr::Greater
+            | Instr::GreaterEqual
+            | Instr::JumpIfFalse(_)
+            | Instr::JumpIfTrue(_)
+            | Instr::Return
+            | Instr::Print
+            | Instr::Println
+            | Instr::Eprintln
+            | Instr::Panic(_) => -1,
+            Instr::Store | Instr::ListPush => -2,
+            Instr::Struct(len)
+            | Instr::Variant { len, .. }
+            | Instr::Array(len)
+            | Instr::Join(len)
+            | Instr::Closure { captures: len, .. } => 1 - len as i64,
+            Instr::Slice { start, end, .. } => -(start as i64) - end as i64,
+            Instr::Call(f) => 1 - self.module.functions[f as usize].arity as i64,
+            Instr::CallDyn { args, .. } | Instr::CallValue(args) => -(args as i64),
+            _ => 0,
+        };
+
+        body.height += effect;
+        body.code.push(instr);
+        body.code.len() - 1
+    }
+
+    fn block(&mut self, block: &'a Block, body: &mut Body<'a>) {
+        for statement in block.statements.iter() {
+            self.statement(statement, body);
+        }
+
+        match &block.trailing_expr {
+            Some(expr) => self.expr(expr, body),
+            None => _ = self.emit(Instr::Void, body),
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement, body: &mut Body<'a>) {
+        match statement {
+            Statement::VariableInit { ident, expr } => {
+                self.expr(expr, body);
+                let slot = body.slot(ident);
+                self.emit(Instr::SetLocal(slot), body);
+            }
+            Statement::Expr(expr) => {
+                self.expr(expr, body);
+                self.emit(Instr::Pop, body);
+            }
+            Statement::Assign {
+                place,
+                operator,
+                expr,
+            } => self.assign(place, *operator, expr, body),
+            Statement::For { kind, block } => self.run_loop(kind, block, body),
+            Statement::Break(depth) | Statement::Continue(depth) => {
+                let labels = &body.loops[body.loops.len() - 1 - depth];
+                let (height, pops) = (body.height, body.height - labels.height);
+                for _ in 0..pops {
+                    self.emit(Instr::Pop, body);
+                }
+
+                let jump = self.emit(Instr::Jump(0), body);
+                let labels = body.loops.len() - 1 - depth;
+                match statement {
+                    Statement::Break(_) => body.loops[labels].breaks.push(jump),
+                    _ => body.loops[labels].continues.push(jump),
+                }
+
+                // The code after the jump is unreachable, but has to agree with the code before.
+                body.height = height;
+            }
+            Statement::Return(expr) => {
+                match expr {
+                    Some(expr) => self.expr(expr, body),
+                    None => _ = self.emit(Instr::Void, body),
+                }
+
+                let height = body.height;
+                self.emit(Instr::Return, body);
+                body.height = height - 1;
+            }
+        }
+    }
+
+    /// Compiles an assignment, the new value is evaluated first since it may read the old one.
+    fn assign(
+        &mut self,
+        place: &'a Expr,
+        operator: Option<Operator>,
+        expr: &'a Expr,
+        body: &mut Body<'a>,
+    ) {
+        self.expr(expr, body);
+        if let ExprKind::Local(ident) = &place.kind {
+            let slot = body.slot(ident);
+            // Locals are updated in their slot, pointers to them would be allocated.
+            if let Some(operator) = operator {
+                let value = body.temp();
+                self.emit(Instr::SetLocal(value), body);
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::GetLocal(value), body);
+                self.binary(operator, &place.ty, body);
+            }
+
+            self.emit(Instr::SetLocal(slot), body);
+            return;
+        }
+
+        let value = body.temp();
+        self.emit(Instr::SetLocal(value), body);
+        self.place(place, body);
+        if let Some(operator) = operator {
+            self.emit(Instr::Dup, body);
+            self.emit(Instr::Load, body);
+            self.emit(Instr::GetLocal(value), body);
+            self.binary(operator, &place.ty, body);
+        } else {
+            self.emit(Instr::GetLocal(value), body);
+        }
+
+        self.emit(Instr::Store, body);
+    }
+
+    fn run_loop(&mut self, kind: &'a Loop, block: &'a Block, body: &mut Body<'a>) {
+        let mut labels = Labels {
+            height: body.height,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        };
+
+        // Loops over ranges count in a slot of their own, the body can't affect the iteration.
+        let counter = match kind {
+            Loop::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let (counter, end_slot) = (body.temp(), body.temp());
+                self.expr(start, body);
+                self.emit(Instr::SetLocal(counter), body);
+                self.expr(end, body);
+                if *inclusive {
+                    self.emit(Instr::Int(1), body);
+                    self.emit(Instr::Add(NumTy::Int(IntTy::Int)), body);
+                }
+
+                self.emit(Instr::SetLocal(end_slot), body);
+                Some((counter, end_slot))
+            }
+            _ => None,
+        };
+
+        let start = body.code.len() as u32;
+        let exit = match kind {
+            Loop::Infinite => None,
+            Loop::While(condition) => {
+                self.expr(condition, body);
+                Some(self.emit(Instr::JumpIfFalse(0), body))
+            }
+            Loop::Range { ident, .. } => {
+                let (counter, end) = counter.expect("Ranges have a counter.");
+                self.emit(Instr::GetLocal(counter), body);
+                self.emit(Instr::GetLocal(end), body);
+                self.emit(Instr::Less, body);
+                let exit = self.emit(Instr::JumpIfFalse(0), body);
+                self.emit(Instr::GetLocal(counter), body);
+                let slot = body.slot(ident);
+                self.emit(Instr::SetLocal(slot), body);
+                Some(exit)
+            }
+            Loop::Optional { ident, expr } => {
+                self.expr(expr, body);
+                let exit = self.emit(Instr::JumpIfNone(0), body);
+                self.emit(Instr::Unwrap, body);
+                let slot = body.slot(ident);
+                self.emit(Instr::SetLocal(slot), body);
+                Some(exit)
+            }
+        };
+
+        body.loops.push(labels);
+        self.block(block, body);
+        self.emit(Instr::Pop, body);
+        labels = body.loops.pop().expect("The loop was pushed above.");
+
+        for jump in labels.continues {
+            body.patch(jump);
+        }
+
+        if let Some((counter, _)) = counter {
+            self.emit(Instr::GetLocal(counter), body);
+            self.emit(Instr::Int(1), body);
+            self.emit(Instr::Add(NumTy::Int(IntTy::Int)), body);
+            self.emit(Instr::SetLocal(counter), body);
+        }
+
+        self.emit(Instr::Jump(start), body);
+        if let Some(exit) = exit {
+            body.patch(exit);
+        }
+
+        // The optional without a value is left on the stack by the jump out of the loop.
+        if let Loop::Optional { .. } = kind {
+            self.emit(Instr::Pop, body);
+        }
+
+        for jump in labels.breaks {
+            body.patch(jump);
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr, body: &mut Body<'a>) {
+        match &expr.kind {
+            ExprKind::Local(ident) => {
+                let slot = body.slot(ident);
+                self.emit(Instr::GetLocal(slot), body);
+            }
+            ExprKind::Receiver => {
+                self.emit(Instr::GetLocal(0), body);
+                // The receiver is passed as a pointer, which references to it are.
+                if !matches!(expr.ty, Type::Reference(_)) {
+                    self.emit(Instr::Load, body);
+                }
+            }
+            // Only the field or element is copied out of its place.
+            ExprKind::Field { .. } | ExprKind::Index { .. } if expr.is_place() => {
+                self.place(expr, body);
+                self.emit(Instr::Load, body);
+            }
+            ExprKind::Field { expr: inner, ident } => {
+                let index = self.field(&inner.ty, ident);
+                self.expr(inner, body);
+                if let Type::Reference(_) = inner.ty {
+                    self.emit(Instr::RefAddr, body);
+                    self.emit(Instr::FieldAddr(index), body);
+                    self.emit(Instr::Load, body);
+                } else {
+                    self.emit(Instr::Field(index), body);
+                }
+            }
+            ExprKind::MethodCall {
+                ident,
+                receiver,
+                args,
+            } => self.method_call(ident, receiver, args, body),
+            ExprKind::Literal(literal) => self.literal(literal, &expr.ty, body),
+            ExprKind::None => _ = self.emit(Instr::None, body),
+            ExprKind::Struct { fields, .. } => {
+                self.exprs(fields, body);
+                self.emit(Instr::Struct(fields.len() as u32), body);
+            }
+            ExprKind::Variant {
+                ident,
+                variant,
+                args,
+            } => {
+                self.exprs(args, body);
+                let tag = self.variants[ident.as_str()]
+                    .iter()
+                    .position(|(ident, _)| ident == variant)
+                    .expect("The checker ensures variants exist.");
+
+                let len = args.len() as u32;
+                self.emit(
+                    Instr::Variant {
+                        tag: tag as u32,
+                        len,
+                    },
+                    body,
+                );
+            }
+            ExprKind::Call { ident, args } => {
+                self.exprs(args, body);
+                self.emit(Instr::Call(self.fns[ident.as_str()]), body);
+            }
+            ExprKind::Unary {
+                operator,
+                expr: inner,
+            } => {
+                self.expr(inner, body);
+                let instr = match operator {
+                    UnaryOperator::Negate => Instr::Neg(num_ty(&expr.ty)),
+                    UnaryOperator::Not => Instr::Not,
+                    UnaryOperator::Reference => Instr::Ref,
+                };
+
+                self.emit(instr, body);
+            }
+            ExprKind::Binary {
+                left,
+                operator: operator @ (Operator::And | Operator::Or),
+                right,
+            } => {
+                // The left operand is the result if it decides it, otherwise the right one is.
+                self.expr(left, body);
+                self.emit(Instr::Dup, body);
+                let jump = match operator {
+                    Operator::And => self.emit(Instr::JumpIfFalse(0), body),
+                    _ => self.emit(Instr::JumpIfTrue(0), body),
+                };
+
+                self.emit(Instr::Pop, body);
+                self.expr(right, body);
+                body.patch(jump);
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expr(left, body);
+                self.expr(right, body);
+                self.binary(*operator, &left.ty, body);
+            }
+            ExprKind::Cast(inner) => {
+                self.expr(inner, body);
+                self.cast(&inner.ty, &expr.ty, body);
+            }
+            ExprKind::CallValue { expr: callee, args } => {
+                self.expr(callee, body);
+                self.exprs(args, body);
+                self.emit(Instr::CallValue(args.len() as u32), body);
+            }
+            ExprKind::Function(ident) => {
+                self.emit(Instr::Function(self.fns[ident.as_str()]), body);
+            }
+            ExprKind::Closure(closure) => self.closure(closure, body),
+            ExprKind::Dyn(inner) => {
+                let Type::Dyn(trait_) = &expr.ty else {
+                    unreachable!("Dyn values have dyn types.")
+                };
+
+                self.expr(inner, body);
+                let vtable = self.vtable(trait_, inner.ty.deref());
+                self.emit(Instr::Dyn(vtable), body);
+            }
+            ExprKind::If {
+                condition,
+                block,
+                else_block,
+            } => {
+                self.expr(condition, body);
+                let otherwise = self.emit(Instr::JumpIfFalse(0), body);
+                let height = body.height;
+                self.block(block, body);
+                let end = self.emit(Instr::Jump(0), body);
+                body.height = height;
+                body.patch(otherwise);
+                match else_block {
+                    Some(else_block) => self.block(else_block, body),
+                    None => _ = self.emit(Instr::Void, body),
+                }
+
+                body.patch(end);
+            }
+            ExprKind::Array(exprs) => {
+                self.exprs(exprs, body);
+                self.emit(Instr::Array(exprs.len() as u32), body);
+            }
+            ExprKind::Repeat { expr: inner, len } => {
+                self.expr(inner, body);
+                self.emit(Instr::Repeat(*len as u32), body);
+            }
+            ExprKind::Index {
+                expr: inner,
+                index,
+                location,
+            } => {
+                self.value(inner, body);
+                self.expr(index, body);
+                self.emit(Instr::Index(self.location(*location)), body);
+            }
+            ExprKind::Slice {
+                expr: inner,
+                start,
+                end,
+                inclusive,
+                location,
+            } => {
+                self.value(inner, body);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound, body);
+                }
+
+                let instr = Instr::Slice {
+                    start: start.is_some(),
+                    end: end.is_some(),
+                    inclusive: *inclusive,
+                    location: self.location(*location),
+                };
+
+                self.emit(instr, body);
+            }
+            ExprKind::Len(inner) => {
+                self.value(inner, body);
+                self.emit(Instr::Len, body);
+            }
+            ExprKind::Format(parts) => {
+                self.exprs(parts, body);
+                self.emit(Instr::Join(parts.len() as u32), body);
+            }
+            ExprKind::ToString(inner) => {
+                self.expr(inner, body);
+                self.emit(Instr::ToString(scalar_ty(&inner.ty)), body);
+            }
+            ExprKind::Builtin {
+                builtin,
+                args,
+                location,
+            } => self.builtin(*builtin, args, *location, body),
+            ExprKind::Match { expr: inner, arms } => self.match_expr(inner, arms, body),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &'a [Expr], body: &mut Body<'a>) {
+        for expr in exprs {
+            self.expr(expr, body);
+        }
+    }
+
+    /// Compiles `expr` and dereferences it if it's a reference.
+    fn value(&mut self, expr: &'a Expr, body: &mut Body<'a>) {
+        self.expr(expr, body);
+        if let Type::Reference(_) = expr.ty {
+            self.emit(Instr::Deref, body);
+        }
+    }
+
+    /// Pushes a pointer to the place `expr` refers to, which the checker ensures it is.
+    fn place(&mut self, expr: &'a Expr, body: &mut Body<'a>) {
+        match &expr.kind {
+            ExprKind::Local(ident) => {
+                let slot = body.slot(ident);
+                self.emit(Instr::LocalAddr(slot), body);
+            }
+            ExprKind::Receiver => _ = self.emit(Instr::GetLocal(0), body),
+            ExprKind::Field { expr: inner, ident } => {
+                let index = self.field(&inner.ty, ident);
+                self.target(inner, body);
+                self.emit(Instr::FieldAddr(index), body);
+            }
+            ExprKind::Index {
+                expr: inner,
+                index,
+                location,
+            } => {
+                let location = self.location(*location);
+                if let Type::List(_) = inner.ty.deref() {
+                    self.value(inner, body);
+                    self.expr(index, body);
+                    self.emit(Instr::ListAddr(location), body);
+                } else {
+                    self.target(inner, body);
+                    self.expr(index, body);
+                    self.emit(Instr::IndexAddr(location), body);
+                }
+            }
+            _ => unreachable!("The checker only assigns to places."),
+        }
+    }
+
+    /// Pushes a pointer to the value `expr` refers to, which is behind it if it's a reference.
+    fn target(&mut self, expr: &'a Expr, body: &mut Body<'a>) {
+        match expr.ty {
+            Type::Reference(_) => {
+                self.expr(expr, body);
+                self.emit(Instr::RefAddr, body);
+            }
+            _ => self.place(expr, body),
+        }
+    }
+
+    /// Compiles a call of the method at `path`, whose receiver is passed as a pointer.
+    /// Temporaries are moved to the heap to point to them.
+    fn method_call(
+        &mut self,
+        path: &'a str,
+        receiver: &'a Expr,
+        args: &'a [Expr],
+        body: &mut Body<'a>,
+    ) {
+        if let Type::Dyn(trait_) = receiver.ty.deref() {
+            self.value(receiver, body);
+            self.exprs(args, body);
+            let (_, ident) = path.rsplit_once('.').expect("Trait methods have a path.");
+            let method = self.traits[trait_.as_str()]
+                .iter()
+                .position(|method| method.ident == ident)
+                .expect("The checker ensures trait methods exist.");
+
+            let args = args.len() as u32;
+            self.emit(
+                Instr::CallDyn {
+                    method: method as u32,
+                    args,
+                },
+                body,
+            );
+            return;
+        }
+
+        match (&receiver.ty, receiver.is_place()) {
+            (Type::Reference(_), _) | (_, true) => self.target(receiver, body),
+            (_, false) => {
+                self.expr(receiver, body);
+                self.emit(Instr::Ref, body);
+                self.emit(Instr::RefAddr, body);
+            }
+        }
+
+        self.exprs(args, body);
+        if let Type::List(_) = receiver.ty.deref() {
+            match path {
+                "push" => {
+                    self.emit(Instr::ListPush, body);
+                    self.emit(Instr::Void, body);
+                }
+                "pop" => _ = self.emit(Instr::ListPop, body),
+                _ => unreachable!("Lists only have the methods push and pop."),
+            }
+
+            return;
+        }
+
+        self.emit(Instr::Call(self.fns[path]), body);
+    }
+
+    fn closure(&mut self, closure: &'a Closure, body: &mut Body<'a>) {
+        let index = self.module.functions.len() as u32;
+        self.declare(&closure.f);
+        let function = self.compile_fn(&closure.f, &closure.captures);
+        self.module.functions[index as usize] = function;
+
+        for (ident, _) in closure.captures.iter() {
+            let slot = body.slot(ident);
+            self.emit(Instr::GetLocal(slot), body);
+        }
+
+        let captures = closure.captures.len() as u32;
+        self.emit(
+            Instr::Closure {
+                function: index,
+                captures,
+            },
+            body,
+        );
+    }
+
+    fn builtin(
+        &mut self,
+        builtin: Builtin,
+        args: &'a [Expr],
+        location: usize,
+        body: &mut Body<'a>,
+    ) {
+        match builtin {
+            Builtin::Print | Builtin::Println | Builtin::Eprintln => {
+                match args.first() {
+                    Some(arg) => self.expr(arg, body),
+                    None => self.string("", body),
+                }
+
+                let instr = match builtin {
+                    Builtin::Print => Instr::Print,
+                    Builtin::Println => Instr::Println,
+                    _ => Instr::Eprintln,
+                };
+
+                self.emit(instr, body);
+            }
+            Builtin::Panic => {
+                match args.first() {
+                    Some(message) => self.expr(message, body),
+                    None => self.string("Panicked.", body),
+                }
+
+                self.emit(Instr::Panic(self.location(location)), body);
+            }
+            Builtin::Assert => {
+                self.expr(&args[0], body);
+                let jump = self.emit(Instr::JumpIfTrue(0), body);
+                match args.get(1) {
+                    Some(message) => self.expr(message, body),
+                    None => self.string("Assertion failed.", body),
+                }
+
+                self.emit(Instr::Panic(self.location(location)), body);
+                body.patch(jump);
+            }
+            Builtin::ReadLine => {
+                self.emit(Instr::ReadLine, body);
+                return;
+            }
+        }
+
+        self.emit(Instr::Void, body);
+    }
+
+    /// Compiles a match, the value is stored in a slot which the patterns test.
+    fn match_expr(&mut self, expr: &'a Expr, arms: &'a [Arm], body: &mut Body<'a>) {
+        self.expr(expr, body);
+        let slot = body.temp();
+        self.emit(Instr::SetLocal(slot), body);
+
+        let (height, mut ends) = (body.height, Vec::new());
+        for arm in arms.iter() {
+            let mut fails = Vec::new();
+            self.pattern(&arm.pattern, slot, &expr.ty, &mut fails, body);
+            self.block(&arm.block, body);
+            ends.push(self.emit(Instr::Jump(0), body));
+            body.height = height;
+            for jump in fails {
+                body.patch(jump);
+            }
+        }
+
+        // The checker ensures a pattern matches, so this is never reached.
+        self.emit(Instr::Void, body);
+        for jump in ends {
+            body.patch(jump);
+        }
+    }
+
+    /// Compiles the test of `pattern` against the value of type `ty` in `slot`, binding its
+    /// locals. The jumps taken if it doesn't match are collected into `fails`.
+    fn pattern(
+        &mut self,
+        pattern: &'a Pattern,
+        slot: u32,
+        ty: &Type,
+        fails: &mut Vec<usize>,
+        body: &mut Body<'a>,
+    ) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(ident) => {
+                self.emit(Instr::GetLocal(slot), body);
+                let binding = body.slot(ident);
+                self.emit(Instr::SetLocal(binding), body);
+            }
+            Pattern::Literal(literal) => {
+                self.emit(Instr::GetLocal(slot), body);
+                self.literal(literal, ty, body);
+                self.emit(Instr::Equal, body);
+                fails.push(self.emit(Instr::JumpIfFalse(0), body));
+            }
+            Pattern::None => {
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::IsSome, body);
+                fails.push(self.emit(Instr::JumpIfTrue(0), body));
+            }
+            Pattern::Some(inner) => {
+                let Type::Optional(ty) = ty else {
+                    unreachable!("The checker ensures patterns match their types.")
+                };
+
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::IsSome, body);
+                fails.push(self.emit(Instr::JumpIfFalse(0), body));
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::Unwrap, body);
+                self.subpattern(inner, ty, fails, body);
+            }
+            Pattern::Deref(inner) => {
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::Deref, body);
+                self.subpattern(inner, ty.deref(), fails, body);
+            }
+            Pattern::Variant {
+                ident,
+                variant,
+                fields,
+            } => {
+                let variants = self.variants[ident.as_str()];
+                let tag = variants
+                    .iter()
+                    .position(|(ident, _)| ident == variant)
+                    .expect("The checker ensures variants exist.");
+
+                self.emit(Instr::GetLocal(slot), body);
+                self.emit(Instr::IsVariant(tag as u32), body);
+                fails.push(self.emit(Instr::JumpIfFalse(0), body));
+                for (index, (field, ty)) in fields.iter().zip(&variants[tag].1).enumerate() {
+                    if let Pattern::Wildcard = field {
+                        continue;
+                    }
+
+                    self.emit(Instr::GetLocal(slot), body);
+                    self.emit(Instr::Field(index as u32), body);
+                    self.subpattern(field, ty, fails, body);
+                }
+            }
+            Pattern::Struct { ident, fields } => {
+                let types = self.fields[ident.as_str()];
+                for (index, (field, (_, ty))) in fields.iter().zip(types).enumerate() {
+                    if let Pattern::Wildcard = field {
+                        continue;
+                    }
+
+                    self.emit(Instr::GetLocal(slot), body);
+                    self.emit(Instr::Field(index as u32), body);
+                    self.subpattern(field, ty, fails, body);
+                }
+            }
+        }
+    }
+
+    /// Compiles the test of `pattern` against the value on top of the stack, which is popped.
+    fn subpattern(
+        &mut self,
+        pattern: &'a Pattern,
+        ty: &Type,
+        fails: &mut Vec<usize>,
+        body: &mut Body<'a>,
+    ) {
+        let slot = match pattern {
+            Pattern::Binding(ident) => body.slot(ident),
+            _ => body.temp(),
+        };
+
+        self.emit(Instr::SetLocal(slot), body);
+        if !matches!(pattern, Pattern::Binding(_)) {
+            self.pattern(pattern, slot, ty, fails, body);
+        }
+    }
+
+    /// Compiles the operator applied to the two operands of type `ty` on top of the stack.
+    fn binary(&mut self, operator: Operator, ty: &Type, body: &mut Body<'a>) {
+        let instr = match operator {
+            Operator::Plus if *ty == Type::String => Instr::Join(2),
+            Operator::Plus => Instr::Add(num_ty(ty)),
+            Operator::Minus => Instr::Sub(num_ty(ty)),
+            Operator::Star => Instr::Mul(num_ty(ty)),
+            Operator::Slash => Instr::Div(num_ty(ty)),
+            Operator::Percent => Instr::Rem(num_ty(ty)),
+            Operator::Equal => Instr::Equal,
+            Operator::NotEqual => Instr::NotEqual,
+            Operator::Less => Instr::Less,
+            Operator::LessEqual => Instr::LessEqual,
+            Operator::Greater => Instr::Greater,
+            Operator::GreaterEqual => Instr::GreaterEqual,
+            Operator::And | Operator::Or => unreachable!("Logical operators are lazy."),
+        };
+
+        self.emit(instr, body);
+    }
+
+    /// Compiles the conversion of the value on top of the stack from `from` into `to`, like a
+    /// cast in the generated `C`.
+    fn cast(&mut self, from: &Type, to: &Type, body: &mut Body<'a>) {
+        if from == to {
+            return;
+        }
+
+        match (from, to) {
+            (Type::Optional(from), Type::Optional(to)) => {
+                let jump = self.emit(Instr::JumpIfNone(0), body);
+                self.emit(Instr::Unwrap, body);
+                self.cast(from, to, body);
+                self.emit(Instr::Some, body);
+                body.patch(jump);
+            }
+            (Type::Optional(from), to) => {
+                self.emit(Instr::Unwrap, body);
+                self.cast(from, to, body);
+            }
+            (from, Type::Optional(to)) => {
+                self.cast(from, to, body);
+                self.emit(Instr::Some, body);
+            }
+            (Type::Array(..), Type::List(_)) => _ = self.emit(Instr::ToList, body),
+            (from, Type::String) => _ = self.emit(Instr::ToString(scalar_ty(from)), body),
+            (Type::Int(_), Type::Int(ty)) => _ = self.emit(Instr::Wrap(*ty), body),
+            (Type::Int(_), Type::Float(ty)) => _ = self.emit(Instr::IntToFloat(*ty), body),
+            (Type::Float(_), Type::Int(ty)) => _ = self.emit(Instr::FloatToInt(*ty), body),
+            (Type::Float(_), Type::Float(ty)) => _ = self.emit(Instr::FloatToFloat(*ty), body),
+            (Type::Bool, Type::Int(_)) => _ = self.emit(Instr::BoolToInt, body),
+            (Type::Reference(_), Type::Int(_)) => _ = self.emit(Instr::Address, body),
+            _ => {}
+        }
+    }
+
+    /// Compiles `literal` whose type is `ty`, integer literals may be floats.
+    fn literal(&mut self, literal: &'a Literal, ty: &Type, body: &mut Body<'a>) {
+        let instr = match (literal, ty) {
+            (Literal::Int(value), Type::Float(ty)) => Instr::Float(round(*value as f64, *ty)),
+            (Literal::Int(value), _) => Instr::Int(*value),
+            (Literal::Float(value), Type::Float(ty)) => Instr::Float(round(*value, *ty)),
+            (Literal::Float(value), _) => Instr::Float(*value),
+            (Literal::Bool(value), _) => Instr::Bool(*value),
+            (Literal::String(value), _) => return self.string(value, body),
+        };
+
+        self.emit(instr, body);
+    }
+
+    /// Pushes the string literal `value`, which is stored in the module once.
+    fn string(&mut self, value: &'a str, body: &mut Body<'a>) {
+        let index = match self.strings.get(value) {
+            Some(index) => *index,
+            None => {
+                let index = self.module.strings.len() as u32;
+                self.module.strings.push(Rc::from(value.as_bytes()));
+                self.strings.insert(value, index);
+                index
+            }
+        };
+
+        self.emit(Instr::String(index), body);
+    }
+
+    /// Returns the index of the vtable implementing `trait_` for `ty`, adding it if it's new.
+    fn vtable(&mut self, trait_: &'a str, ty: &Type) -> u32 {
+        let key = (trait_, ty.to_string());
+        if let Some(index) = self.vtables.get(&key) {
+            return *index;
+        }
+
+        let methods = self.traits[trait_]
+            .iter()
+            .map(|method| self.fns[format!("{}.{}", key.1, method.ident).as_str()])
+            .collect();
+
+        let index = self.module.vtables.len() as u32;
+        self.module.vtables.push(methods);
+        self.vtables.insert(key, index);
+        index
+    }
+
+    /// Returns the index of the field `ident` of the struct `ty` or the struct behind it.
+    fn field(&self, ty: &Type, ident: &str) -> u32 {
+        let Type::Struct(name) = ty.deref() else {
+            unreachable!("Only structs have fields.")
+        };
+
+        self.fields[name.as_str()]
+            .iter()
+            .position(|(field, _)| field == ident)
+            .expect("The checker ensures fields exist.") as u32
+    }
+
+    /// Returns the line and column of `offset` in the source.
+    fn location(&self, offset: usize) -> Location {
+        let (line, column) = self.lines.locate(offset);
+        Location {
+            line: line as u32,
+            column: column as u32,
+        }
+    }
+}
+
+fn num_ty(ty: &Type) -> NumTy {
+    match ty {
+        Type::Int(ty) => NumTy::Int(*ty),
+        Type::Float(ty) => NumTy::Float(*ty),
+        _ => unreachable!("The checker ensures arithmetic operands are numbers."),
+    }
+}
+
+/// Returns the type of the value `ty`, or the value in the optional `ty`, converted into a string.
+fn scalar_ty(ty: &Type) -> ScalarTy {
+    match ty {
+        Type::Optional(inner) => scalar_ty(inner),
+        Type::Bool => ScalarTy::Bool,
+        Type::String => ScalarTy::String,
+        ty => ScalarTy::Num(num_ty(ty)),
+    }
+}
@@ -0,0 +1,165 @@
+//! Prints modules as text, one instruction per line.
+
+use super::{format::VERSION, Instr, Location, Module, NumTy, ScalarTy};
+use crate::checker::typed::Type;
+use std::fmt;
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "; bytecode version {VERSION} of {}", self.path)?;
+
+        for (i, vtable) in self.vtables.iter().enumerate() {
+            let functions: Vec<_> = vtable
+                .iter()
+                .map(|function| self.functions[*function as usize].name.as_str())
+                .collect();
+
+            writeln!(f, "vtable {i}: {}", functions.join(", "))?;
+        }
+
+        for (i, function) in self.functions.iter().enumerate() {
+            let main = if i as u32 == self.main { " main" } else { "" };
+            writeln!(
+                f,
+                "\nfn {i} {}{main} (arity {}, locals {})",
+                function.name, function.arity, function.locals
+            )?;
+
+            for (i, instr) in function.code.iter().enumerate() {
+                writeln!(f, "{i:>6}  {}", instr.display(self))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Instr {
+    /// Returns the instruction as text, naming the strings and functions of `module` it refers to.
+    pub fn display<'a>(&'a self, module: &'a Module) -> impl fmt::Display + 'a {
+        Disasm(self, module)
+    }
+}
+
+struct Disasm<'a>(&'a Instr, &'a Module);
+
+impl fmt::Display for Disasm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Disasm(instr, module) = self;
+        let function = |index: u32| match module.functions.get(index as usize) {
+            Some(function) => format!("{index} ({})", function.name),
+            None => index.to_string(),
+        };
+
+        match **instr {
+            Instr::Void => write!(f, "void"),
+            Instr::Bool(value) => write!(f, "bool {value}"),
+            Instr::Int(value) => write!(f, "int {value}"),
+            Instr::Float(value) => write!(f, "float {value:?}"),
+            Instr::String(index) => match module.strings.get(index as usize) {
+                Some(string) => write!(f, "string {:?}", String::from_utf8_lossy(string)),
+                None => write!(f, "string {index}"),
+            },
+            Instr::None => write!(f, "none"),
+            Instr::Pop => write!(f, "pop"),
+            Instr::Dup => write!(f, "dup"),
+            Instr::GetLocal(slot) => write!(f, "get_local {slot}"),
+            Instr::SetLocal(slot) => write!(f, "set_local {slot}"),
+            Instr::LocalAddr(slot) => write!(f, "local_addr {slot}"),
+            Instr::RefAddr => write!(f, "ref_addr"),
+            Instr::FieldAddr(index) => write!(f, "field_addr {index}"),
+            Instr::IndexAddr(location) => write!(f, "index_addr {}", At(location)),
+            Instr::ListAddr(location) => write!(f, "list_addr {}", At(location)),
+            Instr::Load => write!(f, "load"),
+            Instr::Store => write!(f, "store"),
+            Instr::Struct(len) => write!(f, "struct {len}"),
+            Instr::Field(index) => write!(f, "field {index}"),
+            Instr::Variant { tag, len } => write!(f, "variant {tag} {len}"),
+            Instr::IsVariant(tag) => write!(f, "is_variant {tag}"),
+            Instr::Array(len) => write!(f, "array {len}"),
+            Instr::Repeat(len) => write!(f, "repeat {len}"),
+            Instr::ToList => write!(f, "to_list"),
+            Instr::Index(location) => write!(f, "index {}", At(location)),
+            Instr::Slice {
+                start,
+                end,
+                inclusive,
+                location,
+            } => {
+                let start = if start { "start" } else { "_" };
+                let end = if end { "end" } else { "_" };
+                let range = if inclusive { "..=" } else { ".." };
+                write!(f, "slice {start}{range}{end} {}", At(location))
+            }
+            Instr::Len => write!(f, "len"),
+            Instr::Ref => write!(f, "ref"),
+            Instr::Deref => write!(f, "deref"),
+            Instr::Dyn(vtable) => write!(f, "dyn {vtable}"),
+            Instr::Some => write!(f, "some"),
+            Instr::Unwrap => write!(f, "unwrap"),
+            Instr::IsSome => write!(f, "is_some"),
+            Instr::JumpIfNone(target) => write!(f, "jump_if_none {target}"),
+            Instr::Add(ty) => write!(f, "add {}", Ty(ty)),
+            Instr::Sub(ty) => write!(f, "sub {}", Ty(ty)),
+            Instr::Mul(ty) => write!(f, "mul {}", Ty(ty)),
+            Instr::Div(ty) => write!(f, "div {}", Ty(ty)),
+            Instr::Rem(ty) => write!(f, "rem {}", Ty(ty)),
+            Instr::Neg(ty) => write!(f, "neg {}", Ty(ty)),
+            Instr::Not => write!(f, "not"),
+            Instr::Equal => write!(f, "equal"),
+            Instr::NotEqual => write!(f, "not_equal"),
+            Instr::Less => write!(f, "less"),
+            Instr::LessEqual => write!(f, "less_equal"),
+            Instr::Greater => write!(f, "greater"),
+            Instr::GreaterEqual => write!(f, "greater_equal"),
+            Instr::Wrap(ty) => write!(f, "wrap {ty}"),
+            Instr::IntToFloat(ty) => write!(f, "int_to_float {}", Type::Float(ty)),
+            Instr::FloatToInt(ty) => write!(f, "float_to_int {ty}"),
+            Instr::FloatToFloat(ty) => write!(f, "float_to_float {}", Type::Float(ty)),
+            Instr::BoolToInt => write!(f, "bool_to_int"),
+            Instr::Address => write!(f, "address"),
+            Instr::ToString(ScalarTy::Num(ty)) => write!(f, "to_string {}", Ty(ty)),
+            Instr::ToString(ScalarTy::Bool) => write!(f, "to_string bool"),
+            Instr::ToString(ScalarTy::String) => write!(f, "to_string string"),
+            Instr::Join(len) => write!(f, "join {len}"),
+            Instr::Jump(target) => write!(f, "jump {target}"),
+            Instr::JumpIfFalse(target) => write!(f, "jump_if_false {target}"),
+            Instr::JumpIfTrue(target) => write!(f, "jump_if_true {target}"),
+            Instr::Call(index) => write!(f, "call {}", function(index)),
+            Instr::CallDyn { method, args } => write!(f, "call_dyn {method} {args}"),
+            Instr::CallValue(args) => write!(f, "call_value {args}"),
+            Instr::Function(index) => write!(f, "function {}", function(index)),
+            Instr::Closure {
+                function: index,
+                captures,
+            } => write!(f, "closure {} {captures}", function(index)),
+            Instr::Return => write!(f, "return"),
+            Instr::Print => write!(f, "print"),
+            Instr::Println => write!(f, "println"),
+            Instr::Eprintln => write!(f, "eprintln"),
+            Instr::Panic(location) => write!(f, "panic {}", At(location)),
+            Instr::ReadLine => write!(f, "read_line"),
+            Instr::ListPush => write!(f, "list_push"),
+            Instr::ListPop => write!(f, "list_pop"),
+        }
+    }
+}
+
+struct At(Location);
+
+impl fmt::Display for At {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}:{}", self.0.line, self.0.column)
+    }
+}
+
+struct Ty(NumTy);
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            NumTy::Int(ty) => write!(f, "{ty}"),
+            NumTy::Float(ty) => write!(f, "{}", Type::Float(ty)),
+        }
+    }
+}
@@ -0,0 +1,799 @@
+//! The file format of bytecode modules.
+//!
+//! A file starts with the magic bytes `RZBC` and the version of the format as a little-endian
+//! `u16`, followed by the path of the source, the string literals, the vtables, the functions and
+//! the index of `main`. Integers are LEB128 encoded, signed ones zigzag encoded first, and floats
+//! are stored as their little-endian bits. Instructions are an opcode followed by their operands.
+//! Loading checks that the indices in a module refer to what it contains and that the code of
+//! every function keeps the stack balanced and ends in a return, so modules of another version or
+//! corrupted files are rejected before they run.
+
+use super::{Function, Instr, Location, Module, NumTy, ScalarTy};
+use crate::checker::typed::{FloatTy, IntTy};
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"RZBC";
+/// Version of the format, which changes with the instruction set.
+pub const VERSION: u16 = 2;
+
+const INT_TYS: [IntTy; 8] = [
+    IntTy::Int,
+    IntTy::I8,
+    IntTy::I16,
+    IntTy::I32,
+    IntTy::Uint,
+    IntTy::U8,
+    IntTy::U16,
+    IntTy::U32,
+];
+
+const FLOAT_TYS: [FloatTy; 2] = [FloatTy::Float, FloatTy::F32];
+
+#[derive(Debug, Clone)]
+pub enum LoadError {
+    /// A file which doesn't start with the magic bytes.
+    NotBytecode,
+    UnsupportedVersion(u16),
+    /// A file ending in the middle of the module.
+    Truncated,
+    InvalidOpcode(u8),
+    /// An operand, like a type or an index, which doesn't refer to anything in the module.
+    InvalidOperand(String),
+    /// A function whose code pops values it didn't push or reaches an instruction with different
+    /// numbers of values on the stack.
+    UnbalancedStack(String),
+    /// A function whose code runs past its end.
+    MissingReturn(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotBytecode => write!(f, "The file isn't a bytecode module."),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "The module has version {version} of the bytecode format, but only version \
+                 {VERSION} is supported."
+            ),
+            LoadError::Truncated => write!(f, "The module ends unexpectedly."),
+            LoadError::InvalidOpcode(opcode) => write!(f, "{opcode} isn't an opcode."),
+            LoadError::InvalidOperand(instr) => {
+                write!(
+                    f,
+                    "\"{instr}\" refers to something the module doesn't contain."
+                )
+            }
+            LoadError::UnbalancedStack(function) => {
+                write!(
+                    f,
+                    "The code of \"{function}\" doesn't keep the stack balanced."
+                )
+            }
+            LoadError::MissingReturn(function) => {
+                write!(f, "The code of \"{function}\" runs past its end.")
+            }
+        }
+    }
+}
+
+/// Returns whether `bytes` start like a bytecode module.
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Serializes `module` into the bytes of a file.
+pub fn write(module: &Module) -> Vec<u8> {
+    let mut writer = Writer(MAGIC.to_vec());
+    writer.0.extend_from_slice(&VERSION.to_le_bytes());
+    writer.bytes(module.path.as_bytes());
+
+    writer.uint(module.strings.len() as u128);
+    for string in module.strings.iter() {
+        writer.bytes(string);
+    }
+
+    writer.uint(module.vtables.len() as u128);
+    for vtable in module.vtables.iter() {
+        writer.uint(vtable.len() as u128);
+        for f in vtable {
+            writer.uint(*f as u128);
+        }
+    }
+
+    writer.uint(module.functions.len() as u128);
+    for function in module.functions.iter() {
+        writer.bytes(function.name.as_bytes());
+        writer.uint(function.arity as u128);
+        writer.uint(function.locals as u128);
+        writer.uint(function.code.len() as u128);
+        for instr in function.code.iter() {
+            writer.instr(*instr);
+        }
+    }
+
+    writer.uint(module.main as u128);
+    writer.0
+}
+
+/// Loads a module from the bytes of a file.
+pub fn read(bytes: &[u8]) -> Result<Module, LoadError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err(LoadError::NotBytecode);
+    };
+
+    let mut reader = Reader(rest);
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let path = reader.string()?;
+    let strings = (0..reader.len()?)
+        .map(|_| reader.bytes().map(|bytes| bytes.into()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut vtables = Vec::new();
+    for _ in 0..reader.len()? {
+        vtables.push(
+            (0..reader.len()?)
+                .map(|_| reader.u32())
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+    }
+
+    let mut functions = Vec::new();
+    for _ in 0..reader.len()? {
+        let (name, arity, locals) = (reader.string()?, reader.u32()?, reader.u32()?);
+        let code = (0..reader.len()?)
+            .map(|_| reader.instr())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        functions.push(Function {
+            name,
+            arity,
+            locals,
+            code,
+        });
+    }
+
+    let module = Module {
+        path,
+        strings,
+        vtables,
+        main: reader.u32()?,
+        functions,
+    };
+
+    validate(&module)?;
+    Ok(module)
+}
+
+/// Checks that the indices in `module` refer to what it contains and verifies the code of its
+/// functions.
+fn validate(module: &Module) -> Result<(), LoadError> {
+    let functions = module.functions.len() as u32;
+    let invalid = |instr: String| Err(LoadError::InvalidOperand(instr));
+    // `main` takes nothing or the arguments of the program.
+    if module.main >= functions || module.functions[module.main as usize].arity > 1 {
+        return invalid(format!("main {}", module.main));
+    }
+
+    for vtable in module.vtables.iter() {
+        if let Some(f) = vtable.iter().find(|f| **f >= functions) {
+            return invalid(format!("vtable {f}"));
+        }
+    }
+
+    for function in module.functions.iter() {
+        if function.arity > function.locals {
+            return invalid(format!("arity {}", function.arity));
+        }
+
+        let len = function.code.len() as u32;
+        for instr in function.code.iter() {
+            let valid = match *instr {
+                // Integers are at most 64 bits, so arithmetic on them can't overflow.
+                Instr::Int(value) => (i64::MIN as i128..=u64::MAX as i128).contains(&value),
+                Instr::String(index) => (index as usize) < module.strings.len(),
+                Instr::GetLocal(slot) | Instr::SetLocal(slot) | Instr::LocalAddr(slot) => {
+                    slot < function.locals
+                }
+                Instr::Dyn(vtable) => (vtable as usize) < module.vtables.len(),
+                Instr::Call(f) | Instr::Function(f) => f < functions,
+                // Captures follow the arguments in the locals of closures.
+                Instr::Closure { function, captures } => module
+                    .functions
+                    .get(function as usize)
+                    .is_some_and(|f| f.arity as u64 + captures as u64 <= f.locals as u64),
+                Instr::JumpIfNone(target)
+                | Instr::Jump(target)
+                | Instr::JumpIfFalse(target)
+                | Instr::JumpIfTrue(target) => target < len,
+                _ => true,
+            };
+
+            if !valid {
+                return invalid(format!("{}", instr.display(module)));
+            }
+        }
+
+        verify(module, function)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that every instruction `function` can reach is reached with the same number of values
+/// on the stack, which is enough for the instruction, and that its code doesn't run past its
+/// end. The types of the values aren't known, the virtual machine aborts on wrong ones.
+fn verify(module: &Module, function: &Function) -> Result<(), LoadError> {
+    let mut depths = vec![None; function.code.len()];
+    let mut pending = vec![(0, 0u64)];
+    while let Some((pc, depth)) = pending.pop() {
+        let Some(instr) = function.code.get(pc) else {
+            return Err(LoadError::MissingReturn(function.name.clone()));
+        };
+
+        match depths[pc] {
+            Some(other) if other == depth => continue,
+            Some(_) => return Err(LoadError::UnbalancedStack(function.name.clone())),
+            None => depths[pc] = Some(depth),
+        }
+
+        let (pops, pushes) = effect(module, *instr);
+        let Some(depth) = depth.checked_sub(pops) else {
+            return Err(LoadError::UnbalancedStack(function.name.clone()));
+        };
+
+        let depth = depth + pushes;
+        match *instr {
+            Instr::Return => {}
+            Instr::Jump(target) => pending.push((target as usize, depth)),
+            Instr::JumpIfNone(target) | Instr::JumpIfFalse(target) | Instr::JumpIfTrue(target) => {
+                pending.extend([(target as usize, depth), (pc + 1, depth)]);
+            }
+            _ => pending.push((pc + 1, depth)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns how many values `instr` pops off the stack and how many it pushes afterwards.
+fn effect(module: &Module, instr: Instr) -> (u64, u64) {
+    match instr {
+        Instr::Void
+        | Instr::Bool(_)
+        | Instr::Int(_)
+        | Instr::Float(_)
+        | Instr::String(_)
+        | Instr::None
+        | Instr::GetLocal(_)
+        | Instr::LocalAddr(_)
+        | Instr::Function(_)
+        | Instr::ReadLine => (0, 1),
+        Instr::Jump(_) => (0, 0),
+        Instr::Pop
+        | Instr::SetLocal(_)
+        | Instr::JumpIfFalse(_)
+        | Instr::JumpIfTrue(_)
+        | Instr::Return
+        | Instr::Print
+        | Instr::Println
+        | Instr::Eprintln
+        | Instr::Panic(_) => (1, 0),
+        Instr::Dup => (1, 2),
+        Instr::Store | Instr::ListPush => (2, 0),
+        Instr::IndexAddr(_)
+        | Instr::ListAddr(_)
+        | Instr::Index(_)
+        | Instr::Add(_)
+        | Instr::Sub(_)
+        | Instr::Mul(_)
+        | Instr::Div(_)
+        | Instr::Rem(_)
+        | Instr::Equal
+        | Instr::NotEqual
+        | Instr::Less
+        | Instr::LessEqual
+        | Instr::Greater
+        | Instr::GreaterEqual => (2, 1),
+        Instr::Struct(len) | Instr::Array(len) | Instr::Join(len) => (len as u64, 1),
+        Instr::Variant { len, .. } => (len as u64, 1),
+        Instr::Closure { captures, .. } => (captures as u64, 1),
+        Instr::Slice { start, end, .. } => (1 + start as u64 + end as u64, 1),
+        Instr::Call(f) => (module.functions[f as usize].arity as u64, 1),
+        Instr::CallDyn { args, .. } | Instr::CallValue(args) => (args as u64 + 1, 1),
+        // The optional stays on the stack.
+        Instr::JumpIfNone(_) => (1, 1),
+        Instr::RefAddr
+        | Instr::FieldAddr(_)
+        | Instr::Load
+        | Instr::Field(_)
+        | Instr::IsVariant(_)
+        | Instr::Repeat(_)
+        | Instr::ToList
+        | Instr::Len
+        | Instr::Ref
+        | Instr::Deref
+        | Instr::Dyn(_)
+        | Instr::Some
+        | Instr::Unwrap
+        | Instr::IsSome
+        | Instr::Neg(_)
+        | Instr::Not
+        | Instr::Wrap(_)
+        | Instr::IntToFloat(_)
+        | Instr::FloatToInt(_)
+        | Instr::FloatToFloat(_)
+        | Instr::BoolToInt
+        | Instr::Address
+        | Instr::ToString(_)
+        | Instr::ListPop => (1, 1),
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn uint(&mut self, mut value: u128) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.0.push(byte);
+                return;
+            }
+
+            self.0.push(byte | 0x80);
+        }
+    }
+
+    fn int(&mut self, value: i128) {
+        self.uint(((value << 1) ^ (value >> 127)) as u128);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.uint(bytes.len() as u128);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn int_ty(&mut self, ty: IntTy) {
+        let index = INT_TYS.iter().position(|int| *int == ty);
+        self.0
+            .push(index.expect("Every integer type is listed.") as u8);
+    }
+
+    fn float_ty(&mut self, ty: FloatTy) {
+        let index = FLOAT_TYS.iter().position(|float| *float == ty);
+        self.0
+            .push(index.expect("Every float type is listed.") as u8);
+    }
+
+    /// Writes the type of an arithmetic instruction, floats follow the integers.
+    fn num_ty(&mut self, ty: NumTy) {
+        match ty {
+            NumTy::Int(ty) => self.int_ty(ty),
+            NumTy::Float(ty) => {
+                self.float_ty(ty);
+                *self.0.last_mut().expect("A type was written.") += INT_TYS.len() as u8;
+            }
+        }
+    }
+
+    /// Writes the type of a conversion into a string, `bool` and `string` follow the numbers.
+    fn scalar_ty(&mut self, ty: ScalarTy) {
+        let numbers = (INT_TYS.len() + FLOAT_TYS.len()) as u8;
+        match ty {
+            ScalarTy::Num(ty) => self.num_ty(ty),
+            ScalarTy::Bool => self.0.push(numbers),
+            ScalarTy::String => self.0.push(numbers + 1),
+        }
+    }
+
+    fn instr(&mut self, instr: Instr) {
+        let (opcode, operands): (u8, &[u32]) = match instr {
+            Instr::Void => (0, &[]),
+            Instr::Bool(value) => (1, &[value as u32]),
+            Instr::Int(value) => {
+                self.0.push(2);
+                return self.int(value);
+            }
+            Instr::Float(value) => {
+                self.0.push(3);
+                return self.0.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            Instr::String(index) => (4, &[index]),
+            Instr::None => (5, &[]),
+            Instr::Pop => (6, &[]),
+            Instr::Dup => (7, &[]),
+            Instr::GetLocal(slot) => (8, &[slot]),
+            Instr::SetLocal(slot) => (9, &[slot]),
+            Instr::LocalAddr(slot) => (10, &[slot]),
+            Instr::RefAddr => (11, &[]),
+            Instr::FieldAddr(index) => (12, &[index]),
+            Instr::IndexAddr(location) => (13, &[location.line, location.column]),
+            Instr::ListAddr(location) => (14, &[location.line, location.column]),
+            Instr::Load => (15, &[]),
+            Instr::Store => (16, &[]),
+            Instr::Struct(len) => (17, &[len]),
+            Instr::Field(index) => (18, &[index]),
+            Instr::Variant { tag, len } => (19, &[tag, len]),
+            Instr::IsVariant(tag) => (20, &[tag]),
+            Instr::Array(len) => (21, &[len]),
+            Instr::Repeat(len) => (22, &[len]),
+            Instr::ToList => (23, &[]),
+            Instr::Index(location) => (24, &[location.line, location.column]),
+            Instr::Slice {
+                start,
+                end,
+                inclusive,
+                location,
+            } => {
+                let flags = start as u32 | (end as u32) << 1 | (inclusive as u32) << 2;
+                (25, &[flags, location.line, location.column])
+            }
+            Instr::Len => (26, &[]),
+            Instr::Ref => (27, &[]),
+            Instr::Deref => (28, &[]),
+            Instr::Dyn(vtable) => (29, &[vtable]),
+            Instr::Some => (30, &[]),
+            Instr::Unwrap => (31, &[]),
+            Instr::IsSome => (32, &[]),
+            Instr::JumpIfNone(target) => (33, &[target]),
+            Instr::Add(ty)
+            | Instr::Sub(ty)
+            | Instr::Mul(ty)
+            | Instr::Div(ty)
+            | Instr::Rem(ty)
+            | Instr::Neg(ty) => {
+                let opcode = match instr {
+                    Instr::Add(_) => 34,
+                    Instr::Sub(_) => 35,
+                    Instr::Mul(_) => 36,
+                    Instr::Div(_) => 37,
+                    Instr::Rem(_) => 38,
+                    _ => 39,
+                };
+
+                self.0.push(opcode);
+                return self.num_ty(ty);
+            }
+            Instr::Not => (40, &[]),
+            Instr::Equal => (41, &[]),
+            Instr::NotEqual => (42, &[]),
+            Instr::Less => (43, &[]),
+            Instr::LessEqual => (44, &[]),
+            Instr::Greater => (45, &[]),
+            Instr::GreaterEqual => (46, &[]),
+            Instr::Wrap(ty) | Instr::FloatToInt(ty) => {
+                self.0
+                    .push(if let Instr::Wrap(_) = instr { 47 } else { 49 });
+                return self.int_ty(ty);
+            }
+            Instr::IntToFloat(ty) | Instr::FloatToFloat(ty) => {
+                self.0.push(if let Instr::IntToFloat(_) = instr {
+                    48
+                } else {
+                    50
+                });
+                return self.float_ty(ty);
+            }
+            Instr::BoolToInt => (51, &[]),
+            Instr::Address => (52, &[]),
+            Instr::ToString(ty) => {
+                self.0.push(53);
+                return self.scalar_ty(ty);
+            }
+            Instr::Join(len) => (54, &[len]),
+            Instr::Jump(target) => (55, &[target]),
+            Instr::JumpIfFalse(target) => (56, &[target]),
+            Instr::JumpIfTrue(target) => (57, &[target]),
+            Instr::Call(f) => (58, &[f]),
+            Instr::CallDyn { method, args } => (59, &[method, args]),
+            Instr::CallValue(args) => (60, &[args]),
+            Instr::Function(f) => (61, &[f]),
+            Instr::Closure { function, captures } => (62, &[function, captures]),
+            Instr::Return => (63, &[]),
+            Instr::Print => (64, &[]),
+            Instr::Println => (65, &[]),
+            Instr::Eprintln => (66, &[]),
+            Instr::Panic(location) => (67, &[location.line, location.column]),
+            Instr::ReadLine => (68, &[]),
+            Instr::ListPush => (69, &[]),
+            Instr::ListPop => (70, &[]),
+        };
+
+        self.0.push(opcode);
+        for operand in operands {
+            self.uint(*operand as u128);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, LoadError> {
+        let (byte, rest) = self.0.split_first().ok_or(LoadError::Truncated)?;
+        self.0 = rest;
+        Ok(*byte)
+    }
+
+    fn uint(&mut self) -> Result<u128, LoadError> {
+        let (mut value, mut shift) = (0u128, 0);
+        loop {
+            let byte = self.byte()?;
+            if shift >= 128 {
+                return Err(LoadError::InvalidOperand(format!("{byte}")));
+            }
+
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+
+            shift += 7;
+        }
+    }
+
+    fn int(&mut self) -> Result<i128, LoadError> {
+        let value = self.uint()?;
+        Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let value = self.uint()?;
+        u32::try_from(value).map_err(|_| LoadError::InvalidOperand(value.to_string()))
+    }
+
+    /// Reads a length, which can't be longer than the rest of the file.
+    fn len(&mut self) -> Result<usize, LoadError> {
+        let len = self.u32()? as usize;
+        match len <= self.0.len() {
+            true => Ok(len),
+            false => Err(LoadError::Truncated),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&[u8], LoadError> {
+        let len = self.len()?;
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        Ok(String::from_utf8_lossy(self.bytes()?).into_owned())
+    }
+
+    fn location(&mut self) -> Result<Location, LoadError> {
+        Ok(Location {
+            line: self.u32()?,
+            column: self.u32()?,
+        })
+    }
+
+    fn int_ty(&mut self) -> Result<IntTy, LoadError> {
+        let byte = self.byte()?;
+        INT_TYS
+            .get(byte as usize)
+            .copied()
+            .ok_or(LoadError::InvalidOperand(format!("{byte}")))
+    }
+
+    fn float_ty(&mut self) -> Result<FloatTy, LoadError> {
+        let byte = self.byte()?;
+        FLOAT_TYS
+            .get(byte as usize)
+            .copied()
+            .ok_or(LoadError::InvalidOperand(format!("{byte}")))
+    }
+
+    fn num_ty(&mut self) -> Result<NumTy, LoadError> {
+        let byte = self.byte()?;
+        match (byte as usize).checked_sub(INT_TYS.len()) {
+            None => Ok(NumTy::Int(INT_TYS[byte as usize])),
+            Some(float) => FLOAT_TYS
+                .get(float)
+                .map(|ty| NumTy::Float(*ty))
+                .ok_or(LoadError::InvalidOperand(format!("{byte}"))),
+        }
+    }
+
+    fn scalar_ty(&mut self) -> Result<ScalarTy, LoadError> {
+        let numbers = (INT_TYS.len() + FLOAT_TYS.len()) as u8;
+        let ty = match self.0.first() {
+            Some(&byte) if byte == numbers => ScalarTy::Bool,
+            Some(&byte) if byte == numbers + 1 => ScalarTy::String,
+            _ => return self.num_ty().map(ScalarTy::Num),
+        };
+
+        self.byte()?;
+        Ok(ty)
+    }
+
+    fn instr(&mut self) -> Result<Instr, LoadError> {
+        Ok(match self.byte()? {
+            0 => Instr::Void,
+            1 => Instr::Bool(self.byte()? != 0),
+            2 => Instr::Int(self.int()?),
+            3 => {
+                let mut bits = [0; 8];
+                for byte in bits.iter_mut() {
+                    *byte = self.byte()?;
+                }
+
+                Instr::Float(f64::from_bits(u64::from_le_bytes(bits)))
+            }
+            4 => Instr::String(self.u32()?),
+            5 => Instr::None,
+            6 => Instr::Pop,
+            7 => Instr::Dup,
+            8 => Instr::GetLocal(self.u32()?),
+            9 => Instr::SetLocal(self.u32()?),
+            10 => Instr::LocalAddr(self.u32()?),
+            11 => Instr::RefAddr,
+            12 => Instr::FieldAddr(self.u32()?),
+            13 => Instr::IndexAddr(self.location()?),
+            14 => Instr::ListAddr(self.location()?),
+            15 => Instr::Load,
+            16 => Instr::Store,
+            17 => Instr::Struct(self.u32()?),
+            18 => Instr::Field(self.u32()?),
+            19 => Instr::Variant {
+                tag: self.u32()?,
+                len: self.u32()?,
+            },
+            20 => Instr::IsVariant(self.u32()?),
+            21 => Instr::Array(self.u32()?),
+            22 => Instr::Repeat(self.u32()?),
+            23 => Instr::ToList,
+            24 => Instr::Index(self.location()?),
+            25 => {
+                let flags = self.u32()?;
+                Instr::Slice {
+                    start: flags & 1 != 0,
+                    end: flags & 2 != 0,
+                    inclusive: flags & 4 != 0,
+                    location: self.location()?,
+                }
+            }
+            26 => Instr::Len,
+            27 => Instr::Ref,
+            28 => Instr::Deref,
+            29 => Instr::Dyn(self.u32()?),
+            30 => Instr::Some,
+            31 => Instr::Unwrap,
+            32 => Instr::IsSome,
+            33 => Instr::JumpIfNone(self.u32()?),
+            34 => Instr::Add(self.num_ty()?),
+            35 => Instr::Sub(self.num_ty()?),
+            36 => Instr::Mul(self.num_ty()?),
+            37 => Instr::Div(self.num_ty()?),
+            38 => Instr::Rem(self.num_ty()?),
+            39 => Instr::Neg(self.num_ty()?),
+            40 => Instr::Not,
+            41 => Instr::Equal,
+            42 => Instr::NotEqual,
+            43 => Instr::Less,
+            44 => Instr::LessEqual,
+            45 => Instr::Greater,
+            46 => Instr::GreaterEqual,
+            47 => Instr::Wrap(self.int_ty()?),
+            48 => Instr::IntToFloat(self.float_ty()?),
+            49 => Instr::FloatToInt(self.int_ty()?),
+            50 => Instr::FloatToFloat(self.float_ty()?),
+            51 => Instr::BoolToInt,
+            52 => Instr::Address,
+            53 => Instr::ToString(self.scalar_ty()?),
+            54 => Instr::Join(self.u32()?),
+            55 => Instr::Jump(self.u32()?),
+            56 => Instr::JumpIfFalse(self.u32()?),
+            57 => Instr::JumpIfTrue(self.u32()?),
+            58 => Instr::Call(self.u32()?),
+            59 => Instr::CallDyn {
+                method: self.u32()?,
+                args: self.u32()?,
+            },
+            60 => Instr::CallValue(self.u32()?),
+            61 => Instr::Function(self.u32()?),
+            62 => Instr::Closure {
+                function: self.u32()?,
+                captures: self.u32()?,
+            },
+            63 => Instr::Return,
+            64 => Instr::Print,
+            65 => Instr::Println,
+            66 => Instr::Eprintln,
+            67 => Instr::Panic(self.location()?),
+            68 => Instr::ReadLine,
+            69 => Instr::ListPush,
+            70 => Instr::ListPop,
+            opcode => return Err(LoadError::InvalidOpcode(opcode)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bytecode::compiler, checker, lexer, parser};
+
+    const SOURCE: &str = r#"
+Shape := trait {
+    area := fn (self) -> float
+}
+
+Circle := struct (pub radius: f32): Shape {
+    pub area := fn (self) -> float { 3.14 * self.radius as float * self.radius as float }
+}
+
+main := fn (args: [string]) -> int {
+    shapes: [dyn Shape] = [&Circle(1.0), &Circle(2.0)];
+    total := 0.0;
+    for i in 0..shapes.len {
+        total += shapes[i].area();
+    }
+
+    small: u8 = 200;
+    scale := fn (x: int) -> int { x * small as int };
+    println("{total} {scale(2)} {args.len > 1} {small as i16}");
+    0
+}
+"#;
+
+    /// Compiles [`SOURCE`] into a module.
+    fn module() -> Module {
+        let tokens = lexer::tokenize(SOURCE.bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .expect("The source lexes.");
+
+        let items = parser::parse(tokens)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("The source parses.");
+
+        let program = checker::check(items, false).0.expect("The source checks.");
+
+        compiler::compile(&program, SOURCE, "shapes.rzr").expect("The program compiles.")
+    }
+
+    #[test]
+    fn modules_survive_a_round_trip() {
+        let module = module();
+        let read = read(&write(&module)).expect("The module loads.");
+        assert_eq!(read, module);
+    }
+
+    #[test]
+    fn truncated_modules_are_rejected() {
+        let bytes = write(&module());
+        for len in MAGIC.len()..bytes.len() {
+            let result = read(&bytes[..len]);
+            assert!(
+                matches!(result, Err(LoadError::Truncated)),
+                "{len} of {} bytes: {result:?}",
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = write(&module());
+        let version = VERSION + 1;
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&version.to_le_bytes());
+        assert!(matches!(
+            read(&bytes),
+            Err(LoadError::UnsupportedVersion(found)) if found == version
+        ));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(matches!(
+            read(b"main := fn () {}"),
+            Err(LoadError::NotBytecode)
+        ));
+    }
+}
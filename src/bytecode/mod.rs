@@ -0,0 +1,218 @@
+//! A bytecode backend running checked programs on a stack-based virtual machine.
+//!
+//! [`compiler`] turns the checked program into a [`Module`] of functions, each a list of
+//! [`Instr`]uctions operating on a stack of values. Locals live at the bottom of the frame of
+//! their call and temporaries above them. Modules are written to and loaded from files by
+//! [`format`], printed by [`disasm`] and run by [`vm`], which follows the semantics of the
+//! generated `C` like the interpreter does.
+
+pub mod compiler;
+mod disasm;
+pub mod format;
+pub mod vm;
+
+use crate::checker::typed::{FloatTy, IntTy};
+use std::{fmt, rc::Rc};
+
+/// A compiled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// Path of the source file, which panics and failed bounds checks are reported at.
+    pub path: String,
+    /// String literals, which [`Instr::String`] refers to by index.
+    pub strings: Vec<Rc<[u8]>>,
+    /// Top-level functions, methods and closures, which calls refer to by index.
+    pub functions: Vec<Function>,
+    /// Functions implementing the methods of a trait for a type, in the order the trait
+    /// declares them. [`Instr::Dyn`] refers to them by index.
+    pub vtables: Vec<Vec<u32>>,
+    /// Index of `main`.
+    pub main: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Path of the function like `Node.push`, closures are named after the enclosing function.
+    pub name: String,
+    /// Number of arguments including the receiver, which are the first locals.
+    pub arity: u32,
+    /// Number of locals including the arguments and the captures of closures, which follow the
+    /// arguments.
+    pub locals: u32,
+    pub code: Vec<Instr>,
+}
+
+/// A line and column in the source, reported by panics and failed bounds checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The type of the operands of an arithmetic instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumTy {
+    Int(IntTy),
+    Float(FloatTy),
+}
+
+/// The type of a value converted into a string, or of the value in an optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarTy {
+    Num(NumTy),
+    Bool,
+    String,
+}
+
+/// An instruction of the virtual machine. Jumps target the index of an instruction in the code
+/// of their function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    Void,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    /// Pushes the string literal at the index.
+    String(u32),
+    /// Pushes an optional without a value.
+    None,
+    Pop,
+    Dup,
+    GetLocal(u32),
+    SetLocal(u32),
+    /// Pushes a pointer to a local, which is how receivers are passed.
+    LocalAddr(u32),
+    /// Turns a reference into a pointer to the value behind it.
+    RefAddr,
+    /// Turns a pointer to a struct into a pointer to the field at the index.
+    FieldAddr(u32),
+    /// Pops an index and a pointer to an array and pushes a pointer to the element.
+    IndexAddr(Location),
+    /// Pops an index and a list and pushes a pointer to the element.
+    ListAddr(Location),
+    /// Pops a pointer and pushes the value it points to.
+    Load,
+    /// Pops a value and a pointer and stores the value where the pointer points.
+    Store,
+    /// Pops the number of fields and pushes a struct of them.
+    Struct(u32),
+    /// Pops a struct and pushes the field at the index.
+    Field(u32),
+    /// Pops the payload and pushes the variant at the index of its enum.
+    Variant {
+        tag: u32,
+        len: u32,
+    },
+    /// Pops an enum and pushes whether it's the variant at the index.
+    IsVariant(u32),
+    /// Pops the number of elements and pushes an array of them.
+    Array(u32),
+    /// Pops a value and pushes an array holding the number of copies of it.
+    Repeat(u32),
+    /// Turns an array into a new list.
+    ToList,
+    /// Pops an index and an array, list or string and pushes the element.
+    Index(Location),
+    /// Pops the present bounds and an array, list or string, pushes the range of it.
+    Slice {
+        start: bool,
+        end: bool,
+        inclusive: bool,
+        location: Location,
+    },
+    /// Pops an array, list or string and pushes its length.
+    Len,
+    /// Moves a value to the heap and pushes a reference to it.
+    Ref,
+    /// Pops a reference and pushes the value behind it.
+    Deref,
+    /// Turns a reference into a `dyn` value using the vtable at the index.
+    Dyn(u32),
+    /// Wraps a value into an optional.
+    Some,
+    /// Pops an optional and pushes its value, aborting if it has none.
+    Unwrap,
+    /// Pops an optional and pushes whether it has a value.
+    IsSome,
+    /// Jumps if the optional on top of the stack has no value, leaving it there.
+    JumpIfNone(u32),
+    Add(NumTy),
+    Sub(NumTy),
+    Mul(NumTy),
+    Div(NumTy),
+    Rem(NumTy),
+    Neg(NumTy),
+    Not,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    /// Converts an integer into another integer type.
+    Wrap(IntTy),
+    IntToFloat(FloatTy),
+    /// Converts a float into an integer, saturating at its limits.
+    FloatToInt(IntTy),
+    FloatToFloat(FloatTy),
+    BoolToInt,
+    /// Pops a reference and pushes the address of the value behind it.
+    Address,
+    /// Converts a number, `bool`, string or optional of them of the type into a string.
+    ToString(ScalarTy),
+    /// Pops the number of strings and pushes their concatenation.
+    Join(u32),
+    Jump(u32),
+    /// Pops a `bool` and jumps if it's `false`.
+    JumpIfFalse(u32),
+    /// Pops a `bool` and jumps if it's `true`.
+    JumpIfTrue(u32),
+    /// Calls the function at the index with the arguments on top of the stack.
+    Call(u32),
+    /// Calls the method at the index of the trait of the `dyn` value below the arguments.
+    CallDyn {
+        method: u32,
+        args: u32,
+    },
+    /// Calls the function value below the arguments.
+    CallValue(u32),
+    /// Pushes the function at the index as a value.
+    Function(u32),
+    /// Pops the captured values and pushes the closure at the index.
+    Closure {
+        function: u32,
+        captures: u32,
+    },
+    /// Pops the returned value and leaves the function.
+    Return,
+    /// Pops a string and prints it to the standard output.
+    Print,
+    Println,
+    Eprintln,
+    /// Pops a message and aborts with it.
+    Panic(Location),
+    /// Pushes a line of the standard input, or no value at its end.
+    ReadLine,
+    /// Pops a value and a pointer to a list and appends the value.
+    ListPush,
+    /// Pops a pointer to a list and pushes its last element, which is removed.
+    ListPop,
+}
+
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    /// An external function, which is implemented in `C`.
+    Extern(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Extern(ident) => write!(
+                f,
+                "The external function \"{ident}\" is implemented in C, so it can't run on the \
+                 virtual machine."
+            ),
+        }
+    }
+}
@@ -0,0 +1,778 @@
+//! The virtual machine running bytecode modules.
+//!
+//! Frames of calls share one stack, their locals followed by their temporaries. Structs, payloads
+//! of variants and arrays live on the heap and are shared until they are written to, which copies
+//! them, so they behave like values. References, lists and `dyn` values point to cells which all
+//! their copies share. Receivers are passed as [`Pointer`]s into a frame or a cell.
+
+use super::{Instr, Location, Module, NumTy, ScalarTy};
+use crate::{
+    checker::typed::{FloatTy, IntTy},
+    interp::{abort, format_float, round, saturate, wrap, MAX_FRAMES},
+};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone)]
+enum Value {
+    Void,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    /// The bytes of a string, which aren't necessarily valid UTF-8 after slicing.
+    String(Rc<[u8]>),
+    Optional(Option<Box<Value>>),
+    /// The fields of a struct in declaration order.
+    Struct(Rc<Vec<Value>>),
+    /// The index of a variant in its enum and its payload.
+    Variant(u32, Rc<Vec<Value>>),
+    Array(Rc<Vec<Value>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Reference(Rc<RefCell<Value>>),
+    /// A reference along with the index of the vtable implementing the trait for its type.
+    Dyn(Rc<RefCell<Value>>, u32),
+    Fn(u32),
+    /// A closure with the values of its captured locals.
+    Closure(u32, Rc<[Value]>),
+    Pointer(Rc<Pointer>),
+}
+
+/// A location holding a value, a path of field and element indices into a local or a cell.
+#[derive(Debug, Clone)]
+struct Pointer {
+    root: Root,
+    path: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum Root {
+    /// A local by its index in the stack.
+    Local(usize),
+    Cell(Rc<RefCell<Value>>),
+    /// A list, whose element is the first index of the path.
+    List(Rc<RefCell<Vec<Value>>>),
+}
+
+/// A call of a function.
+struct Frame {
+    function: u32,
+    /// Index of the next instruction, which is only up to date for the frames of callers.
+    pc: usize,
+    /// Index of the first local in the stack.
+    base: usize,
+}
+
+/// Runs `module`, `args` are passed to `main` after the path. Returns the exit code.
+pub fn run(module: &Module, args: Vec<String>) -> i32 {
+    let mut vm = Vm {
+        module,
+        stack: Vec::new(),
+        frames: Vec::new(),
+    };
+
+    if module.functions[module.main as usize].arity == 1 {
+        let args = std::iter::once(module.path.clone())
+            .chain(args)
+            .map(|arg| Value::String(arg.into_bytes().into()))
+            .collect();
+
+        vm.stack.push(Value::List(Rc::new(RefCell::new(args))));
+    }
+
+    vm.call(module.main, 0);
+    let code = match vm.execute() {
+        Value::Int(code) => wrap(code, IntTy::I32) as i32,
+        _ => 0,
+    };
+
+    _ = io::stdout().flush();
+    code
+}
+
+struct Vm<'a> {
+    module: &'a Module,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl Vm<'_> {
+    /// Enters the function at index `f` whose arguments and `captures` are on the stack.
+    fn call(&mut self, f: u32, captures: usize) {
+        if self.frames.len() == MAX_FRAMES {
+            abort(b"Stack overflow.");
+        }
+
+        let function = &self.module.functions[f as usize];
+        let base = self.stack.len() - function.arity as usize - captures;
+        self.stack
+            .resize(base + function.locals as usize, Value::Void);
+
+        self.frames.push(Frame {
+            function: f,
+            pc: 0,
+            base,
+        });
+    }
+
+    /// Runs until the first frame returns, returning its value.
+    fn execute(&mut self) -> Value {
+        let module = self.module;
+        let frame = self.frames.last().expect("A function was called.");
+        let (mut code, mut pc, mut base) = (
+            &module.functions[frame.function as usize].code[..],
+            0,
+            frame.base,
+        );
+
+        loop {
+            let instr = code[pc];
+            pc += 1;
+            match instr {
+                Instr::Void => self.push(Value::Void),
+                Instr::Bool(value) => self.push(Value::Bool(value)),
+                Instr::Int(value) => self.push(Value::Int(value)),
+                Instr::Float(value) => self.push(Value::Float(value)),
+                Instr::String(index) => {
+                    let string = self.module.strings[index as usize].clone();
+                    self.push(Value::String(string));
+                }
+                Instr::None => self.push(Value::Optional(None)),
+                Instr::Pop => _ = self.pop(),
+                Instr::Dup => {
+                    let value = self.peek().clone();
+                    self.push(value);
+                }
+                Instr::GetLocal(slot) => {
+                    let value = self.stack[base + slot as usize].clone();
+                    self.push(value);
+                }
+                Instr::SetLocal(slot) => {
+                    let value = self.pop();
+                    self.stack[base + slot as usize] = value;
+                }
+                Instr::LocalAddr(slot) => self.push(Value::Pointer(Rc::new(Pointer {
+                    root: Root::Local(base + slot as usize),
+                    path: Vec::new(),
+                }))),
+                Instr::RefAddr => {
+                    let pointer = match self.pop() {
+                        // References to the receiver already point to it.
+                        pointer @ Value::Pointer(_) => pointer,
+                        Value::Reference(cell) => Value::Pointer(Rc::new(Pointer {
+                            root: Root::Cell(cell),
+                            path: Vec::new(),
+                        })),
+                        _ => corrupted(),
+                    };
+
+                    self.push(pointer);
+                }
+                Instr::FieldAddr(index) => {
+                    let mut pointer = self.pop_pointer();
+                    pointer.path.push(index);
+                    self.push(Value::Pointer(Rc::new(pointer)));
+                }
+                Instr::IndexAddr(location) => {
+                    let index = self.pop_int();
+                    let mut pointer = self.pop_pointer();
+                    let len = self.load(&pointer).len();
+                    pointer.path.push(self.check_index(index, len, location));
+                    self.push(Value::Pointer(Rc::new(pointer)));
+                }
+                Instr::ListAddr(location) => {
+                    let index = self.pop_int();
+                    let Value::List(list) = self.pop() else {
+                        corrupted()
+                    };
+
+                    let index = self.check_index(index, list.borrow().len(), location);
+                    self.push(Value::Pointer(Rc::new(Pointer {
+                        root: Root::List(list),
+                        path: vec![index],
+                    })));
+                }
+                Instr::Load => {
+                    let pointer = self.pop_pointer();
+                    let value = self.load(&pointer);
+                    self.push(value);
+                }
+                Instr::Store => {
+                    let value = self.pop();
+                    let pointer = self.pop_pointer();
+                    self.store(&pointer, value);
+                }
+                Instr::Struct(len) => {
+                    let fields = self.pop_many(len);
+                    self.push(Value::Struct(Rc::new(fields)));
+                }
+                Instr::Field(index) => {
+                    let value = self.pop().item(index).clone();
+                    self.push(value);
+                }
+                Instr::Variant { tag, len } => {
+                    let payload = self.pop_many(len);
+                    self.push(Value::Variant(tag, Rc::new(payload)));
+                }
+                Instr::IsVariant(tag) => {
+                    let Value::Variant(actual, _) = self.pop() else {
+                        corrupted()
+                    };
+
+                    self.push(Value::Bool(actual == tag));
+                }
+                Instr::Array(len) => {
+                    let items = self.pop_many(len);
+                    self.push(Value::Array(Rc::new(items)));
+                }
+                Instr::Repeat(len) => {
+                    let value = self.pop();
+                    self.push(Value::Array(Rc::new(vec![value; len as usize])));
+                }
+                Instr::ToList => {
+                    let Value::Array(items) = self.pop() else {
+                        corrupted()
+                    };
+
+                    let items = Rc::unwrap_or_clone(items);
+                    self.push(Value::List(Rc::new(RefCell::new(items))));
+                }
+                Instr::Index(location) => {
+                    let index = self.pop_int();
+                    let value = self.pop();
+                    let index = self.check_index(index, value.len(), location) as usize;
+                    let element = match value {
+                        Value::Array(items) => items[index].clone(),
+                        Value::List(items) => items.borrow()[index].clone(),
+                        Value::String(bytes) => Value::Int(bytes[index] as i128),
+                        _ => corrupted(),
+                    };
+
+                    self.push(element);
+                }
+                Instr::Slice {
+                    start,
+                    end,
+                    inclusive,
+                    location,
+                } => {
+                    let end = end.then(|| self.pop_int() + inclusive as i128);
+                    let start = if start { self.pop_int() } else { 0 };
+                    let value = self.pop();
+                    let len = value.len();
+                    let end = end.unwrap_or(len as i128);
+                    if start < 0 || start > end || end > len as i128 {
+                        let message = format!(
+                            "{}: Range {start}..{end} is out of bounds for length {len}.",
+                            self.location(location)
+                        );
+                        abort(message.as_bytes());
+                    }
+
+                    let range = start as usize..end as usize;
+                    let slice = match value {
+                        Value::String(bytes) => Value::String(bytes[range].into()),
+                        Value::Array(items) => Value::list(items[range].to_vec()),
+                        Value::List(items) => Value::list(items.borrow()[range].to_vec()),
+                        _ => corrupted(),
+                    };
+
+                    self.push(slice);
+                }
+                Instr::Len => {
+                    let len = self.pop().len();
+                    self.push(Value::Int(len as i128));
+                }
+                Instr::Ref => {
+                    let value = self.pop();
+                    self.push(Value::Reference(Rc::new(RefCell::new(value))));
+                }
+                Instr::Deref => {
+                    let value = match self.pop() {
+                        Value::Pointer(pointer) => self.load(&pointer),
+                        Value::Reference(cell) => cell.borrow().clone(),
+                        _ => corrupted(),
+                    };
+
+                    self.push(value);
+                }
+                Instr::Dyn(vtable) => {
+                    let cell = self.pop_reference();
+                    self.push(Value::Dyn(cell, vtable));
+                }
+                Instr::Some => {
+                    let value = self.pop();
+                    self.push(Value::Optional(Some(Box::new(value))));
+                }
+                Instr::Unwrap => match self.pop() {
+                    Value::Optional(Some(value)) => self.push(*value),
+                    _ => abort(b"Unwrapped an empty optional."),
+                },
+                Instr::IsSome => {
+                    let is_some = matches!(self.pop(), Value::Optional(Some(_)));
+                    self.push(Value::Bool(is_some));
+                }
+                Instr::JumpIfNone(target) => {
+                    if let Value::Optional(None) = self.peek() {
+                        pc = target as usize;
+                    }
+                }
+                Instr::Add(ty)
+                | Instr::Sub(ty)
+                | Instr::Mul(ty)
+                | Instr::Div(ty)
+                | Instr::Rem(ty) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(arithmetic(instr, ty, left, right));
+                }
+                Instr::Neg(ty) => {
+                    let value = match (self.pop(), ty) {
+                        (Value::Int(value), NumTy::Int(ty)) => Value::Int(wrap(-value, ty)),
+                        (Value::Float(value), _) => Value::Float(-value),
+                        _ => corrupted(),
+                    };
+
+                    self.push(value);
+                }
+                Instr::Not => {
+                    let value = self.pop_bool();
+                    self.push(Value::Bool(!value));
+                }
+                Instr::Equal
+                | Instr::NotEqual
+                | Instr::Less
+                | Instr::LessEqual
+                | Instr::Greater
+                | Instr::GreaterEqual => {
+                    let right = self.pop();
+                    let ordering = self.pop().compare(&right);
+                    let result = match instr {
+                        Instr::Equal => ordering == Some(Ordering::Equal),
+                        Instr::NotEqual => ordering != Some(Ordering::Equal),
+                        Instr::Less => ordering == Some(Ordering::Less),
+                        Instr::LessEqual => ordering.is_some_and(Ordering::is_le),
+                        Instr::Greater => ordering == Some(Ordering::Greater),
+                        _ => ordering.is_some_and(Ordering::is_ge),
+                    };
+
+                    self.push(Value::Bool(result));
+                }
+                Instr::Wrap(ty) => {
+                    let value = self.pop_int();
+                    self.push(Value::Int(wrap(value, ty)));
+                }
+                Instr::IntToFloat(ty) => {
+                    let value = match ty {
+                        FloatTy::Float => self.pop_int() as f64,
+                        FloatTy::F32 => self.pop_int() as f32 as f64,
+                    };
+
+                    self.push(Value::Float(value));
+                }
+                Instr::FloatToInt(ty) => {
+                    let value = self.pop_float();
+                    self.push(Value::Int(saturate(value, ty)));
+                }
+                Instr::FloatToFloat(ty) => {
+                    let value = self.pop_float();
+                    self.push(Value::Float(round(value, ty)));
+                }
+                Instr::BoolToInt => {
+                    let value = self.pop_bool();
+                    self.push(Value::Int(value as i128));
+                }
+                Instr::Address => {
+                    let cell = self.pop_reference();
+                    self.push(Value::Int(Rc::as_ptr(&cell) as usize as i128));
+                }
+                Instr::ToString(ty) => {
+                    let string = self.pop().to_string(ty);
+                    self.push(Value::String(string.into()));
+                }
+                Instr::Join(len) => {
+                    let mut bytes = Vec::new();
+                    for part in self.pop_many(len) {
+                        bytes.extend_from_slice(part.as_bytes());
+                    }
+
+                    self.push(Value::String(bytes.into()));
+                }
+                Instr::Jump(target) => pc = target as usize,
+                Instr::JumpIfFalse(target) => {
+                    if !self.pop_bool() {
+                        pc = target as usize;
+                    }
+                }
+                Instr::JumpIfTrue(target) => {
+                    if self.pop_bool() {
+                        pc = target as usize;
+                    }
+                }
+                Instr::Call(_) | Instr::CallDyn { .. } | Instr::CallValue(_) => {
+                    self.frames.last_mut().expect("A function is running.").pc = pc;
+                    match instr {
+                        Instr::Call(f) => self.call(f, 0),
+                        Instr::CallDyn { method, args } => {
+                            // The receiver is passed as a pointer to the value behind the
+                            // `dyn` value.
+                            let receiver = self.stack.len() - args as usize - 1;
+                            let Value::Dyn(cell, vtable) = self.stack[receiver].clone() else {
+                                corrupted()
+                            };
+
+                            self.stack[receiver] = Value::Pointer(Rc::new(Pointer {
+                                root: Root::Cell(cell),
+                                path: Vec::new(),
+                            }));
+
+                            let f = self.module.vtables[vtable as usize]
+                                .get(method as usize)
+                                .copied()
+                                .unwrap_or_else(|| corrupted());
+                            self.check_arity(f, args + 1);
+                            self.call(f, 0);
+                        }
+                        Instr::CallValue(args) => {
+                            let callee = self.stack.len() - args as usize - 1;
+                            match self.stack.remove(callee) {
+                                Value::Fn(f) => {
+                                    self.check_arity(f, args);
+                                    self.call(f, 0);
+                                }
+                                Value::Closure(f, captures) => {
+                                    self.check_arity(f, args);
+                                    self.stack.extend(captures.iter().cloned());
+                                    self.call(f, captures.len());
+                                }
+                                _ => corrupted(),
+                            }
+                        }
+                        _ => unreachable!("Only calls are matched."),
+                    }
+
+                    let frame = self.frames.last().expect("A function was called.");
+                    code = &module.functions[frame.function as usize].code;
+                    (pc, base) = (0, frame.base);
+                }
+                Instr::Function(f) => self.push(Value::Fn(f)),
+                Instr::Closure { function, captures } => {
+                    let captures = self.pop_many(captures);
+                    self.push(Value::Closure(function, captures.into()));
+                }
+                Instr::Return => {
+                    let value = self.pop();
+                    self.stack.truncate(base);
+                    self.frames.pop();
+                    let Some(caller) = self.frames.last() else {
+                        return value;
+                    };
+
+                    code = &module.functions[caller.function as usize].code;
+                    (pc, base) = (caller.pc, caller.base);
+                    self.push(value);
+                }
+                Instr::Print | Instr::Println | Instr::Eprintln => {
+                    let mut bytes = self.pop().as_bytes().to_vec();
+                    if instr != Instr::Print {
+                        bytes.push(b'\n');
+                    }
+
+                    // Like `fwrite`, output to a closed pipe is ignored.
+                    _ = match instr {
+                        Instr::Eprintln => io::stderr().write_all(&bytes),
+                        _ => io::stdout().write_all(&bytes),
+                    };
+                }
+                Instr::Panic(location) => {
+                    let message = self.pop();
+                    let location = self.location(location);
+                    abort(&[location.as_bytes(), b": ", message.as_bytes()].concat());
+                }
+                Instr::ReadLine => {
+                    let mut line = Vec::new();
+                    if io::stdin().lock().read_until(b'\n', &mut line).unwrap_or(0) == 0 {
+                        self.push(Value::Optional(None));
+                        continue;
+                    }
+
+                    if line.last() == Some(&b'\n') {
+                        line.pop();
+                    }
+
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+
+                    let line = Value::String(line.into());
+                    self.push(Value::Optional(Some(Box::new(line))));
+                }
+                Instr::ListPush => {
+                    let value = self.pop();
+                    let pointer = self.pop_pointer();
+                    let Value::List(list) = self.load(&pointer) else {
+                        corrupted()
+                    };
+
+                    list.borrow_mut().push(value);
+                }
+                Instr::ListPop => {
+                    let pointer = self.pop_pointer();
+                    let Value::List(list) = self.load(&pointer) else {
+                        corrupted()
+                    };
+
+                    let value = list.borrow_mut().pop().map(Box::new);
+                    self.push(Value::Optional(value));
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("The compiler keeps the stack balanced.")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack
+            .last()
+            .expect("The compiler keeps the stack balanced.")
+    }
+
+    /// Pops the `len` values on top of the stack, the topmost one last.
+    fn pop_many(&mut self, len: u32) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - len as usize)
+    }
+
+    fn pop_bool(&mut self) -> bool {
+        match self.pop() {
+            Value::Bool(value) => value,
+            _ => corrupted(),
+        }
+    }
+
+    /// Pops an integer as is, values are already wrapped into their own type and `uint` values
+    /// may exceed `int`.
+    fn pop_int(&mut self) -> i128 {
+        match self.pop() {
+            Value::Int(value) => value,
+            _ => corrupted(),
+        }
+    }
+
+    fn pop_float(&mut self) -> f64 {
+        match self.pop() {
+            Value::Float(value) => value,
+            _ => corrupted(),
+        }
+    }
+
+    fn pop_reference(&mut self) -> Rc<RefCell<Value>> {
+        match self.pop() {
+            Value::Reference(cell) => cell,
+            _ => corrupted(),
+        }
+    }
+
+    fn pop_pointer(&mut self) -> Pointer {
+        match self.pop() {
+            Value::Pointer(pointer) => Rc::unwrap_or_clone(pointer),
+            _ => corrupted(),
+        }
+    }
+
+    /// Returns the value `pointer` points to.
+    fn load(&self, pointer: &Pointer) -> Value {
+        let (root, path) = (&pointer.root, &pointer.path[..]);
+        match root {
+            Root::Local(slot) => follow(&self.stack[*slot], path).clone(),
+            Root::Cell(cell) => follow(&cell.borrow(), path).clone(),
+            Root::List(list) => {
+                let list = list.borrow();
+                let element = list.get(path[0] as usize).unwrap_or_else(|| corrupted());
+                follow(element, &path[1..]).clone()
+            }
+        }
+    }
+
+    /// Writes `value` where `pointer` points to, copying the structs and arrays on the way which
+    /// are shared.
+    fn store(&mut self, pointer: &Pointer, value: Value) {
+        let (root, path) = (&pointer.root, &pointer.path[..]);
+        match root {
+            Root::Local(slot) => *follow_mut(&mut self.stack[*slot], path) = value,
+            Root::Cell(cell) => *follow_mut(&mut cell.borrow_mut(), path) = value,
+            Root::List(list) => {
+                let mut list = list.borrow_mut();
+                let element = list
+                    .get_mut(path[0] as usize)
+                    .unwrap_or_else(|| corrupted());
+                *follow_mut(element, &path[1..]) = value;
+            }
+        }
+    }
+
+    /// Returns `index` as a `u32`, aborting if it's out of bounds for `len`.
+    fn check_index(&self, index: i128, len: usize, location: Location) -> u32 {
+        if index < 0 || index >= len as i128 {
+            let message = format!(
+                "{}: Index {index} is out of bounds for length {len}.",
+                self.location(location)
+            );
+            abort(message.as_bytes());
+        }
+
+        index as u32
+    }
+
+    /// Aborts unless the function at index `f` takes `args` arguments, which calls of values
+    /// can only check when they run.
+    fn check_arity(&self, f: u32, args: u32) {
+        if self.module.functions[f as usize].arity != args {
+            corrupted();
+        }
+    }
+
+    /// Returns `location` in the source like `path:1:5`.
+    fn location(&self, location: Location) -> String {
+        format!("{}:{}:{}", self.module.path, location.line, location.column)
+    }
+}
+
+impl Value {
+    fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    /// Returns the fields of a struct, the payload of a variant or the elements of an array.
+    fn items(&self) -> &[Value] {
+        match self {
+            Value::Struct(items) | Value::Variant(_, items) | Value::Array(items) => items,
+            _ => corrupted(),
+        }
+    }
+
+    /// Returns the field of a struct, the payload of a variant or the element of an array at
+    /// `index`.
+    fn item(&self, index: u32) -> &Value {
+        self.items()
+            .get(index as usize)
+            .unwrap_or_else(|| corrupted())
+    }
+
+    fn items_mut(&mut self) -> &mut Vec<Value> {
+        match self {
+            Value::Struct(items) | Value::Array(items) => Rc::make_mut(items),
+            _ => corrupted(),
+        }
+    }
+
+    /// Returns the length of an array, list or string.
+    fn len(&self) -> usize {
+        match self {
+            Value::Array(items) => items.len(),
+            Value::List(items) => items.borrow().len(),
+            Value::String(bytes) => bytes.len(),
+            _ => corrupted(),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Value::String(bytes) => bytes,
+            _ => corrupted(),
+        }
+    }
+
+    /// Converts a number, `bool` or optional of them into the bytes of a string, floats are of
+    /// type `ty`.
+    fn to_string(&self, ty: ScalarTy) -> Vec<u8> {
+        match (self, ty) {
+            (Value::Optional(Some(value)), ty) => value.to_string(ty),
+            (Value::Optional(None), _) => b"?".to_vec(),
+            (Value::String(bytes), ScalarTy::String) => bytes.to_vec(),
+            (Value::Int(value), ScalarTy::Num(NumTy::Int(_))) => value.to_string().into_bytes(),
+            (Value::Float(value), ScalarTy::Num(NumTy::Float(ty))) => {
+                format_float(*value, ty).into_bytes()
+            }
+            (Value::Bool(value), ScalarTy::Bool) => value.to_string().into_bytes(),
+            _ => corrupted(),
+        }
+    }
+
+    /// Compares two values of the same type, `NaN` isn't ordered.
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => left.partial_cmp(right),
+            (Value::Float(left), Value::Float(right)) => left.partial_cmp(right),
+            (Value::Bool(left), Value::Bool(right)) => left.partial_cmp(right),
+            (Value::String(left), Value::String(right)) => left.partial_cmp(right),
+            _ => corrupted(),
+        }
+    }
+}
+
+fn follow<'a>(mut value: &'a Value, path: &[u32]) -> &'a Value {
+    for index in path {
+        value = value.item(*index);
+    }
+
+    value
+}
+
+fn follow_mut<'a>(mut value: &'a mut Value, path: &[u32]) -> &'a mut Value {
+    for index in path {
+        value = value
+            .items_mut()
+            .get_mut(*index as usize)
+            .unwrap_or_else(|| corrupted());
+    }
+
+    value
+}
+
+/// Aborts at a value of the wrong kind or an index out of range for it. Loading a module verifies
+/// that its code keeps the stack balanced, but it doesn't know the types of the values, so only
+/// corrupted modules get here.
+fn corrupted() -> ! {
+    abort(b"The bytecode module is corrupted.")
+}
+
+/// Applies the arithmetic `instr` to two numbers of type `ty`.
+fn arithmetic(instr: Instr, ty: NumTy, left: Value, right: Value) -> Value {
+    match (left, right, ty) {
+        (Value::Int(left), Value::Int(right), NumTy::Int(ty)) => {
+            let value = match instr {
+                Instr::Add(_) => left + right,
+                Instr::Sub(_) => left - right,
+                Instr::Mul(_) => left.wrapping_mul(right),
+                Instr::Div(_) | Instr::Rem(_) if right == 0 => abort(b"Division by zero."),
+                Instr::Div(_) => left / right,
+                _ => left % right,
+            };
+
+            Value::Int(wrap(value, ty))
+        }
+        (Value::Float(left), Value::Float(right), NumTy::Float(ty)) => {
+            let value = match instr {
+                Instr::Add(_) => left + right,
+                Instr::Sub(_) => left - right,
+                Instr::Mul(_) => left * right,
+                Instr::Div(_) => left / right,
+                _ => left % right,
+            };
+
+            Value::Float(round(value, ty))
+        }
+        _ => corrupted(),
+    }
+}
@@ -0,0 +1,215 @@
+//! Arrays, lists and the indexing and slicing of them.
+//!
+//! Arrays have a length known at compile time and are copied like structs, lists live on the
+//! heap, grow as values are pushed and are shared by their copies. Arrays convert into lists
+//! where one is expected, so `[1, 2, 3]` can initialize either. Strings are indexed and sliced
+//! by byte.
+//!
+//! Slices copy their range into a new list rather than sharing the elements, so writes through a
+//! slice expression like `xs[0..2][1] = 42` or `xs[1..].push(3)` are rejected. A slice stored in a
+//! local is a list of its own, which doesn't alias the original.
+
+use super::{
+    typed::{Expr, ExprKind, IntTy, Type},
+    wrap_ty, CheckError, Checker, Signature,
+};
+use crate::{
+    parser::item,
+    util::{Span, Spanned},
+};
+use std::ops::Range;
+
+impl Checker {
+    /// Resolves an array type like `[int; 4]` or a list type like `[int]`.
+    pub(super) fn resolve_array_ty(&mut self, ty: &item::Ty) -> Result<Type, Spanned<CheckError>> {
+        let element = self.resolve_ty(&ty.args[0])?;
+        if element == Type::Void {
+            return Err(CheckError::VoidValue.span(ty.args[0].ident.range()));
+        }
+
+        let resolved = match ty.len {
+            Some(len) => Type::Array(Box::new(element), len),
+            None => Type::List(Box::new(element)),
+        };
+
+        Ok(wrap_ty(resolved, ty))
+    }
+
+    /// Checks an array literal, the first element decides the element type unless an array or
+    /// list is expected.
+    pub(super) fn check_array(
+        &mut self,
+        exprs: &[item::Expr],
+        expected: Option<&Type>,
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let mut element = expected_element(expected).cloned();
+        let mut checked = Vec::new();
+        for expr in exprs.iter() {
+            let value = self.check_expr(expr, element.as_ref())?;
+            if value.ty == Type::Void {
+                return Err(CheckError::VoidValue.span(expr.range()));
+            }
+
+            let element = element.get_or_insert_with(|| value.ty.clone());
+            checked.push(self.coerce(value, element, expr.range())?);
+        }
+
+        let Some(element) = element else {
+            return Err(CheckError::EmptyArray.span(range));
+        };
+
+        Ok(Expr {
+            ty: Type::Array(Box::new(element), checked.len()),
+            kind: ExprKind::Array(checked),
+        })
+    }
+
+    /// Checks `[expr; len]`.
+    pub(super) fn check_repeat(
+        &mut self,
+        expr: &item::Expr,
+        len: usize,
+        expected: Option<&Type>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let element = expected_element(expected);
+        let mut checked = self.check_expr(expr, element)?;
+        if checked.ty == Type::Void {
+            return Err(CheckError::VoidValue.span(expr.range()));
+        }
+
+        if let Some(element) = element {
+            checked = self.coerce(checked, element, expr.range())?;
+        }
+
+        Ok(Expr {
+            ty: Type::Array(Box::new(checked.ty.clone()), len),
+            kind: ExprKind::Repeat {
+                expr: Box::new(checked),
+                len,
+            },
+        })
+    }
+
+    /// Checks `expr[index]`, `location` is the offset reported when it's out of bounds.
+    pub(super) fn check_index(
+        &mut self,
+        expr: &item::Expr,
+        index: &item::Expr,
+        location: usize,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let (checked, element) = self.check_indexed(expr)?;
+        Ok(Expr {
+            kind: ExprKind::Index {
+                expr: Box::new(checked),
+                index: Box::new(self.check_position(index)?),
+                location,
+            },
+            ty: element,
+        })
+    }
+
+    /// Checks `expr[start..end]`, which copies the elements into a new list. Slices of strings
+    /// are strings sharing the bytes.
+    pub(super) fn check_slice(
+        &mut self,
+        expr: &item::Expr,
+        start: Option<&item::Expr>,
+        end: Option<&item::Expr>,
+        inclusive: bool,
+        location: usize,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let (checked, element) = self.check_indexed(expr)?;
+        let ty = match checked.ty.deref() {
+            Type::String => Type::String,
+            _ => Type::List(Box::new(element)),
+        };
+        let start = start.map(|start| self.check_position(start)).transpose()?;
+        let end = end.map(|end| self.check_position(end)).transpose()?;
+
+        Ok(Expr {
+            kind: ExprKind::Slice {
+                expr: Box::new(checked),
+                start: start.map(Box::new),
+                end: end.map(Box::new),
+                inclusive,
+                location,
+            },
+            ty,
+        })
+    }
+
+    /// Checks the array, list or string being indexed, returning it with its element type.
+    /// Strings are indexed by byte.
+    fn check_indexed(&mut self, expr: &item::Expr) -> Result<(Expr, Type), Spanned<CheckError>> {
+        let checked = self.check_expr(expr, None)?;
+        let element = match checked.ty.deref() {
+            Type::String => Some(Type::Int(IntTy::U8)),
+            ty => ty.element().cloned(),
+        };
+
+        match element {
+            Some(element) => Ok((checked, element)),
+            None => Err(CheckError::NotIndexable(checked.ty).span(expr.range())),
+        }
+    }
+
+    /// Checks an index or a bound of a slice, which can be of any integer type.
+    fn check_position(&mut self, expr: &item::Expr) -> Result<Expr, Spanned<CheckError>> {
+        let checked = self.check_expr(expr, Some(&Type::Int(IntTy::Int)))?;
+        match checked.ty {
+            Type::Int(_) => Ok(checked),
+            found => Err(CheckError::MismatchedTypes {
+                expected: Type::Int(IntTy::Int),
+                found,
+            }
+            .span(expr.range())),
+        }
+    }
+}
+
+/// Returns the path and signature of the list method `ident`, `push` appends a value and `pop`
+/// removes the last one.
+pub(super) fn list_method(ty: &Type, ident: &str) -> Option<(String, Signature)> {
+    let Type::List(element) = ty.deref() else {
+        return None;
+    };
+
+    let (args, ty) = match ident {
+        "push" => (vec![(**element).clone()], Type::Void),
+        "pop" => (Vec::new(), Type::Optional(element.clone())),
+        _ => return None,
+    };
+
+    let signature = Signature {
+        params: Vec::new(),
+        public: true,
+        receiver: true,
+        args,
+        ty,
+    };
+
+    Some((ident.to_owned(), signature))
+}
+
+/// Returns whether the place `expr` is an element or field of a slice, which isn't stored
+/// anywhere. Values behind references and in lists are shared by the copy, so they don't count.
+pub(super) fn in_slice(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Slice { expr, .. } => expr.ty.deref() != &Type::String,
+        ExprKind::Index { expr, .. } => match expr.ty {
+            Type::List(_) => matches!(expr.kind, ExprKind::Slice { .. }),
+            _ => in_slice(expr),
+        },
+        ExprKind::Field { expr, .. } => !matches!(expr.ty, Type::Reference(_)) && in_slice(expr),
+        _ => false,
+    }
+}
+
+/// Returns the element type of the array or list which is `expected`.
+fn expected_element(expected: Option<&Type>) -> Option<&Type> {
+    match expected? {
+        Type::Optional(ty) => ty.element(),
+        ty => ty.element(),
+    }
+}
@@ -0,0 +1,207 @@
+//! Function values, which are either top-level functions or closures.
+//!
+//! A closure is checked like a function nested inside of the current one. Locals of the enclosing
+//! functions it uses are captured, the closure copies them when it's created so they can't be
+//! assigned to inside of it.
+
+use super::{
+    typed::{Closure, Expr, ExprKind, Fn, Type},
+    wrap_ty, CheckError, Checker,
+};
+use crate::{
+    parser::item::{self, Arg, FnArg},
+    util::{Span, Spanned},
+};
+use std::{collections::HashMap, ops::Range};
+
+/// A closure whose body is being checked.
+#[derive(Debug, Clone)]
+pub(super) struct ClosureInfo {
+    /// Index of the closure's outermost scope, locals declared below it are captured.
+    scope: usize,
+    captures: Vec<(String, Type)>,
+}
+
+impl Checker {
+    /// Resolves a function type like `fn(int) -> int`.
+    pub(super) fn resolve_fn_ty(&mut self, ty: &item::Ty) -> Result<Type, Spanned<CheckError>> {
+        let args = ty
+            .args
+            .iter()
+            .map(|arg| self.resolve_ty(arg))
+            .collect::<Result<_, _>>()?;
+
+        let ret = match &ty.ret {
+            Some(ret) => self.resolve_ty(ret)?,
+            None => Type::Void,
+        };
+
+        let resolved = Type::Fn {
+            args,
+            ty: Box::new(ret),
+        };
+
+        Ok(wrap_ty(resolved, ty))
+    }
+
+    /// Checks an anonymous function, its body can't leave the loops around it and returns from
+    /// the closure itself.
+    pub(super) fn check_closure(
+        &mut self,
+        args: &[FnArg],
+        ty: Option<&item::Ty>,
+        block: &item::Block,
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let signature = self.resolve_signature(false, false, args, ty)?;
+        self.closure_count += 1;
+        let ident = format!("{}.closure{}", self.current_fn, self.closure_count);
+
+        let ty = std::mem::replace(&mut self.ty, signature.ty.clone());
+        let reachable = std::mem::replace(&mut self.reachable, true);
+        let loops = std::mem::take(&mut self.loops);
+        self.closures.push(ClosureInfo {
+            scope: self.scopes.len(),
+            captures: Vec::new(),
+        });
+
+        // Arguments get unique identifiers since they share the locals of the enclosing function.
+        self.scopes.push(HashMap::new());
+        let args = args
+            .iter()
+            .zip(signature.args.iter())
+            .map(|(arg, ty)| (self.declare(&arg.ident.value, ty.clone()), ty.clone()))
+            .collect();
+
+        let block = self.check_fn_block(block, &signature.ty, range.clone());
+        self.scopes.pop();
+
+        let info = self.closures.pop().expect("The closure was pushed above.");
+        self.ty = ty;
+        self.reachable = reachable;
+        self.loops = loops;
+
+        let f = Fn {
+            ident: ident.span(range),
+            receiver: None,
+            args,
+            ty: signature.ty.clone(),
+            block,
+            no_mangle: false,
+            exported: false,
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Closure(Box::new(Closure {
+                f,
+                captures: info.captures,
+            })),
+            ty: Type::Fn {
+                args: signature.args,
+                ty: Box::new(signature.ty),
+            },
+        })
+    }
+
+    /// Looks up a local, capturing it in every closure between its scope and the current one.
+    pub(super) fn lookup_capturing(&mut self, ident: &str) -> Option<(String, Type)> {
+        let (scope, local) = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, scope)| Some((index, scope.get(ident)?.clone())))?;
+
+        for closure in self
+            .closures
+            .iter_mut()
+            .filter(|closure| closure.scope > scope)
+        {
+            if !closure.captures.iter().any(|(other, _)| *other == local.0) {
+                closure.captures.push(local.clone());
+            }
+        }
+
+        Some(local)
+    }
+
+    /// Returns whether the local `ident` is captured by the current closure.
+    pub(super) fn captured(&self, ident: &str) -> bool {
+        self.closures
+            .last()
+            .is_some_and(|closure| closure.captures.iter().any(|(other, _)| other == ident))
+    }
+
+    /// Returns the top-level function `ident` as a value, generic functions have to be called.
+    pub(super) fn function_value(&self, ident: &str) -> Option<Expr> {
+        let signature = self.fns.get(ident)?;
+        if !signature.params.is_empty() {
+            return None;
+        }
+
+        Some(Expr {
+            kind: ExprKind::Function(ident.to_owned()),
+            ty: Type::Fn {
+                args: signature.args.clone(),
+                ty: Box::new(signature.ty.clone()),
+            },
+        })
+    }
+
+    /// Checks that the assigned `place` isn't stored in a local captured by the current closure,
+    /// places behind a reference or in a list are shared with the enclosing function.
+    pub(super) fn check_captured(
+        &self,
+        place: &Expr,
+        range: Range<usize>,
+    ) -> Result<(), Spanned<CheckError>> {
+        let Some(closure) = self.closures.last() else {
+            return Ok(());
+        };
+
+        let mut root = place;
+        while let ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } = &root.kind {
+            if let Type::Reference(_) | Type::List(_) = expr.ty {
+                return Ok(());
+            }
+
+            root = expr;
+        }
+
+        let ExprKind::Local(unique) = &root.kind else {
+            return Ok(());
+        };
+
+        let captured = self.scopes[..closure.scope]
+            .iter()
+            .flat_map(|scope| scope.iter())
+            .find(|(_, (other, _))| other == unique);
+
+        match captured {
+            Some((ident, _)) => Err(CheckError::AssignedCapture(ident.clone()).span(range)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a call of the function value `callee`.
+    pub(super) fn check_call_value(
+        &mut self,
+        callee: Expr,
+        args: &[Arg],
+        callee_range: Range<usize>,
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let Type::Fn { args: tys, ty } = callee.ty.clone() else {
+            return Err(CheckError::NotCallable.span(callee_range));
+        };
+
+        let args = self.check_args(args, &tys, range)?;
+        Ok(Expr {
+            kind: ExprKind::CallValue {
+                expr: Box::new(callee),
+                args,
+            },
+            ty: *ty,
+        })
+    }
+}
@@ -0,0 +1,297 @@
+//! Exhaustiveness checking of `match` expressions.
+//!
+//! Follows the usefulness algorithm from "Warnings for pattern matching" by Luc Maranget: a row
+//! of patterns is useful if it matches some values which no row above it matches. An arm which
+//! isn't useful can never run, and a `match` is exhaustive once a wildcard isn't useful anymore.
+
+use super::{
+    typed::{Literal, Pattern, Type},
+    Checker,
+};
+use std::slice;
+
+static WILDCARD: Pattern = Pattern::Wildcard;
+
+/// The outermost shape of a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    None,
+    Some,
+    /// Index of an enum variant.
+    Variant(usize),
+    /// The only constructor of structs and references.
+    Single,
+    Int(i128),
+    Float(f64),
+    String(String),
+}
+
+/// A value which isn't matched, its fields are in the same order as the constructor's.
+#[derive(Debug, Clone)]
+enum Witness {
+    Wildcard,
+    Ctor(Ctor, Vec<Witness>),
+}
+
+impl Checker {
+    /// Returns the values of type `ty` which none of the `patterns` match, written as patterns.
+    pub(super) fn missing_patterns(&self, patterns: &[&Pattern], ty: &Type) -> Vec<String> {
+        let rows = patterns
+            .iter()
+            .map(|pattern| vec![*pattern])
+            .collect::<Vec<_>>();
+
+        self.missing(&rows, slice::from_ref(ty))
+            .into_iter()
+            .map(|witness| self.format_witness(&witness[0], ty))
+            .collect()
+    }
+
+    /// Returns whether `pattern` matches a value of type `ty` which none of `previous` match.
+    pub(super) fn is_useful(&self, previous: &[&Pattern], pattern: &Pattern, ty: &Type) -> bool {
+        let rows = previous
+            .iter()
+            .map(|pattern| vec![*pattern])
+            .collect::<Vec<_>>();
+
+        self.useful(&rows, &[pattern], slice::from_ref(ty))
+    }
+
+    fn useful(&self, rows: &[Vec<&Pattern>], row: &[&Pattern], tys: &[Type]) -> bool {
+        let Some((ty, rest)) = tys.split_first() else {
+            return rows.is_empty();
+        };
+
+        if let Some((ctor, _)) = self.ctor(row[0]) {
+            let tys = self.ctor_tys(&ctor, ty, rest);
+            let arity = tys.len() - rest.len();
+            let row = self.specialize(&[row.to_vec()], &ctor, arity).remove(0);
+            return self.useful(&self.specialize(rows, &ctor, arity), &row, &tys);
+        }
+
+        match self.complete_ctors(rows, ty) {
+            Some(ctors) => ctors.iter().any(|ctor| {
+                let tys = self.ctor_tys(ctor, ty, rest);
+                let arity = tys.len() - rest.len();
+                let row = self.specialize(&[row.to_vec()], ctor, arity).remove(0);
+                self.useful(&self.specialize(rows, ctor, arity), &row, &tys)
+            }),
+            None => self.useful(&self.default_rows(rows), &row[1..], rest),
+        }
+    }
+
+    /// Returns the values which none of the `rows` match.
+    fn missing(&self, rows: &[Vec<&Pattern>], tys: &[Type]) -> Vec<Vec<Witness>> {
+        let Some((ty, rest)) = tys.split_first() else {
+            return match rows.is_empty() {
+                true => vec![Vec::new()],
+                false => Vec::new(),
+            };
+        };
+
+        let used = self.used_ctors(rows);
+        let all = self.all_ctors(ty);
+        if let Some(ctors) = all.as_ref().filter(|_| !used.is_empty()) {
+            // Values built by unused constructors are only matched by wildcards.
+            let mut missing = Vec::new();
+            let missing_rest = self.missing(&self.default_rows(rows), rest);
+            for ctor in ctors.iter() {
+                if used.contains(ctor) {
+                    missing.extend(self.missing_ctor(rows, ctor, ty, rest));
+                    continue;
+                }
+
+                let arity = self.ctor_tys(ctor, ty, &[]).len();
+                for rest in missing_rest.iter() {
+                    let fields = vec![Witness::Wildcard; arity];
+                    let mut witness = vec![Witness::Ctor(ctor.clone(), fields)];
+                    witness.extend(rest.iter().cloned());
+                    missing.push(witness);
+                }
+            }
+
+            return missing;
+        }
+
+        let mut missing = Vec::new();
+        if all.is_none() {
+            let mut seen = Vec::new();
+            for ctor in used {
+                if !seen.contains(&ctor) {
+                    missing.extend(self.missing_ctor(rows, &ctor, ty, rest));
+                    seen.push(ctor);
+                }
+            }
+        }
+
+        // Every remaining value is summed up as `_`.
+        for rest in self.missing(&self.default_rows(rows), rest) {
+            let mut witness = vec![Witness::Wildcard];
+            witness.extend(rest);
+            missing.push(witness);
+        }
+
+        missing
+    }
+
+    /// Returns the values built by `ctor` which none of the `rows` match.
+    fn missing_ctor(
+        &self,
+        rows: &[Vec<&Pattern>],
+        ctor: &Ctor,
+        ty: &Type,
+        rest: &[Type],
+    ) -> Vec<Vec<Witness>> {
+        let tys = self.ctor_tys(ctor, ty, rest);
+        let arity = tys.len() - rest.len();
+        self.missing(&self.specialize(rows, ctor, arity), &tys)
+            .into_iter()
+            .map(|mut fields| {
+                let rest = fields.split_off(arity);
+                let mut witness = vec![Witness::Ctor(ctor.clone(), fields)];
+                witness.extend(rest);
+                witness
+            })
+            .collect()
+    }
+
+    /// Returns every constructor of `ty` if the first column of `rows` uses all of them.
+    fn complete_ctors(&self, rows: &[Vec<&Pattern>], ty: &Type) -> Option<Vec<Ctor>> {
+        let used = self.used_ctors(rows);
+        self.all_ctors(ty)
+            .filter(|ctors| ctors.iter().all(|ctor| used.contains(ctor)))
+    }
+
+    fn used_ctors(&self, rows: &[Vec<&Pattern>]) -> Vec<Ctor> {
+        rows.iter()
+            .filter_map(|row| self.ctor(row[0]).map(|(ctor, _)| ctor))
+            .collect()
+    }
+
+    /// Returns the constructors of `ty`, or `None` if there are too many to list.
+    fn all_ctors(&self, ty: &Type) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Bool => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+            Type::Optional(_) => Some(vec![Ctor::None, Ctor::Some]),
+            Type::Enum(ident) => Some(
+                (0..self.enums[ident].variants.len())
+                    .map(Ctor::Variant)
+                    .collect(),
+            ),
+            Type::Struct(_) | Type::Reference(_) => Some(vec![Ctor::Single]),
+            _ => None,
+        }
+    }
+
+    /// Returns the types of the fields of `ctor` followed by the types in `rest`.
+    fn ctor_tys(&self, ctor: &Ctor, ty: &Type, rest: &[Type]) -> Vec<Type> {
+        let mut tys = match (ctor, ty) {
+            (Ctor::Some, Type::Optional(inner)) | (Ctor::Single, Type::Reference(inner)) => {
+                vec![(**inner).clone()]
+            }
+            (Ctor::Single, Type::Struct(ident)) => self.structs[ident]
+                .fields
+                .iter()
+                .map(|(_, ty, _)| ty.clone())
+                .collect(),
+            (Ctor::Variant(index), Type::Enum(ident)) => {
+                self.enums[ident].variants[*index].1.clone()
+            }
+            _ => Vec::new(),
+        };
+
+        tys.extend(rest.iter().cloned());
+        tys
+    }
+
+    /// Returns the constructor and fields of `pattern`, `None` if it matches anything.
+    fn ctor<'a>(&self, pattern: &'a Pattern) -> Option<(Ctor, Vec<&'a Pattern>)> {
+        let ctor = match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => return None,
+            Pattern::Literal(Literal::Bool(value)) => Ctor::Bool(*value),
+            Pattern::Literal(Literal::Int(value)) => Ctor::Int(*value),
+            Pattern::Literal(Literal::Float(value)) => Ctor::Float(*value),
+            Pattern::Literal(Literal::String(value)) => Ctor::String(value.clone()),
+            Pattern::None => Ctor::None,
+            Pattern::Some(inner) => return Some((Ctor::Some, vec![inner])),
+            Pattern::Deref(inner) => return Some((Ctor::Single, vec![inner])),
+            Pattern::Variant {
+                ident,
+                variant,
+                fields,
+            } => {
+                let index = self.enums[ident]
+                    .variants
+                    .iter()
+                    .position(|(other, _)| other == variant)
+                    .expect("Patterns only name existing variants.");
+
+                return Some((Ctor::Variant(index), fields.iter().collect()));
+            }
+            Pattern::Struct { fields, .. } => return Some((Ctor::Single, fields.iter().collect())),
+        };
+
+        Some((ctor, Vec::new()))
+    }
+
+    /// Keeps the rows which match `ctor` and replaces their first pattern with its fields.
+    fn specialize<'a>(
+        &self,
+        rows: &[Vec<&'a Pattern>],
+        ctor: &Ctor,
+        arity: usize,
+    ) -> Vec<Vec<&'a Pattern>> {
+        rows.iter()
+            .filter_map(|row| {
+                let mut fields = match self.ctor(row[0]) {
+                    None => vec![&WILDCARD; arity],
+                    Some((other, fields)) if other == *ctor => fields,
+                    Some(_) => return None,
+                };
+
+                fields.extend_from_slice(&row[1..]);
+                Some(fields)
+            })
+            .collect()
+    }
+
+    /// Keeps the rows whose first pattern matches anything and removes it.
+    fn default_rows<'a>(&self, rows: &[Vec<&'a Pattern>]) -> Vec<Vec<&'a Pattern>> {
+        rows.iter()
+            .filter(|row| self.ctor(row[0]).is_none())
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    fn format_witness(&self, witness: &Witness, ty: &Type) -> String {
+        let Witness::Ctor(ctor, fields) = witness else {
+            return "_".to_owned();
+        };
+
+        let tys = self.ctor_tys(ctor, ty, &[]);
+        let fields = fields
+            .iter()
+            .zip(tys.iter())
+            .map(|(field, ty)| self.format_witness(field, ty))
+            .collect::<Vec<_>>();
+
+        match (ctor, ty) {
+            (Ctor::Bool(value), _) => value.to_string(),
+            (Ctor::Int(value), _) => value.to_string(),
+            (Ctor::Float(value), _) => format!("{value:?}"),
+            (Ctor::String(value), _) => format!("{value:?}"),
+            (Ctor::None, _) => "?".to_owned(),
+            // Values are matched through optionals and references implicitly.
+            (Ctor::Some, _) | (Ctor::Single, Type::Reference(_)) => fields[0].clone(),
+            (Ctor::Variant(index), Type::Enum(ident)) => {
+                let variant = &self.enums[ident].variants[*index].0;
+                match fields.is_empty() {
+                    true => format!("{ident}.{variant}"),
+                    false => format!("{ident}.{variant}({})", fields.join(", ")),
+                }
+            }
+            (_, ty) => format!("{ty}({})", fields.join(", ")),
+        }
+    }
+}
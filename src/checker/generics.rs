@@ -0,0 +1,605 @@
+//! Generic functions and structs.
+//!
+//! Generic items are checked once with their type parameters as opaque [`Type::Param`]s, which
+//! reports every error in their bodies. Each list of concrete type arguments then instantiates
+//! a copy of the item whose types are substituted, instances are named like `List[int]` and
+//! are checked again to produce the typed items handed to code generation. A body which is valid
+//! for opaque parameters stays valid for any types, so errors of instances are only reported if
+//! the generic item didn't report them already.
+
+use super::{
+    is_literal,
+    typed::{self, Type},
+    CheckError, Checker, Signature, StructInfo,
+};
+use crate::{
+    expr_tree::ExprTree,
+    parser::item::{self, Item, ItemKind, TypeParam, Value},
+    util::{Span, Spanned},
+};
+use std::{collections::HashMap, ops::Range};
+
+/// Type arguments may only nest this deep, which stops instantiations from recursing forever.
+const MAX_DEPTH: usize = 32;
+
+/// A generic function or struct.
+#[derive(Debug, Clone)]
+pub(super) struct Generic {
+    pub params: Vec<String>,
+    /// Traits bounding each parameter.
+    pub bounds: HashMap<String, Vec<String>>,
+    pub item: Spanned<Item>,
+}
+
+impl Checker {
+    /// Returns the names of the type parameters `params`, reporting duplicates.
+    pub(super) fn declare_params(&mut self, params: &[TypeParam]) -> Vec<String> {
+        let mut declared = Vec::<String>::new();
+        for TypeParam { ident, .. } in params.iter() {
+            if declared.contains(&ident.value) {
+                self.errors
+                    .push(CheckError::DuplicateItem(ident.value.clone()).span(ident.range()));
+                continue;
+            }
+
+            declared.push(ident.value.clone());
+        }
+
+        declared
+    }
+
+    /// Returns the generic struct `ident` unless a type parameter shadows it.
+    pub(super) fn generic_struct(&self, ident: &str) -> Option<&Generic> {
+        if self.type_params.contains_key(ident) {
+            return None;
+        }
+
+        self.generics
+            .get(ident)
+            .filter(|generic| matches!(generic.item.kind, ItemKind::Struct { .. }))
+    }
+
+    /// Returns the name of the instance of `generic` with `args`, like `List[int]`.
+    fn instance_name(generic: &str, args: &[Type]) -> String {
+        let args = args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("{generic}[{args}]")
+    }
+
+    /// Returns the instance of the generic struct `generic` with its own parameters, which is
+    /// used to check the struct itself and to infer its type arguments.
+    pub(super) fn template(&mut self, generic: &str) -> Result<String, CheckError> {
+        let params = self.generics[generic]
+            .params
+            .iter()
+            .map(|param| Type::Param(param.clone()))
+            .collect::<Vec<_>>();
+
+        self.instantiate_struct(generic, params)
+    }
+
+    /// Returns the instance of the generic struct `generic` with `args`, declaring it if it's
+    /// new. Errors the template already reported aren't reported again.
+    pub(super) fn instantiate_struct(
+        &mut self,
+        generic: &str,
+        args: Vec<Type>,
+    ) -> Result<String, CheckError> {
+        let name = Self::instance_name(generic, &args);
+        if self.structs.contains_key(&name) {
+            return Ok(name);
+        }
+
+        if args.iter().any(|arg| self.depth(arg) > MAX_DEPTH) {
+            return Err(CheckError::InfiniteInstantiation(generic.to_owned()));
+        }
+
+        let Generic { params, item, .. } = self.generics[generic].clone();
+        let ItemKind::Struct {
+            fields,
+            traits,
+            methods,
+            ..
+        } = &item.kind
+        else {
+            unreachable!("Only structs are instantiated as types.");
+        };
+
+        let template = args
+            .iter()
+            .zip(params.iter())
+            .all(|(arg, param)| matches!(arg, Type::Param(ident) if ident == param));
+        let concrete = args.iter().all(|arg| self.is_concrete(arg));
+        if !template {
+            self.check_bounds(generic, &args)?;
+        }
+
+        // Declare the instance up front so that its fields can refer to it.
+        self.instances
+            .insert(name.clone(), (generic.to_owned(), args.clone()));
+        self.structs.insert(name.clone(), StructInfo::default());
+
+        let errors = self.errors.len();
+        let type_params = std::mem::replace(
+            &mut self.type_params,
+            params.iter().cloned().zip(args).collect(),
+        );
+
+        let info = StructInfo {
+            fields: self.declare_fields(fields),
+            methods: self.declare_methods(methods),
+            traits: self.resolve_traits(traits),
+        };
+
+        self.type_params = type_params;
+        if !template {
+            self.drop_errors(errors);
+        }
+
+        if concrete {
+            self.pending.push_back(name.clone());
+        }
+
+        self.structs.insert(name.clone(), info);
+        Ok(name)
+    }
+
+    /// Returns the instance of the generic function `generic` with `args`, queueing it to be
+    /// checked if it's new and concrete.
+    pub(super) fn instantiate_fn(
+        &mut self,
+        generic: &str,
+        args: Vec<Type>,
+    ) -> Result<String, CheckError> {
+        let name = Self::instance_name(generic, &args);
+        if self.instances.contains_key(&name) {
+            return Ok(name);
+        }
+
+        if args.iter().any(|arg| self.depth(arg) > MAX_DEPTH) {
+            return Err(CheckError::InfiniteInstantiation(generic.to_owned()));
+        }
+
+        self.check_bounds(generic, &args)?;
+        if args.iter().all(|arg| self.is_concrete(arg)) {
+            self.pending.push_back(name.clone());
+        }
+
+        self.instances
+            .insert(name.clone(), (generic.to_owned(), args));
+        Ok(name)
+    }
+
+    /// Checks every queued instance, which may queue further instances.
+    pub(super) fn check_instances(&mut self) -> HashMap<String, Vec<Spanned<typed::Item>>> {
+        let mut checked = HashMap::<_, Vec<_>>::new();
+        while let Some(name) = self.pending.pop_front() {
+            let (generic, args) = self.instances[&name].clone();
+            let Generic {
+                params,
+                bounds,
+                item,
+            } = self.generics[&generic].clone();
+            let Spanned {
+                start,
+                end,
+                value: item,
+            } = item;
+
+            let (errors, warnings) = (self.errors.len(), self.warnings.len());
+            self.type_params = params.into_iter().zip(args).collect();
+            self.bounds = bounds;
+
+            let instance = match item.kind {
+                ItemKind::Fn {
+                    receiver,
+                    args,
+                    ty,
+                    block,
+                    ..
+                } => self
+                    .resolve_signature(item.public, receiver, &args, ty.as_ref())
+                    .ok()
+                    .map(|signature| {
+                        let ident = name.clone().span(item.ident.range());
+                        typed::Item::Fn(self.check_fn(ident, None, &signature, args, &block))
+                    }),
+                ItemKind::Struct { methods, .. } => {
+                    let methods = self.check_methods(Type::Struct(name.clone()), methods);
+                    let fields = self.structs[&name]
+                        .fields
+                        .iter()
+                        .map(|(ident, ty, _)| (ident.clone(), ty.clone()))
+                        .collect();
+
+                    Some(typed::Item::Struct(typed::Struct {
+                        ident: name.clone().span(item.ident.range()),
+                        fields,
+                        methods,
+                    }))
+                }
+                ItemKind::Enum { .. } | ItemKind::Trait { .. } | ItemKind::Extern { .. } => {
+                    unreachable!("Enums, traits and external functions can't be generic.")
+                }
+            };
+
+            self.type_params.clear();
+            self.bounds.clear();
+            self.drop_errors(errors);
+            self.warnings.truncate(warnings);
+            if let Some(instance) = instance {
+                checked
+                    .entry(generic)
+                    .or_default()
+                    .push(instance.span(start..end));
+            }
+        }
+
+        checked
+    }
+
+    /// Drops the errors reported since `len` which belong to an instance and were already
+    /// reported for the generic item or another instance at the same span. The rest, like
+    /// infinite instantiations, can't be found by checking the generic item and are kept.
+    fn drop_errors(&mut self, len: usize) {
+        for error in self.errors.split_off(len) {
+            let reported = self
+                .errors
+                .iter()
+                .any(|other| other.start == error.start && other.end == error.end);
+
+            if !reported {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    /// Returns the generic struct named by `expr` if it isn't shadowed by a local.
+    pub(super) fn generic_path(&self, expr: &item::Expr) -> Option<String> {
+        match expr {
+            ExprTree::Value(Spanned {
+                value: Value::Ident(ident),
+                ..
+            }) if self.lookup(ident).is_none() && self.generic_struct(ident).is_some() => {
+                Some(ident.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks a call of a generic function, returning the inferred type arguments, the checked
+    /// arguments and the type the call produces.
+    pub(super) fn check_generic_call(
+        &mut self,
+        signature: &Signature,
+        args: &[item::Arg],
+        expected: Option<&Type>,
+        range: Range<usize>,
+    ) -> Result<(Vec<Type>, Vec<typed::Expr>, Type), Spanned<CheckError>> {
+        let exprs = args.iter().map(|arg| &arg.expr).collect::<Vec<_>>();
+        let (type_args, args) = self.infer(
+            &signature.params,
+            &exprs,
+            &signature.args,
+            &signature.ty,
+            expected,
+            range.clone(),
+        )?;
+
+        let bindings = param_bindings(&signature.params, &type_args);
+        let ty = self
+            .substitute(&signature.ty, &bindings)
+            .map_err(|error| error.span(range))?;
+
+        Ok((type_args, args, ty))
+    }
+
+    /// Checks a call of a static method of the generic struct `generic` like `List.new()`,
+    /// the type arguments of the struct are inferred from the call.
+    pub(super) fn check_generic_method_call(
+        &mut self,
+        generic: &str,
+        ident: &Spanned<String>,
+        args: &[item::Arg],
+        expected: Option<&Type>,
+        range: Range<usize>,
+    ) -> Result<typed::Expr, Spanned<CheckError>> {
+        let template = self
+            .template(generic)
+            .map_err(|error| error.span(range.clone()))?;
+
+        // Report the generic struct itself rather than its template.
+        if !self.structs[&template].methods.contains_key(&ident.value) {
+            return Err(CheckError::UnknownMethod {
+                ty: Type::Struct(generic.to_owned()),
+                ident: ident.value.clone(),
+            }
+            .span(ident.range()));
+        }
+
+        let (_, signature) = self.method(&Type::Struct(template), ident)?;
+        if signature.receiver {
+            return Err(CheckError::MismatchedReceiver.span(range));
+        }
+
+        let signature = Signature {
+            params: self.generics[generic].params.clone(),
+            ..signature
+        };
+
+        let (type_args, args, ty) =
+            self.check_generic_call(&signature, args, expected, range.clone())?;
+        let instance = self
+            .instantiate_struct(generic, type_args)
+            .map_err(|error| error.span(range))?;
+
+        Ok(typed::Expr {
+            kind: typed::ExprKind::Call {
+                ident: format!("{instance}.{}", ident.value),
+                args,
+            },
+            ty,
+        })
+    }
+
+    /// Returns the generic struct `name` is an instance of, or `name` itself.
+    pub(super) fn base_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.instances
+            .get(name)
+            .map_or(name, |(generic, _)| generic.as_str())
+    }
+
+    /// Checks `args` against `tys` which may contain the type parameters `params`. The
+    /// parameters are inferred from the arguments first and then from `expected` matched
+    /// against `ty`, the type the call produces.
+    pub(super) fn infer(
+        &mut self,
+        params: &[String],
+        args: &[&item::Expr],
+        tys: &[Type],
+        ty: &Type,
+        expected: Option<&Type>,
+        range: Range<usize>,
+    ) -> Result<(Vec<Type>, Vec<typed::Expr>), Spanned<CheckError>> {
+        if args.len() != tys.len() {
+            return Err(CheckError::WrongArgCount {
+                expected: tys.len(),
+                found: args.len(),
+            }
+            .span(range));
+        }
+
+        let mut bindings = params
+            .iter()
+            .map(|param| (param.clone(), None))
+            .collect::<HashMap<_, _>>();
+
+        // Literals, `?` and arrays of them adapt to the type they are passed as, so they are
+        // checked last.
+        let mut checked = vec![None; args.len()];
+        for adapting in [false, true] {
+            if let (true, Some(expected)) = (adapting, expected) {
+                self.unify(ty, expected, &mut bindings);
+            }
+
+            for (i, (arg, generic)) in args.iter().zip(tys.iter()).enumerate() {
+                if adapts(arg) != adapting {
+                    continue;
+                }
+
+                let expected = match self.is_bound(generic, &bindings) {
+                    true => Some(
+                        self.substitute(generic, &resolved(&bindings))
+                            .map_err(|error| error.span(arg.range()))?,
+                    ),
+                    false => None,
+                };
+
+                let expr = self.check_expr(arg, expected.as_ref())?;
+                self.unify(generic, &expr.ty, &mut bindings);
+                checked[i] = Some(expr);
+            }
+        }
+
+        let type_args = params
+            .iter()
+            .map(|param| {
+                bindings[param]
+                    .clone()
+                    .ok_or_else(|| CheckError::CannotInfer(param.clone()).span(range.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let bindings = resolved(&bindings);
+        let mut coerced = Vec::new();
+        for ((expr, arg), generic) in checked.into_iter().zip(args.iter()).zip(tys.iter()) {
+            let ty = self
+                .substitute(generic, &bindings)
+                .map_err(|error| error.span(arg.range()))?;
+            let expr = expr.expect("Every argument was checked above.");
+            coerced.push(self.coerce(expr, &ty, arg.range())?);
+        }
+
+        Ok((type_args, coerced))
+    }
+
+    /// Replaces the type parameters in `ty` with their `bindings`.
+    pub(super) fn substitute(
+        &mut self,
+        ty: &Type,
+        bindings: &HashMap<String, Type>,
+    ) -> Result<Type, CheckError> {
+        Ok(match ty {
+            Type::Param(ident) => bindings.get(ident).cloned().unwrap_or_else(|| ty.clone()),
+            Type::Optional(inner) => Type::Optional(Box::new(self.substitute(inner, bindings)?)),
+            Type::Reference(inner) => Type::Reference(Box::new(self.substitute(inner, bindings)?)),
+            Type::Array(inner, len) => {
+                Type::Array(Box::new(self.substitute(inner, bindings)?), *len)
+            }
+            Type::List(inner) => Type::List(Box::new(self.substitute(inner, bindings)?)),
+            Type::Fn { args, ty } => Type::Fn {
+                args: args
+                    .iter()
+                    .map(|arg| self.substitute(arg, bindings))
+                    .collect::<Result<_, _>>()?,
+                ty: Box::new(self.substitute(ty, bindings)?),
+            },
+            Type::Struct(name) => match self.instances.get(name).cloned() {
+                Some((generic, args)) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.substitute(arg, bindings))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    Type::Struct(self.instantiate_struct(&generic, args)?)
+                }
+                None => ty.clone(),
+            },
+            ty => ty.clone(),
+        })
+    }
+
+    /// Binds the unbound parameters in `generic` to the matching parts of `ty`.
+    fn unify(&self, generic: &Type, ty: &Type, bindings: &mut HashMap<String, Option<Type>>) {
+        match (generic, ty) {
+            (Type::Param(ident), ty) => {
+                if let Some(binding @ None) = bindings.get_mut(ident) {
+                    *binding = Some(ty.clone());
+                }
+            }
+            (Type::Optional(generic), Type::Optional(ty))
+            | (Type::Reference(generic), Type::Reference(ty))
+            | (Type::List(generic), Type::List(ty))
+            | (Type::Array(generic, _), Type::Array(ty, _)) => self.unify(generic, ty, bindings),
+            // Arrays convert into lists.
+            (Type::List(generic), Type::Array(ty, _)) => self.unify(generic, ty, bindings),
+            // A `T` can be passed as a `T?`.
+            (Type::Optional(generic), ty) => self.unify(generic, ty, bindings),
+            (
+                Type::Fn {
+                    args: generic_args,
+                    ty: generic,
+                },
+                Type::Fn { args, ty },
+            ) => {
+                for (generic, arg) in generic_args.iter().zip(args.iter()) {
+                    self.unify(generic, arg, bindings);
+                }
+
+                self.unify(generic, ty, bindings);
+            }
+            (Type::Struct(generic), Type::Struct(name)) => {
+                if let (Some((generic, generic_args)), Some((base, args))) =
+                    (self.instances.get(generic), self.instances.get(name))
+                {
+                    if generic == base {
+                        for (generic, ty) in generic_args.iter().zip(args.iter()) {
+                            self.unify(generic, ty, bindings);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns whether every parameter in `ty` which is being inferred is bound.
+    fn is_bound(&self, ty: &Type, bindings: &HashMap<String, Option<Type>>) -> bool {
+        match ty {
+            Type::Param(ident) => !matches!(bindings.get(ident), Some(None)),
+            Type::Optional(inner)
+            | Type::Reference(inner)
+            | Type::Array(inner, _)
+            | Type::List(inner) => self.is_bound(inner, bindings),
+            Type::Fn { args, ty } => {
+                args.iter().all(|arg| self.is_bound(arg, bindings)) && self.is_bound(ty, bindings)
+            }
+            Type::Struct(name) => self
+                .instances
+                .get(name)
+                .is_none_or(|(_, args)| args.iter().all(|arg| self.is_bound(arg, bindings))),
+            _ => true,
+        }
+    }
+
+    /// Returns how deeply types are nested in `ty`, counting instances and every other type
+    /// built from types.
+    fn depth(&self, ty: &Type) -> usize {
+        match ty {
+            Type::Optional(inner)
+            | Type::Reference(inner)
+            | Type::Array(inner, _)
+            | Type::List(inner) => 1 + self.depth(inner),
+            Type::Fn { args, ty } => {
+                1 + args
+                    .iter()
+                    .chain([&**ty])
+                    .map(|ty| self.depth(ty))
+                    .max()
+                    .unwrap_or(0)
+            }
+            Type::Struct(name) => self.instances.get(name).map_or(0, |(_, args)| {
+                1 + args.iter().map(|arg| self.depth(arg)).max().unwrap_or(0)
+            }),
+            _ => 0,
+        }
+    }
+
+    /// Returns whether `ty` doesn't contain any type parameters.
+    pub(super) fn is_concrete(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Param(_) => false,
+            Type::Optional(inner)
+            | Type::Reference(inner)
+            | Type::Array(inner, _)
+            | Type::List(inner) => self.is_concrete(inner),
+            Type::Fn { args, ty } => {
+                args.iter().all(|arg| self.is_concrete(arg)) && self.is_concrete(ty)
+            }
+            Type::Struct(name) => self
+                .instances
+                .get(name)
+                .is_none_or(|(_, args)| args.iter().all(|arg| self.is_concrete(arg))),
+            _ => true,
+        }
+    }
+}
+
+/// Returns the type parameters `params` bound to themselves, as they are inside of their item.
+pub(super) fn param_types(params: &[String]) -> HashMap<String, Type> {
+    params
+        .iter()
+        .map(|param| (param.clone(), Type::Param(param.clone())))
+        .collect()
+}
+
+fn param_bindings(params: &[String], args: &[Type]) -> HashMap<String, Type> {
+    params.iter().cloned().zip(args.iter().cloned()).collect()
+}
+
+/// Returns the parameters which are bound.
+fn resolved(bindings: &HashMap<String, Option<Type>>) -> HashMap<String, Type> {
+    bindings
+        .iter()
+        .filter_map(|(param, ty)| Some((param.clone(), ty.clone()?)))
+        .collect()
+}
+
+/// Returns whether the type of `expr` adapts to the type it is used as.
+fn adapts(expr: &item::Expr) -> bool {
+    match expr {
+        ExprTree::Value(Spanned {
+            value: Value::None, ..
+        }) => true,
+        // Including `[]`, whose element type only comes from where it's passed.
+        ExprTree::Value(Spanned {
+            value: Value::Array(exprs),
+            ..
+        }) => exprs.iter().all(adapts),
+        expr => is_literal(expr),
+    }
+}
@@ -0,0 +1,2696 @@
+mod arrays;
+mod closures;
+mod exhaustive;
+mod generics;
+mod prelude;
+#[cfg(test)]
+mod tests;
+mod traits;
+pub mod typed;
+
+use crate::{
+    codegen,
+    expr_tree::ExprTree,
+    lexer::token::{LiteralKind, Operator},
+    parser::item::{self, Item, ItemKind, Pattern, UnaryOperator, Value, Visibility},
+    util::{Span, Spanned},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    ops::Range,
+};
+use traits::TraitInfo;
+use typed::{
+    Arm, Block, Builtin, Enum, Expr, ExprKind, Fn, IntTy, Literal, Loop, Program, Statement,
+    Struct, Type,
+};
+
+#[derive(Debug, Clone)]
+pub enum CheckError {
+    UnknownType(String),
+    UnknownIdent(String),
+    DuplicateItem(String),
+    MismatchedTypes {
+        expected: Type,
+        found: Type,
+    },
+    InvalidCast {
+        from: Type,
+        to: Type,
+    },
+    InvalidOperands {
+        operator: Operator,
+        left: Type,
+        right: Type,
+    },
+    InvalidOperand {
+        operator: UnaryOperator,
+        ty: Type,
+    },
+    /// A `void` value used where a value is required.
+    VoidValue,
+    LiteralOutOfRange {
+        value: String,
+        ty: Type,
+    },
+    WrongArgCount {
+        expected: usize,
+        found: usize,
+    },
+    NotCallable,
+    NotOptional(Type),
+    /// `break` or `continue` outside of a loop.
+    OutsideLoop,
+    UnknownLabel(String),
+    /// A `self` argument in a function outside of a struct.
+    ReceiverOutsideStruct,
+    /// Structs and enums may only contain functions.
+    ExpectedMethod,
+    UnknownField {
+        ty: Type,
+        ident: String,
+    },
+    UnknownMethod {
+        ty: Type,
+        ident: String,
+    },
+    PrivateField(String),
+    PrivateMethod(String),
+    /// A `pub get` field written outside of its struct's methods.
+    ReadOnlyField(String),
+    NotAssignable,
+    /// A method taking `self` called without a value or the other way around.
+    MismatchedReceiver,
+    /// The end of a non-`void` function can be reached without producing a value.
+    MissingReturn(Type),
+    /// Named arguments are only allowed when constructing structs.
+    NamedArg,
+    PositionalAfterNamed,
+    DuplicateArg(String),
+    MissingFields(Vec<String>),
+    /// `?` where no optional is expected.
+    UnexpectedNone,
+    /// A `match` without an arm for the listed values.
+    NonExhaustive(Vec<String>),
+    WrongTypeArgCount {
+        expected: usize,
+        found: usize,
+    },
+    CannotInfer(String),
+    /// Type parameters on a method, only items can be generic.
+    GenericMethod,
+    /// Instantiating the generic item requires instantiating it with ever deeper types.
+    InfiniteInstantiation(String),
+    UnknownTrait(String),
+    /// A trait method without `self`, which can't be called through a `dyn` value.
+    StaticTraitMethod,
+    MissingTraitMethod {
+        ident: String,
+        trait_: String,
+    },
+    /// A method whose signature differs from the one declared by the trait.
+    MismatchedTraitMethod {
+        ident: String,
+        trait_: String,
+    },
+    NotImplemented {
+        ty: Type,
+        trait_: String,
+    },
+    /// `self` used inside of a closure.
+    CapturedReceiver,
+    /// A reference to the receiver used as anything but a local or the owner of fields and
+    /// methods, it only lives as long as the method call.
+    EscapingReceiver,
+    /// Closures copy the locals they capture, so assigning to them would have no effect outside.
+    AssignedCapture(String),
+    /// Slices copy their range into a new list, so writing to one would have no effect on the
+    /// array or list it was taken from.
+    AssignedSlice,
+    /// `[]` where no array or list is expected.
+    EmptyArray,
+    NotIndexable(Type),
+    /// A struct or enum which contains itself without a reference in between, so it would be
+    /// infinitely large.
+    RecursiveType(String),
+    /// An interpolated or printed value which isn't a string, number or `bool`.
+    NotPrintable(Type),
+    UnknownAttribute(String),
+    /// A type which can't be passed to or returned from an external function.
+    NotForeign(Type),
+    /// `@no_mangle` on anything but a top-level function without type parameters.
+    MisplacedAttribute(String),
+    /// A `main` which doesn't take nothing or `args: [string]` and return `void` or `int`.
+    IncorrectMain,
+    MissingMain,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckWarning {
+    /// Code following a `return`, `break` or `continue`.
+    Unreachable,
+    /// A `match` arm whose values are all matched by the arms above it.
+    UnreachableArm,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::UnknownType(ident) => write!(f, "Unknown type \"{ident}\"."),
+            CheckError::UnknownIdent(ident) => write!(f, "Unknown identifier \"{ident}\"."),
+            CheckError::DuplicateItem(ident) => write!(f, "\"{ident}\" is already defined."),
+            CheckError::MismatchedTypes { expected, found } => {
+                write!(f, "Expected \"{expected}\", found \"{found}\".")
+            }
+            CheckError::InvalidCast { from, to } => {
+                write!(f, "Cannot cast \"{from}\" as \"{to}\".")
+            }
+            CheckError::InvalidOperands {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "Cannot apply \"{operator}\" to \"{left}\" and \"{right}\"."
+            ),
+            CheckError::InvalidOperand { operator, ty } => {
+                write!(f, "Cannot apply \"{operator}\" to \"{ty}\".")
+            }
+            CheckError::VoidValue => write!(f, "Expected a value, found \"void\"."),
+            CheckError::LiteralOutOfRange { value, ty } => {
+                write!(f, "Literal {value} does not fit into \"{ty}\".")
+            }
+            CheckError::WrongArgCount { expected, found } => {
+                write!(f, "Expected {expected} arguments, found {found}.")
+            }
+            CheckError::NotCallable => write!(f, "Expression is not callable."),
+            CheckError::NotOptional(ty) => write!(f, "Expected an optional, found \"{ty}\"."),
+            CheckError::OutsideLoop => write!(f, "Used outside of a loop."),
+            CheckError::UnknownLabel(label) => write!(f, "Unknown loop label \"{label}\"."),
+            CheckError::ReceiverOutsideStruct => {
+                write!(f, "Only methods inside of structs can take \"self\".")
+            }
+            CheckError::ExpectedMethod => write!(f, "Structs and enums may only contain methods."),
+            CheckError::UnknownField { ty, ident } => {
+                write!(f, "\"{ty}\" has no field \"{ident}\".")
+            }
+            CheckError::UnknownMethod { ty, ident } => {
+                write!(f, "\"{ty}\" has no method \"{ident}\".")
+            }
+            CheckError::PrivateField(ident) => write!(f, "Field \"{ident}\" is private."),
+            CheckError::PrivateMethod(ident) => write!(f, "Method \"{ident}\" is private."),
+            CheckError::ReadOnlyField(ident) => {
+                write!(
+                    f,
+                    "Field \"{ident}\" can only be written by its struct's methods."
+                )
+            }
+            CheckError::NotAssignable => write!(f, "Expression cannot be assigned to."),
+            CheckError::MismatchedReceiver => {
+                write!(
+                    f,
+                    "Methods taking \"self\" must be called on a value and only them."
+                )
+            }
+            CheckError::MissingReturn(ty) => {
+                write!(f, "Not every path returns a value of type \"{ty}\".")
+            }
+            CheckError::NamedArg => {
+                write!(
+                    f,
+                    "Named arguments are only allowed when constructing structs."
+                )
+            }
+            CheckError::PositionalAfterNamed => {
+                write!(f, "Positional arguments must come before named ones.")
+            }
+            CheckError::DuplicateArg(ident) => {
+                write!(f, "Field \"{ident}\" is given more than once.")
+            }
+            CheckError::MissingFields(fields) => {
+                write!(f, "Missing fields \"{}\".", fields.join("\", \""))
+            }
+            CheckError::UnexpectedNone => {
+                write!(f, "\"?\" can only be used where an optional is expected.")
+            }
+            CheckError::NonExhaustive(patterns) => {
+                write!(f, "Missing patterns \"{}\".", patterns.join("\", \""))
+            }
+            CheckError::WrongTypeArgCount { expected, found } => {
+                write!(f, "Expected {expected} type arguments, found {found}.")
+            }
+            CheckError::CannotInfer(param) => {
+                write!(f, "Cannot infer type parameter \"{param}\".")
+            }
+            CheckError::GenericMethod => write!(f, "Methods cannot have type parameters."),
+            CheckError::InfiniteInstantiation(ident) => {
+                write!(f, "Instantiating \"{ident}\" recurses infinitely.")
+            }
+            CheckError::UnknownTrait(ident) => write!(f, "Unknown trait \"{ident}\"."),
+            CheckError::StaticTraitMethod => {
+                write!(f, "Methods of traits must take \"self\".")
+            }
+            CheckError::MissingTraitMethod { ident, trait_ } => {
+                write!(f, "Method \"{ident}\" of \"{trait_}\" is missing.")
+            }
+            CheckError::MismatchedTraitMethod { ident, trait_ } => {
+                write!(
+                    f,
+                    "Method \"{ident}\" does not match its declaration in \"{trait_}\"."
+                )
+            }
+            CheckError::NotImplemented { ty, trait_ } => {
+                write!(f, "\"{ty}\" does not conform to \"{trait_}\".")
+            }
+            CheckError::CapturedReceiver => write!(f, "Closures cannot capture \"self\"."),
+            CheckError::EscapingReceiver => write!(
+                f,
+                "A reference to \"self\" can only be stored in locals and used through its \
+                 fields and methods."
+            ),
+            CheckError::AssignedSlice => write!(
+                f,
+                "Slices are copies, writing to one has no effect on what it was taken from."
+            ),
+            CheckError::AssignedCapture(ident) => {
+                write!(f, "Captured local \"{ident}\" cannot be assigned to.")
+            }
+            CheckError::EmptyArray => {
+                write!(f, "Cannot infer the element type of an empty array.")
+            }
+            CheckError::NotIndexable(ty) => write!(f, "\"{ty}\" cannot be indexed."),
+            CheckError::RecursiveType(ident) => write!(
+                f,
+                "\"{ident}\" contains itself, store it behind a reference like \"&{ident}\"."
+            ),
+            CheckError::NotPrintable(ty) => write!(
+                f,
+                "\"{ty}\" cannot be printed, only strings, numbers, booleans and optionals of \
+                 them can."
+            ),
+            CheckError::UnknownAttribute(ident) => write!(f, "Unknown attribute \"@{ident}\"."),
+            CheckError::NotForeign(ty) => write!(
+                f,
+                "\"{ty}\" cannot be passed to C, external functions take numbers, booleans and \
+                 strings and return numbers and booleans."
+            ),
+            CheckError::MisplacedAttribute(ident) => write!(
+                f,
+                "\"@{ident}\" can only be put on top-level functions without type parameters."
+            ),
+            CheckError::IncorrectMain => write!(
+                f,
+                "\"main\" has to take no arguments or \"args: [string]\" and return \"void\" \
+                 or \"int\"."
+            ),
+            CheckError::MissingMain => write!(f, "The program has no \"main\" function."),
+        }
+    }
+}
+
+impl fmt::Display for CheckWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckWarning::Unreachable => write!(f, "Unreachable code."),
+            CheckWarning::UnreachableArm => write!(f, "Unreachable pattern."),
+        }
+    }
+}
+
+pub type CheckResult = Result<Program, Vec<Spanned<CheckError>>>;
+
+/// Resolves names and checks types of `Razor` items, lowering them into a typed [`Program`].
+/// Programs need a `main`, a `library` doesn't. The warnings are returned whether or not
+/// checking succeeded.
+pub fn check(
+    items: impl IntoIterator<Item = Spanned<Item>>,
+    library: bool,
+) -> (CheckResult, Vec<Spanned<CheckWarning>>) {
+    let items = items.into_iter().collect::<Vec<_>>();
+    let mut checker = Checker::default();
+
+    // Declare every type first so types can refer to types declared later.
+    for item in items.iter() {
+        let ident = item.ident.value.clone();
+        match &item.kind {
+            ItemKind::Struct { params, .. } if !params.is_empty() => {
+                checker.declare_item(&item.ident);
+                let generic = generics::Generic {
+                    params: checker.declare_params(params),
+                    bounds: HashMap::new(),
+                    item: item.clone(),
+                };
+
+                checker.generics.insert(ident, generic);
+            }
+            ItemKind::Struct { .. } => {
+                checker.declare_item(&item.ident);
+                checker.structs.insert(ident, StructInfo::default());
+            }
+            ItemKind::Enum { .. } => {
+                checker.declare_item(&item.ident);
+                checker.enums.insert(ident, EnumInfo::default());
+            }
+            ItemKind::Trait { .. } => {
+                checker.declare_item(&item.ident);
+                checker.traits.insert(ident, TraitInfo::default());
+            }
+            ItemKind::Fn { .. } | ItemKind::Extern { .. } => {}
+        }
+    }
+
+    // Bounds of generic structs are needed as soon as the structs are instantiated.
+    for item in items.iter() {
+        if let ItemKind::Struct { params, .. } = &item.kind {
+            if !params.is_empty() {
+                let bounds = checker.resolve_bounds(params);
+                if let Some(generic) = checker.generics.get_mut(&item.ident.value) {
+                    generic.bounds = bounds;
+                }
+            }
+        }
+    }
+
+    // Collect every signature so items can be used before they are declared.
+    for item in items.iter() {
+        checker.check_attributes(item, false);
+        match &item.kind {
+            ItemKind::Fn {
+                params,
+                receiver,
+                args,
+                ty,
+                ..
+            } => {
+                checker.declare_item(&item.ident);
+                if *receiver {
+                    checker
+                        .errors
+                        .push(CheckError::ReceiverOutsideStruct.span(item.ident.range()));
+                }
+
+                let bounds = checker.resolve_bounds(params);
+                let params = checker.declare_params(params);
+                checker.type_params = generics::param_types(&params);
+                checker.bounds = bounds.clone();
+                let signature =
+                    checker.resolve_signature(item.public, *receiver, args, ty.as_ref());
+                checker.type_params.clear();
+                checker.bounds.clear();
+
+                match signature {
+                    Ok(signature) => {
+                        let ident = item.ident.value.clone();
+                        if !params.is_empty() {
+                            let generic = generics::Generic {
+                                params: params.clone(),
+                                bounds,
+                                item: item.clone(),
+                            };
+
+                            checker.generics.insert(ident.clone(), generic);
+                        }
+
+                        checker.fns.insert(
+                            ident,
+                            Signature {
+                                params,
+                                ..signature
+                            },
+                        );
+                    }
+                    Err(error) => checker.errors.push(error),
+                }
+            }
+            ItemKind::Struct { params, .. } if !params.is_empty() => {
+                if let Err(error) = checker.template(&item.ident.value) {
+                    checker.errors.push(error.span(item.ident.range()));
+                }
+            }
+            ItemKind::Struct {
+                fields,
+                traits,
+                methods,
+                ..
+            } => {
+                let info = StructInfo {
+                    fields: checker.declare_fields(fields),
+                    traits: checker.resolve_traits(traits),
+                    methods: checker.declare_methods(methods),
+                };
+
+                checker.structs.insert(item.ident.value.clone(), info);
+            }
+            ItemKind::Enum {
+                variants,
+                traits,
+                methods,
+            } => {
+                let mut info = EnumInfo {
+                    traits: checker.resolve_traits(traits),
+                    methods: checker.declare_methods(methods),
+                    ..Default::default()
+                };
+
+                for variant in variants.iter() {
+                    let ident = &variant.ident;
+                    if info.variants.iter().any(|other| other.0 == ident.value)
+                        || info.methods.contains_key(&ident.value)
+                    {
+                        checker.errors.push(
+                            CheckError::DuplicateItem(ident.value.clone()).span(ident.range()),
+                        );
+                    }
+
+                    let fields = variant
+                        .fields
+                        .iter()
+                        .map(|ty| checker.resolve_ty(ty))
+                        .collect::<Result<Vec<_>, _>>();
+
+                    match fields {
+                        Ok(fields) => info.variants.push((ident.value.clone(), fields)),
+                        Err(error) => checker.errors.push(error),
+                    }
+                }
+
+                checker.enums.insert(item.ident.value.clone(), info);
+            }
+            ItemKind::Trait { methods } => {
+                let info = checker.declare_trait(methods);
+                checker.traits.insert(item.ident.value.clone(), info);
+            }
+            ItemKind::Extern {
+                receiver, args, ty, ..
+            } => {
+                checker.declare_item(&item.ident);
+                if *receiver {
+                    checker
+                        .errors
+                        .push(CheckError::ReceiverOutsideStruct.span(item.ident.range()));
+                }
+
+                if let Some(signature) = checker.declare_extern(args, ty.as_ref()) {
+                    checker.fns.insert(item.ident.value.clone(), signature);
+                }
+            }
+        }
+    }
+
+    // Conformance needs the signatures of both the traits and the methods.
+    for item in items.iter() {
+        let ty = match &item.kind {
+            ItemKind::Struct { params, .. } if !params.is_empty() => {
+                match checker.template(&item.ident.value) {
+                    Ok(name) => Type::Struct(name),
+                    Err(_) => continue,
+                }
+            }
+            ItemKind::Struct { .. } => Type::Struct(item.ident.value.clone()),
+            ItemKind::Enum { .. } => Type::Enum(item.ident.value.clone()),
+            ItemKind::Fn { .. } | ItemKind::Trait { .. } | ItemKind::Extern { .. } => continue,
+        };
+
+        if let ItemKind::Struct { traits, .. } | ItemKind::Enum { traits, .. } = &item.kind {
+            checker.check_conformance(&ty, traits);
+        }
+
+        if checker.contains(&ty, &ty, &mut HashSet::new()) {
+            let ident = item.ident.value.clone();
+            checker
+                .errors
+                .push(CheckError::RecursiveType(ident).span(item.ident.range()));
+        }
+    }
+
+    if !library {
+        checker.check_main(&items);
+    }
+
+    let mut checked = Vec::new();
+    // Generic items are only checked for errors, their instances take their place.
+    let mut positions = Vec::new();
+    for Spanned {
+        start,
+        end,
+        value: item,
+    } in items
+    {
+        if let Some(generic) = checker.generics.get(&item.ident.value) {
+            positions.push((checked.len(), item.ident.value.clone()));
+            checker.type_params = generics::param_types(&generic.params);
+            checker.bounds = generic.bounds.clone();
+            match item.kind {
+                ItemKind::Fn { args, block, .. } => {
+                    let signature = checker.fns[&item.ident.value].clone();
+                    checker.check_fn(item.ident, None, &signature, args, &block);
+                }
+                ItemKind::Struct { methods, .. } => {
+                    if let Ok(name) = checker.template(&item.ident.value) {
+                        checker.check_methods(Type::Struct(name), methods);
+                    }
+                }
+                ItemKind::Enum { .. } | ItemKind::Trait { .. } | ItemKind::Extern { .. } => {}
+            }
+
+            checker.type_params.clear();
+            checker.bounds.clear();
+            continue;
+        }
+
+        match item.kind {
+            ItemKind::Fn { args, block, .. } => {
+                let Some(signature) = checker.fns.get(&item.ident.value).cloned() else {
+                    continue;
+                };
+
+                let mut f = checker.check_fn(item.ident, None, &signature, args, &block);
+                f.no_mangle = no_mangle(&item.attributes);
+                f.exported = item.public;
+                checked.push(typed::Item::Fn(f).span(start..end));
+            }
+            ItemKind::Struct { methods, .. } => {
+                let ident = item.ident.value.clone();
+                let checked_methods = checker.check_methods(Type::Struct(ident.clone()), methods);
+                let fields = checker.structs[&ident]
+                    .fields
+                    .iter()
+                    .map(|(ident, ty, _)| (ident.clone(), ty.clone()))
+                    .collect();
+
+                checked.push(
+                    typed::Item::Struct(Struct {
+                        ident: item.ident,
+                        fields,
+                        methods: checked_methods,
+                    })
+                    .span(start..end),
+                );
+            }
+            ItemKind::Enum { methods, .. } => {
+                let ident = item.ident.value.clone();
+                let checked_methods = checker.check_methods(Type::Enum(ident.clone()), methods);
+                checked.push(
+                    typed::Item::Enum(Enum {
+                        ident: item.ident,
+                        variants: checker.enums[&ident].variants.clone(),
+                        methods: checked_methods,
+                    })
+                    .span(start..end),
+                );
+            }
+            ItemKind::Trait { .. } => {
+                let trait_ = checker.trait_item(item.ident);
+                checked.push(typed::Item::Trait(trait_).span(start..end));
+            }
+            ItemKind::Extern { header, .. } => {
+                let Some(signature) = checker.fns.get(&item.ident.value) else {
+                    continue;
+                };
+
+                let extern_ = typed::Extern {
+                    header: header.map(|header| header.value),
+                    args: signature.args.clone(),
+                    ty: signature.ty.clone(),
+                    ident: item.ident,
+                };
+
+                checked.push(typed::Item::Extern(extern_).span(start..end));
+            }
+        }
+    }
+
+    let mut instances = checker.check_instances();
+    for (index, generic) in positions.into_iter().rev() {
+        let instances = instances.remove(&generic).unwrap_or_default();
+        checked.splice(index..index, instances);
+    }
+
+    // Errors on signatures are found before errors in bodies, keep them in source order.
+    checker.errors.sort_by_key(|error| error.start);
+    if !checker.errors.is_empty() {
+        return (Err(checker.errors), checker.warnings);
+    }
+
+    (Ok(Program { items: checked }), checker.warnings)
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    /// Type parameters of a generic function.
+    params: Vec<String>,
+    public: bool,
+    receiver: bool,
+    args: Vec<Type>,
+    ty: Type,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StructInfo {
+    fields: Vec<(String, Type, Visibility)>,
+    /// Traits the struct conforms to.
+    traits: Vec<String>,
+    methods: HashMap<String, Signature>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct EnumInfo {
+    variants: Vec<(String, Vec<Type>)>,
+    traits: Vec<String>,
+    methods: HashMap<String, Signature>,
+}
+
+#[derive(Default)]
+struct Checker {
+    /// Names of all top-level items, used to find duplicates.
+    items: HashSet<String>,
+    fns: HashMap<String, Signature>,
+    structs: HashMap<String, StructInfo>,
+    enums: HashMap<String, EnumInfo>,
+    /// Generic functions and structs by name.
+    generics: HashMap<String, generics::Generic>,
+    /// Maps the names of instances to their generic item and type arguments.
+    instances: HashMap<String, (String, Vec<Type>)>,
+    /// Instances which still have to be checked.
+    pending: VecDeque<String>,
+    /// Types of the type parameters in scope.
+    type_params: HashMap<String, Type>,
+    traits: HashMap<String, TraitInfo>,
+    /// Traits bounding the type parameters in scope.
+    bounds: HashMap<String, Vec<String>>,
+    /// Struct or enum whose methods are being checked.
+    current_type: Option<String>,
+    /// Type of `self` in the current function.
+    receiver: Option<Type>,
+    /// Path of the current function, which closures inside of it are named after.
+    current_fn: String,
+    /// Closures enclosing the current expression.
+    closures: Vec<closures::ClosureInfo>,
+    /// Counter used to name closures.
+    closure_count: usize,
+    /// Maps local names to their unique identifiers and types.
+    scopes: Vec<HashMap<String, (String, Type)>>,
+    /// Unique identifiers of the locals in the current function.
+    locals: HashSet<String>,
+    /// Locals which may hold a reference to the receiver.
+    borrowed: HashSet<String>,
+    /// How the value about to be checked may use a reference to the receiver.
+    borrow: Borrow,
+    /// Return type of the current function.
+    ty: Type,
+    /// Whether the current statement can be reached, cleared by `return`, `break` and `continue`.
+    reachable: bool,
+    /// Loops enclosing the current statement.
+    loops: Vec<LoopInfo>,
+    errors: Vec<Spanned<CheckError>>,
+    warnings: Vec<Spanned<CheckWarning>>,
+}
+
+/// Where a value is used, which decides whether it can be a reference to the receiver.
+///
+/// `self` is copied when it's used as a value, but stored in a local it becomes a reference to the
+/// receiver instead, so that methods can walk linked structures starting at it. Since the receiver
+/// may live anywhere, such references can't leave the locals of the method.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Borrow {
+    /// The value has to be owned.
+    #[default]
+    Owned,
+    /// The value initializes or is assigned to a local holding a reference to the receiver.
+    Local,
+    /// Only fields and methods of the value are used.
+    Base,
+}
+
+#[derive(Debug, Clone)]
+struct LoopInfo {
+    label: Option<String>,
+    /// Whether a `break` exits this loop, infinite loops are only left this way.
+    broken: bool,
+}
+
+impl Checker {
+    fn resolve_ty(&mut self, ty: &item::Ty) -> Result<Type, Spanned<CheckError>> {
+        if ty.function {
+            return self.resolve_fn_ty(ty);
+        }
+
+        if ty.array {
+            return self.resolve_array_ty(ty);
+        }
+
+        let ident = &ty.ident;
+        if ty.dynamic && !self.traits.contains_key(&ident.value) {
+            return Err(CheckError::UnknownTrait(ident.value.clone()).span(ident.range()));
+        }
+
+        let simple = ty
+            .dynamic
+            .then(|| Type::Dyn(ident.value.clone()))
+            .or_else(|| self.type_params.get(&ident.value).cloned())
+            .or_else(|| Type::primitive(ident))
+            .or_else(|| {
+                self.structs
+                    .contains_key(&ident.value)
+                    .then(|| Type::Struct(ident.value.clone()))
+            })
+            .or_else(|| {
+                self.enums
+                    .contains_key(&ident.value)
+                    .then(|| Type::Enum(ident.value.clone()))
+            });
+
+        let expected = match simple {
+            Some(_) => Some(0),
+            None => self
+                .generic_struct(&ident.value)
+                .map(|generic| generic.params.len()),
+        };
+
+        match expected {
+            Some(expected) if expected != ty.args.len() => {
+                return Err(CheckError::WrongTypeArgCount {
+                    expected,
+                    found: ty.args.len(),
+                }
+                .span(ident.range()))
+            }
+            Some(_) => {}
+            None => return Err(CheckError::UnknownType(ident.value.clone()).span(ident.range())),
+        }
+
+        let resolved = match simple {
+            Some(resolved) => resolved,
+            None => {
+                let args = ty
+                    .args
+                    .iter()
+                    .map(|arg| self.resolve_ty(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let name = self
+                    .instantiate_struct(&ident.value, args)
+                    .map_err(|error| error.span(ident.range()))?;
+
+                Type::Struct(name)
+            }
+        };
+
+        Ok(wrap_ty(resolved, ty))
+    }
+
+    fn resolve_signature(
+        &mut self,
+        public: bool,
+        receiver: bool,
+        args: &[item::FnArg],
+        ty: Option<&item::Ty>,
+    ) -> Result<Signature, Spanned<CheckError>> {
+        Ok(Signature {
+            params: Vec::new(),
+            public,
+            receiver,
+            args: args
+                .iter()
+                .map(|arg| self.resolve_ty(&arg.ty))
+                .collect::<Result<_, _>>()?,
+            ty: match ty {
+                Some(ty) => self.resolve_ty(ty)?,
+                None => Type::Void,
+            },
+        })
+    }
+
+    /// Resolves the signature of an external function, which may only take numbers, booleans and
+    /// strings and return numbers and booleans.
+    fn declare_extern(&mut self, args: &[item::FnArg], ty: Option<&item::Ty>) -> Option<Signature> {
+        let signature = match self.resolve_signature(true, false, args, ty) {
+            Ok(signature) => signature,
+            Err(error) => {
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        let args = args
+            .iter()
+            .zip(signature.args.iter())
+            .map(|(arg, resolved)| (arg.ty.ident.range(), resolved, true));
+        let ty = ty.map(|ty| (ty.ident.range(), &signature.ty, false));
+
+        let mut valid = true;
+        for (range, ty, arg) in args.chain(ty) {
+            let allowed = match ty {
+                Type::Int(_) | Type::Float(_) | Type::Bool => true,
+                Type::String => arg,
+                Type::Void => !arg,
+                _ => false,
+            };
+
+            if !allowed {
+                valid = false;
+                self.errors
+                    .push(CheckError::NotForeign(ty.clone()).span(range));
+            }
+        }
+
+        valid.then_some(signature)
+    }
+
+    /// Reports unknown attributes and `@no_mangle` on methods, generic functions and types.
+    fn check_attributes(&mut self, item: &Item, method: bool) {
+        let exportable = match &item.kind {
+            ItemKind::Fn { params, .. } => !method && params.is_empty(),
+            _ => false,
+        };
+
+        for attribute in item.attributes.iter() {
+            let error = match attribute.value.as_str() {
+                "no_mangle" if exportable => continue,
+                "no_mangle" => CheckError::MisplacedAttribute(attribute.value.clone()),
+                _ => CheckError::UnknownAttribute(attribute.value.clone()),
+            };
+
+            self.errors.push(error.span(attribute.range()));
+        }
+    }
+
+    /// Reports a missing `main` or one with a signature the backends can't start.
+    fn check_main(&mut self, items: &[Spanned<Item>]) {
+        let Some(item) = items.iter().find(|item| item.ident.value == "main") else {
+            self.errors.push(CheckError::MissingMain.span(0..0));
+            return;
+        };
+
+        let ItemKind::Fn { params, .. } = &item.kind else {
+            self.errors
+                .push(CheckError::IncorrectMain.span(item.ident.range()));
+            return;
+        };
+
+        // Errors in the signature itself are already reported.
+        let Some(signature) = self.fns.get("main") else {
+            return;
+        };
+
+        let strings = Type::List(Box::new(Type::String));
+        let args = match &signature.args[..] {
+            [] => true,
+            [ty] => *ty == strings,
+            _ => false,
+        };
+
+        if !params.is_empty()
+            || !args
+            || !matches!(signature.ty, Type::Void | Type::Int(IntTy::Int))
+        {
+            self.errors
+                .push(CheckError::IncorrectMain.span(item.ident.range()));
+        }
+    }
+
+    /// Records a top-level item name, reporting it if it's already taken.
+    fn declare_item(&mut self, ident: &Spanned<String>) {
+        if !self.items.insert(ident.value.clone()) {
+            self.errors
+                .push(CheckError::DuplicateItem(ident.value.clone()).span(ident.range()));
+        }
+    }
+
+    /// Resolves the types of the fields of a struct.
+    fn declare_fields(&mut self, fields: &[item::Field]) -> Vec<(String, Type, Visibility)> {
+        let mut declared = Vec::<(String, Type, Visibility)>::new();
+        for field in fields.iter() {
+            if declared.iter().any(|other| other.0 == field.ident.value) {
+                self.errors.push(
+                    CheckError::DuplicateItem(field.ident.value.clone()).span(field.ident.range()),
+                );
+            }
+
+            match self.resolve_ty(&field.ty) {
+                Ok(ty) => declared.push((field.ident.value.clone(), ty, field.visibility)),
+                Err(error) => self.errors.push(error),
+            }
+        }
+
+        declared
+    }
+
+    /// Resolves the signatures of the methods of a struct or enum.
+    fn declare_methods(&mut self, methods: &[Spanned<Item>]) -> HashMap<String, Signature> {
+        let mut signatures = HashMap::new();
+        for method in methods.iter() {
+            self.check_attributes(method, true);
+            let ItemKind::Fn {
+                params,
+                receiver,
+                args,
+                ty,
+                ..
+            } = &method.kind
+            else {
+                self.errors
+                    .push(CheckError::ExpectedMethod.span(method.ident.range()));
+                continue;
+            };
+
+            if !params.is_empty() {
+                self.errors
+                    .push(CheckError::GenericMethod.span(method.ident.range()));
+                continue;
+            }
+
+            match self.resolve_signature(method.public, *receiver, args, ty.as_ref()) {
+                Ok(signature) => {
+                    let ident = method.ident.value.clone();
+                    if signatures.insert(ident.clone(), signature).is_some() {
+                        self.errors
+                            .push(CheckError::DuplicateItem(ident).span(method.ident.range()));
+                    }
+                }
+                Err(error) => self.errors.push(error),
+            }
+        }
+
+        signatures
+    }
+
+    /// Checks the bodies of the methods of the struct or enum `ty`.
+    fn check_methods(&mut self, ty: Type, methods: Vec<Spanned<Item>>) -> Vec<Spanned<Fn>> {
+        let Some((ident, signatures)) = self.methods_of(&ty) else {
+            return Vec::new();
+        };
+
+        let (ident, signatures) = (ident.clone(), signatures.clone());
+        self.current_type = Some(ident.clone());
+
+        let mut checked = Vec::new();
+        for Spanned {
+            start,
+            end,
+            value: method,
+        } in methods
+        {
+            let ItemKind::Fn { args, block, .. } = method.kind else {
+                continue;
+            };
+
+            let Some(signature) = signatures.get(&method.ident.value) else {
+                continue;
+            };
+
+            let path = format!("{ident}.{}", method.ident.value).span(method.ident.range());
+            let receiver = signature.receiver.then(|| ty.clone());
+            let f = self.check_fn(path, receiver, signature, args, &block);
+            checked.push(f.span(start..end));
+        }
+
+        self.current_type = None;
+        checked
+    }
+
+    /// Returns the name and methods of the struct or enum behind `ty`.
+    fn methods_of<'a>(
+        &'a self,
+        ty: &'a Type,
+    ) -> Option<(&'a String, &'a HashMap<String, Signature>)> {
+        match ty.deref() {
+            Type::Struct(name) => Some((name, &self.structs[name].methods)),
+            Type::Enum(name) => Some((name, &self.enums[name].methods)),
+            _ => None,
+        }
+    }
+
+    fn check_fn(
+        &mut self,
+        ident: Spanned<String>,
+        receiver: Option<Type>,
+        signature: &Signature,
+        args: Vec<item::FnArg>,
+        block: &item::Block,
+    ) -> Fn {
+        self.receiver = receiver.clone();
+        self.current_fn = ident.value.clone();
+        self.ty = signature.ty.clone();
+        self.reachable = true;
+        self.scopes.push(HashMap::new());
+        self.locals.clear();
+        self.borrowed.clear();
+        let args = args
+            .into_iter()
+            .zip(signature.args.iter())
+            .map(|(arg, ty)| (self.declare(&arg.ident.value, ty.clone()), ty.clone()))
+            .collect::<Vec<_>>();
+
+        let block = self.check_fn_block(block, &signature.ty, ident.range());
+        self.scopes.pop();
+        self.receiver = None;
+
+        Fn {
+            ident,
+            receiver,
+            args,
+            ty: signature.ty.clone(),
+            block,
+            no_mangle: false,
+            exported: false,
+        }
+    }
+
+    /// Returns the struct or enum named by `expr` if it isn't shadowed by a local.
+    fn type_path(&self, expr: &item::Expr) -> Option<Type> {
+        let ExprTree::Value(Spanned {
+            value: Value::Ident(ident),
+            ..
+        }) = expr
+        else {
+            return None;
+        };
+
+        if self.lookup(ident).is_some() {
+            return None;
+        }
+
+        if self.structs.contains_key(ident) {
+            return Some(Type::Struct(ident.clone()));
+        }
+
+        self.enums
+            .contains_key(ident)
+            .then(|| Type::Enum(ident.clone()))
+    }
+
+    /// Returns the field `ident` of `ty` checking that it's visible from the current function.
+    fn field(
+        &self,
+        ty: &Type,
+        ident: &Spanned<String>,
+        write: bool,
+    ) -> Result<Type, Spanned<CheckError>> {
+        let field = match ty.deref() {
+            Type::Struct(name) => self.structs[name]
+                .fields
+                .iter()
+                .find(|field| field.0 == ident.value)
+                .map(|field| (name, field)),
+            _ => None,
+        };
+
+        let Some((name, (_, field_ty, visibility))) = field else {
+            return Err(CheckError::UnknownField {
+                ty: ty.clone(),
+                ident: ident.value.clone(),
+            }
+            .span(ident.range()));
+        };
+
+        let inside = self.inside(name);
+        match (visibility, write) {
+            (Visibility::Private, _) if !inside => {
+                Err(CheckError::PrivateField(ident.value.clone()).span(ident.range()))
+            }
+            (Visibility::Get, true) if !inside => {
+                Err(CheckError::ReadOnlyField(ident.value.clone()).span(ident.range()))
+            }
+            _ => Ok(field_ty.clone()),
+        }
+    }
+
+    /// Returns the path and signature of the method `ident` of `ty`.
+    fn method(
+        &self,
+        ty: &Type,
+        ident: &Spanned<String>,
+    ) -> Result<(String, Signature), Spanned<CheckError>> {
+        if let Some(method) = self
+            .trait_method(ty, &ident.value)
+            .or_else(|| arrays::list_method(ty, &ident.value))
+        {
+            return Ok(method);
+        }
+
+        let method = self.methods_of(ty).and_then(|(name, methods)| {
+            methods.get(&ident.value).map(|signature| (name, signature))
+        });
+
+        let Some((name, signature)) = method else {
+            return Err(CheckError::UnknownMethod {
+                ty: ty.clone(),
+                ident: ident.value.clone(),
+            }
+            .span(ident.range()));
+        };
+
+        if !signature.public && !self.inside(name) && !self.bound_method(ty, &ident.value) {
+            return Err(CheckError::PrivateMethod(ident.value.clone()).span(ident.range()));
+        }
+
+        Ok((format!("{name}.{}", ident.value), signature.clone()))
+    }
+
+    /// Returns whether the methods of the struct or enum `name` are being checked, the
+    /// methods of a generic struct are inside all of its instances.
+    fn inside(&self, name: &str) -> bool {
+        self.current_type
+            .as_deref()
+            .is_some_and(|current| self.base_name(current) == self.base_name(name))
+    }
+
+    /// Implicitly converts `expr` into `ty`, `T` converts into `T?`, values conforming to a
+    /// trait convert into its `dyn` values and arrays convert into lists.
+    fn coerce(
+        &self,
+        expr: Expr,
+        ty: &Type,
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        if expr.ty == *ty {
+            return Ok(expr);
+        }
+
+        let mismatched = |expr: Expr| {
+            CheckError::MismatchedTypes {
+                expected: ty.clone(),
+                found: expr.ty,
+            }
+            .span(range.clone())
+        };
+
+        let inner = match ty {
+            Type::Dyn(trait_) => return self.to_dyn(expr, trait_).map_err(mismatched),
+            Type::List(_) if is_array_of(&expr.ty, ty) => expr,
+            Type::Optional(inner) if **inner == expr.ty || is_array_of(&expr.ty, inner) => expr,
+            Type::Optional(inner) => match &**inner {
+                Type::Dyn(trait_) => self.to_dyn(expr, trait_).map_err(mismatched)?,
+                _ => return Err(mismatched(expr)),
+            },
+            _ => return Err(mismatched(expr)),
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Cast(Box::new(inner)),
+            ty: ty.clone(),
+        })
+    }
+
+    /// Returns whether `expr` may be a reference to the receiver, which isn't owned.
+    fn is_receiver_ref(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Receiver => matches!(expr.ty, Type::Reference(_)),
+            ExprKind::Local(ident) => self.borrowed.contains(ident),
+            _ => false,
+        }
+    }
+
+    /// Checks that `place` can be assigned to.
+    fn check_place(&self, place: &item::Expr, checked: &Expr) -> Result<(), Spanned<CheckError>> {
+        if arrays::in_slice(checked) {
+            return Err(CheckError::AssignedSlice.span(place.range()));
+        }
+
+        if !checked.is_place() {
+            return Err(CheckError::NotAssignable.span(place.range()));
+        }
+
+        // Only fields themselves can be read-only, what they are accessed through doesn't matter.
+        if let (
+            ExprTree::Value(Spanned {
+                value: Value::Field { ident, .. },
+                ..
+            }),
+            ExprKind::Field { expr, .. },
+        ) = (place, &checked.kind)
+        {
+            self.field(&expr.ty, ident, true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Declares a local in the innermost scope and returns its unique identifier, which is also
+    /// safe to use in the generated `C`.
+    fn declare(&mut self, ident: &str, ty: Type) -> String {
+        let escaped = codegen::c_local(ident);
+        let mut unique = escaped.clone();
+        let mut count = 0;
+        while self.locals.contains(&unique) {
+            count += 1;
+            unique = format!("{escaped}_{count}");
+        }
+
+        self.locals.insert(unique.clone());
+        self.scopes
+            .last_mut()
+            .expect("Locals are always declared inside a scope.")
+            .insert(ident.to_owned(), (unique.clone(), ty));
+
+        unique
+    }
+
+    /// Returns whether values of `ty` store a `target` inline, through fields, optionals and
+    /// arrays but not through references or lists.
+    fn contains(&self, ty: &Type, target: &Type, visited: &mut HashSet<Type>) -> bool {
+        let inner = match ty {
+            Type::Struct(ident) => self.structs[ident]
+                .fields
+                .iter()
+                .map(|(_, ty, _)| ty.clone())
+                .collect(),
+            Type::Enum(ident) => self.enums[ident]
+                .variants
+                .iter()
+                .flat_map(|(_, tys)| tys.iter().cloned())
+                .collect(),
+            Type::Optional(inner) | Type::Array(inner, _) => vec![(**inner).clone()],
+            _ => Vec::new(),
+        };
+
+        inner.iter().any(|inner| {
+            inner == target
+                || (visited.insert(inner.clone()) && self.contains(inner, target, visited))
+        })
+    }
+
+    fn lookup(&self, ident: &str) -> Option<&(String, Type)> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident))
+    }
+
+    fn check_fn_block(&mut self, block: &item::Block, ty: &Type, range: Range<usize>) -> Block {
+        let mut checked = self.check_block(block, Some(ty));
+
+        // `void` functions simply discard their trailing expression.
+        if *ty == Type::Void {
+            discard_trailing(&mut checked, block);
+            return checked;
+        }
+
+        match (checked.trailing_expr.take(), &block.trailing_expr) {
+            // A trailing `if` without `else` or other `void` expression doesn't produce a value.
+            (Some(expr), Some(trailing)) if expr.ty == Type::Void => {
+                checked
+                    .statements
+                    .push(Statement::Expr(expr.value).span(trailing.range()));
+
+                if self.reachable {
+                    self.errors
+                        .push(CheckError::MissingReturn(ty.clone()).span(range));
+                }
+            }
+            (Some(expr), Some(trailing)) => match self.coerce(expr.value, ty, trailing.range()) {
+                Ok(expr) => checked.trailing_expr = Some(Box::new(expr.span(trailing.range()))),
+                Err(error) => self.errors.push(error),
+            },
+            // Every path already returned.
+            (_, None) if !self.reachable => {}
+            (_, None) => self
+                .errors
+                .push(CheckError::MissingReturn(ty.clone()).span(range)),
+            // The trailing expression already reported an error.
+            (None, Some(_)) => {}
+        }
+
+        checked
+    }
+
+    /// Checks a block in a new scope, `expected` guides the type of its trailing expression.
+    fn check_block(&mut self, block: &item::Block, expected: Option<&Type>) -> Block {
+        self.scopes.push(HashMap::new());
+
+        // Only the first unreachable statement is reported, which includes nested blocks.
+        let mut warned = !self.reachable;
+        let mut statements = Vec::new();
+        for statement in block.statements.iter() {
+            if !self.reachable && !warned {
+                self.warnings
+                    .push(CheckWarning::Unreachable.span(statement.range()));
+                warned = true;
+            }
+
+            match self.check_statement(statement) {
+                Ok(checked) => statements.push(checked.span(statement.range())),
+                Err(error) => self.errors.push(error),
+            }
+        }
+
+        if let (Some(expr), false, false) = (&block.trailing_expr, self.reachable, warned) {
+            self.warnings
+                .push(CheckWarning::Unreachable.span(expr.range()));
+        }
+
+        let trailing_expr =
+            block
+                .trailing_expr
+                .as_ref()
+                .and_then(|expr| match self.check_expr(expr, expected) {
+                    Ok(checked) => Some(Box::new(checked.span(expr.range()))),
+                    Err(error) => {
+                        self.errors.push(error);
+                        None
+                    }
+                });
+
+        self.scopes.pop();
+        Block {
+            statements,
+            trailing_expr,
+        }
+    }
+
+    fn check_statement(
+        &mut self,
+        statement: &Spanned<item::Statement>,
+    ) -> Result<Statement, Spanned<CheckError>> {
+        match &statement.value {
+            item::Statement::VariableInit { ident, ty, expr } => {
+                let ty = ty.as_ref().map(|ty| self.resolve_ty(ty)).transpose()?;
+                self.borrow = Borrow::Local;
+                let mut checked = self.check_expr(expr, ty.as_ref())?;
+                if let Some(ty) = ty {
+                    checked = self.coerce(checked, &ty, expr.range())?;
+                }
+
+                if checked.ty == Type::Void {
+                    return Err(CheckError::VoidValue.span(expr.range()));
+                }
+
+                let ident = self.declare(ident, checked.ty.clone());
+                if self.is_receiver_ref(&checked) {
+                    self.borrowed.insert(ident.clone());
+                }
+
+                Ok(Statement::VariableInit {
+                    ident,
+                    expr: checked,
+                })
+            }
+            item::Statement::Expr(expr) => Ok(Statement::Expr(self.check_expr(expr, None)?)),
+            item::Statement::Assign {
+                place,
+                operator,
+                expr,
+            } => {
+                self.borrow = Borrow::Base;
+                let checked_place = self.check_expr(place, None)?;
+                self.check_place(place, &checked_place)?;
+                self.check_captured(&checked_place, place.range())?;
+
+                if let ExprKind::Local(ident) = &checked_place.kind {
+                    if operator.is_none() && self.borrowed.contains(ident) {
+                        self.borrow = Borrow::Local;
+                    }
+                }
+
+                let checked = self.check_expr(expr, Some(&checked_place.ty))?;
+                if let Some(operator) = operator {
+                    let valid = match operator {
+                        Operator::Percent => matches!(checked_place.ty, Type::Int(_)),
+                        Operator::Plus if checked_place.ty == Type::String => true,
+                        _ => checked_place.ty.is_numeric(),
+                    };
+
+                    if !valid || checked.ty != checked_place.ty {
+                        return Err(CheckError::InvalidOperands {
+                            operator: *operator,
+                            left: checked_place.ty,
+                            right: checked.ty,
+                        }
+                        .span(statement.range()));
+                    }
+                }
+
+                Ok(Statement::Assign {
+                    expr: self.coerce(checked, &checked_place.ty, expr.range())?,
+                    place: checked_place,
+                    operator: *operator,
+                })
+            }
+            item::Statement::For { label, kind, block } => {
+                let reachable = self.reachable;
+                self.scopes.push(HashMap::new());
+                let kind = match self.check_loop(kind) {
+                    Ok(kind) => kind,
+                    Err(error) => {
+                        self.scopes.pop();
+                        return Err(error);
+                    }
+                };
+
+                self.loops.push(LoopInfo {
+                    label: label.as_ref().map(|label| label.value.clone()),
+                    broken: false,
+                });
+                let mut checked = self.check_block(block, None);
+                discard_trailing(&mut checked, block);
+                let info = self.loops.pop().expect("The loop was pushed above.");
+                self.scopes.pop();
+
+                // Only a `break` can leave an infinite loop.
+                self.reachable = reachable && (!matches!(kind, Loop::Infinite) || info.broken);
+
+                Ok(Statement::For {
+                    kind,
+                    block: checked,
+                })
+            }
+            item::Statement::Break(label) => {
+                let depth = self.resolve_loop(label, statement.range())?;
+                let index = self.loops.len() - 1 - depth;
+                self.loops[index].broken = true;
+                self.reachable = false;
+                Ok(Statement::Break(depth))
+            }
+            item::Statement::Continue(label) => {
+                let depth = self.resolve_loop(label, statement.range())?;
+                self.reachable = false;
+                Ok(Statement::Continue(depth))
+            }
+            item::Statement::Return(expr) => {
+                let checked = self.check_return(expr.as_ref(), statement.range());
+                self.reachable = false;
+                Ok(Statement::Return(checked?))
+            }
+        }
+    }
+
+    /// Checks the value of a `return` against the type of the current function.
+    fn check_return(
+        &mut self,
+        expr: Option<&item::Expr>,
+        range: Range<usize>,
+    ) -> Result<Option<Expr>, Spanned<CheckError>> {
+        let ty = self.ty.clone();
+        let Some(expr) = expr else {
+            return match ty {
+                Type::Void => Ok(None),
+                ty => Err(CheckError::MismatchedTypes {
+                    expected: ty,
+                    found: Type::Void,
+                }
+                .span(range)),
+            };
+        };
+
+        let checked = self.check_expr(expr, Some(&ty))?;
+        if ty == Type::Void {
+            return Err(CheckError::MismatchedTypes {
+                expected: ty,
+                found: checked.ty,
+            }
+            .span(expr.range()));
+        }
+
+        Ok(Some(self.coerce(checked, &ty, expr.range())?))
+    }
+
+    /// Checks the construction of the struct `name`, the type arguments of a generic struct
+    /// are inferred when `name` is its template.
+    fn check_construct(
+        &mut self,
+        name: String,
+        args: &[item::Arg],
+        expected: Option<&Type>,
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let fields = self.structs[&name].fields.clone();
+        if !self.inside(&name) {
+            if let Some((ident, ..)) = fields
+                .iter()
+                .find(|(.., visibility)| *visibility == Visibility::Private)
+            {
+                return Err(CheckError::PrivateField(ident.clone()).span(range));
+            }
+        }
+
+        let args = self.field_args(&name, args, range.clone())?;
+        let tys = fields.into_iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+        let (name, fields) = match self.instances.get(&name).cloned() {
+            Some((generic, _)) => {
+                let params = self.generics[&generic].params.clone();
+                let ty = Type::Struct(name);
+                let (type_args, fields) =
+                    self.infer(&params, &args, &tys, &ty, expected, range.clone())?;
+
+                let name = self
+                    .instantiate_struct(&generic, type_args)
+                    .map_err(|error| error.span(range))?;
+
+                (name, fields)
+            }
+            None => {
+                let fields = args
+                    .iter()
+                    .zip(tys.iter())
+                    .map(|(arg, ty)| {
+                        let expr = self.check_expr(arg, Some(ty))?;
+                        self.coerce(expr, ty, arg.range())
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                (name, fields)
+            }
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Struct {
+                ident: name.clone(),
+                fields,
+            },
+            ty: Type::Struct(name),
+        })
+    }
+
+    /// Returns the arguments of a construction of the struct `name` in the order of its
+    /// fields, arguments are matched to fields by position until the first named one.
+    fn field_args<'a>(
+        &self,
+        name: &str,
+        args: &'a [item::Arg],
+        range: Range<usize>,
+    ) -> Result<Vec<&'a item::Expr>, Spanned<CheckError>> {
+        let fields = &self.structs[name].fields;
+        let mut ordered = vec![None; fields.len()];
+        let mut named = false;
+        for (i, arg) in args.iter().enumerate() {
+            let index = match &arg.ident {
+                Some(ident) => {
+                    named = true;
+                    fields
+                        .iter()
+                        .position(|field| field.0 == ident.value)
+                        .ok_or_else(|| {
+                            CheckError::UnknownField {
+                                ty: Type::Struct(name.to_owned()),
+                                ident: ident.value.clone(),
+                            }
+                            .span(ident.range())
+                        })?
+                }
+                None if named => {
+                    return Err(CheckError::PositionalAfterNamed.span(arg.expr.range()))
+                }
+                None if i >= fields.len() => {
+                    return Err(CheckError::WrongArgCount {
+                        expected: fields.len(),
+                        found: args.len(),
+                    }
+                    .span(range))
+                }
+                None => i,
+            };
+
+            if ordered[index].is_some() {
+                let ident = fields[index].0.clone();
+                return Err(CheckError::DuplicateArg(ident).span(arg.expr.range()));
+            }
+
+            ordered[index] = Some(&arg.expr);
+        }
+
+        let missing = fields
+            .iter()
+            .zip(ordered.iter())
+            .filter(|(_, expr)| expr.is_none())
+            .map(|((ident, ..), _)| ident.clone())
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            return Err(CheckError::MissingFields(missing).span(range));
+        }
+
+        Ok(ordered.into_iter().flatten().collect())
+    }
+
+    /// Returns the payload types of the variant `ident` of the enum `name`.
+    fn variant(&self, name: &str, ident: &str) -> Option<&Vec<Type>> {
+        self.enums[name]
+            .variants
+            .iter()
+            .find(|(variant, _)| variant == ident)
+            .map(|(_, fields)| fields)
+    }
+
+    /// Checks the construction of an enum variant like `Shape.Circle(1.0)`.
+    fn check_variant(
+        &mut self,
+        name: String,
+        ident: &Spanned<String>,
+        args: &[&item::Expr],
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let fields = self
+            .variant(&name, ident)
+            .cloned()
+            .expect("The caller ensures the variant exists.");
+
+        if fields.len() != args.len() {
+            return Err(CheckError::WrongArgCount {
+                expected: fields.len(),
+                found: args.len(),
+            }
+            .span(range));
+        }
+
+        let args = args
+            .iter()
+            .zip(fields.iter())
+            .map(|(arg, ty)| {
+                let expr = self.check_expr(arg, Some(ty))?;
+                self.coerce(expr, ty, arg.range())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Expr {
+            kind: ExprKind::Variant {
+                ident: name.clone(),
+                variant: ident.value.clone(),
+                args,
+            },
+            ty: Type::Enum(name),
+        })
+    }
+
+    /// Checks the header of a loop, declaring its bindings in the current scope.
+    fn check_loop(&mut self, kind: &item::Loop) -> Result<Loop, Spanned<CheckError>> {
+        match kind {
+            item::Loop::Infinite => Ok(Loop::Infinite),
+            item::Loop::While(condition) => {
+                let checked = self.check_expr(condition, Some(&Type::Bool))?;
+                Ok(Loop::While(self.coerce(
+                    checked,
+                    &Type::Bool,
+                    condition.range(),
+                )?))
+            }
+            item::Loop::Range {
+                ident,
+                start: start_expr,
+                end: end_expr,
+                inclusive,
+            } => {
+                let mut start = self.check_expr(start_expr, None)?;
+                let end = self.check_expr(end_expr, Some(&start.ty))?;
+                if start.ty != end.ty && is_literal(start_expr) {
+                    start = self.check_expr(start_expr, Some(&end.ty))?;
+                }
+
+                if !matches!(start.ty, Type::Int(_)) {
+                    return Err(CheckError::MismatchedTypes {
+                        expected: Type::Int(IntTy::Int),
+                        found: start.ty,
+                    }
+                    .span(start_expr.range()));
+                }
+
+                let end = self.coerce(end, &start.ty, end_expr.range())?;
+                Ok(Loop::Range {
+                    ident: self.declare(ident, start.ty.clone()),
+                    start,
+                    end,
+                    inclusive: *inclusive,
+                })
+            }
+            item::Loop::Optional { ident, expr } => {
+                let checked = self.check_expr(expr, None)?;
+                let Type::Optional(ty) = &checked.ty else {
+                    return Err(CheckError::NotOptional(checked.ty).span(expr.range()));
+                };
+
+                Ok(Loop::Optional {
+                    ident: self.declare(ident, (**ty).clone()),
+                    expr: checked,
+                })
+            }
+        }
+    }
+
+    /// Returns how many loops a `break` or `continue` with `label` exits.
+    fn resolve_loop(
+        &self,
+        label: &Option<Spanned<String>>,
+        range: Range<usize>,
+    ) -> Result<usize, Spanned<CheckError>> {
+        let Some(label) = label else {
+            return match self.loops.is_empty() {
+                true => Err(CheckError::OutsideLoop.span(range)),
+                false => Ok(0),
+            };
+        };
+
+        self.loops
+            .iter()
+            .rev()
+            .position(|other| other.label.as_deref() == Some(&label.value))
+            .ok_or_else(|| CheckError::UnknownLabel(label.value.clone()).span(label.range()))
+    }
+
+    /// Checks an expression, `expected` only guides the types of literals and is not enforced.
+    fn check_expr(
+        &mut self,
+        expr: &item::Expr,
+        expected: Option<&Type>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        match expr {
+            ExprTree::Value(value) => self.check_value(value, expected),
+            ExprTree::Enclosed(expr) => self.check_expr(expr, expected),
+            ExprTree::Expression {
+                left: left_expr,
+                operator,
+                right: right_expr,
+            } => {
+                let expected = match operator.value {
+                    operator if operator.is_logical() => Some(&Type::Bool),
+                    operator if operator.is_comparison() => None,
+                    _ => expected,
+                };
+
+                let mut left = self.check_expr(left_expr, expected)?;
+                let right = self.check_expr(right_expr, Some(&left.ty))?;
+                if left.ty != right.ty && is_literal(left_expr) {
+                    left = self.check_expr(left_expr, Some(&right.ty))?;
+                }
+
+                let valid = left.ty == right.ty
+                    && match operator.value {
+                        Operator::And | Operator::Or => left.ty == Type::Bool,
+                        Operator::Equal | Operator::NotEqual => {
+                            left.ty.is_numeric() || matches!(left.ty, Type::Bool | Type::String)
+                        }
+                        Operator::Percent => matches!(left.ty, Type::Int(_)),
+                        // `+` concatenates strings, which compare by their bytes.
+                        operator if operator == Operator::Plus || operator.is_comparison() => {
+                            left.ty.is_numeric() || left.ty == Type::String
+                        }
+                        _ => left.ty.is_numeric(),
+                    };
+
+                if !valid {
+                    return Err(CheckError::InvalidOperands {
+                        operator: operator.value,
+                        left: left.ty,
+                        right: right.ty,
+                    }
+                    .span(operator.range()));
+                }
+
+                let ty = match operator.value.is_comparison() {
+                    true => Type::Bool,
+                    false => left.ty.clone(),
+                };
+
+                Ok(Expr {
+                    ty,
+                    kind: ExprKind::Binary {
+                        left: Box::new(left),
+                        operator: operator.value,
+                        right: Box::new(right),
+                    },
+                })
+            }
+        }
+    }
+
+    fn check_value(
+        &mut self,
+        value: &Spanned<Value>,
+        expected: Option<&Type>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        // Only the value itself may be a reference to the receiver, not the ones inside of it.
+        let borrow = std::mem::take(&mut self.borrow);
+        match &value.value {
+            Value::Ident(ident) => match (self.lookup_capturing(ident), &self.receiver) {
+                (Some((unique, _)), _)
+                    if self.borrowed.contains(&unique) && self.captured(&unique) =>
+                {
+                    Err(CheckError::CapturedReceiver.span(value.range()))
+                }
+                (Some((unique, _)), _)
+                    if self.borrowed.contains(&unique) && borrow == Borrow::Owned =>
+                {
+                    Err(CheckError::EscapingReceiver.span(value.range()))
+                }
+                (Some((unique, ty)), _) => Ok(Expr {
+                    kind: ExprKind::Local(unique),
+                    ty,
+                }),
+                (None, Some(_)) if ident == "self" && !self.closures.is_empty() => {
+                    Err(CheckError::CapturedReceiver.span(value.range()))
+                }
+                (None, Some(ty)) if ident == "self" => {
+                    let ty = match borrow == Borrow::Local && expected != Some(ty) {
+                        true => Type::Reference(Box::new(ty.clone())),
+                        false => ty.clone(),
+                    };
+
+                    Ok(Expr {
+                        kind: ExprKind::Receiver,
+                        ty,
+                    })
+                }
+                _ => self
+                    .function_value(ident)
+                    .ok_or_else(|| CheckError::UnknownIdent(ident.clone()).span(value.range())),
+            },
+            Value::Literal {
+                value: literal,
+                kind,
+            } => check_literal(literal, *kind, expected).map_err(|error| error.span(value.range())),
+            Value::None => match expected {
+                Some(ty @ Type::Optional(_)) => Ok(Expr {
+                    kind: ExprKind::None,
+                    ty: ty.clone(),
+                }),
+                _ => Err(CheckError::UnexpectedNone.span(value.range())),
+            },
+            Value::Unary { operator, expr } => {
+                // Fold negative literals so that e.g. `-128` fits into an `i8`.
+                if let (
+                    UnaryOperator::Negate,
+                    ExprTree::Value(Spanned {
+                        value:
+                            Value::Literal {
+                                value: literal,
+                                kind,
+                            },
+                        ..
+                    }),
+                ) = (operator, &**expr)
+                {
+                    return check_literal(&format!("-{literal}"), *kind, expected)
+                        .map_err(|error| error.span(value.range()));
+                }
+
+                if let UnaryOperator::Reference = operator {
+                    let expected = match expected {
+                        Some(Type::Reference(ty)) => Some(&**ty),
+                        Some(Type::Optional(ty)) => match &**ty {
+                            Type::Reference(ty) => Some(&**ty),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+
+                    let checked = self.check_expr(expr, expected)?;
+                    if checked.ty == Type::Void {
+                        return Err(CheckError::VoidValue.span(expr.range()));
+                    }
+
+                    return Ok(Expr {
+                        ty: Type::Reference(Box::new(checked.ty.clone())),
+                        kind: ExprKind::Unary {
+                            operator: *operator,
+                            expr: Box::new(checked),
+                        },
+                    });
+                }
+
+                let checked = self.check_expr(expr, expected)?;
+                let valid = match (operator, &checked.ty) {
+                    (UnaryOperator::Negate, Type::Int(ty)) => ty.signed(),
+                    (UnaryOperator::Negate, Type::Float(_)) => true,
+                    (UnaryOperator::Not, Type::Bool) => true,
+                    _ => false,
+                };
+
+                if !valid {
+                    return Err(CheckError::InvalidOperand {
+                        operator: *operator,
+                        ty: checked.ty,
+                    }
+                    .span(value.range()));
+                }
+
+                Ok(Expr {
+                    ty: checked.ty.clone(),
+                    kind: ExprKind::Unary {
+                        operator: *operator,
+                        expr: Box::new(checked),
+                    },
+                })
+            }
+            Value::Call { expr, args } => {
+                if let Some(Type::Struct(name)) = self.type_path(expr) {
+                    return self.check_construct(name, args, expected, value.range());
+                }
+
+                if let Some(generic) = self.generic_path(expr) {
+                    let name = self
+                        .template(&generic)
+                        .map_err(|error| error.span(value.range()))?;
+
+                    return self.check_construct(name, args, expected, value.range());
+                }
+
+                if let Some(ident) = args.iter().find_map(|arg| arg.ident.as_ref()) {
+                    return Err(CheckError::NamedArg.span(ident.range()));
+                }
+
+                let (ident, signature, receiver) = match &**expr {
+                    ExprTree::Value(Spanned {
+                        start,
+                        end,
+                        value: Value::Ident(ident),
+                    }) if self.lookup(ident).is_none() => {
+                        let Some(signature) = self.fns.get(ident).cloned() else {
+                            // Functions of the prelude can be shadowed by any item.
+                            if let Some(builtin) = Builtin::from_ident(ident) {
+                                return self.check_builtin(builtin, args, value.range());
+                            }
+
+                            return Err(CheckError::UnknownIdent(ident.clone()).span(*start..*end));
+                        };
+
+                        if !signature.params.is_empty() {
+                            let (type_args, args, ty) =
+                                self.check_generic_call(&signature, args, expected, value.range())?;
+                            let ident = self
+                                .instantiate_fn(ident, type_args)
+                                .map_err(|error| error.span(value.range()))?;
+
+                            return Ok(Expr {
+                                kind: ExprKind::Call { ident, args },
+                                ty,
+                            });
+                        }
+
+                        (ident.clone(), signature, None)
+                    }
+                    ExprTree::Value(Spanned {
+                        value: Value::Field { expr, ident },
+                        ..
+                    }) => {
+                        if let Some(generic) = self.generic_path(expr) {
+                            return self.check_generic_method_call(
+                                &generic,
+                                ident,
+                                args,
+                                expected,
+                                value.range(),
+                            );
+                        }
+
+                        let (ty, receiver) = match self.type_path(expr) {
+                            Some(Type::Enum(name)) if self.variant(&name, ident).is_some() => {
+                                let args = args.iter().map(|arg| &arg.expr).collect::<Vec<_>>();
+                                return self.check_variant(name, ident, &args, value.range());
+                            }
+                            Some(ty) => (ty, None),
+                            None => {
+                                self.borrow = Borrow::Base;
+                                let checked = self.check_expr(expr, None)?;
+                                (checked.ty.clone(), Some(checked))
+                            }
+                        };
+
+                        let ((path, signature), receiver) =
+                            match (self.method(&ty, ident), receiver) {
+                                (Ok(method), receiver) => (method, receiver),
+                                // Fields holding functions are called like methods.
+                                (Err(error), Some(receiver)) => {
+                                    let Ok(field_ty @ Type::Fn { .. }) =
+                                        self.field(&receiver.ty, ident, false)
+                                    else {
+                                        return Err(error);
+                                    };
+
+                                    let callee = Expr {
+                                        kind: ExprKind::Field {
+                                            expr: Box::new(receiver),
+                                            ident: ident.value.clone(),
+                                        },
+                                        ty: field_ty,
+                                    };
+
+                                    return self.check_call_value(
+                                        callee,
+                                        args,
+                                        expr.range(),
+                                        value.range(),
+                                    );
+                                }
+                                (Err(error), None) => return Err(error),
+                            };
+
+                        if signature.receiver != receiver.is_some() {
+                            return Err(CheckError::MismatchedReceiver.span(value.range()));
+                        }
+
+                        // The list methods modify the list they are called on.
+                        if receiver.as_ref().is_some_and(|receiver| {
+                            matches!(receiver.kind, ExprKind::Slice { .. })
+                                && arrays::list_method(&receiver.ty, &ident.value).is_some()
+                        }) {
+                            return Err(CheckError::AssignedSlice.span(value.range()));
+                        }
+
+                        (path, signature, receiver)
+                    }
+                    _ => {
+                        let callee = self.check_expr(expr, None)?;
+                        return self.check_call_value(callee, args, expr.range(), value.range());
+                    }
+                };
+
+                let args = self.check_args(args, &signature.args, value.range())?;
+
+                let kind = match receiver {
+                    Some(receiver) => ExprKind::MethodCall {
+                        ident,
+                        receiver: Box::new(receiver),
+                        args,
+                    },
+                    None => ExprKind::Call { ident, args },
+                };
+
+                Ok(Expr {
+                    kind,
+                    ty: signature.ty,
+                })
+            }
+            Value::Field { expr, ident } => {
+                match self.type_path(expr) {
+                    Some(Type::Enum(name)) if self.variant(&name, ident).is_some() => {
+                        return self.check_variant(name, ident, &[], value.range());
+                    }
+                    Some(ty) => {
+                        return Err(CheckError::UnknownField {
+                            ty,
+                            ident: ident.value.clone(),
+                        }
+                        .span(ident.range()))
+                    }
+                    None => {}
+                }
+
+                self.borrow = Borrow::Base;
+                let checked = self.check_expr(expr, None)?;
+                let ty = checked.ty.deref();
+                if ident.value == "len" && (ty.element().is_some() || *ty == Type::String) {
+                    return Ok(Expr {
+                        kind: ExprKind::Len(Box::new(checked)),
+                        ty: Type::Int(IntTy::Int),
+                    });
+                }
+
+                let ty = self.field(&checked.ty, ident, false)?;
+
+                Ok(Expr {
+                    kind: ExprKind::Field {
+                        expr: Box::new(checked),
+                        ident: ident.value.clone(),
+                    },
+                    ty,
+                })
+            }
+            Value::Cast { expr, ty } => {
+                let ty = self.resolve_ty(ty)?;
+                let checked = self.check_expr(expr, None)?;
+                if !valid_cast(&checked.ty, &ty) {
+                    return Err(CheckError::InvalidCast {
+                        from: checked.ty,
+                        to: ty,
+                    }
+                    .span(value.range()));
+                }
+
+                Ok(Expr {
+                    kind: ExprKind::Cast(Box::new(checked)),
+                    ty,
+                })
+            }
+            Value::If {
+                condition,
+                block: then_block,
+                else_block,
+            } => {
+                let condition = self.check_expr(condition, Some(&Type::Bool))?;
+                if condition.ty != Type::Bool {
+                    return Err(CheckError::MismatchedTypes {
+                        expected: Type::Bool,
+                        found: condition.ty,
+                    }
+                    .span(value.range()));
+                }
+
+                let (reachable, errors) = (self.reachable, self.errors.len());
+                let mut block = self.check_block(then_block, expected);
+                let then_reachable = self.reachable;
+                let then_failed = self.errors.len() > errors;
+                self.reachable = reachable;
+
+                let Some(else_block) = else_block else {
+                    // Without an `else` there is no value to produce.
+                    discard_trailing(&mut block, then_block);
+                    return Ok(Expr {
+                        kind: ExprKind::If {
+                            condition: Box::new(condition),
+                            block,
+                            else_block: None,
+                        },
+                        ty: Type::Void,
+                    });
+                };
+
+                let then_ty = block.ty();
+                let errors = self.errors.len();
+                let mut else_checked = self.check_block(else_block, expected.or(Some(&then_ty)));
+                let else_reachable = self.reachable;
+                let else_failed = self.errors.len() > errors;
+                self.reachable = then_reachable || else_reachable;
+
+                // A branch which never finishes doesn't have to produce a value. The type of a
+                // branch with errors is unknown, so the other one decides it.
+                let ty = match (then_ty, else_checked.ty()) {
+                    (then_ty, _) if else_failed => then_ty,
+                    (_, else_ty) if then_failed => else_ty,
+                    (then_ty, else_ty) if then_ty == else_ty => then_ty,
+                    (Type::Void, else_ty) if !then_reachable => else_ty,
+                    (then_ty, Type::Void) if !else_reachable => then_ty,
+                    (Type::Optional(then_ty), else_ty) if *then_ty == else_ty => {
+                        let ty = Type::Optional(then_ty);
+                        coerce_trailing(&mut else_checked, &ty);
+                        ty
+                    }
+                    (then_ty, Type::Optional(else_ty)) if then_ty == *else_ty => {
+                        let ty = Type::Optional(else_ty);
+                        coerce_trailing(&mut block, &ty);
+                        ty
+                    }
+                    (then_ty, else_ty) => {
+                        return Err(CheckError::MismatchedTypes {
+                            expected: then_ty,
+                            found: else_ty,
+                        }
+                        .span(value.range()))
+                    }
+                };
+
+                Ok(Expr {
+                    kind: ExprKind::If {
+                        condition: Box::new(condition),
+                        block,
+                        else_block: Some(else_checked),
+                    },
+                    ty,
+                })
+            }
+            Value::Match { expr, arms } => self.check_match(expr, arms, expected),
+            Value::Fn { args, ty, block } => {
+                self.check_closure(args, ty.as_ref(), block, value.range())
+            }
+            Value::Array(exprs) => self.check_array(exprs, expected, value.range()),
+            Value::Repeat { expr, len } => self.check_repeat(expr, *len, expected),
+            Value::Index { expr, index } => self.check_index(expr, index, value.start),
+            Value::Slice {
+                expr,
+                start,
+                end,
+                inclusive,
+            } => self.check_slice(
+                expr,
+                start.as_deref(),
+                end.as_deref(),
+                *inclusive,
+                value.start,
+            ),
+            Value::Format(parts) => self.check_format(parts),
+        }
+    }
+
+    /// Checks an interpolated string literal, the interpolated values are converted into strings
+    /// like they are by `as string`.
+    fn check_format(&mut self, parts: &[item::FormatPart]) -> Result<Expr, Spanned<CheckError>> {
+        let mut checked = Vec::new();
+        for part in parts {
+            let expr = match part {
+                item::FormatPart::Literal(literal) => Expr {
+                    kind: ExprKind::Literal(Literal::String(literal.clone())),
+                    ty: Type::String,
+                },
+                item::FormatPart::Expr(expr) => self.check_printable(expr)?,
+            };
+
+            checked.push(expr);
+        }
+
+        Ok(Expr {
+            kind: ExprKind::Format(checked),
+            ty: Type::String,
+        })
+    }
+
+    /// Checks a value which is interpolated or printed, converting it into a string.
+    fn check_printable(&mut self, expr: &item::Expr) -> Result<Expr, Spanned<CheckError>> {
+        let value = self.check_expr(expr, None)?;
+        let printable = |ty: &Type| ty.is_numeric() || matches!(ty, Type::Bool | Type::String);
+        let valid = match &value.ty {
+            Type::Optional(inner) => printable(inner),
+            ty => printable(ty),
+        };
+
+        if !valid {
+            return Err(CheckError::NotPrintable(value.ty).span(expr.range()));
+        }
+
+        Ok(match value.ty {
+            Type::String => value,
+            _ => Expr {
+                kind: ExprKind::ToString(Box::new(value)),
+                ty: Type::String,
+            },
+        })
+    }
+
+    /// Checks the arguments of a call against the types of the arguments the callee takes.
+    fn check_args(
+        &mut self,
+        args: &[item::Arg],
+        tys: &[Type],
+        range: Range<usize>,
+    ) -> Result<Vec<Expr>, Spanned<CheckError>> {
+        if tys.len() != args.len() {
+            return Err(CheckError::WrongArgCount {
+                expected: tys.len(),
+                found: args.len(),
+            }
+            .span(range));
+        }
+
+        args.iter()
+            .zip(tys.iter())
+            .map(|(arg, ty)| {
+                let expr = self.check_expr(&arg.expr, Some(ty))?;
+                self.coerce(expr, ty, arg.expr.range())
+            })
+            .collect()
+    }
+
+    /// Checks a `match`, every arm gets its own scope holding the bindings of its pattern.
+    fn check_match(
+        &mut self,
+        expr: &item::Expr,
+        arms: &[item::Arm],
+        expected: Option<&Type>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let checked = self.check_expr(expr, None)?;
+        if checked.ty == Type::Void {
+            return Err(CheckError::VoidValue.span(expr.range()));
+        }
+
+        let reachable = self.reachable;
+        let mut any_reachable = false;
+        let mut checked_arms = Vec::new();
+        let mut ty = None;
+        for arm in arms.iter() {
+            self.scopes.push(HashMap::new());
+            let pattern = match self.check_pattern(&arm.pattern, &checked.ty) {
+                Ok(pattern) => pattern,
+                Err(error) => {
+                    self.scopes.pop();
+                    self.reachable = reachable;
+                    return Err(error);
+                }
+            };
+
+            self.reachable = reachable;
+            let errors = self.errors.len();
+            let mut block = self.check_block(&arm.block, expected.or(ty.as_ref()));
+            self.scopes.pop();
+
+            // An arm which never finishes doesn't have to produce a value, neither does one with
+            // errors since its type is unknown.
+            let arm_ty = block.ty();
+            let failed = self.errors.len() > errors;
+            if !failed && (self.reachable || arm_ty != Type::Void) {
+                let range = arm
+                    .block
+                    .trailing_expr
+                    .as_ref()
+                    .map_or(arm.pattern.range(), |expr| expr.range());
+
+                ty = Some(match ty {
+                    None => arm_ty,
+                    Some(ty) if ty == arm_ty => ty,
+                    Some(Type::Optional(inner)) if *inner == arm_ty => {
+                        coerce_trailing(&mut block, &Type::Optional(inner.clone()));
+                        Type::Optional(inner)
+                    }
+                    Some(ty) if arm_ty == Type::Optional(Box::new(ty.clone())) => {
+                        for Arm { block, .. } in checked_arms.iter_mut() {
+                            if block.ty() == ty {
+                                coerce_trailing(block, &arm_ty);
+                            }
+                        }
+
+                        arm_ty
+                    }
+                    Some(ty) => {
+                        return Err(CheckError::MismatchedTypes {
+                            expected: ty,
+                            found: arm_ty,
+                        }
+                        .span(range))
+                    }
+                });
+            }
+
+            any_reachable |= self.reachable;
+            checked_arms.push(Arm { pattern, block });
+        }
+
+        self.reachable = any_reachable;
+
+        let patterns = checked_arms
+            .iter()
+            .map(|arm| &arm.pattern)
+            .collect::<Vec<_>>();
+
+        for (i, arm) in arms.iter().enumerate() {
+            if !self.is_useful(&patterns[..i], patterns[i], &checked.ty) {
+                self.warnings
+                    .push(CheckWarning::UnreachableArm.span(arm.pattern.range()));
+            }
+        }
+
+        let missing = self.missing_patterns(&patterns, &checked.ty);
+        if !missing.is_empty() {
+            return Err(CheckError::NonExhaustive(missing).span(expr.range()));
+        }
+
+        Ok(Expr {
+            kind: ExprKind::Match {
+                expr: Box::new(checked),
+                arms: checked_arms,
+            },
+            ty: ty.unwrap_or_default(),
+        })
+    }
+
+    /// Checks a pattern matching values of type `ty`, declaring its bindings in the current
+    /// scope. Patterns match the value of an optional and the value behind a reference directly,
+    /// so a binding only matches optionals holding a value while `_` matches every value.
+    fn check_pattern(
+        &mut self,
+        pattern: &Spanned<Pattern>,
+        ty: &Type,
+    ) -> Result<typed::Pattern, Spanned<CheckError>> {
+        let range = pattern.range();
+        match (&pattern.value, ty) {
+            (Pattern::Wildcard, _) => Ok(typed::Pattern::Wildcard),
+            (Pattern::None, Type::Optional(_)) => Ok(typed::Pattern::None),
+            (Pattern::None, _) => Err(CheckError::UnexpectedNone.span(range)),
+            (_, Type::Optional(inner)) => Ok(typed::Pattern::Some(Box::new(
+                self.check_pattern(pattern, inner)?,
+            ))),
+            (Pattern::Binding(ident), ty) => {
+                let scope = self
+                    .scopes
+                    .last()
+                    .expect("Patterns are checked in a scope.");
+                if scope.contains_key(ident) {
+                    return Err(CheckError::DuplicateItem(ident.clone()).span(range));
+                }
+
+                Ok(typed::Pattern::Binding(self.declare(ident, ty.clone())))
+            }
+            (_, Type::Reference(inner)) => Ok(typed::Pattern::Deref(Box::new(
+                self.check_pattern(pattern, inner)?,
+            ))),
+            (Pattern::Literal { value, kind }, ty) => {
+                let checked = check_literal(value, *kind, Some(ty))
+                    .map_err(|error| error.span(range.clone()))?;
+
+                match checked.kind {
+                    ExprKind::Literal(literal) if checked.ty == *ty => {
+                        Ok(typed::Pattern::Literal(literal))
+                    }
+                    _ => Err(CheckError::MismatchedTypes {
+                        expected: ty.clone(),
+                        found: checked.ty,
+                    }
+                    .span(range)),
+                }
+            }
+            (
+                Pattern::Variant {
+                    ty: name,
+                    variant,
+                    fields,
+                },
+                ty,
+            ) => {
+                if !self.enums.contains_key(&name.value) {
+                    return Err(CheckError::UnknownType(name.value.clone()).span(name.range()));
+                }
+
+                let found = Type::Enum(name.value.clone());
+                if found != *ty {
+                    return Err(CheckError::MismatchedTypes {
+                        expected: ty.clone(),
+                        found,
+                    }
+                    .span(range));
+                }
+
+                let Some(tys) = self.variant(&name.value, variant).cloned() else {
+                    return Err(CheckError::UnknownField {
+                        ty: found,
+                        ident: variant.value.clone(),
+                    }
+                    .span(variant.range()));
+                };
+
+                if let Some(ident) = fields.iter().find_map(|field| field.ident.as_ref()) {
+                    return Err(CheckError::NamedArg.span(ident.range()));
+                }
+
+                if tys.len() != fields.len() {
+                    return Err(CheckError::WrongArgCount {
+                        expected: tys.len(),
+                        found: fields.len(),
+                    }
+                    .span(range));
+                }
+
+                Ok(typed::Pattern::Variant {
+                    ident: name.value.clone(),
+                    variant: variant.value.clone(),
+                    fields: fields
+                        .iter()
+                        .zip(tys.iter())
+                        .map(|(field, ty)| self.check_pattern(&field.pattern, ty))
+                        .collect::<Result<_, _>>()?,
+                })
+            }
+            (Pattern::Struct { ident, fields }, ty) => {
+                if !self.structs.contains_key(&ident.value)
+                    && self.generic_struct(&ident.value).is_none()
+                {
+                    return Err(CheckError::UnknownType(ident.value.clone()).span(ident.range()));
+                }
+
+                // Patterns name generic structs without type arguments.
+                match ty {
+                    Type::Struct(name) if self.base_name(name) == ident.value => {
+                        self.check_struct_pattern(&name.clone(), fields, range)
+                    }
+                    ty => Err(CheckError::MismatchedTypes {
+                        expected: ty.clone(),
+                        found: Type::Struct(ident.value.clone()),
+                    }
+                    .span(range)),
+                }
+            }
+        }
+    }
+
+    /// Checks the field patterns of the struct `name`, which are matched to fields like the
+    /// arguments of a construction. Fields without a pattern match any value.
+    fn check_struct_pattern(
+        &mut self,
+        name: &str,
+        args: &[item::PatternArg],
+        range: Range<usize>,
+    ) -> Result<typed::Pattern, Spanned<CheckError>> {
+        let fields = self.structs[name].fields.clone();
+        let mut checked = vec![None; fields.len()];
+        let mut named = false;
+        for (i, arg) in args.iter().enumerate() {
+            let index = match &arg.ident {
+                Some(ident) => {
+                    named = true;
+                    fields
+                        .iter()
+                        .position(|field| field.0 == ident.value)
+                        .ok_or_else(|| {
+                            CheckError::UnknownField {
+                                ty: Type::Struct(name.to_owned()),
+                                ident: ident.value.clone(),
+                            }
+                            .span(ident.range())
+                        })?
+                }
+                None if named => {
+                    return Err(CheckError::PositionalAfterNamed.span(arg.pattern.range()))
+                }
+                None if i >= fields.len() => {
+                    return Err(CheckError::WrongArgCount {
+                        expected: fields.len(),
+                        found: args.len(),
+                    }
+                    .span(range))
+                }
+                None => i,
+            };
+
+            let (ident, ty, visibility) = &fields[index];
+            if *visibility == Visibility::Private && !self.inside(name) {
+                return Err(CheckError::PrivateField(ident.clone()).span(arg.pattern.range()));
+            }
+
+            if checked[index].is_some() {
+                return Err(CheckError::DuplicateArg(ident.clone()).span(arg.pattern.range()));
+            }
+
+            checked[index] = Some(self.check_pattern(&arg.pattern, ty)?);
+        }
+
+        Ok(typed::Pattern::Struct {
+            ident: name.to_owned(),
+            fields: checked
+                .into_iter()
+                .map(|pattern| pattern.unwrap_or(typed::Pattern::Wildcard))
+                .collect(),
+        })
+    }
+}
+
+/// Applies the reference and optional of `ty` to the type it names.
+fn wrap_ty(resolved: Type, ty: &item::Ty) -> Type {
+    let resolved = match ty.reference {
+        true => Type::Reference(Box::new(resolved)),
+        false => resolved,
+    };
+
+    match ty.optional {
+        true => Type::Optional(Box::new(resolved)),
+        false => resolved,
+    }
+}
+
+/// Moves the trailing expression of a checked block into its statements.
+fn discard_trailing(checked: &mut Block, block: &item::Block) {
+    if let (Some(expr), Some(trailing)) = (checked.trailing_expr.take(), &block.trailing_expr) {
+        checked
+            .statements
+            .push(Statement::Expr(expr.value).span(trailing.range()));
+    }
+}
+
+/// Wraps the trailing expression of a block into `T?`, the caller ensures it is of type `T`.
+fn coerce_trailing(block: &mut Block, ty: &Type) {
+    if let Some(expr) = block.trailing_expr.take() {
+        let Spanned { start, end, value } = *expr;
+        let expr = Expr {
+            kind: ExprKind::Cast(Box::new(value)),
+            ty: ty.clone(),
+        };
+
+        block.trailing_expr = Some(Box::new(expr.span(start..end)));
+    }
+}
+
+/// Returns whether `attributes` include `@no_mangle`.
+fn no_mangle(attributes: &[Spanned<String>]) -> bool {
+    attributes
+        .iter()
+        .any(|attribute| attribute.value == "no_mangle")
+}
+
+/// Types a literal, preferring `expected` if the literal fits it.
+fn check_literal(
+    value: &str,
+    kind: LiteralKind,
+    expected: Option<&Type>,
+) -> Result<Expr, CheckError> {
+    let expected = match expected {
+        Some(Type::Optional(ty)) => Some(&**ty),
+        expected => expected,
+    };
+
+    let (literal, ty) = match (kind, expected) {
+        (LiteralKind::Bool, _) => (Some(Literal::Bool(value == "true")), Type::Bool),
+        (LiteralKind::Int, Some(Type::Float(ty))) | (LiteralKind::Float, Some(Type::Float(ty))) => {
+            (value.parse().map(Literal::Float).ok(), Type::Float(*ty))
+        }
+        (LiteralKind::Int, expected) => {
+            let ty = match expected {
+                Some(Type::Int(ty)) => *ty,
+                _ => IntTy::Int,
+            };
+
+            let literal = value
+                .parse::<i128>()
+                .ok()
+                .filter(|value| (ty.min()..=ty.max()).contains(value));
+
+            (literal.map(Literal::Int), Type::Int(ty))
+        }
+        (LiteralKind::Float, _) => (
+            value.parse().map(Literal::Float).ok(),
+            Type::Float(typed::FloatTy::Float),
+        ),
+        (LiteralKind::String, _) => (Some(Literal::String(value.to_owned())), Type::String),
+    };
+
+    match literal {
+        Some(literal) => Ok(Expr {
+            kind: ExprKind::Literal(literal),
+            ty,
+        }),
+        None => Err(CheckError::LiteralOutOfRange {
+            value: value.to_owned(),
+            ty,
+        }),
+    }
+}
+
+/// Returns whether `expr` only consists of literals, such expressions can adapt their type.
+fn is_literal(expr: &item::Expr) -> bool {
+    match expr {
+        ExprTree::Value(Spanned {
+            value: Value::Literal { .. },
+            ..
+        }) => true,
+        ExprTree::Value(Spanned {
+            value: Value::Unary { expr, .. },
+            ..
+        }) => is_literal(expr),
+        ExprTree::Value(_) => false,
+        ExprTree::Expression { left, right, .. } => is_literal(left) && is_literal(right),
+        ExprTree::Enclosed(expr) => is_literal(expr),
+    }
+}
+
+/// Returns whether `from` is an array converting into the list `to`.
+fn is_array_of(from: &Type, to: &Type) -> bool {
+    matches!((from, to), (Type::Array(from, _), Type::List(to)) if from == to)
+}
+
+/// Returns whether an explicit `from as to` cast is allowed.
+fn valid_cast(from: &Type, to: &Type) -> bool {
+    match (from, to) {
+        (from, to) if from == to => true,
+        (from, to) if from.is_numeric() && to.is_numeric() => true,
+        (Type::Bool, Type::Int(_)) => true,
+        // Addresses only fit into 64 bit integers.
+        (Type::Reference(_), Type::Int(ty)) => ty.bits() == 64,
+        (from, Type::String) => from.is_numeric() || *from == Type::Bool,
+        (from, to) if is_array_of(from, to) => true,
+        (Type::Optional(from), Type::Optional(to)) => valid_cast(from, to),
+        // Unwrapping aborts at runtime if there is no value.
+        (Type::Optional(from), to) => valid_cast(from, to),
+        (from, Type::Optional(to)) => valid_cast(from, to),
+        _ => false,
+    }
+}
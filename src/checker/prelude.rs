@@ -0,0 +1,65 @@
+//! The prelude, functions every program can call without defining them.
+//!
+//! They are implemented by the runtime header. The printing functions take any value which can
+//! be interpolated into a string, so they are checked here instead of through a signature.
+
+use super::{
+    typed::{Builtin, Expr, ExprKind, Type},
+    CheckError, Checker,
+};
+use crate::{
+    parser::item,
+    util::{Span, Spanned},
+};
+use std::ops::Range;
+
+impl Checker {
+    /// Checks a call of `builtin`. `print` takes a value, `println`, `eprintln` and `panic` take
+    /// an optional one and `assert` takes a condition followed by an optional message.
+    pub(super) fn check_builtin(
+        &mut self,
+        builtin: Builtin,
+        args: &[item::Arg],
+        range: Range<usize>,
+    ) -> Result<Expr, Spanned<CheckError>> {
+        let (min, max) = match builtin {
+            Builtin::Print => (1, 1),
+            Builtin::Println | Builtin::Eprintln | Builtin::Panic => (0, 1),
+            Builtin::Assert => (1, 2),
+            Builtin::ReadLine => (0, 0),
+        };
+
+        if !(min..=max).contains(&args.len()) {
+            return Err(CheckError::WrongArgCount {
+                expected: args.len().clamp(min, max),
+                found: args.len(),
+            }
+            .span(range));
+        }
+
+        let mut checked = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            checked.push(match (builtin, i) {
+                (Builtin::Assert, 0) => {
+                    let condition = self.check_expr(&arg.expr, Some(&Type::Bool))?;
+                    self.coerce(condition, &Type::Bool, arg.expr.range())?
+                }
+                _ => self.check_printable(&arg.expr)?,
+            });
+        }
+
+        let ty = match builtin {
+            Builtin::ReadLine => Type::Optional(Box::new(Type::String)),
+            _ => Type::Void,
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Builtin {
+                builtin,
+                args: checked,
+                location: range.start,
+            },
+            ty,
+        })
+    }
+}
@@ -0,0 +1,269 @@
+//! Checks small programs and compares the diagnostics they produce.
+
+use crate::{lexer, parser};
+
+/// Checks `source`, returning the messages of its errors and warnings in source order.
+fn check(source: &str) -> (Vec<String>, Vec<String>) {
+    let tokens = lexer::tokenize(source.bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .expect("The source lexes.");
+
+    let items = parser::parse(tokens)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("The source parses.");
+
+    let (result, warnings) = super::check(items, false);
+    let errors = result.err().unwrap_or_default();
+    (
+        errors.iter().map(|error| error.to_string()).collect(),
+        warnings.iter().map(|warning| warning.to_string()).collect(),
+    )
+}
+
+/// Checks `items` followed by an empty `main`.
+fn check_items(items: &str) -> (Vec<String>, Vec<String>) {
+    check(&format!("{items}\nmain := fn () {{}}\n"))
+}
+
+const TYPES: &str = "
+Color := enum (Red, Green, Blue)
+Shape := enum (Circle(Color), Square(int, Color?), Empty)
+Point := struct (pub x: int, pub y: bool)
+";
+
+/// Checks a function matching `ty` with `arms` next to the [`TYPES`].
+fn check_match(ty: &str, arms: &str) -> (Vec<String>, Vec<String>) {
+    check_items(&format!(
+        "{TYPES}\nf := fn (value: {ty}) -> int {{\n    match value {{ {arms} }}\n}}"
+    ))
+}
+
+#[test]
+fn exhaustive_matches_pass() {
+    let matches = [
+        ("Color", "Color.Red => 1, Color.Green => 2, Color.Blue => 3"),
+        ("Color", "Color.Red => 1, _ => 2"),
+        ("bool", "true => 1, false => 2"),
+        ("int", "0 => 1, n => n"),
+        ("int?", "? => 0, n => n"),
+        (
+            "Shape",
+            "Shape.Circle(_) => 1, Shape.Square(n, _) => n, Shape.Empty => 0",
+        ),
+        ("Point", "Point(_, true) => 1, Point(x, false) => x"),
+    ];
+
+    for (ty, arms) in matches {
+        assert_eq!(check_match(ty, arms), (vec![], vec![]), "{ty}: {arms}");
+    }
+}
+
+#[test]
+fn missing_variants_are_reported() {
+    let (errors, _) = check_match("Color", "Color.Red => 1, Color.Green => 2");
+    assert_eq!(errors, ["Missing patterns \"Color.Blue\"."]);
+}
+
+#[test]
+fn missing_nested_variants_are_reported() {
+    let (errors, _) = check_match(
+        "Shape",
+        "Shape.Circle(Color.Red) => 1, Shape.Square(_, ?) => 2, Shape.Empty => 3",
+    );
+
+    assert_eq!(
+        errors,
+        [
+            "Missing patterns \"Shape.Circle(Color.Green)\", \"Shape.Circle(Color.Blue)\", \
+          \"Shape.Square(_, _)\"."
+        ]
+    );
+}
+
+#[test]
+fn missing_optionals_are_reported() {
+    let (errors, _) = check_match("int?", "1 => 1");
+    assert_eq!(errors, ["Missing patterns \"?\", \"_\"."]);
+
+    let (errors, _) = check_match(
+        "Color?",
+        "Color.Red => 1, Color.Green => 2, Color.Blue => 3",
+    );
+    assert_eq!(errors, ["Missing patterns \"?\"."]);
+}
+
+#[test]
+fn missing_struct_fields_are_reported() {
+    let (errors, _) = check_match("Point", "Point(_, true) => 1, Point(x: 0, y: false) => 2");
+    assert_eq!(errors, ["Missing patterns \"Point(_, false)\"."]);
+}
+
+#[test]
+fn unreachable_arms_are_reported() {
+    let arms = [
+        ("bool", "_ => 1, true => 2"),
+        ("Color", "Color.Red => 1, c => 2, Color.Blue => 3"),
+        ("int?", "? => 0, n => n, 1 => 1"),
+        (
+            "Shape",
+            "Shape.Circle(_) => 1, Shape.Circle(Color.Red) => 2, _ => 3",
+        ),
+        ("Point", "Point(_, _) => 1, Point(0, true) => 2"),
+    ];
+
+    for (ty, arms) in arms {
+        let (errors, warnings) = check_match(ty, arms);
+        assert_eq!(errors, Vec::<String>::new(), "{ty}: {arms}");
+        assert_eq!(warnings, ["Unreachable pattern."], "{ty}: {arms}");
+    }
+}
+
+#[test]
+fn failed_arms_dont_decide_the_type() {
+    let (errors, _) = check("main := fn () {\n    x := match 1 { 1 => \"a\" + 1, _ => 2 };\n}");
+    assert_eq!(errors, ["Cannot apply \"+\" to \"string\" and \"int\"."]);
+}
+
+#[test]
+fn failed_branches_dont_decide_the_type() {
+    let (errors, _) =
+        check("main := fn () {\n    x := if true { \"a\" + 1 } else { 2 };\n    y: int = x;\n}");
+    assert_eq!(errors, ["Cannot apply \"+\" to \"string\" and \"int\"."]);
+
+    let (errors, _) = check("main := fn () {\n    x := if true { 1 } else { \"a\" };\n}");
+    assert_eq!(errors, ["Expected \"int\", found \"string\"."]);
+}
+
+/// Checks a `main` casting the value `expr` of type `ty` as `to`.
+fn check_cast(ty: &str, expr: &str, to: &str) -> Vec<String> {
+    let source = format!(
+        "R := struct (pub x: int)\nmain := fn () {{\n    value: {ty} = {expr};\n    \
+         cast := value as {to};\n}}"
+    );
+
+    check(&source).0
+}
+
+#[test]
+fn valid_casts_pass() {
+    let casts = [
+        ("int", "1", "u8"),
+        ("int", "1", "f32"),
+        ("float", "1.5", "i32"),
+        ("bool", "true", "int"),
+        ("&R", "&R(1)", "int"),
+        ("&R", "&R(1)", "uint"),
+        ("float", "1.5", "string"),
+        ("bool", "true", "string"),
+        ("[int; 2]", "[1, 2]", "[int]"),
+        ("int", "1", "int?"),
+        ("int?", "1", "float"),
+        ("int?", "1", "u8?"),
+    ];
+
+    for (ty, expr, to) in casts {
+        assert_eq!(
+            check_cast(ty, expr, to),
+            Vec::<String>::new(),
+            "{ty} as {to}"
+        );
+    }
+}
+
+#[test]
+fn invalid_casts_are_reported() {
+    let casts = [
+        ("&R", "&R(1)", "i32"),
+        ("string", "\"1\"", "int"),
+        ("int", "1", "bool"),
+        ("R", "R(1)", "int"),
+        ("int?", "1", "bool?"),
+    ];
+
+    for (ty, expr, to) in casts {
+        let error = format!("Cannot cast \"{ty}\" as \"{to}\".");
+        assert_eq!(check_cast(ty, expr, to), [error], "{ty} as {to}");
+    }
+}
+
+#[test]
+fn functions_returning_on_every_path_pass() {
+    let bodies = [
+        "if x { return 1; } else { return 2; }",
+        "if x { return 1; }\n    2",
+        "for { if x { return 1; } }",
+        "match x { true => { return 1; }, false => 2 }",
+        "return 1;",
+    ];
+
+    for body in bodies {
+        let (errors, _) = check_items(&format!("f := fn (x: bool) -> int {{\n    {body}\n}}"));
+        assert_eq!(errors, Vec::<String>::new(), "{body}");
+    }
+}
+
+#[test]
+fn missing_returns_are_reported() {
+    let bodies = [
+        "if x { return 1; }",
+        "for x { return 1; }",
+        "match x { true => { return 1; }, false => {} }",
+        "",
+    ];
+
+    for body in bodies {
+        let (errors, _) = check_items(&format!("f := fn (x: bool) -> int {{\n    {body}\n}}"));
+        assert_eq!(
+            errors,
+            ["Not every path returns a value of type \"int\"."],
+            "{body}"
+        );
+    }
+}
+
+#[test]
+fn code_after_return_is_unreachable() {
+    let (errors, warnings) = check_items("f := fn () -> int {\n    return 1;\n    2\n}");
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(warnings, ["Unreachable code."]);
+}
+
+#[test]
+fn writes_through_slices_are_reported() {
+    let statements = [
+        "xs[0..2] = [1, 2];",
+        "xs[0..2][1] = 42;",
+        "xs[1..][0] += 1;",
+        "xs[..2].push(3);",
+        "ps[0..1][0].x = 1;",
+    ];
+
+    for statement in statements {
+        let (errors, _) = check_items(&format!(
+            "{TYPES}\nf := fn (xs: [int], ps: [Point]) {{\n    {statement}\n}}"
+        ));
+
+        assert_eq!(
+            errors,
+            ["Slices are copies, writing to one has no effect on what it was taken from."],
+            "{statement}"
+        );
+    }
+}
+
+#[test]
+fn writes_to_values_shared_by_slices_pass() {
+    let statements = [
+        "sl := xs[0..2];\n    sl[1] = 42;",
+        "ls[0..1][0].push(1);",
+        "rs[0..1][0].x = 1;",
+    ];
+
+    for statement in statements {
+        let (errors, _) = check_items(&format!(
+            "{TYPES}\nf := fn (xs: [int], ls: [[int]], rs: [&Point]) {{\n    {statement}\n}}"
+        ));
+
+        assert_eq!(errors, Vec::<String>::new(), "{statement}");
+    }
+}
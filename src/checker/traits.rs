@@ -0,0 +1,224 @@
+//! Traits, the conformance of structs and enums to them and their use as bounds and `dyn`
+//! values.
+//!
+//! A generic item calls the methods of the traits bounding its parameters, every instance then
+//! calls the methods of its type arguments directly. A `dyn Trait` value instead holds a
+//! reference to a conforming value together with the methods of its type, which calls look up
+//! at runtime.
+
+use super::{
+    typed::{self, Expr, ExprKind, Type},
+    CheckError, Checker, Signature,
+};
+use crate::{
+    parser::item::{TraitMethod, TypeParam, UnaryOperator},
+    util::{Span, Spanned},
+};
+use std::collections::HashMap;
+
+/// The methods a trait declares, in declaration order.
+#[derive(Debug, Clone, Default)]
+pub(super) struct TraitInfo {
+    pub methods: Vec<(String, Signature)>,
+}
+
+impl Checker {
+    /// Resolves the signatures of the methods of a trait, which all have to take `self`.
+    pub(super) fn declare_trait(&mut self, methods: &[Spanned<TraitMethod>]) -> TraitInfo {
+        let mut info = TraitInfo::default();
+        for method in methods.iter() {
+            let ident = &method.ident;
+            if !method.receiver {
+                self.errors
+                    .push(CheckError::StaticTraitMethod.span(ident.range()));
+            }
+
+            if info.methods.iter().any(|(other, _)| *other == ident.value) {
+                self.errors
+                    .push(CheckError::DuplicateItem(ident.value.clone()).span(ident.range()));
+                continue;
+            }
+
+            match self.resolve_signature(true, true, &method.args, method.ty.as_ref()) {
+                Ok(signature) => info.methods.push((ident.value.clone(), signature)),
+                Err(error) => self.errors.push(error),
+            }
+        }
+
+        info
+    }
+
+    /// Returns the names of the traits in `traits`, reporting unknown ones.
+    pub(super) fn resolve_traits(&mut self, traits: &[Spanned<String>]) -> Vec<String> {
+        let mut resolved = Vec::new();
+        for ident in traits.iter() {
+            match self.traits.contains_key(&ident.value) {
+                true => resolved.push(ident.value.clone()),
+                false => self
+                    .errors
+                    .push(CheckError::UnknownTrait(ident.value.clone()).span(ident.range())),
+            }
+        }
+
+        resolved
+    }
+
+    /// Returns the bounds of the type parameters `params`.
+    pub(super) fn resolve_bounds(&mut self, params: &[TypeParam]) -> HashMap<String, Vec<String>> {
+        params
+            .iter()
+            .map(|param| {
+                (
+                    param.ident.value.clone(),
+                    self.resolve_traits(&param.bounds),
+                )
+            })
+            .collect()
+    }
+
+    /// Checks that the struct or enum `ty` has every method of the traits it conforms to, only the
+    /// first missing or mismatched method of each trait is reported.
+    pub(super) fn check_conformance(&mut self, ty: &Type, traits: &[Spanned<String>]) {
+        let Some((_, methods)) = self.methods_of(ty) else {
+            return;
+        };
+
+        let methods = methods.clone();
+        for ident in traits.iter() {
+            let Some(info) = self.traits.get(&ident.value) else {
+                continue;
+            };
+
+            for (method, expected) in info.methods.iter() {
+                let error = match methods.get(method) {
+                    None => CheckError::MissingTraitMethod {
+                        ident: method.clone(),
+                        trait_: ident.value.clone(),
+                    },
+                    Some(found)
+                        if found.receiver != expected.receiver
+                            || found.args != expected.args
+                            || found.ty != expected.ty =>
+                    {
+                        CheckError::MismatchedTraitMethod {
+                            ident: method.clone(),
+                            trait_: ident.value.clone(),
+                        }
+                    }
+                    Some(_) => continue,
+                };
+
+                self.errors.push(error.span(ident.range()));
+                break;
+            }
+        }
+    }
+
+    /// Returns whether values of `ty` conform to `trait_`, the traits of references are the
+    /// traits of the values behind them.
+    pub(super) fn implements(&self, ty: &Type, trait_: &str) -> bool {
+        let traits = match ty.deref() {
+            Type::Struct(name) => &self.structs[name].traits,
+            Type::Enum(name) => &self.enums[name].traits,
+            Type::Param(ident) => match self.bounds.get(ident) {
+                Some(bounds) => bounds,
+                None => return false,
+            },
+            Type::Dyn(ident) => return ident == trait_,
+            _ => return false,
+        };
+
+        traits.iter().any(|other| other == trait_)
+    }
+
+    /// Checks that the type arguments `args` of `generic` conform to the bounds of its
+    /// parameters.
+    pub(super) fn check_bounds(&self, generic: &str, args: &[Type]) -> Result<(), CheckError> {
+        let generic = &self.generics[generic];
+        for (param, arg) in generic.params.iter().zip(args.iter()) {
+            let mut bounds = generic.bounds.get(param).into_iter().flatten();
+            if let Some(trait_) = bounds.find(|bound| !self.implements(arg, bound)) {
+                return Err(CheckError::NotImplemented {
+                    ty: arg.clone(),
+                    trait_: trait_.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path and signature of the method `ident` of a `dyn` value or of a type
+    /// parameter, which is found in its traits.
+    pub(super) fn trait_method(&self, ty: &Type, ident: &str) -> Option<(String, Signature)> {
+        let traits = match ty.deref() {
+            Type::Dyn(trait_) => std::slice::from_ref(trait_),
+            Type::Param(param) => self.bounds.get(param)?.as_slice(),
+            _ => return None,
+        };
+
+        traits.iter().find_map(|trait_| {
+            let (_, signature) = self.traits[trait_]
+                .methods
+                .iter()
+                .find(|(method, _)| method == ident)?;
+
+            Some((format!("{trait_}.{ident}"), signature.clone()))
+        })
+    }
+
+    /// Returns whether the method `ident` of `ty` is called through a trait bounding the type
+    /// parameter `ty` is the argument of, which makes it callable even if it isn't public.
+    pub(super) fn bound_method(&self, ty: &Type, ident: &str) -> bool {
+        self.type_params.iter().any(|(param, arg)| {
+            arg.deref() == ty.deref()
+                && self.bounds.get(param).is_some_and(|traits| {
+                    traits
+                        .iter()
+                        .any(|trait_| self.traits[trait_].methods.iter().any(|(m, _)| m == ident))
+                })
+        })
+    }
+
+    /// Converts `expr` into a `dyn` value of `trait_` if its type conforms to it, values are
+    /// moved to the heap first.
+    pub(super) fn to_dyn(&self, expr: Expr, trait_: &str) -> Result<Expr, Expr> {
+        let conforms = matches!(expr.ty.deref(), Type::Struct(_) | Type::Enum(_))
+            && self.implements(&expr.ty, trait_);
+
+        if !conforms {
+            return Err(expr);
+        }
+
+        let expr = match expr.ty {
+            Type::Reference(_) => expr,
+            _ => Expr {
+                ty: Type::Reference(Box::new(expr.ty.clone())),
+                kind: ExprKind::Unary {
+                    operator: UnaryOperator::Reference,
+                    expr: Box::new(expr),
+                },
+            },
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Dyn(Box::new(expr)),
+            ty: Type::Dyn(trait_.to_owned()),
+        })
+    }
+
+    /// Returns the typed declaration of the trait `ident`.
+    pub(super) fn trait_item(&self, ident: Spanned<String>) -> typed::Trait {
+        let methods = self.traits[&ident.value]
+            .methods
+            .iter()
+            .map(|(method, signature)| typed::TraitMethod {
+                ident: method.clone(),
+                args: signature.args.clone(),
+                ty: signature.ty.clone(),
+            })
+            .collect();
+
+        typed::Trait { ident, methods }
+    }
+}
@@ -0,0 +1,494 @@
+use crate::{lexer::token::Operator, parser::item::UnaryOperator, util::Spanned};
+use std::fmt;
+
+/// A checked program ready for code generation.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub items: Vec<Spanned<Item>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Fn(Fn),
+    Struct(Struct),
+    Enum(Enum),
+    Trait(Trait),
+    Extern(Extern),
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub ident: Spanned<String>,
+    pub fields: Vec<(String, Type)>,
+    pub methods: Vec<Spanned<Fn>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub ident: Spanned<String>,
+    /// Variants with the types of their payloads.
+    pub variants: Vec<(String, Vec<Type>)>,
+    pub methods: Vec<Spanned<Fn>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub ident: Spanned<String>,
+    /// Methods in declaration order, they all take `self`.
+    pub methods: Vec<TraitMethod>,
+}
+
+/// A function implemented in `C`, which is called by its name.
+#[derive(Debug, Clone)]
+pub struct Extern {
+    pub ident: Spanned<String>,
+    /// Header declaring the function, which is included instead of declaring it.
+    pub header: Option<String>,
+    pub args: Vec<Type>,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub ident: String,
+    pub args: Vec<Type>,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fn {
+    /// Path of the function, methods are prefixed with their struct like `Node.push`.
+    pub ident: Spanned<String>,
+    /// Type of `self` which is passed by pointer.
+    pub receiver: Option<Type>,
+    pub args: Vec<(String, Type)>,
+    pub ty: Type,
+    pub block: Block,
+    /// Whether the function is marked `@no_mangle`, keeping its name in the generated `C`.
+    pub no_mangle: bool,
+    /// Whether it's a `pub` top-level function, which libraries export under its name.
+    pub exported: bool,
+}
+
+/// An anonymous function, `captures` are the locals of the enclosing functions it uses.
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub f: Fn,
+    /// Unique identifiers and types of the captured locals, which the closure copies when it's
+    /// created.
+    pub captures: Vec<(String, Type)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub statements: Vec<Spanned<Statement>>,
+    pub trailing_expr: Option<Box<Spanned<Expr>>>,
+}
+
+impl Expr {
+    /// Returns whether the expression refers to a memory location which can be assigned to.
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            ExprKind::Local(_) | ExprKind::Receiver => true,
+            // Fields behind a reference live on the heap.
+            ExprKind::Field { expr, .. } => {
+                matches!(expr.ty, Type::Reference(_)) || expr.is_place()
+            }
+            // The bytes of strings are immutable.
+            ExprKind::Index { expr, .. } => expr.ty.deref() != &Type::String && expr.is_place(),
+            _ => false,
+        }
+    }
+}
+
+impl Block {
+    pub fn ty(&self) -> Type {
+        self.trailing_expr
+            .as_ref()
+            .map_or(Type::Void, |expr| expr.ty.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    /// Initializes a new local, `ident` is unique within the function.
+    VariableInit {
+        ident: String,
+        expr: Expr,
+    },
+    Expr(Expr),
+    Assign {
+        place: Expr,
+        operator: Option<Operator>,
+        expr: Expr,
+    },
+    For {
+        kind: Loop,
+        block: Block,
+    },
+    /// Breaks out of the loop at the given depth, `0` being the innermost one.
+    Break(usize),
+    /// Continues the loop at the given depth, `0` being the innermost one.
+    Continue(usize),
+    Return(Option<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Loop {
+    Infinite,
+    While(Expr),
+    Range {
+        ident: String,
+        start: Expr,
+        end: Expr,
+        inclusive: bool,
+    },
+    Optional {
+        ident: String,
+        expr: Expr,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Local(String),
+    /// `self` inside of a method.
+    Receiver,
+    Literal(Literal),
+    /// An optional without a value.
+    None,
+    /// Constructs a struct, `fields` are in declaration order.
+    Struct {
+        ident: String,
+        fields: Vec<Expr>,
+    },
+    /// Constructs the variant `variant` of the enum `ident`.
+    Variant {
+        ident: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    Call {
+        ident: String,
+        args: Vec<Expr>,
+    },
+    /// Calls a method taking `self`, the receiver is passed by pointer, references are passed
+    /// as they are.
+    MethodCall {
+        ident: String,
+        receiver: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// Calls the function value `expr`.
+    CallValue {
+        expr: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// The top-level function `ident` used as a value.
+    Function(String),
+    Closure(Box<Closure>),
+    Field {
+        expr: Box<Expr>,
+        ident: String,
+    },
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: Operator,
+        right: Box<Expr>,
+    },
+    /// Converts `expr` into [`Expr::ty`], both explicit `as` casts and implicit `T` to `T?`
+    /// conversions end up here.
+    Cast(Box<Expr>),
+    /// Turns a reference to a value conforming to a trait into a `dyn` value of the trait.
+    Dyn(Box<Expr>),
+    If {
+        condition: Box<Expr>,
+        block: Block,
+        else_block: Option<Block>,
+    },
+    /// An array literal, elements are in order.
+    Array(Vec<Expr>),
+    /// An array holding `len` copies of `expr`.
+    Repeat {
+        expr: Box<Expr>,
+        len: usize,
+    },
+    /// Reads an element of an array or list, `location` is the offset of the indexing in the
+    /// source which is reported when `index` is out of bounds.
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+        location: usize,
+    },
+    /// Copies a range of an array or list into a new list, a missing bound is the start or end.
+    Slice {
+        expr: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        inclusive: bool,
+        location: usize,
+    },
+    /// The length of an array, list or string.
+    Len(Box<Expr>),
+    /// Concatenates the strings produced by an interpolated string literal.
+    Format(Vec<Expr>),
+    /// Converts a printed or interpolated value into a string, an optional without a value
+    /// becomes `?`.
+    ToString(Box<Expr>),
+    /// A call of a function of the prelude, `location` is the offset reported by `panic` and
+    /// `assert`.
+    Builtin {
+        builtin: Builtin,
+        args: Vec<Expr>,
+        location: usize,
+    },
+    /// Runs the first arm whose pattern matches `expr`, the checker ensures one always does.
+    Match {
+        expr: Box<Expr>,
+        arms: Vec<Arm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub block: Block,
+}
+
+/// A checked pattern, optionals and references are matched explicitly through [`Pattern::Some`]
+/// and [`Pattern::Deref`].
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    /// Binds the matched value to a new local, `ident` is unique within the function.
+    Binding(String),
+    Literal(Literal),
+    /// An optional without a value.
+    None,
+    /// An optional holding a value matching the pattern.
+    Some(Box<Pattern>),
+    /// A value behind a reference matching the pattern.
+    Deref(Box<Pattern>),
+    Variant {
+        ident: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
+    /// A struct whose fields, in declaration order, match the patterns.
+    Struct {
+        ident: String,
+        fields: Vec<Pattern>,
+    },
+}
+
+/// The functions of the prelude, which every program can call without defining them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Println,
+    Eprintln,
+    Panic,
+    Assert,
+    ReadLine,
+}
+
+#[derive(Debug, Clone)]
+pub enum Literal {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Type {
+    #[default]
+    Void,
+    Bool,
+    Int(IntTy),
+    Float(FloatTy),
+    /// An immutable UTF-8 string, slices of it share its bytes.
+    String,
+    Optional(Box<Type>),
+    Struct(String),
+    Enum(String),
+    /// `&T`, a pointer to a heap allocated value.
+    Reference(Box<Type>),
+    /// A type parameter inside of its generic item, which is replaced in every instance.
+    Param(String),
+    /// `dyn Trait`, a reference to a value of any type conforming to the trait.
+    Dyn(String),
+    /// A function value, either a top-level function or a closure.
+    Fn {
+        args: Vec<Type>,
+        ty: Box<Type>,
+    },
+    /// `[T; N]`, a fixed number of values stored inline.
+    Array(Box<Type>, usize),
+    /// `[T]`, a growable list on the heap which copies share.
+    List(Box<Type>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IntTy {
+    Int,
+    I8,
+    I16,
+    I32,
+    Uint,
+    U8,
+    U16,
+    U32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FloatTy {
+    Float,
+    F32,
+}
+
+impl Type {
+    /// Returns the primitive type named `ident`.
+    pub fn primitive(ident: &str) -> Option<Type> {
+        Some(match ident {
+            "void" => Type::Void,
+            "bool" => Type::Bool,
+            "int" => Type::Int(IntTy::Int),
+            "i8" => Type::Int(IntTy::I8),
+            "i16" => Type::Int(IntTy::I16),
+            "i32" => Type::Int(IntTy::I32),
+            "uint" => Type::Int(IntTy::Uint),
+            "u8" => Type::Int(IntTy::U8),
+            "u16" => Type::Int(IntTy::U16),
+            "u32" => Type::Int(IntTy::U32),
+            "float" => Type::Float(FloatTy::Float),
+            "f32" => Type::Float(FloatTy::F32),
+            "string" => Type::String,
+            _ => return None,
+        })
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int(_) | Type::Float(_))
+    }
+
+    /// Returns the type behind a reference, field accesses and method calls dereference
+    /// automatically.
+    pub fn deref(&self) -> &Type {
+        match self {
+            Type::Reference(ty) => ty,
+            ty => ty,
+        }
+    }
+
+    /// Returns the element type of an array or list.
+    pub fn element(&self) -> Option<&Type> {
+        match self {
+            Type::Array(ty, _) | Type::List(ty) => Some(ty),
+            _ => None,
+        }
+    }
+}
+
+impl Builtin {
+    pub fn from_ident(ident: &str) -> Option<Builtin> {
+        Some(match ident {
+            "print" => Builtin::Print,
+            "println" => Builtin::Println,
+            "eprintln" => Builtin::Eprintln,
+            "panic" => Builtin::Panic,
+            "assert" => Builtin::Assert,
+            "read_line" => Builtin::ReadLine,
+            _ => return None,
+        })
+    }
+}
+
+impl IntTy {
+    pub fn signed(self) -> bool {
+        matches!(self, IntTy::Int | IntTy::I8 | IntTy::I16 | IntTy::I32)
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::Int | IntTy::Uint => 64,
+        }
+    }
+
+    pub fn min(self) -> i128 {
+        if self.signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Bool => write!(f, "bool"),
+            Type::Int(ty) => write!(f, "{ty}"),
+            Type::Float(FloatTy::Float) => write!(f, "float"),
+            Type::Float(FloatTy::F32) => write!(f, "f32"),
+            Type::String => write!(f, "string"),
+            Type::Optional(ty) => write!(f, "{ty}?"),
+            Type::Struct(ident) | Type::Enum(ident) | Type::Param(ident) => write!(f, "{ident}"),
+            Type::Reference(ty) => write!(f, "&{ty}"),
+            Type::Dyn(ident) => write!(f, "dyn {ident}"),
+            Type::Array(ty, len) => write!(f, "[{ty}; {len}]"),
+            Type::List(ty) => write!(f, "[{ty}]"),
+            Type::Fn { args, ty } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                match **ty {
+                    Type::Void => write!(f, "fn({args})"),
+                    _ => write!(f, "fn({args}) -> {ty}"),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for IntTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ident = match self {
+            IntTy::Int => "int",
+            IntTy::I8 => "i8",
+            IntTy::I16 => "i16",
+            IntTy::I32 => "i32",
+            IntTy::Uint => "uint",
+            IntTy::U8 => "u8",
+            IntTy::U16 => "u16",
+            IntTy::U32 => "u32",
+        };
+
+        write!(f, "{ident}")
+    }
+}
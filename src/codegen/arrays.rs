@@ -0,0 +1,287 @@
+//! Arrays, lists and the bounds checked accesses of them, which strings share.
+//!
+//! An array is a struct wrapping a `C` array so that it's copied by value. A list is a pointer to
+//! a counted allocation holding its length, its capacity and a separately allocated buffer of
+//! elements. Indexing and slicing abort with the source location when they are out of bounds,
+//! release builds leave the checks out.
+
+use super::{rc::is_borrowed, type_ident, Codegen};
+use crate::checker::typed::{Expr, IntTy, Type};
+
+impl Codegen {
+    /// Generates the `typedef` of the array or list type `ty`.
+    pub(super) fn gen_array_typedef(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Array(inner, len) => format!(
+                "\ntypedef struct {{ {} items[{len}]; }} {};\n",
+                self.c_type(inner),
+                type_ident(ty)
+            ),
+            Type::List(inner) => format!(
+                "\ntypedef struct {{ int64_t len; int64_t cap; {}* items; }} {};\n",
+                self.c_type(inner),
+                type_ident(ty)
+            ),
+            _ => unreachable!("Only arrays and lists are passed."),
+        }
+    }
+
+    /// Generates an array holding `len` copies of `expr`.
+    pub(super) fn gen_repeat(&mut self, expr: &Expr, len: usize, ty: &Type) -> String {
+        let (value, array, i) = (self.next_temp(), self.next_temp(), self.next_temp());
+        let copy = self.gen_retain(value.clone(), &expr.ty);
+        let drop = self.gen_drop(&value, &expr.ty).unwrap_or_default();
+        format!(
+            "({{ {} {value} = {}; {} {array}; \
+             for (int64_t {i} = 0; {i} < {len}; {i}++) {array}.items[{i}] = {copy}; \
+             {drop} {array}; }})",
+            self.c_type(&expr.ty),
+            self.gen_owned(expr),
+            self.c_type(ty),
+        )
+    }
+
+    /// Generates `expr[index]`.
+    pub(super) fn gen_index(
+        &mut self,
+        expr: &Expr,
+        index: &Expr,
+        location: usize,
+        ty: &Type,
+    ) -> String {
+        let index = format!("(int64_t)({})", self.gen_expr(index));
+        self.gen_access(expr, ty, true, |codegen, code| {
+            let (items, len) = items(code, &expr.ty);
+            let index = match codegen.options.release {
+                true => index,
+                false => {
+                    codegen.bounds_checks = true;
+                    format!(
+                        "razor_check_index({index}, {len}, {})",
+                        codegen.location(location)
+                    )
+                }
+            };
+
+            format!("{items}[{index}]")
+        })
+    }
+
+    /// Generates `expr[start..end]`, copying the elements into a new list. Slices of strings
+    /// share their bytes instead.
+    pub(super) fn gen_slice(
+        &mut self,
+        expr: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+        inclusive: bool,
+        location: usize,
+        ty: &Type,
+    ) -> String {
+        let start = match start {
+            Some(start) => format!("(int64_t)({})", self.gen_expr(start)),
+            None => "0".to_owned(),
+        };
+        let end = end.map(|end| format!("(int64_t)({})", self.gen_expr(end)));
+
+        let id = type_ident(ty);
+        let (start_temp, end_temp) = (self.next_temp(), self.next_temp());
+        self.gen_access(expr, ty, false, |codegen, code| {
+            let (items, len) = items(code, &expr.ty);
+            let end = match (end, inclusive) {
+                (Some(end), true) => format!("{end} + 1"),
+                (Some(end), false) => end,
+                (None, _) => len.clone(),
+            };
+
+            let check = match codegen.options.release {
+                true => String::new(),
+                false => {
+                    codegen.bounds_checks = true;
+                    format!(
+                        "razor_check_range({start_temp}, {end_temp}, {len}, {}); ",
+                        codegen.location(location)
+                    )
+                }
+            };
+
+            let slice = match expr.ty.deref() {
+                Type::String => format!(
+                    "razor_string_slice({}, {start_temp}, {end_temp})",
+                    deref(code, &expr.ty)
+                ),
+                _ => format!("razor_{id}_from({items}, {start_temp}, {end_temp})"),
+            };
+
+            format!("({{ int64_t {start_temp} = {start}, {end_temp} = {end}; {check}{slice}; }})")
+        })
+    }
+
+    /// Generates the length of the array, list or string `expr`.
+    pub(super) fn gen_len(&mut self, expr: &Expr) -> String {
+        self.gen_access(expr, &Type::Int(IntTy::Int), false, |_, code| {
+            items(code, &expr.ty).1
+        })
+    }
+
+    /// Generates `access` of the array or list `expr`, a temporary one is stored first and
+    /// released afterwards. Results which are `borrowed` from it are retained before that.
+    fn gen_access(
+        &mut self,
+        expr: &Expr,
+        ty: &Type,
+        borrowed: bool,
+        access: impl FnOnce(&mut Self, &str) -> String,
+    ) -> String {
+        let code = self.gen_expr(expr);
+        if is_borrowed(expr) {
+            return access(self, &code);
+        }
+
+        let (temp, result) = (self.next_temp(), self.next_temp());
+        let value = access(self, &temp);
+        let value = match borrowed {
+            true => self.gen_retain(value, ty),
+            false => value,
+        };
+        let drop = self.gen_drop(&temp, &expr.ty).unwrap_or_default();
+
+        format!(
+            "({{ {} {temp} = {code}; {} {result} = {value}; {drop} {result}; }})",
+            self.c_type(&expr.ty),
+            self.c_type(ty),
+        )
+    }
+
+    /// Converts the owned array `code` into a new list.
+    pub(super) fn gen_to_list(&mut self, code: String, from: &Type, to: &Type) -> String {
+        let Type::Array(_, len) = from else {
+            unreachable!("Only arrays convert into lists.")
+        };
+
+        let (array, list) = (self.next_temp(), self.next_temp());
+        let drop = self.gen_drop(&array, from).unwrap_or_default();
+        format!(
+            "({{ {} {array} = {code}; {} {list} = razor_{}_from({array}.items, 0, {len}); \
+             {drop} {list}; }})",
+            self.c_type(from),
+            self.c_type(to),
+            type_ident(to),
+        )
+    }
+
+    /// Generates the functions checking indices and ranges against a length.
+    pub(super) fn gen_bounds_checks(&self) -> String {
+        "\nstatic inline int64_t razor_check_index(int64_t index, int64_t len, \
+                                                 const char* at) {\n    \
+             if (index < 0 || index >= len) {\n        \
+                 razor_abort(\"%s: Index %lld is out of bounds for length %lld.\\n\", at, \
+                             (long long)index, (long long)len);\n    \
+             }\n    \
+             return index;\n\
+         }\n\
+         static inline void razor_check_range(int64_t start, int64_t end, int64_t len, \
+                                              const char* at) {\n    \
+             if (start < 0 || start > end || end > len) {\n        \
+                 razor_abort(\"%s: Range %lld..%lld is out of bounds for length %lld.\\n\", at, \
+                             (long long)start, (long long)end, (long long)len);\n    \
+             }\n\
+         }\n"
+        .to_owned()
+    }
+
+    /// Generates the prototypes of the functions of every list type.
+    pub(super) fn gen_list_prototypes(&mut self) -> String {
+        let mut code = String::new();
+        for ty in self.lists.clone() {
+            let Type::List(inner) = &ty else {
+                unreachable!("Only lists are collected.")
+            };
+
+            let (c_ty, c_inner, id) = (self.c_type(&ty), self.c_type(inner), type_ident(&ty));
+            let optional = self.c_type(&Type::Optional(inner.clone()));
+            code += &format!(
+                "static inline {c_ty} razor_{id}_from({c_inner} const* items, int64_t start, \
+                                                      int64_t end);\n\
+                 static inline void razor_{id}_push({c_ty} list, {c_inner} value);\n\
+                 static inline {optional} razor_{id}_pop({c_ty} list);\n"
+            );
+        }
+
+        code
+    }
+
+    /// Generates the functions creating, growing and shrinking every list type.
+    pub(super) fn gen_list_fns(&mut self) -> String {
+        let mut code = String::new();
+        for ty in self.lists.clone() {
+            let Type::List(inner) = &ty else {
+                unreachable!("Only lists are collected.")
+            };
+
+            self.allocates = true;
+            let (c_ty, c_inner, id) = (self.c_type(&ty), self.c_type(inner), type_ident(&ty));
+            let optional = self.c_type(&Type::Optional(inner.clone()));
+            let copy = self.gen_retain("items[start + i]".to_owned(), inner);
+            code += &format!(
+                "\nstatic inline {c_ty} razor_{id}_from({c_inner} const* items, int64_t start, \
+                                                        int64_t end) {{\n    \
+                     {c_ty} list = razor_alloc(sizeof(*list));\n    \
+                     list->len = list->cap = end - start;\n    \
+                     list->items = razor_items(NULL, list->len, sizeof({c_inner}));\n    \
+                     for (int64_t i = 0; i < list->len; i++) list->items[i] = {copy};\n    \
+                     return list;\n\
+                 }}\n\
+                 \n\
+                 static inline void razor_{id}_push({c_ty} list, {c_inner} value) {{\n    \
+                     if (list->len == list->cap) {{\n        \
+                         list->cap = list->cap ? list->cap * 2 : 4;\n        \
+                         list->items = \
+                             razor_items(list->items, list->cap, sizeof({c_inner}));\n    \
+                     }}\n    \
+                     list->items[list->len++] = value;\n\
+                 }}\n\
+                 \n\
+                 static inline {optional} razor_{id}_pop({c_ty} list) {{\n    \
+                     if (!list->len) return ({optional}){{ false }};\n    \
+                     return ({optional}){{ true, list->items[--list->len] }};\n\
+                 }}\n"
+            );
+        }
+
+        code
+    }
+
+    /// Generates the function resizing the element buffers of lists.
+    pub(super) fn gen_list_runtime(&self) -> String {
+        "\nstatic inline void* razor_items(void* items, int64_t cap, size_t size) {\n    \
+             items = realloc(items, cap ? (size_t)cap * size : 1);\n    \
+             if (!items) razor_abort(\"Out of memory.\\n\");\n    \
+             return items;\n\
+         }\n"
+        .to_owned()
+    }
+}
+
+/// Returns the `C` arrays of elements and the length of the array, list or string `code` of type
+/// `ty`.
+fn items(code: &str, ty: &Type) -> (String, String) {
+    match ty {
+        Type::Array(_, len) => (format!("{code}.items"), len.to_string()),
+        Type::List(_) => (format!("{code}->items"), format!("{code}->len")),
+        Type::String => (
+            format!("((const uint8_t*){code}.data)"),
+            format!("{code}.len"),
+        ),
+        Type::Reference(inner) => items(&format!("(*{code})"), inner),
+        _ => unreachable!("Only arrays, lists and strings are indexed."),
+    }
+}
+
+/// Returns the value `code` of type `ty` points at if it is a reference.
+fn deref(code: &str, ty: &Type) -> String {
+    match ty {
+        Type::Reference(_) => format!("(*{code})"),
+        _ => code.to_owned(),
+    }
+}
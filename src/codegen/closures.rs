@@ -0,0 +1,234 @@
+//! Function values and the closures creating them.
+//!
+//! A function value is a function pointer taking an environment followed by the arguments, the
+//! environment and a function releasing it. Closures are lifted into functions of their own
+//! whose environment is a counted allocation holding the captured locals, top-level functions
+//! are called through a thunk ignoring the environment.
+
+use super::{c_ident, rc::is_borrowed, type_ident, Codegen, Target};
+use crate::checker::typed::{Closure, Expr, Type};
+
+impl Codegen {
+    /// Generates the `typedef` of the function type `ty`.
+    pub(super) fn gen_fn_typedef(&mut self, ty: &Type) -> String {
+        let Type::Fn { args, ty: ret } = ty else {
+            unreachable!("Only function types are passed.")
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| ", ".to_owned() + &self.c_type(arg))
+            .collect::<String>();
+
+        format!(
+            "\ntypedef struct {{ {} (*call)(void*{args}); void* env; void (*drop)(void*); }} {};\n",
+            self.c_type(ret),
+            type_ident(ty),
+        )
+    }
+
+    /// Generates a closure value, the function it's lifted into is emitted before the function
+    /// containing it.
+    pub(super) fn gen_closure(&mut self, closure: &Closure, ty: &Type) -> String {
+        let f = &closure.f;
+        let (name, c_ty) = (c_ident(&f.ident), self.c_type(ty));
+        let env = format!("{name}_env");
+
+        // The body is a function of its own, so it starts without any scopes or loops.
+        let scopes = std::mem::take(&mut self.scopes);
+        let loops = std::mem::take(&mut self.loops);
+        let depth = std::mem::replace(&mut self.depth, 0);
+        let switches = std::mem::replace(&mut self.switches, 0);
+
+        let mut params = match closure.captures.is_empty() {
+            true => vec!["__attribute__((unused)) void* razor_env".to_owned()],
+            false => vec!["void* razor_env".to_owned()],
+        };
+
+        self.scopes.push(Vec::new());
+        for (ident, ty) in f.args.iter() {
+            params.push(self.c_type(ty) + " " + ident);
+            self.declare(ident, ty);
+        }
+
+        // Captured locals are borrowed from the environment.
+        let bindings = closure
+            .captures
+            .iter()
+            .map(|(ident, ty)| {
+                let value = format!("(({env}*)razor_env)->{ident}");
+                (ident.clone(), ty.clone(), value)
+            })
+            .collect();
+
+        let block = self.gen_block_with(&f.block, Target::Return, bindings);
+
+        self.scopes = scopes;
+        self.loops = loops;
+        self.depth = depth;
+        self.switches = switches;
+
+        let mut lifted = String::new();
+        if !closure.captures.is_empty() {
+            let (mut fields, mut drops) = (String::new(), String::new());
+            for (ident, ty) in closure.captures.iter() {
+                fields += &format!("{} {ident}; ", self.c_type(ty));
+                if let Some(drop) = self.gen_drop(&format!("env->{ident}"), ty) {
+                    drops += &format!("        {drop}\n");
+                }
+            }
+
+            lifted += &format!(
+                "\ntypedef struct {{ {fields}}} {env};\n\
+                 \n\
+                 static void {name}_drop(void* razor_env) {{\n    \
+                     {env}* env = razor_env;\n    \
+                     if (razor_release(env)) {{\n\
+                     {drops}        \
+                         razor_free(env);\n    \
+                     }}\n\
+                 }}\n"
+            );
+        }
+
+        lifted += &format!(
+            "\n{}static {} {name}({}) {block}\n",
+            self.gen_line(f.ident.start),
+            self.c_type(&f.ty),
+            params.join(", ")
+        );
+        self.lifted += &lifted;
+
+        if closure.captures.is_empty() {
+            return format!("({c_ty}){{ {name}, NULL, NULL }}");
+        }
+
+        self.allocates = true;
+        let captures = closure
+            .captures
+            .iter()
+            .map(|(ident, ty)| self.gen_retain(ident.clone(), ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let temp = self.next_temp();
+        format!(
+            "({{ {env}* {temp} = razor_alloc(sizeof({env})); *{temp} = ({env}){{ {captures} }}; \
+             ({c_ty}){{ {name}, {temp}, {name}_drop }}; }})"
+        )
+    }
+
+    /// Generates the top-level function `ident` as a value calling it through a thunk.
+    pub(super) fn gen_function_value(&mut self, ident: &str, ty: &Type) -> String {
+        self.fn_values.insert((ident.to_owned(), ty.clone()));
+        format!(
+            "({}){{ razor_fn_{}, NULL, NULL }}",
+            self.c_type(ty),
+            c_ident(ident)
+        )
+    }
+
+    /// Generates the signature of the thunk of the top-level function `ident` of type `ty`.
+    fn gen_thunk_signature(&mut self, ident: &str, ty: &Type) -> String {
+        let Type::Fn { args, ty: ret } = ty else {
+            unreachable!("Functions have function types.")
+        };
+
+        let mut params = vec!["__attribute__((unused)) void* razor_env".to_owned()];
+        for (i, arg) in args.iter().enumerate() {
+            params.push(format!("{} _{i}", self.c_type(arg)));
+        }
+
+        format!(
+            "static {} razor_fn_{}({})",
+            self.c_type(ret),
+            c_ident(ident),
+            params.join(", ")
+        )
+    }
+
+    /// Generates the prototypes of the thunks of top-level functions used as values.
+    pub(super) fn gen_thunk_prototypes(&mut self) -> String {
+        let mut code = String::new();
+        for (ident, ty) in self.fn_values.clone() {
+            code += &format!("{};\n", self.gen_thunk_signature(&ident, &ty));
+        }
+
+        code
+    }
+
+    /// Generates the thunks of top-level functions used as values.
+    pub(super) fn gen_thunks(&mut self) -> String {
+        let mut code = String::new();
+        for (ident, ty) in self.fn_values.clone() {
+            let Type::Fn { args, ty: ret } = &ty else {
+                unreachable!("Functions have function types.")
+            };
+
+            let call = match self.externs.contains(&ident) {
+                true => {
+                    let args = args
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| (format!("_{i}"), ty, true))
+                        .collect();
+
+                    self.gen_foreign_call(&ident, args, ret)
+                }
+                false => {
+                    let args = (0..args.len())
+                        .map(|i| format!("_{i}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    format!("{}({args})", self.fn_ident(&ident))
+                }
+            };
+            let body = match **ret {
+                Type::Void => format!("{call};"),
+                _ => format!("return {call};"),
+            };
+
+            code += &format!(
+                "\n{} {{\n    {body}\n}}\n",
+                self.gen_thunk_signature(&ident, &ty)
+            );
+        }
+
+        code
+    }
+
+    /// Generates a call of the function value `callee`, temporaries are released after the call.
+    pub(super) fn gen_call_value(&mut self, callee: &Expr, args: &[Expr], ty: &Type) -> String {
+        let mut args = args
+            .iter()
+            .map(|arg| self.gen_owned(arg))
+            .collect::<Vec<_>>();
+
+        if is_borrowed(callee) {
+            let code = self.gen_expr(callee);
+            args.insert(0, format!("{code}.env"));
+            return format!("{code}.call({})", args.join(", "));
+        }
+
+        let temp = self.next_temp();
+        args.insert(0, format!("{temp}.env"));
+        let call = format!("{temp}.call({})", args.join(", "));
+        let drop = self.gen_drop(&temp, &callee.ty).unwrap_or_default();
+        let callee = format!(
+            "{} {temp} = {};",
+            self.c_type(&callee.ty),
+            self.gen_expr(callee)
+        );
+
+        if *ty == Type::Void {
+            return format!("({{ {callee} {call}; {drop} }})");
+        }
+
+        let result = self.next_temp();
+        format!(
+            "({{ {callee} {} {result} = {call}; {drop} {result}; }})",
+            self.c_type(ty)
+        )
+    }
+}
@@ -0,0 +1,89 @@
+//! Calls of external functions, which are implemented in `C` and called by their name.
+//!
+//! Numbers and booleans are passed as they are. Strings are passed as NUL-terminated copies which
+//! are freed once the call returns, so `C` code must not hold on to them.
+
+use super::{rc::is_borrowed, Codegen};
+use crate::checker::typed::{Expr, Extern, Type};
+
+impl Codegen {
+    /// Generates the prototype of `extern_`, which is only needed if no header declares it.
+    pub(super) fn gen_extern_prototype(&mut self, extern_: &Extern) -> String {
+        let args = extern_
+            .args
+            .iter()
+            .map(|ty| match ty {
+                Type::String => "const char*".to_owned(),
+                ty => self.c_type(ty),
+            })
+            .collect::<Vec<_>>();
+
+        let args = match args.is_empty() {
+            true => "void".to_owned(),
+            false => args.join(", "),
+        };
+
+        format!(
+            "{} {}({args});\n",
+            self.c_type(&extern_.ty),
+            extern_.ident.value
+        )
+    }
+
+    /// Generates a call of the external function `ident` returning `ty`.
+    pub(super) fn gen_extern_call(&mut self, ident: &str, args: &[Expr], ty: &Type) -> String {
+        let args = args
+            .iter()
+            .map(|arg| match is_borrowed(arg) {
+                true => (self.gen_expr(arg), &arg.ty, false),
+                false => (self.gen_owned(arg), &arg.ty, true),
+            })
+            .collect();
+
+        self.gen_foreign_call(ident, args, ty)
+    }
+
+    /// Generates a call of the external function `ident` with the code, type and ownership of its
+    /// arguments. Owned strings are released after the call along with their copies.
+    pub(super) fn gen_foreign_call(
+        &mut self,
+        ident: &str,
+        args: Vec<(String, &Type, bool)>,
+        ty: &Type,
+    ) -> String {
+        let (mut setup, mut codes, mut cleanup) = (String::new(), Vec::new(), String::new());
+        for (code, arg_ty, owned) in args {
+            if *arg_ty != Type::String {
+                codes.push(code);
+                continue;
+            }
+
+            let (string, copy) = (self.next_temp(), self.next_temp());
+            setup += &format!(
+                "{} {string} = {code}; char* {copy} = razor_string_to_c({string}); ",
+                self.c_type(&Type::String)
+            );
+            cleanup += &format!("free({copy}); ");
+            if owned {
+                cleanup += &self.gen_drop(&string, &Type::String).unwrap_or_default();
+            }
+
+            codes.push(copy);
+        }
+
+        let call = format!("{ident}({})", codes.join(", "));
+        if setup.is_empty() {
+            return call;
+        }
+
+        if *ty == Type::Void {
+            return format!("({{ {setup}{call}; {cleanup}}})");
+        }
+
+        let result = self.next_temp();
+        format!(
+            "({{ {setup}{} {result} = {call}; {cleanup} {result}; }})",
+            self.c_type(ty)
+        )
+    }
+}
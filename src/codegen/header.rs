@@ -0,0 +1,146 @@
+//! Headers of libraries, which declare the exported functions to `C` code calling into `Razor`.
+//!
+//! The structs and optionals in their signatures are defined exactly like in the generated `C`, so
+//! both sides agree on them, and get an alias named after the `Razor` type like `Point` or
+//! `optional_Point`. Generic structs keep their mangled name. Optionals and the runtime they use
+//! are guarded, so the headers of several libraries can be included together.
+
+use super::{is_reserved, type_ident, Codegen, CodegenError, ABORT};
+use crate::{
+    checker::typed::{Fn, Type},
+    util::{Span, Spanned},
+};
+use std::{collections::HashSet, path::Path};
+
+impl Codegen {
+    /// Generates the header declaring the exported functions `fns`.
+    pub(super) fn gen_header(&mut self, fns: &[Fn]) -> Result<String, Spanned<CodegenError>> {
+        let mut types = Vec::new();
+        for f in fns {
+            for ty in f.args.iter().map(|(_, ty)| ty).chain([&f.ty]) {
+                self.export_type(ty, &mut types)
+                    .map_err(|error| error.span(f.ident.range()))?;
+            }
+        }
+
+        let stem = Path::new(&self.options.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let guard = stem
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .collect::<String>();
+
+        let mut code = format!(
+            "/* Generated from {}, declares the functions the library exports. */\n\
+             #ifndef RAZOR_{guard}_H\n\
+             #define RAZOR_{guard}_H\n\n\
+             #include <stdarg.h>\n\
+             #include <stdbool.h>\n\
+             #include <stdint.h>\n\
+             #include <stdio.h>\n\
+             #include <stdlib.h>\n",
+            self.options.path
+        );
+
+        // Unwrapping optionals aborts on empty ones.
+        if types.iter().any(|ty| matches!(ty, Type::Optional(_))) {
+            code += ABORT;
+        }
+
+        for ty in types.iter() {
+            if let Type::Struct(_) = ty {
+                let ident = self.c_type(ty);
+                code += &format!("\ntypedef struct {ident} {ident};");
+            }
+        }
+
+        if types.iter().any(|ty| matches!(ty, Type::Struct(_))) {
+            code += "\n";
+        }
+
+        let (mut defined, mut definitions) = (HashSet::new(), String::new());
+        for ty in types.iter() {
+            self.gen_definition(ty, &mut defined, &mut definitions);
+        }
+
+        code += &definitions;
+        if !types.is_empty() {
+            code += "\n";
+        }
+
+        for ty in types.iter() {
+            let (c_ty, alias) = (self.c_type(ty), header_ident(ty));
+            if c_ty != alias {
+                code += &format!("typedef {c_ty} {alias};\n");
+            }
+        }
+
+        code += "\n";
+        for f in fns {
+            let args = f
+                .args
+                .iter()
+                .map(|(ident, ty)| format!("{} {ident}", self.header_type(ty)))
+                .collect::<Vec<_>>();
+
+            let args = match args.is_empty() {
+                true => "void".to_owned(),
+                false => args.join(", "),
+            };
+
+            code += &format!("{} {}({args});\n", self.header_type(&f.ty), f.ident.value);
+        }
+
+        Ok(code + &format!("\n#endif /* RAZOR_{guard}_H */\n"))
+    }
+
+    /// Collects the structs and optionals `ty` consists of into `types`, only numbers, booleans
+    /// and structs and optionals of them can be passed between `C` and `Razor` by value.
+    fn export_type(&self, ty: &Type, types: &mut Vec<Type>) -> Result<(), CodegenError> {
+        match ty {
+            Type::Void | Type::Bool | Type::Int(_) | Type::Float(_) => return Ok(()),
+            Type::Struct(ident) => {
+                if is_reserved(ident) {
+                    return Err(CodegenError::ReservedExport(ident.clone()));
+                }
+
+                for (_, field) in self.fields[ident].iter() {
+                    self.export_type(field, types)?;
+                }
+            }
+            Type::Optional(inner) => self.export_type(inner, types)?,
+            ty => return Err(CodegenError::NotExportable(ty.clone())),
+        }
+
+        if !types.contains(ty) {
+            types.push(ty.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the name of `ty` in the header.
+    fn header_type(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Struct(_) | Type::Optional(_) => header_ident(ty),
+            ty => self.c_type(ty),
+        }
+    }
+}
+
+/// Returns the alias of a struct or optional in the header, which is only its mangled name for
+/// instances of generic structs.
+fn header_ident(ty: &Type) -> String {
+    match ty {
+        Type::Struct(ident) if ident.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            ident.clone()
+        }
+        Type::Optional(inner) => format!("optional_{}", header_ident(inner)),
+        ty => type_ident(ty),
+    }
+}
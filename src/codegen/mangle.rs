@@ -0,0 +1,189 @@
+//! Names of `Razor` items, locals and fields in the generated `C`.
+//!
+//! Items are prefixed with `rz_` and every identifier in their path is preceded by its length,
+//! so `Node.push` becomes `rz_4Node_4push`. Two paths never end up with the same name and none
+//! of them can clash with the `C` library or the runtime. Functions marked `@no_mangle` keep
+//! their name so `C` code can call them.
+//!
+//! Locals and fields keep their name unless it's a `C` keyword or a name the generated code
+//! relies on, those get a trailing `_`.
+
+/// `C` keywords, including the macros of `stdbool.h`.
+const KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// The names from the standard headers which the generated code refers to inside of functions,
+/// where a local of the same name would shadow them.
+const RESERVED: &[&str] = &[
+    "main", "NULL", "EOF", "stdin", "stdout", "stderr", "abort", "free", "fflush", "fprintf",
+    "fputc", "fputs", "fwrite", "memcpy", "strlen",
+];
+
+/// Returns the `C` name of the item at `path`, like `Stack[&Node].push` or `last[int]`.
+pub(super) fn c_ident(path: &str) -> String {
+    let mut ident = "rz_".to_owned();
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    word.push(c);
+                }
+
+                ident += &format!("{}{word}", word.len());
+            }
+            // Array lengths, which are always followed by `]`.
+            '0'..='9' => ident.push(c),
+            '.' => ident.push('_'),
+            '[' => ident.push('I'),
+            ']' => ident.push('E'),
+            ';' => ident.push('S'),
+            '&' => ident.push('R'),
+            '?' => ident.push('O'),
+            '(' => ident.push('P'),
+            ')' => ident.push('Q'),
+            '>' => ident.push('T'),
+            // Spaces, commas and the `-` of `->`, the lengths already separate the identifiers.
+            _ => {}
+        }
+    }
+
+    ident
+}
+
+/// Returns the `C` name of the local or field `ident`. Reserved names and names which already end
+/// in `_` get another `_`, so no two identifiers end up the same.
+pub fn c_local(ident: &str) -> String {
+    match is_reserved(ident) || ident.ends_with('_') {
+        true => format!("{ident}_"),
+        false => ident.to_owned(),
+    }
+}
+
+/// Returns the `C` name of the payload of `variant` in the union of its enum, which also holds
+/// the tag.
+pub(super) fn c_variant(variant: &str) -> String {
+    match variant {
+        "tag" => "tag_".to_owned(),
+        variant => c_local(variant),
+    }
+}
+
+/// Returns whether `ident` is a keyword in `C`, which no declaration can be named.
+pub(super) fn is_keyword(ident: &str) -> bool {
+    KEYWORDS.contains(&ident)
+}
+
+/// Returns whether `ident` can't be used as is in the generated `C`. Besides the reserved words
+/// this covers the prefixes of the runtime, mangled items, temporaries and the types generated
+/// for optionals, arrays, lists and functions, as well as the suffixes of the integer types and
+/// their limits.
+pub(super) fn is_reserved(ident: &str) -> bool {
+    let numbered = |prefix: &str| {
+        ident
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+    };
+
+    is_keyword(ident)
+        || RESERVED.contains(&ident)
+        || ["_", "razor_", "rz_", "optional_", "list_", "ref_", "dyn_"]
+            .iter()
+            .any(|prefix| ident.starts_with(prefix))
+        || ["_t", "_MIN", "_MAX"]
+            .iter()
+            .any(|suffix| ident.ends_with(suffix))
+        || numbered("array")
+        || numbered("fn")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_get_distinct_names() {
+        assert_eq!(c_ident("Node.push"), "rz_4Node_4push");
+        assert_ne!(c_ident("a.b"), c_ident("a_b"));
+        assert_ne!(c_ident("ab.c"), c_ident("a.bc"));
+        assert_ne!(c_ident("last[int]"), c_ident("last_int"));
+        assert_ne!(c_ident("f[[int; 2]]"), c_ident("f[[int; 22]]"));
+    }
+
+    #[test]
+    fn reserved_locals_get_a_trailing_underscore() {
+        for ident in [
+            "int", "static", "main", "NULL", "strlen", "rz_x", "int32_t", "array2",
+        ] {
+            assert!(is_reserved(ident), "{ident}");
+            assert_eq!(c_local(ident), format!("{ident}_"));
+        }
+
+        assert_eq!(c_local("x"), "x");
+        assert_eq!(c_local("array"), "array");
+        assert_eq!(c_variant("tag"), "tag_");
+    }
+
+    #[test]
+    fn trailing_underscores_are_doubled() {
+        assert_eq!(c_local("x_"), "x__");
+        assert_eq!(c_local("int_"), "int__");
+        assert_ne!(c_local("int"), c_local("int_"));
+    }
+
+    #[test]
+    fn only_c_keywords_are_keywords() {
+        for ident in ["int", "while", "bool", "static_assert"] {
+            assert!(is_keyword(ident), "{ident}");
+        }
+
+        for ident in ["abort", "free", "strlen", "main", "printf"] {
+            assert!(!is_keyword(ident), "{ident}");
+        }
+    }
+}
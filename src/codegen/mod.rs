@@ -1,56 +1,1665 @@
 use crate::{
-    parser::item::{Item, ItemKind, Ty},
-    util::{Span, Spanned},
+    checker::typed::{
+        Arm, Block, Expr, ExprKind, FloatTy, Fn, IntTy, Item, Literal, Loop, Pattern, Program,
+        Statement, TraitMethod, Type,
+    },
+    lexer::token::Operator,
+    parser::item::UnaryOperator,
+    util::{Lines, Span, Spanned},
+};
+use mangle::{c_ident, c_variant, is_keyword, is_reserved};
+use rc::is_borrowed;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
 };
 
-#[derive(Debug, Clone, Copy)]
+mod arrays;
+mod closures;
+mod ffi;
+mod header;
+mod mangle;
+mod prelude;
+mod rc;
+mod strings;
+mod vtable;
+
+pub use mangle::c_local;
+
+/// Aborts the program after flushing the output written so far and reporting a message, every
+/// runtime error goes through it. Library headers define it as well, hence the guard.
+const ABORT: &str = "\n#ifndef RAZOR_ABORT\n\
+                     #define RAZOR_ABORT\n\
+                     __attribute__((noreturn)) static inline void razor_abort(const char* format, \
+                                                                          ...) {\n    \
+                         fflush(stdout);\n    \
+                         va_list args;\n    \
+                         va_start(args, format);\n    \
+                         vfprintf(stderr, format, args);\n    \
+                         va_end(args);\n    \
+                         abort();\n\
+                     }\n\
+                     #endif\n";
+
+#[derive(Debug, Clone)]
 pub enum CodegenError {
-    IncorrectMain,
+    /// A `@no_mangle` or exported function or an exported struct whose name is reserved in the
+    /// generated `C`.
+    ReservedExport(String),
+    /// An external function named like a `C` keyword, which `C` can't declare or call.
+    KeywordExtern(String),
+    /// A type in the signature of an exported function which `C` code can't use.
+    NotExportable(Type),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::ReservedExport(ident) => write!(
+                f,
+                "\"{ident}\" is reserved in the generated C, so it can't keep its name."
+            ),
+            CodegenError::KeywordExtern(ident) => write!(
+                f,
+                "\"{ident}\" is a keyword in C, so no external function can be named like it."
+            ),
+            CodegenError::NotExportable(ty) => write!(
+                f,
+                "\"{ty}\" can't be exported to C, exported functions take and return numbers, \
+                 booleans and structs and optionals of them."
+            ),
+        }
+    }
 }
 
-/// Generates `C` code out of `Razor` items.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Reports allocations which are still alive when the program exits.
+    pub leak_check: bool,
+    /// Leaves out the bounds checks of indexing and slicing.
+    pub release: bool,
+    /// Path of the source file, runtime errors report locations in it.
+    pub path: String,
+    /// Emits `#line` directives so compiler errors, sanitizers and debuggers point into the
+    /// source instead of the generated code.
+    pub debug: bool,
+    /// Generates a library without a `C` entry point, which exports its `pub` top-level
+    /// functions along with a header declaring them.
+    pub library: bool,
+}
+
+/// The generated `C` code, libraries also get a header.
+pub struct Output {
+    pub code: String,
+    pub header: Option<String>,
+}
+
+/// Generates `C` code out of a checked `Razor` program, `source` is the code it was checked from.
 pub fn gen_c(
-    items: impl IntoIterator<Item = Spanned<Item>>,
-) -> Result<String, Spanned<CodegenError>> {
+    program: Program,
+    source: &str,
+    options: Options,
+) -> Result<Output, Spanned<CodegenError>> {
+    let mut codegen = Codegen {
+        options,
+        lines: Lines::new(source),
+        ..Default::default()
+    };
+
+    let mut exported = Vec::new();
+    for item in program.items.iter() {
+        match &item.value {
+            Item::Struct(s) => {
+                codegen.structs.push(s.ident.value.clone());
+                codegen
+                    .fields
+                    .insert(s.ident.value.clone(), s.fields.clone());
+            }
+            Item::Enum(e) => {
+                codegen.structs.push(e.ident.value.clone());
+                codegen
+                    .variants
+                    .insert(e.ident.value.clone(), e.variants.clone());
+            }
+            Item::Trait(t) => {
+                codegen
+                    .traits
+                    .insert(t.ident.value.clone(), t.methods.clone());
+            }
+            Item::Fn(f) if f.no_mangle || (f.exported && codegen.options.library) => {
+                if is_reserved(&f.ident.value) {
+                    let error = CodegenError::ReservedExport(f.ident.value.clone());
+                    return Err(error.span(f.ident.range()));
+                }
+
+                codegen.exports.insert(f.ident.value.clone());
+                if f.exported && codegen.options.library {
+                    exported.push(f.clone());
+                }
+            }
+            Item::Fn(_) => {}
+            Item::Extern(e) => {
+                if is_keyword(&e.ident.value) {
+                    let error = CodegenError::KeywordExtern(e.ident.value.clone());
+                    return Err(error.span(e.ident.range()));
+                }
+
+                match &e.header {
+                    Some(header) => _ = codegen.headers.insert(header.clone()),
+                    None => {
+                        let prototype = codegen.gen_extern_prototype(e);
+                        codegen.prototypes += &prototype;
+                    }
+                }
+
+                codegen.externs.insert(e.ident.value.clone());
+            }
+        }
+    }
+
+    codegen.find_droppable();
+
     let mut code = String::new();
-    for item in items {
+    // Traits only exist as the vtables declared in the prologue and externs as prototypes.
+    let items = program
+        .items
+        .into_iter()
+        .filter(|item| !matches!(item.value, Item::Trait(_) | Item::Extern(_)));
+
+    let mut main = String::new();
+    for Spanned { value: item, .. } in items {
         code += "\n";
-        match item.value {
-            Item {
-                ident,
-                kind: ItemKind::Fn { args, ty, block: _ },
+        match item {
+            Item::Fn(f) => {
+                if f.ident.value == "main" && !codegen.options.library {
+                    main = codegen.gen_main(&f);
+                }
+
+                code += &codegen.gen_fn(&f);
+            }
+            Item::Struct(s) => {
+                let fields = s
+                    .fields
+                    .iter()
+                    .map(|(ident, ty)| format!("    {} {};\n", codegen.c_type(ty), c_local(ident)))
+                    .collect::<String>();
+
+                let definition = format!("\nstruct {} {{\n{fields}}};\n", c_ident(&s.ident));
+                codegen.definitions.insert(s.ident.value, definition);
+                for method in s.methods.iter() {
+                    code += "\n";
+                    code += &codegen.gen_fn(method);
+                }
+            }
+            Item::Enum(e) => {
+                let definition = codegen.gen_enum(&e.ident.value, &e.variants);
+                codegen.definitions.insert(e.ident.value, definition);
+                for method in e.methods.iter() {
+                    code += "\n";
+                    code += &codegen.gen_fn(method);
+                }
+            }
+            Item::Trait(_) | Item::Extern(_) => {
+                unreachable!("Traits and externs are skipped above.")
+            }
+        }
+    }
+
+    let header = match codegen.options.library {
+        true => Some(codegen.gen_header(&exported)?),
+        false => None,
+    };
+
+    // List functions retain elements, so they are generated before the drops are collected.
+    let lists = codegen.gen_list_fns();
+
+    // Drop functions are generated last since every other function may use them.
+    let mut helpers = String::new();
+    let drops = codegen.collect_drops();
+    for ty in drops.iter() {
+        helpers += &codegen.gen_drop_fns(ty);
+    }
+
+    helpers += &lists;
+    helpers += &codegen.gen_vtables();
+    helpers += &codegen.gen_thunks();
+    helpers += &main;
+
+    // The functions of the program come last, so the `#line` directives in them don't apply to
+    // the helpers.
+    Ok(Output {
+        code: codegen.prologue() + &helpers + &code,
+        header,
+    })
+}
+
+#[derive(Default)]
+struct Codegen {
+    options: Options,
+    /// Structs and enums which are declared up front so they can refer to each other.
+    structs: Vec<String>,
+    fields: HashMap<String, Vec<(String, Type)>>,
+    variants: HashMap<String, Vec<(String, Vec<Type>)>>,
+    /// Definitions of the structs and enums, emitted after the types they contain.
+    definitions: HashMap<String, String>,
+    /// Prototypes of the functions, so they can call each other regardless of their order.
+    prototypes: String,
+    traits: BTreeMap<String, Vec<TraitMethod>>,
+    /// Functions marked `@no_mangle`, which keep their name.
+    exports: HashSet<String>,
+    /// Functions implemented in `C`, which are called by their name.
+    externs: HashSet<String>,
+    /// Headers declaring the externs.
+    headers: BTreeSet<String>,
+    /// Traits and the types converted into `dyn` values of them.
+    vtables: BTreeSet<(String, Type)>,
+    /// Structs and enums holding references which have to be released.
+    droppable: HashSet<String>,
+    /// Whether `razor_alloc` is used by `&expr`.
+    allocates: bool,
+    /// Whether strings are used, which are defined by the bundled runtime header.
+    strings: bool,
+    /// Types whose retain and drop functions are used.
+    drops: BTreeSet<Type>,
+    /// Locals owning references in each scope of the current function.
+    scopes: Vec<Vec<(String, Type)>>,
+    /// Locals which may hold a reference to the receiver, which they don't own.
+    borrowed: HashSet<String>,
+    /// Optional types which need a `typedef`.
+    optionals: BTreeSet<Type>,
+    /// Function types which need a `typedef`.
+    fn_types: BTreeSet<Type>,
+    /// Array types which need a `typedef`.
+    arrays: BTreeSet<Type>,
+    /// List types which need a `typedef` and the functions growing them.
+    lists: BTreeSet<Type>,
+    /// Whether indexing or slicing checks its bounds.
+    bounds_checks: bool,
+    lines: Lines,
+    /// Top-level functions used as values, which need a thunk.
+    fn_values: BTreeSet<(String, Type)>,
+    /// Functions lifted out of closures in the current function.
+    lifted: String,
+    /// Integer types which need a saturating conversion from `double`.
+    float_casts: BTreeSet<IntTy>,
+    /// Integer types whose division and remainder check for a zero divisor.
+    divisions: BTreeSet<IntTy>,
+    /// Counter used to create unique temporaries.
+    temps: usize,
+    /// Indentation depth of the generated statements.
+    depth: usize,
+    /// Loops enclosing the current statement.
+    loops: Vec<LoopLabels>,
+    /// Counter used to create unique loop labels.
+    loop_count: usize,
+    /// Number of `switch` statements enclosing the current statement.
+    switches: usize,
+}
+
+/// Tracks which labels of a loop are jumped to by a `goto`.
+#[derive(Debug, Clone, Copy)]
+struct LoopLabels {
+    id: usize,
+    /// Scope of the loop's body, exiting the loop releases it and every nested scope.
+    scope: usize,
+    /// Enclosing `switch` statements, a `break` inside a newer one has to jump out of it.
+    switches: usize,
+    used_break: bool,
+    used_continue: bool,
+}
+
+/// Where the value of a block's trailing expression ends up.
+#[derive(Debug, Clone, Copy)]
+enum Target<'a> {
+    Discard,
+    Return,
+    Assign(&'a str),
+}
+
+impl Codegen {
+    /// Generates the includes and helpers used by the generated code.
+    fn prologue(&mut self) -> String {
+        let mut code = "#include <stdarg.h>\n\
+                        #include <stdbool.h>\n\
+                        #include <stddef.h>\n\
+                        #include <stdint.h>\n\
+                        #include <stdio.h>\n\
+                        #include <stdlib.h>\n"
+            .to_owned();
+
+        for header in self.headers.iter() {
+            code += &format!("#include \"{header}\"\n");
+        }
+
+        code += ABORT;
+
+        for ident in self.structs.iter() {
+            let ident = c_ident(ident);
+            code += &format!("\ntypedef struct {ident} {ident};");
+        }
+
+        if !self.structs.is_empty() {
+            code += "\n";
+        }
+
+        code += &self.gen_dyn_typedefs();
+
+        if self.allocates || self.strings || !self.drops.is_empty() {
+            code += &self.gen_runtime();
+        }
+
+        if self.strings {
+            code += &self.gen_string_runtime();
+        }
+
+        if !self.lists.is_empty() {
+            code += &self.gen_list_runtime();
+        }
+
+        if self.bounds_checks {
+            code += &self.gen_bounds_checks();
+        }
+
+        code += &self.gen_definitions();
+
+        code += &self.gen_vtable_structs();
+
+        for ty in self.float_casts.clone() {
+            let (min, max) = c_limits(ty);
+            let c_ty = self.c_type(&Type::Int(ty));
+            code += &format!(
+                "\nstatic inline {c_ty} razor_f2i_{ty}(double value) {{\n    \
+                     if (value != value) return 0;\n    \
+                     if (value <= {min}) return {min};\n    \
+                     if (value >= {max}) return {max};\n    \
+                     return ({c_ty})value;\n\
+                 }}\n"
+            );
+        }
+
+        for ty in self.divisions.clone() {
+            code += &self.gen_division_fns(ty);
+        }
+
+        if !self.drops.is_empty() {
+            code += "\n";
+        }
+
+        for ty in self.drops.clone() {
+            code += &self.gen_drop_prototypes(&ty);
+        }
+
+        if !self.lists.is_empty() {
+            code += "\n";
+        }
+
+        code += &self.gen_list_prototypes();
+
+        if !self.fn_values.is_empty() {
+            code += "\n";
+        }
+
+        code += &self.gen_thunk_prototypes();
+        code + &self.prototypes
+    }
+
+    /// Generates the definitions of the structs and enums and the types built out of them, each
+    /// after the ones it holds by value.
+    fn gen_definitions(&mut self) -> String {
+        let types = self
+            .structs
+            .iter()
+            .map(|ident| match self.variants.contains_key(ident) {
+                true => Type::Enum(ident.clone()),
+                false => Type::Struct(ident.clone()),
+            })
+            .chain(self.optionals.iter().cloned())
+            .chain(self.fn_types.iter().cloned())
+            .chain(self.arrays.iter().cloned())
+            .chain(self.lists.iter().cloned())
+            .collect::<Vec<_>>();
+
+        let (mut defined, mut code) = (HashSet::new(), String::new());
+        for ty in types.iter() {
+            self.gen_definition(ty, &mut defined, &mut code);
+        }
+
+        code
+    }
+
+    fn gen_definition(&mut self, ty: &Type, defined: &mut HashSet<Type>, code: &mut String) {
+        if !defined.insert(ty.clone()) {
+            return;
+        }
+
+        // Incomplete types can only be pointed to or named in declarations.
+        let (by_value, by_name) = match ty {
+            Type::Struct(ident) => (
+                self.fields[ident].iter().map(|(_, ty)| ty).collect(),
+                vec![],
+            ),
+            Type::Enum(ident) => (
+                self.variants[ident]
+                    .iter()
+                    .flat_map(|(_, tys)| tys)
+                    .collect(),
+                vec![],
+            ),
+            Type::Optional(inner) | Type::Array(inner, _) => (vec![&**inner], vec![]),
+            Type::List(inner) => (vec![], vec![&**inner]),
+            Type::Fn { args, ty } => (vec![], args.iter().chain([&**ty]).collect()),
+            _ => unreachable!("Only types with a definition are passed."),
+        };
+
+        let dependencies = by_value
+            .into_iter()
+            .filter_map(|ty| definition_of(ty, true))
+            .chain(
+                by_name
+                    .into_iter()
+                    .filter_map(|ty| definition_of(ty, false)),
+            )
+            .collect::<Vec<_>>();
+
+        for dependency in dependencies.iter() {
+            self.gen_definition(dependency, defined, code);
+        }
+
+        *code += &match ty {
+            Type::Struct(ident) | Type::Enum(ident) => self.definitions[ident].clone(),
+            Type::Optional(inner) => {
+                let (c_ty, c_inner) = (self.c_type(ty), self.c_type(inner));
+                // Library headers define optionals as well, so they are guarded.
+                format!(
+                    "\n#ifndef RAZOR_{c_ty}\n\
+                     #define RAZOR_{c_ty}\n\
+                     typedef struct {c_ty} {{ bool some; {c_inner} value; }} {c_ty};\n\
+                     static inline {c_inner} razor_unwrap_{c_ty}({c_ty} optional) {{\n    \
+                         if (!optional.some) {{\n        \
+                             razor_abort(\"Unwrapped an empty optional.\\n\");\n    \
+                         }}\n    \
+                         return optional.value;\n\
+                     }}\n\
+                     #endif\n"
+                )
+            }
+            Type::Fn { .. } => self.gen_fn_typedef(ty),
+            _ => self.gen_array_typedef(ty),
+        };
+    }
+
+    /// Generates an enum as a tagged union, unit variants only get a tag.
+    fn gen_enum(&mut self, ident: &str, variants: &[(String, Vec<Type>)]) -> String {
+        let tags = variants
+            .iter()
+            .map(|(variant, _)| c_tag(ident, variant))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut payloads = String::new();
+        for (variant, fields) in variants.iter().filter(|(_, fields)| !fields.is_empty()) {
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| format!("{} _{i}; ", self.c_type(ty)))
+                .collect::<String>();
+
+            payloads += &format!("        struct {{ {fields}}} {};\n", c_variant(variant));
+        }
+
+        let union = match payloads.is_empty() {
+            true => String::new(),
+            false => format!("    union {{\n{payloads}    }};\n"),
+        };
+
+        format!(
+            "\nstruct {} {{\n    enum {{ {tags} }} tag;\n{union}}};\n",
+            c_ident(ident)
+        )
+    }
+
+    /// Generates the `C` entry point calling the `Razor` `main`, which gets the command line
+    /// arguments including the program's name if it takes `args: [string]`. An `int` it returns
+    /// is the exit code. The checker ensures it has one of those signatures.
+    fn gen_main(&mut self, f: &Fn) -> String {
+        let takes_args = !f.args.is_empty();
+        let call = match takes_args {
+            true => format!("{}(args)", c_ident("main")),
+            false => format!("{}()", c_ident("main")),
+        };
+
+        let call = match f.ty {
+            Type::Void => format!("    {call};\n    return 0;\n"),
+            _ => format!("    return (int){call};\n"),
+        };
+
+        if !takes_args {
+            return format!("\nint main(void) {{\n{call}}}\n");
+        }
+
+        let strings = Type::List(Box::new(Type::String));
+        let list = self.c_type(&strings);
+        let id = type_ident(&strings);
+        let string = self.c_type(&Type::String);
+        format!(
+            "\nint main(int argc, char** argv) {{\n    \
+                 {list} args = razor_{id}_from(NULL, 0, 0);\n    \
+                 for (int i = 0; i < argc; i++) {{\n        \
+                     {string} arg = {{ (int64_t)strlen(argv[i]), argv[i], NULL }};\n        \
+                     razor_{id}_push(args, arg);\n    \
+                 }}\n\
+             {call}\
+             }}\n"
+        )
+    }
+
+    fn gen_fn(&mut self, f: &Fn) -> String {
+        self.borrowed.clear();
+        let mut c_args = Vec::new();
+        if let Some(receiver) = &f.receiver {
+            c_args.push(format!("{}* self", self.c_type(receiver)));
+        }
+
+        // Arguments are owned by the callee.
+        self.scopes.push(Vec::new());
+        for (ident, ty) in f.args.iter() {
+            c_args.push(self.c_type(ty) + " " + ident);
+            self.declare(ident, ty);
+        }
+
+        let block = self.gen_block(&f.block, Target::Return);
+        self.scopes.pop();
+
+        let ident = self.fn_ident(&f.ident);
+        // Libraries only export the functions declared in their header.
+        let linkage = match self.options.library && !self.exports.contains(&f.ident.value) {
+            true => "static ",
+            false => "",
+        };
+
+        // Before C23 empty parentheses don't declare a prototype.
+        if c_args.is_empty() {
+            c_args.push("void".to_owned());
+        }
+
+        let signature = format!(
+            "{linkage}{} {ident}({})",
+            self.c_type(&f.ty),
+            c_args.join(", ")
+        );
+        self.prototypes += &format!("{signature};\n");
+        let code = format!("{}{signature} {block}\n", self.gen_line(f.ident.start));
+
+        std::mem::take(&mut self.lifted) + &code
+    }
+
+    /// Returns the `C` name of the top-level function `path`.
+    fn fn_ident(&self, path: &str) -> String {
+        match self.exports.contains(path) || self.externs.contains(path) {
+            true => path.to_owned(),
+            false => c_ident(path),
+        }
+    }
+
+    fn gen_block(&mut self, block: &Block, target: Target) -> String {
+        self.gen_block_with(block, target, Vec::new())
+    }
+
+    /// Generates a block whose scope starts with `bindings` holding borrowed values.
+    fn gen_block_with(
+        &mut self,
+        block: &Block,
+        target: Target,
+        bindings: Vec<(String, Type, String)>,
+    ) -> String {
+        self.depth += 1;
+        self.scopes.push(Vec::new());
+        let mut code = "{\n".to_owned();
+        for (ident, ty, value) in bindings {
+            code += &format!(
+                "{}{} {ident} = {};\n",
+                self.indent(),
+                self.c_type(&ty),
+                self.gen_retain(value, &ty)
+            );
+            self.declare(&ident, &ty);
+        }
+
+        for statement in block.statements.iter() {
+            code += &self.gen_line(statement.start);
+            code += &self.gen_statement(statement);
+        }
+
+        if let Some(expr) = &block.trailing_expr {
+            code += &self.gen_line(expr.start);
+        }
+
+        match (&block.trailing_expr, target) {
+            // Returning releases every scope by itself.
+            (Some(expr), Target::Return) => code += &self.gen_tail(expr, target),
+            (Some(expr), _) => {
+                code += &self.gen_tail(expr, target);
+                code += &self.gen_release(self.scopes.len() - 1);
+            }
+            // Falling off the end of a function.
+            (None, Target::Return) => code += &self.gen_release(0),
+            (None, _) => code += &self.gen_release(self.scopes.len() - 1),
+        }
+
+        self.scopes.pop();
+        self.depth -= 1;
+        code + &self.indent() + "}"
+    }
+
+    fn gen_statement(&mut self, statement: &Statement) -> String {
+        match statement {
+            Statement::VariableInit { ident, expr } => {
+                if self.is_receiver_ref(expr) {
+                    self.borrowed.insert(ident.clone());
+                }
+
+                let code = format!(
+                    "{}{} {ident} = {};\n",
+                    self.indent(),
+                    self.c_type(&expr.ty),
+                    self.gen_owned(expr)
+                );
+
+                self.declare(ident, &expr.ty);
+                code
+            }
+            Statement::Expr(expr) => self.gen_tail(expr, Target::Discard),
+            Statement::Assign {
+                place,
+                operator,
+                expr,
             } => {
-                if *ident == "main" {
-                    if !args.is_empty() {
-                        return Err(CodegenError::IncorrectMain.span(ident.start..ident.end));
+                let place_code = self.gen_expr(place);
+
+                // The new value is evaluated first since it may read the old one, the place is
+                // only evaluated once since indices may have side effects.
+                let pointer = self.next_temp();
+                if let Some(mut drop) = self.gen_drop(&format!("*{pointer}"), &place.ty) {
+                    if self.is_receiver_ref(place) {
+                        drop = format!("if (*{pointer} != self) {drop}");
                     }
 
-                    code += "int main() { return 0; }";
-                    continue;
+                    let (temp, c_ty) = (self.next_temp(), self.c_type(&place.ty));
+                    // Strings are concatenated into a new one replacing the old one.
+                    if let Some(Operator::Plus) = operator {
+                        let result = self.next_temp();
+                        let drop_temp = self.gen_drop(&temp, &Type::String).unwrap_or_default();
+                        return format!(
+                            "{}{{ {c_ty} {temp} = {}; {c_ty}* {pointer} = &{place_code}; \
+                             {c_ty} {result} = razor_string_concat(*{pointer}, {temp}); {drop} \
+                             {drop_temp} *{pointer} = {result}; }}\n",
+                            self.indent(),
+                            self.gen_owned(expr),
+                        );
+                    }
+
+                    return format!(
+                        "{}{{ {c_ty} {temp} = {}; {c_ty}* {pointer} = &{place_code}; {drop} \
+                         *{pointer} = {temp}; }}\n",
+                        self.indent(),
+                        self.gen_owned(expr),
+                    );
                 }
 
-                let c_args = args
-                    .into_iter()
-                    .map(|arg| c_type(Some(arg.ty)) + " " + &arg.ident.value)
+                if let (Some(operator), Type::Int(ty)) = (operator, &place.ty) {
+                    let value = match operator {
+                        Operator::Slash | Operator::Percent => {
+                            let (left, right) = (format!("*{pointer}"), self.gen_expr(expr));
+                            self.gen_division(*operator, &place.ty, left, right)
+                        }
+                        _ if wraps(*ty) => {
+                            let (left, right) = (format!("*{pointer}"), self.gen_expr(expr));
+                            self.gen_wrapping(c_operator(*operator), &place.ty, &left, &right)
+                        }
+                        _ => return self.gen_compound(place_code, Some(*operator), expr),
+                    };
+
+                    let c_ty = self.c_type(&place.ty);
+                    return format!(
+                        "{}{{ {c_ty}* {pointer} = &{place_code}; *{pointer} = {value}; }}\n",
+                        self.indent(),
+                    );
+                }
+
+                self.gen_compound(place_code, *operator, expr)
+            }
+            Statement::For { kind, block } => {
+                self.scopes.push(Vec::new());
+                self.loops.push(LoopLabels {
+                    id: self.loop_count,
+                    scope: self.scopes.len() - 1,
+                    switches: self.switches,
+                    used_break: false,
+                    used_continue: false,
+                });
+                self.loop_count += 1;
+
+                self.depth += 1;
+                let inner = self.indent();
+                let (header, prelude) = match kind {
+                    Loop::Infinite => ("for (;;)".to_owned(), String::new()),
+                    Loop::While(condition) => (
+                        format!("while ({})", self.gen_expr(condition)),
+                        String::new(),
+                    ),
+                    Loop::Range {
+                        ident,
+                        start,
+                        end,
+                        inclusive,
+                    } => {
+                        let (ty, index, end_temp) =
+                            (self.c_type(&start.ty), self.next_temp(), self.next_temp());
+                        let (start, end) = (self.gen_expr(start), self.gen_expr(end));
+
+                        // The index is copied so that the body can't affect the iteration.
+                        let header = match inclusive {
+                            false => format!(
+                                "for ({ty} {index} = {start}, {end_temp} = {end}; \
+                                 {index} < {end_temp}; {index}++)"
+                            ),
+                            true => {
+                                let done = self.next_temp();
+                                format!(
+                                    "for ({ty} {index} = {start}, {end_temp} = {end}, \
+                                     {done} = {index} > {end_temp}; !{done}; \
+                                     {done} = {index} == {end_temp}, {index}++)"
+                                )
+                            }
+                        };
+
+                        (header, format!("{inner}{ty} {ident} = {index};\n"))
+                    }
+                    Loop::Optional { ident, expr } => {
+                        let Type::Optional(inner_ty) = &expr.ty else {
+                            unreachable!()
+                        };
+
+                        // The binding takes over the value, it is released with the body.
+                        let temp = self.next_temp();
+                        let prelude = format!(
+                            "{inner}{} {temp} = {};\n\
+                             {inner}if (!{temp}.some) break;\n\
+                             {inner}{} {ident} = {temp}.value;\n",
+                            self.c_type(&expr.ty),
+                            self.gen_owned(expr),
+                            self.c_type(inner_ty),
+                        );
+                        self.declare(ident, inner_ty);
+
+                        ("for (;;)".to_owned(), prelude)
+                    }
+                };
+
+                let mut body = prelude;
+                for statement in block.statements.iter() {
+                    body += &self.gen_line(statement.start);
+                    body += &self.gen_statement(statement);
+                }
+
+                body += &self.gen_release(self.scopes.len() - 1);
+                self.scopes.pop();
+                let labels = self.loops.pop().expect("The loop was pushed above.");
+                if labels.used_continue {
+                    body += &format!("{inner}_l{}_continue:;\n", labels.id);
+                }
+
+                self.depth -= 1;
+                let mut code = format!("{}{header} {{\n{body}{}}}\n", self.indent(), self.indent());
+                if labels.used_break {
+                    code += &format!("{}_l{}_break:;\n", self.indent(), labels.id);
+                }
+
+                code
+            }
+            Statement::Break(depth) => {
+                let index = self.loops.len() - 1 - depth;
+                let release = self.gen_release(self.loops[index].scope);
+                if *depth == 0 && self.loops[index].switches == self.switches {
+                    return format!("{release}{}break;\n", self.indent());
+                }
+
+                self.loops[index].used_break = true;
+                format!(
+                    "{release}{}goto _l{}_break;\n",
+                    self.indent(),
+                    self.loops[index].id
+                )
+            }
+            Statement::Continue(depth) => {
+                let index = self.loops.len() - 1 - depth;
+                let release = self.gen_release(self.loops[index].scope);
+                if *depth == 0 {
+                    return format!("{release}{}continue;\n", self.indent());
+                }
+
+                self.loops[index].used_continue = true;
+                format!(
+                    "{release}{}goto _l{}_continue;\n",
+                    self.indent(),
+                    self.loops[index].id
+                )
+            }
+            Statement::Return(Some(expr)) => self.gen_return(expr),
+            Statement::Return(None) => format!("{}{}return;\n", self.gen_release(0), self.indent()),
+        }
+    }
+
+    /// Generates a statement storing the value of `expr` into `target`.
+    fn gen_tail(&mut self, expr: &Expr, target: Target) -> String {
+        let code = match &expr.kind {
+            ExprKind::If {
+                condition,
+                block,
+                else_block,
+            } => self.gen_if(condition, block, else_block.as_ref(), target),
+            ExprKind::Match { expr, arms } => self.gen_match(expr, arms, target),
+            _ => match target {
+                Target::Return => return self.gen_return(expr),
+                Target::Discard => {
+                    let code = self.gen_expr(expr);
+                    match is_borrowed(expr) {
+                        true => format!("{code};"),
+                        false => self
+                            .gen_drop(&code, &expr.ty)
+                            .unwrap_or_else(|| format!("{code};")),
+                    }
+                }
+                Target::Assign(ident) => format!("{ident} = {};", self.gen_owned(expr)),
+            },
+        };
+
+        self.indent() + &code + "\n"
+    }
+
+    /// Generates a `return`, releasing every local once the value is computed.
+    fn gen_return(&mut self, expr: &Expr) -> String {
+        let code = self.gen_owned(expr);
+        if !self.owns_locals(0) {
+            return format!("{}return {code};\n", self.indent());
+        }
+
+        let release = self.gen_release(0);
+        if expr.ty == Type::Void {
+            return format!(
+                "{}{code};\n{release}{}return;\n",
+                self.indent(),
+                self.indent()
+            );
+        }
+
+        let temp = self.next_temp();
+        format!(
+            "{}{} {temp} = {code};\n{release}{}return {temp};\n",
+            self.indent(),
+            self.c_type(&expr.ty),
+            self.indent()
+        )
+    }
+
+    fn gen_if(
+        &mut self,
+        condition: &Expr,
+        block: &Block,
+        else_block: Option<&Block>,
+        target: Target,
+    ) -> String {
+        let mut code = format!(
+            "if ({}) {}",
+            self.gen_expr(condition),
+            self.gen_block(block, target)
+        );
+
+        if let Some(else_block) = else_block {
+            code += &format!(" else {}", self.gen_block(else_block, target));
+        }
+
+        code
+    }
+
+    /// Generates a `match` as a `switch` over the tag of an enum if the arms only check variants,
+    /// otherwise as an `if` chain. The last arm needs no check since the match is exhaustive.
+    fn gen_match(&mut self, expr: &Expr, arms: &[Arm], target: Target) -> String {
+        self.depth += 1;
+        self.scopes.push(Vec::new());
+
+        // A borrowed value stays owned by its place, anything else is released after the match.
+        let temp = self.next_temp();
+        let mut code = format!(
+            "{{\n{}{} {temp} = {};\n",
+            self.indent(),
+            self.c_type(&expr.ty),
+            self.gen_expr(expr)
+        );
+        if !is_borrowed(expr) {
+            self.declare(&temp, &expr.ty);
+        }
+
+        let mut checked = Vec::new();
+        for arm in arms.iter() {
+            let (mut tests, mut bindings) = (Vec::new(), Vec::new());
+            self.gen_pattern(&arm.pattern, &temp, &expr.ty, &mut tests, &mut bindings);
+            let irrefutable = tests.is_empty();
+            checked.push((arm, tests, bindings));
+
+            // Any further arm is unreachable.
+            if irrefutable {
+                break;
+            }
+        }
+
+        let switch = matches!(expr.ty, Type::Enum(_))
+            && arms.iter().zip(checked.iter()).all(|(arm, (_, tests, _))| {
+                tests.is_empty()
+                    || matches!(&arm.pattern, Pattern::Variant { .. }) && tests.len() == 1
+            });
+
+        code += &self.indent();
+        let last = checked.len() - 1;
+        if switch {
+            self.switches += 1;
+            code += &format!("switch ({temp}.tag) {{\n");
+
+            let mut seen = HashSet::new();
+            for (i, (arm, _, bindings)) in checked.into_iter().enumerate() {
+                let label = match &arm.pattern {
+                    _ if i == last => "default".to_owned(),
+                    Pattern::Variant { ident, variant, .. } if seen.insert(variant) => {
+                        format!("case {}", c_tag(ident, variant))
+                    }
+                    // A variant which is already handled.
+                    _ => continue,
+                };
+
+                let block = self.gen_block_with(&arm.block, target, bindings);
+                code += &format!("{}{label}: {block} break;\n", self.indent());
+            }
+
+            code += &format!("{}}}\n", self.indent());
+            self.switches -= 1;
+        } else {
+            for (i, (arm, tests, bindings)) in checked.into_iter().enumerate() {
+                let block = self.gen_block_with(&arm.block, target, bindings);
+                code += &match (i, i == last) {
+                    (0, true) => block,
+                    (0, false) => format!("if ({}) {block}", tests.join(" && ")),
+                    (_, false) => format!(" else if ({}) {block}", tests.join(" && ")),
+                    (_, true) => format!(" else {block}"),
+                };
+            }
+
+            code += "\n";
+        }
+
+        code += &self.gen_release(self.scopes.len() - 1);
+        self.scopes.pop();
+        self.depth -= 1;
+        code + &self.indent() + "}"
+    }
+
+    /// Collects the checks `pattern` makes on the value at `place` and the values it binds.
+    fn gen_pattern(
+        &mut self,
+        pattern: &Pattern,
+        place: &str,
+        ty: &Type,
+        tests: &mut Vec<String>,
+        bindings: &mut Vec<(String, Type, String)>,
+    ) {
+        match (pattern, ty) {
+            (Pattern::Wildcard, _) => {}
+            (Pattern::Binding(ident), ty) => {
+                bindings.push((ident.clone(), ty.clone(), place.to_owned()));
+            }
+            (Pattern::Literal(Literal::Bool(true)), _) => tests.push(place.to_owned()),
+            (Pattern::Literal(Literal::Bool(false)), _) => tests.push(format!("!{place}")),
+            (Pattern::Literal(literal), ty) => {
+                let literal = self.gen_expr(&Expr {
+                    kind: ExprKind::Literal(literal.clone()),
+                    ty: ty.clone(),
+                });
+
+                match ty {
+                    Type::String => tests.push(format!("razor_string_eq({place}, {literal})")),
+                    _ => tests.push(format!("{place} == {literal}")),
+                }
+            }
+            (Pattern::None, _) => tests.push(format!("!{place}.some")),
+            (Pattern::Some(inner), Type::Optional(ty)) => {
+                tests.push(format!("{place}.some"));
+                self.gen_pattern(inner, &format!("{place}.value"), ty, tests, bindings);
+            }
+            (Pattern::Deref(inner), Type::Reference(ty)) => {
+                self.gen_pattern(inner, &format!("(*{place})"), ty, tests, bindings);
+            }
+            (
+                Pattern::Variant {
+                    ident,
+                    variant,
+                    fields,
+                },
+                _,
+            ) => {
+                tests.push(format!("{place}.tag == {}", c_tag(ident, variant)));
+                let tys = self.variants[ident]
+                    .iter()
+                    .find(|(other, _)| other == variant)
+                    .map(|(_, tys)| tys.clone())
+                    .unwrap_or_default();
+
+                for (i, (field, ty)) in fields.iter().zip(tys.iter()).enumerate() {
+                    let place = format!("{place}.{}._{i}", c_variant(variant));
+                    self.gen_pattern(field, &place, ty, tests, bindings);
+                }
+            }
+            (Pattern::Struct { ident, fields }, _) => {
+                for (field, (name, ty)) in fields.iter().zip(self.fields[ident].clone()) {
+                    let place = format!("{place}.{}", c_local(&name));
+                    self.gen_pattern(field, &place, &ty, tests, bindings);
+                }
+            }
+            _ => unreachable!("The checker ensures patterns match their types."),
+        }
+    }
+
+    fn indent(&self) -> String {
+        "    ".repeat(self.depth)
+    }
+
+    fn gen_expr(&mut self, expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Local(ident) => ident.clone(),
+            ExprKind::Receiver => match expr.ty {
+                Type::Reference(_) => "self".to_owned(),
+                _ => "(*self)".to_owned(),
+            },
+            ExprKind::Field { expr: inner, ident } => {
+                let access = match inner.ty {
+                    Type::Reference(_) => "->",
+                    _ => ".",
+                };
+
+                let (code, ident) = (self.gen_expr(inner), c_local(ident));
+                if is_borrowed(inner) || !self.needs_drop(&inner.ty) {
+                    return format!("{code}{access}{ident}");
+                }
+
+                // The field has to outlive the temporary it is read from.
+                let (temp, field) = (self.next_temp(), self.next_temp());
+                let drop = self.gen_drop(&temp, &inner.ty).unwrap_or_default();
+                format!(
+                    "({{ {} {temp} = {code}; {} {field} = {}; {drop} {field}; }})",
+                    self.c_type(&inner.ty),
+                    self.c_type(&expr.ty),
+                    self.gen_retain(format!("{temp}{access}{ident}"), &expr.ty),
+                )
+            }
+            ExprKind::MethodCall {
+                ident,
+                receiver,
+                args,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.gen_owned(arg))
+                    .collect::<Vec<_>>();
+
+                if is_borrowed(receiver) {
+                    let receiver_code = self.gen_expr(receiver);
+                    return gen_method_call(ident, &receiver_code, &receiver.ty, args);
+                }
+
+                // Receivers are passed by pointer so temporaries have to be stored first and
+                // released after the call.
+                let temp = self.next_temp();
+                let call = gen_method_call(ident, &temp, &receiver.ty, args);
+                let drop = self.gen_drop(&temp, &receiver.ty).unwrap_or_default();
+                let receiver = format!(
+                    "{} {temp} = {};",
+                    self.c_type(&receiver.ty),
+                    self.gen_expr(receiver)
+                );
+
+                if expr.ty == Type::Void {
+                    return format!("({{ {receiver} {call}; {drop} }})");
+                }
+
+                let result = self.next_temp();
+                format!(
+                    "({{ {receiver} {} {result} = {call}; {drop} {result}; }})",
+                    self.c_type(&expr.ty)
+                )
+            }
+            ExprKind::Literal(Literal::Int(value)) => match expr.ty {
+                Type::Int(IntTy::Int) if *value == i64::MIN as i128 => "INT64_MIN".to_owned(),
+                Type::Int(IntTy::Uint) => format!("{value}ull"),
+                _ => value.to_string(),
+            },
+            // Unsuffixed literals are `double`, which would compute `f32` arithmetic in it.
+            ExprKind::Literal(Literal::Float(value)) => match expr.ty {
+                Type::Float(FloatTy::F32) => format!("{value:?}f"),
+                _ => format!("{value:?}"),
+            },
+            ExprKind::Literal(Literal::Bool(value)) => value.to_string(),
+            ExprKind::Literal(Literal::String(value)) => self.gen_string_literal(value),
+            ExprKind::None => format!("({}){{ false }}", self.c_type(&expr.ty)),
+            ExprKind::Struct { ident, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| self.gen_owned(field))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!("({}){{ {fields} }}", c_ident(ident))
+            }
+            ExprKind::Variant {
+                ident,
+                variant,
+                args,
+            } if args.is_empty() => {
+                format!("({}){{ .tag = {} }}", c_ident(ident), c_tag(ident, variant))
+            }
+            ExprKind::Variant {
+                ident,
+                variant,
+                args,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.gen_owned(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!(
+                    "({}){{ .tag = {}, .{} = {{ {args} }} }}",
+                    c_ident(ident),
+                    c_tag(ident, variant),
+                    c_variant(variant)
+                )
+            }
+            ExprKind::Call { ident, args } if self.externs.contains(ident) => {
+                self.gen_extern_call(ident, args, &expr.ty)
+            }
+            ExprKind::Call { ident, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.gen_owned(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!("{}({args})", self.fn_ident(ident))
+            }
+            ExprKind::Unary {
+                operator: UnaryOperator::Reference,
+                expr: inner,
+            } => {
+                self.allocates = true;
+                let temp = self.next_temp();
+                let ty = self.c_type(&inner.ty);
+                format!(
+                    "({{ {ty}* {temp} = razor_alloc(sizeof({ty})); *{temp} = {}; {temp}; }})",
+                    self.gen_owned(inner)
+                )
+            }
+            ExprKind::Unary {
+                operator: UnaryOperator::Negate,
+                expr: inner,
+            } if matches!(inner.ty, Type::Int(ty) if wraps(ty)) => {
+                let code = self.gen_expr(inner);
+                self.gen_wrapping("-", &inner.ty, "0", &code)
+            }
+            ExprKind::Unary { operator, expr } => {
+                let operator = match operator {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "!",
+                    UnaryOperator::Reference => unreachable!(),
+                };
+
+                let code = format!("({operator}{})", self.gen_expr(expr));
+                self.narrow(code, &expr.ty)
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } if left.ty == Type::String => {
+                self.gen_string_binary(left, *operator, right, &expr.ty)
+            }
+            ExprKind::Binary {
+                left,
+                operator: operator @ (Operator::Slash | Operator::Percent),
+                right,
+            } if matches!(left.ty, Type::Int(_)) => {
+                let (left_code, right_code) = (self.gen_expr(left), self.gen_expr(right));
+                self.gen_division(*operator, &left.ty, left_code, right_code)
+            }
+            ExprKind::Binary {
+                left,
+                operator: operator @ (Operator::Plus | Operator::Minus | Operator::Star),
+                right,
+            } if matches!(left.ty, Type::Int(ty) if wraps(ty)) => {
+                let (left_code, right_code) = (self.gen_expr(left), self.gen_expr(right));
+                self.gen_wrapping(c_operator(*operator), &left.ty, &left_code, &right_code)
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let operator = c_operator(*operator);
+
+                let code = format!(
+                    "({} {operator} {})",
+                    self.gen_expr(left),
+                    self.gen_expr(right)
+                );
+
+                self.narrow(code, &expr.ty)
+            }
+            ExprKind::Cast(inner) => {
+                let code = self.gen_owned(inner);
+                self.gen_cast(code, &inner.ty, &expr.ty)
+            }
+            ExprKind::CallValue { expr: callee, args } => {
+                self.gen_call_value(callee, args, &expr.ty)
+            }
+            ExprKind::Function(ident) => self.gen_function_value(ident, &expr.ty),
+            ExprKind::Closure(closure) => self.gen_closure(closure, &expr.ty),
+            ExprKind::Dyn(inner) => {
+                let Type::Dyn(trait_) = &expr.ty else {
+                    unreachable!()
+                };
+
+                self.gen_dyn(inner, trait_)
+            }
+            ExprKind::If {
+                condition,
+                block,
+                else_block,
+            } => {
+                if expr.ty == Type::Void {
+                    let code = self.gen_if(condition, block, else_block.as_ref(), Target::Discard);
+                    return format!("({{ {code} }})");
+                }
+
+                let temp = self.next_temp();
+                let ty = self.c_type(&expr.ty);
+                let code =
+                    self.gen_if(condition, block, else_block.as_ref(), Target::Assign(&temp));
+
+                format!("({{ {ty} {temp}; {code} {temp}; }})")
+            }
+            ExprKind::Array(exprs) => {
+                let items = exprs
+                    .iter()
+                    .map(|expr| self.gen_owned(expr))
                     .collect::<Vec<_>>()
                     .join(", ");
 
-                code += &format!("{} {}({}) {{\n}}", c_type(ty), ident.value, c_args);
+                format!("({}){{ {{ {items} }} }}", self.c_type(&expr.ty))
+            }
+            ExprKind::Repeat { expr: inner, len } => self.gen_repeat(inner, *len, &expr.ty),
+            ExprKind::Index {
+                expr: inner,
+                index,
+                location,
+            } => self.gen_index(inner, index, *location, &expr.ty),
+            ExprKind::Slice {
+                expr: inner,
+                start,
+                end,
+                inclusive,
+                location,
+            } => self.gen_slice(
+                inner,
+                start.as_deref(),
+                end.as_deref(),
+                *inclusive,
+                *location,
+                &expr.ty,
+            ),
+            ExprKind::Len(inner) => self.gen_len(inner),
+            ExprKind::Format(parts) => self.gen_format(parts),
+            ExprKind::ToString(inner) => {
+                let code = self.gen_owned(inner);
+                self.gen_to_string(code, &inner.ty)
+            }
+            ExprKind::Builtin {
+                builtin,
+                args,
+                location,
+            } => self.gen_builtin(*builtin, args, *location, &expr.ty),
+            ExprKind::Match { expr: inner, arms } => {
+                if expr.ty == Type::Void {
+                    let code = self.gen_match(inner, arms, Target::Discard);
+                    return format!("({{ {code} }})");
+                }
+
+                let temp = self.next_temp();
+                let ty = self.c_type(&expr.ty);
+                let code = self.gen_match(inner, arms, Target::Assign(&temp));
+
+                format!("({{ {ty} {temp}; {code} {temp}; }})")
+            }
+        }
+    }
+
+    /// Truncates the result of an arithmetic expression back to `ty`, since integers smaller than
+    /// `int` are promoted in `C`.
+    fn narrow(&mut self, code: String, ty: &Type) -> String {
+        match ty {
+            Type::Int(int) if int.bits() < 32 => format!("(({}){code})", self.c_type(ty)),
+            _ => code,
+        }
+    }
+
+    /// Applies the arithmetic `operator` to integers of type `ty` on their unsigned 64 bit
+    /// values, so they wrap around on overflow like in the other backends instead of being
+    /// undefined.
+    fn gen_wrapping(&mut self, operator: &str, ty: &Type, left: &str, right: &str) -> String {
+        format!(
+            "(({})((uint64_t)({left}) {operator} (uint64_t)({right})))",
+            self.c_type(ty)
+        )
+    }
+
+    /// Assigns `expr` to `place_code`, applying `operator` to both first.
+    fn gen_compound(
+        &mut self,
+        place_code: String,
+        operator: Option<Operator>,
+        expr: &Expr,
+    ) -> String {
+        format!(
+            "{}{place_code} {}= {};\n",
+            self.indent(),
+            operator.map_or("", c_operator),
+            self.gen_expr(expr)
+        )
+    }
+
+    /// Divides or takes the remainder of integers of type `ty` through a function which aborts on
+    /// a zero divisor, `C` would trap or leave it undefined.
+    fn gen_division(
+        &mut self,
+        operator: Operator,
+        ty: &Type,
+        left: String,
+        right: String,
+    ) -> String {
+        let Type::Int(ty) = ty else {
+            unreachable!("Only integer divisions are checked.")
+        };
+
+        self.divisions.insert(*ty);
+        let name = match operator {
+            Operator::Slash => "div",
+            _ => "rem",
+        };
+
+        format!("razor_{name}_{ty}({left}, {right})")
+    }
+
+    /// Generates the division and remainder functions of the integer type `ty`. Dividing the
+    /// smallest signed value by `-1` overflows, so the quotient wraps around like the other
+    /// operators.
+    fn gen_division_fns(&mut self, ty: IntTy) -> String {
+        let c_ty = self.c_type(&Type::Int(ty));
+        let (quotient, remainder) = match ty.signed() {
+            true => (
+                format!("right == -1 ? ({c_ty})(0 - (uint64_t)left) : left / right"),
+                "right == -1 ? 0 : left % right",
+            ),
+            false => ("left / right".to_owned(), "left % right"),
+        };
+
+        format!(
+            "\nstatic inline {c_ty} razor_div_{ty}({c_ty} left, {c_ty} right) {{\n    \
+                 if (right == 0) razor_abort(\"Division by zero.\\n\");\n    \
+                 return {quotient};\n\
+             }}\n\
+             static inline {c_ty} razor_rem_{ty}({c_ty} left, {c_ty} right) {{\n    \
+                 if (right == 0) razor_abort(\"Division by zero.\\n\");\n    \
+                 return {remainder};\n\
+             }}\n"
+        )
+    }
+
+    /// Converts `code` of type `from` into `to`.
+    fn gen_cast(&mut self, code: String, from: &Type, to: &Type) -> String {
+        if from == to {
+            return code;
+        }
+
+        match (from, to) {
+            (Type::Optional(inner_from), Type::Optional(inner_to)) => {
+                let temp = self.next_temp();
+                let value = self.gen_cast(format!("{temp}.value"), inner_from, inner_to);
+                format!(
+                    "({{ {} {temp} = {code}; ({}){{ {temp}.some, {value} }}; }})",
+                    self.c_type(from),
+                    self.c_type(to),
+                )
+            }
+            (Type::Optional(inner), to) => {
+                let code = format!("razor_unwrap_{}({code})", self.c_type(from));
+                self.gen_cast(code, inner, to)
+            }
+            (from, Type::Optional(inner)) => {
+                let value = self.gen_cast(code, from, inner);
+                format!("({}){{ true, {value} }}", self.c_type(to))
+            }
+            (Type::Array(..), Type::List(_)) => self.gen_to_list(code, from, to),
+            (from, Type::String) => self.gen_to_string(code, from),
+            (Type::Float(_), Type::Int(ty)) => {
+                self.float_casts.insert(*ty);
+                format!("razor_f2i_{ty}({code})")
+            }
+            (Type::Reference(_), Type::Int(_)) => {
+                let (temp, address) = (self.next_temp(), self.next_temp());
+                let (from_ty, to_ty) = (self.c_type(from), self.c_type(to));
+                let drop = self.gen_drop(&temp, from).unwrap_or_default();
+                format!(
+                    "({{ {from_ty} {temp} = {code}; {to_ty} {address} = ({to_ty})(uintptr_t)\
+                     {temp}; {drop} {address}; }})"
+                )
+            }
+            (_, to) => format!("(({}){code})", self.c_type(to)),
+        }
+    }
+
+    fn next_temp(&mut self) -> String {
+        self.temps += 1;
+        format!("_t{}", self.temps)
+    }
+
+    fn c_type(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Void => "void".to_owned(),
+            Type::Bool => "bool".to_owned(),
+            Type::Int(ty) => match ty {
+                IntTy::Int => "int64_t",
+                IntTy::I8 => "int8_t",
+                IntTy::I16 => "int16_t",
+                IntTy::I32 => "int32_t",
+                IntTy::Uint => "uint64_t",
+                IntTy::U8 => "uint8_t",
+                IntTy::U16 => "uint16_t",
+                IntTy::U32 => "uint32_t",
+            }
+            .to_owned(),
+            Type::Float(FloatTy::Float) => "double".to_owned(),
+            Type::Float(FloatTy::F32) => "float".to_owned(),
+            Type::String => {
+                self.strings = true;
+                "razor_string".to_owned()
             }
+            Type::Struct(ident) | Type::Enum(ident) => c_ident(ident),
+            Type::Reference(inner) => self.c_type(inner) + "*",
+            Type::Optional(inner) => {
+                self.c_type(inner);
+                self.optionals.insert(ty.clone());
+                format!("optional_{}", type_ident(inner))
+            }
+            Type::Dyn(ident) => format!("dyn_{}", c_ident(ident)),
+            Type::Fn { args, ty: ret } => {
+                for ty in args.iter().chain([&**ret]) {
+                    self.c_type(ty);
+                }
+
+                self.fn_types.insert(ty.clone());
+                type_ident(ty)
+            }
+            Type::Array(inner, _) => {
+                self.c_type(inner);
+                self.arrays.insert(ty.clone());
+                type_ident(ty)
+            }
+            // Lists can always be popped, which needs an optional of the element type.
+            Type::List(inner) => {
+                self.c_type(&Type::Optional(inner.clone()));
+                self.lists.insert(ty.clone());
+                type_ident(ty) + "*"
+            }
+            Type::Param(_) => unreachable!("Only instances of generic items are generated."),
         }
     }
 
-    Ok(code)
+    /// Returns a string literal holding the source location of `offset` like `path:1:5`.
+    fn location(&self, offset: usize) -> String {
+        let (line, column) = self.lines.locate(offset);
+        let location = format!("{}:{line}:{column}", self.options.path);
+
+        format!("{location:?}")
+    }
+
+    /// Returns a `#line` directive attributing the following code to the source line of `offset`
+    /// if debug info is enabled.
+    fn gen_line(&self, offset: usize) -> String {
+        match self.options.debug {
+            true => format!(
+                "#line {} {:?}\n",
+                self.lines.locate(offset).0,
+                self.options.path
+            ),
+            false => String::new(),
+        }
+    }
 }
 
-fn c_type(ty: Option<Ty>) -> String {
-    let Some(ty) = ty else {
-        return "void".to_owned();
+/// Generates a call of the method at `path` on the value `receiver` of type `ty`, methods of
+/// `dyn` values are looked up in their vtable and lists have their methods generated.
+fn gen_method_call(path: &str, receiver: &str, ty: &Type, mut args: Vec<String>) -> String {
+    let access = match ty {
+        Type::Reference(_) => "->",
+        _ => ".",
     };
 
-    if ty.optional {
-        format!("optional_{}", ty.ident.value)
-    } else {
-        ty.ident.value
+    if let Type::Dyn(_) = ty.deref() {
+        let (_, method) = path.rsplit_once('.').expect("Trait methods have a path.");
+        args.insert(0, format!("{receiver}{access}data"));
+        let method = c_local(method);
+        return format!("{receiver}{access}vtable->{method}({})", args.join(", "));
+    }
+
+    // Lists already are pointers.
+    if let list @ Type::List(_) = ty.deref() {
+        let receiver = match ty {
+            Type::Reference(_) => format!("(*{receiver})"),
+            _ => receiver.to_owned(),
+        };
+
+        args.insert(0, receiver);
+        return format!("razor_{}_{path}({})", type_ident(list), args.join(", "));
+    }
+
+    let pointer = match ty {
+        Type::Reference(_) => "",
+        _ => "&",
+    };
+
+    args.insert(0, format!("{pointer}{receiver}"));
+    format!("{}({})", c_ident(path), args.join(", "))
+}
+
+/// Returns whether arithmetic on integers of type `ty` may overflow with undefined behavior in
+/// `C`. Besides signed integers, small unsigned integers are promoted to `int` first.
+fn wraps(ty: IntTy) -> bool {
+    ty.signed() || ty.bits() < 32
+}
+
+fn c_operator(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Plus => "+",
+        Operator::Minus => "-",
+        Operator::Star => "*",
+        Operator::Slash => "/",
+        Operator::Percent => "%",
+        Operator::Equal => "==",
+        Operator::NotEqual => "!=",
+        Operator::Less => "<",
+        Operator::LessEqual => "<=",
+        Operator::Greater => ">",
+        Operator::GreaterEqual => ">=",
+        Operator::And => "&&",
+        Operator::Or => "||",
+    }
+}
+
+/// Returns an identifier safe name for `ty`.
+fn type_ident(ty: &Type) -> String {
+    match ty {
+        Type::Optional(inner) => format!("optional_{}", type_ident(inner)),
+        Type::Reference(inner) => format!("ref_{}", type_ident(inner)),
+        Type::Struct(ident) | Type::Enum(ident) => c_ident(ident),
+        Type::Dyn(ident) => format!("dyn_{}", c_ident(ident)),
+        Type::Array(inner, len) => format!("array{len}_{}", type_ident(inner)),
+        Type::List(inner) => format!("list_{}", type_ident(inner)),
+        // The arity makes nested function types unambiguous.
+        Type::Fn { args, ty } => {
+            let mut ident = format!("fn{}", args.len());
+            for ty in args.iter().chain([&**ty]) {
+                ident += "_";
+                ident += &type_ident(ty);
+            }
+
+            ident
+        }
+        ty => ty.to_string(),
+    }
+}
+
+/// Returns the type whose definition has to precede a use of `ty`, structs and enums are declared
+/// up front so they are only needed `by_value`.
+fn definition_of(ty: &Type, by_value: bool) -> Option<Type> {
+    match ty {
+        Type::Struct(_) | Type::Enum(_) => by_value.then(|| ty.clone()),
+        Type::Optional(_) | Type::Array(..) | Type::List(_) | Type::Fn { .. } => Some(ty.clone()),
+        Type::Reference(inner) => definition_of(inner, false),
+        _ => None,
+    }
+}
+
+/// Returns the `C` name of the tag of `variant` of the enum `ident`.
+fn c_tag(ident: &str, variant: &str) -> String {
+    format!("{}_tag_{variant}", c_ident(ident))
+}
+
+/// Returns the `C` macros holding the limits of `ty`.
+fn c_limits(ty: IntTy) -> (&'static str, &'static str) {
+    match ty {
+        IntTy::Int => ("INT64_MIN", "INT64_MAX"),
+        IntTy::I8 => ("INT8_MIN", "INT8_MAX"),
+        IntTy::I16 => ("INT16_MIN", "INT16_MAX"),
+        IntTy::I32 => ("INT32_MIN", "INT32_MAX"),
+        IntTy::Uint => ("0", "UINT64_MAX"),
+        IntTy::U8 => ("0", "UINT8_MAX"),
+        IntTy::U16 => ("0", "UINT16_MAX"),
+        IntTy::U32 => ("0", "UINT32_MAX"),
     }
 }
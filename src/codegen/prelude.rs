@@ -0,0 +1,73 @@
+//! Calls of the prelude, whose functions are defined by the runtime header.
+
+use super::{rc::is_borrowed, Codegen};
+use crate::checker::typed::{Builtin, Expr, ExprKind, Type};
+
+impl Codegen {
+    /// Generates a call of `builtin`, `panic` and `assert` report `location` when they fail.
+    pub(super) fn gen_builtin(
+        &mut self,
+        builtin: Builtin,
+        args: &[Expr],
+        location: usize,
+        ty: &Type,
+    ) -> String {
+        match builtin {
+            Builtin::Print | Builtin::Println | Builtin::Eprintln => {
+                let stream = match builtin {
+                    Builtin::Eprintln => "stderr",
+                    _ => "stdout",
+                };
+
+                let newline = builtin != Builtin::Print;
+                let Some(arg) = args.first() else {
+                    let empty = self.gen_string_literal("");
+                    return format!("razor_print({stream}, {empty}, {newline})");
+                };
+
+                let code = self.gen_expr(arg);
+                if is_borrowed(arg) || matches!(arg.kind, ExprKind::Literal(_)) {
+                    return format!("razor_print({stream}, {code}, {newline})");
+                }
+
+                // The printed string is released right after.
+                let temp = self.next_temp();
+                let drop = self.gen_drop(&temp, &arg.ty).unwrap_or_default();
+                format!(
+                    "({{ {} {temp} = {code}; razor_print({stream}, {temp}, {newline}); {drop} }})",
+                    self.c_type(&arg.ty)
+                )
+            }
+            // The message is never released since the program aborts.
+            Builtin::Panic => {
+                let message = match args.first() {
+                    Some(message) => self.gen_expr(message),
+                    None => self.gen_string_literal("Panicked."),
+                };
+
+                format!("razor_panic({message}, {})", self.location(location))
+            }
+            Builtin::Assert => {
+                let condition = self.gen_expr(&args[0]);
+                let message = match args.get(1) {
+                    Some(message) => self.gen_expr(message),
+                    None => self.gen_string_literal("Assertion failed."),
+                };
+
+                format!(
+                    "({{ if (!({condition})) razor_panic({message}, {}); }})",
+                    self.location(location)
+                )
+            }
+            Builtin::ReadLine => {
+                let (line, some) = (self.next_temp(), self.next_temp());
+                format!(
+                    "({{ {} {line}; bool {some} = razor_read_line(&{line}); \
+                     ({}){{ {some}, {line} }}; }})",
+                    self.c_type(&Type::String),
+                    self.c_type(ty)
+                )
+            }
+        }
+    }
+}
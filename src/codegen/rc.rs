@@ -0,0 +1,351 @@
+//! Reference counting of heap allocations created by `&expr`.
+//!
+//! Every local, argument and field owns the references it holds. Values read out of a place are
+//! borrowed and get retained when they are stored somewhere else, every other expression
+//! produces an owned value which is released once it goes out of scope or gets overwritten.
+
+use super::{c_local, c_tag, c_variant, type_ident, Codegen};
+use crate::checker::typed::{Expr, ExprKind, Type};
+use std::collections::BTreeSet;
+
+impl Codegen {
+    /// Returns whether values of `ty` hold references which have to be released.
+    pub(super) fn needs_drop(&self, ty: &Type) -> bool {
+        match ty {
+            Type::String | Type::Reference(_) | Type::Dyn(_) | Type::Fn { .. } | Type::List(_) => {
+                true
+            }
+            Type::Optional(inner) | Type::Array(inner, _) => self.needs_drop(inner),
+            Type::Struct(ident) | Type::Enum(ident) => self.droppable.contains(ident),
+            _ => false,
+        }
+    }
+
+    /// Finds the structs and enums which hold references, directly or through other types.
+    pub(super) fn find_droppable(&mut self) {
+        loop {
+            let fields = self
+                .fields
+                .iter()
+                .map(|(ident, fields)| (ident, fields.iter().map(|(_, ty)| ty).collect()));
+            let variants = self.variants.iter().map(|(ident, variants)| {
+                (ident, variants.iter().flat_map(|(_, tys)| tys).collect())
+            });
+
+            let found = fields
+                .chain(variants)
+                .filter(|(ident, tys): &(&String, Vec<&Type>)| {
+                    !self.droppable.contains(*ident) && tys.iter().any(|ty| self.needs_drop(ty))
+                })
+                .map(|(ident, _)| ident.clone())
+                .collect::<Vec<_>>();
+
+            if found.is_empty() {
+                break;
+            }
+
+            self.droppable.extend(found);
+        }
+    }
+
+    /// Generates `expr` as a value the caller owns, retaining it if it is borrowed.
+    pub(super) fn gen_owned(&mut self, expr: &Expr) -> String {
+        let code = self.gen_expr(expr);
+        if self.is_receiver_ref(expr) {
+            // The receiver isn't counted, it outlives the locals referring to it.
+            let (temp, ty) = (self.next_temp(), self.c_type(&expr.ty));
+            let retain = self.gen_retain(temp.clone(), &expr.ty);
+            return format!("({{ {ty} {temp} = {code}; {temp} == self ? {temp} : {retain}; }})");
+        }
+
+        match is_borrowed(expr) {
+            true => self.gen_retain(code, &expr.ty),
+            false => code,
+        }
+    }
+
+    /// Returns whether `expr` may be a reference to the receiver, which isn't counted.
+    pub(super) fn is_receiver_ref(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Receiver => matches!(expr.ty, Type::Reference(_)),
+            ExprKind::Local(ident) => self.borrowed.contains(ident),
+            _ => false,
+        }
+    }
+
+    /// Retains the borrowed `code` if values of `ty` hold references.
+    pub(super) fn gen_retain(&mut self, code: String, ty: &Type) -> String {
+        if !self.needs_drop(ty) {
+            return code;
+        }
+
+        self.drops.insert(ty.clone());
+        format!("razor_retain_{}({code})", type_ident(ty))
+    }
+
+    /// Returns a call releasing `code` if values of `ty` have to be released.
+    pub(super) fn gen_drop(&mut self, code: &str, ty: &Type) -> Option<String> {
+        if !self.needs_drop(ty) {
+            return None;
+        }
+
+        self.drops.insert(ty.clone());
+        Some(format!("razor_drop_{}({code});", type_ident(ty)))
+    }
+
+    /// Declares a local owned by the innermost scope.
+    pub(super) fn declare(&mut self, ident: &str, ty: &Type) {
+        if self.needs_drop(ty) {
+            self.scopes
+                .last_mut()
+                .expect("Locals are always declared inside a scope.")
+                .push((ident.to_owned(), ty.clone()));
+        }
+    }
+
+    /// Generates statements releasing the locals of every scope starting at `scope`.
+    pub(super) fn gen_release(&mut self, scope: usize) -> String {
+        let locals = self.scopes[scope..]
+            .iter()
+            .rev()
+            .flat_map(|locals| locals.iter().rev())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut code = String::new();
+        for (ident, ty) in locals {
+            if let Some(drop) = self.gen_drop(&ident, &ty) {
+                let guard = match self.borrowed.contains(&ident) {
+                    true => format!("if ({ident} != self) "),
+                    false => String::new(),
+                };
+
+                code += &format!("{}{guard}{drop}\n", self.indent());
+            }
+        }
+
+        code
+    }
+
+    /// Returns whether any scope starting at `scope` owns a local.
+    pub(super) fn owns_locals(&self, scope: usize) -> bool {
+        self.scopes[scope..].iter().any(|locals| !locals.is_empty())
+    }
+
+    /// Generates the runtime used to count references.
+    pub(super) fn gen_runtime(&self) -> String {
+        let (alloc, free) = match self.options.leak_check {
+            true => ("\n    razor_live++;", "\n    razor_live--;"),
+            false => ("", ""),
+        };
+
+        let mut code = String::new();
+        if self.options.leak_check {
+            code += "\nstatic size_t razor_live = 0;\n\
+                     static void razor_report_leaks(void) {\n    \
+                         if (razor_live) {\n        \
+                             fprintf(stderr, \"Leak check: %zu allocations still alive.\\n\", \
+                                     razor_live);\n    \
+                         }\n\
+                     }\n\
+                     __attribute__((constructor)) static void razor_init_leak_check(void) {\n    \
+                         atexit(razor_report_leaks);\n\
+                     }\n";
+        }
+
+        code += &format!(
+            "\ntypedef union {{ size_t count; max_align_t align; }} razor_header;\n\
+             static inline void* razor_alloc(size_t size) {{\n    \
+                 razor_header* header = malloc(sizeof(razor_header) + size);\n    \
+                 if (!header) razor_abort(\"Out of memory.\\n\");\n    \
+                 header->count = 1;{alloc}\n    \
+                 return header + 1;\n\
+             }}\n\
+             static inline void razor_retain(void* ptr) {{\n    \
+                 ((razor_header*)ptr - 1)->count++;\n\
+             }}\n\
+             /* Returns whether the last reference is gone, the value then has to be freed. */\n\
+             static inline bool razor_release(void* ptr) {{\n    \
+                 return --((razor_header*)ptr - 1)->count == 0;\n\
+             }}\n\
+             static inline void razor_free(void* ptr) {{{free}\n    \
+                 free((razor_header*)ptr - 1);\n\
+             }}\n"
+        );
+
+        code
+    }
+
+    /// Collects every type whose retain and drop functions are needed, including the types
+    /// they recurse into.
+    pub(super) fn collect_drops(&mut self) -> BTreeSet<Type> {
+        let mut pending = self.drops.iter().cloned().collect::<Vec<_>>();
+        while let Some(ty) = pending.pop() {
+            let inner = match &ty {
+                Type::Reference(inner)
+                | Type::Optional(inner)
+                | Type::Array(inner, _)
+                | Type::List(inner) => vec![(**inner).clone()],
+                Type::Struct(ident) => self.fields[ident]
+                    .iter()
+                    .map(|(_, ty)| ty.clone())
+                    .collect(),
+                Type::Enum(ident) => self.variants[ident]
+                    .iter()
+                    .flat_map(|(_, tys)| tys.iter().cloned())
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            for inner in inner {
+                if self.needs_drop(&inner) && self.drops.insert(inner.clone()) {
+                    pending.push(inner);
+                }
+            }
+        }
+
+        self.drops.clone()
+    }
+
+    /// Generates the prototypes of the retain and drop functions of `ty`.
+    pub(super) fn gen_drop_prototypes(&mut self, ty: &Type) -> String {
+        let (c_ty, id) = (self.c_type(ty), type_ident(ty));
+        format!(
+            "static inline {c_ty} razor_retain_{id}({c_ty} value);\n\
+             static inline void razor_drop_{id}({c_ty} value);\n"
+        )
+    }
+
+    /// Generates the retain and drop functions of `ty`, structs and enums recurse into their
+    /// fields and arrays into their elements.
+    pub(super) fn gen_drop_fns(&mut self, ty: &Type) -> String {
+        let (c_ty, id) = (self.c_type(ty), type_ident(ty));
+        let (retain, drop) = match ty {
+            Type::Reference(inner) => {
+                let drop_inner = match self.needs_drop(inner) {
+                    true => format!("\n        razor_drop_{}(*value);", type_ident(inner)),
+                    false => String::new(),
+                };
+
+                (
+                    "    razor_retain(value);\n".to_owned(),
+                    format!(
+                        "    if (razor_release(value)) {{{drop_inner}\n        \
+                             razor_free(value);\n    \
+                         }}\n"
+                    ),
+                )
+            }
+            Type::Optional(inner) => {
+                let inner = type_ident(inner);
+                (
+                    format!("    if (value.some) razor_retain_{inner}(value.value);\n"),
+                    format!("    if (value.some) razor_drop_{inner}(value.value);\n"),
+                )
+            }
+            Type::Array(inner, len) => {
+                let inner = type_ident(inner);
+                let each = format!("    for (int64_t i = 0; i < {len}; i++) ");
+                (
+                    format!("{each}razor_retain_{inner}(value.items[i]);\n"),
+                    format!("{each}razor_drop_{inner}(value.items[i]);\n"),
+                )
+            }
+            // The elements are only released along with the list.
+            Type::List(inner) => {
+                let drop_items = match self.needs_drop(inner) {
+                    true => format!(
+                        "\n        for (int64_t i = 0; i < value->len; i++) \
+                         razor_drop_{}(value->items[i]);",
+                        type_ident(inner)
+                    ),
+                    false => String::new(),
+                };
+
+                (
+                    "    razor_retain(value);\n".to_owned(),
+                    format!(
+                        "    if (razor_release(value)) {{{drop_items}\n        \
+                             free(value->items);\n        \
+                             razor_free(value);\n    \
+                         }}\n"
+                    ),
+                )
+            }
+            // Literals aren't allocated.
+            Type::String => (
+                "    if (value.owner) razor_retain(value.owner);\n".to_owned(),
+                "    if (value.owner && razor_release(value.owner)) razor_free(value.owner);\n"
+                    .to_owned(),
+            ),
+            Type::Dyn(_) => (
+                "    razor_retain(value.data);\n".to_owned(),
+                "    value.vtable->razor_drop(value.data);\n".to_owned(),
+            ),
+            // Top-level functions have no environment.
+            Type::Fn { .. } => (
+                "    if (value.env) razor_retain(value.env);\n".to_owned(),
+                "    if (value.env) value.drop(value.env);\n".to_owned(),
+            ),
+            Type::Struct(ident) => {
+                let (mut retain, mut drop) = (String::new(), String::new());
+                for (field, ty) in self.fields[ident].clone() {
+                    if self.needs_drop(&ty) {
+                        let id = type_ident(&ty);
+                        let field = c_local(&field);
+                        retain += &format!("    razor_retain_{id}(value.{field});\n");
+                        drop += &format!("    razor_drop_{id}(value.{field});\n");
+                    }
+                }
+
+                (retain, drop)
+            }
+            Type::Enum(ident) => {
+                let (mut retain, mut drop) = (String::new(), String::new());
+                for (variant, tys) in self.variants[ident].clone() {
+                    let (mut retain_case, mut drop_case) = (String::new(), String::new());
+                    let payload = c_variant(&variant);
+                    for (i, ty) in tys.iter().enumerate() {
+                        if self.needs_drop(ty) {
+                            let id = type_ident(ty);
+                            retain_case += &format!(" razor_retain_{id}(value.{payload}._{i});");
+                            drop_case += &format!(" razor_drop_{id}(value.{payload}._{i});");
+                        }
+                    }
+
+                    if !drop_case.is_empty() {
+                        let case = format!("        case {}:", c_tag(ident, &variant));
+                        retain += &format!("{case}{retain_case} break;\n");
+                        drop += &format!("{case}{drop_case} break;\n");
+                    }
+                }
+
+                (
+                    format!("    switch (value.tag) {{\n{retain}        default: break;\n    }}\n"),
+                    format!("    switch (value.tag) {{\n{drop}        default: break;\n    }}\n"),
+                )
+            }
+            _ => unreachable!("Only types holding references are dropped."),
+        };
+
+        format!(
+            "\nstatic inline {c_ty} razor_retain_{id}({c_ty} value) {{\n\
+             {retain}    \
+                 return value;\n\
+             }}\n\
+             \n\
+             static inline void razor_drop_{id}({c_ty} value) {{\n\
+             {drop}\
+             }}\n"
+        )
+    }
+}
+
+/// Returns whether `expr` reads a value out of a place without taking ownership of it.
+pub(super) fn is_borrowed(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Local(_) | ExprKind::Receiver => true,
+        ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } => is_borrowed(expr),
+        _ => false,
+    }
+}
@@ -0,0 +1,163 @@
+/* Runtime library of Razor, bundled into the compiler and pasted into programs using strings.
+ * It builds on the counted allocations defined right before it. */
+
+#include <string.h>
+
+/* `len` bytes of UTF-8 at `data`. `owner` is the counted allocation holding the bytes, which
+ * slices of the string share, or NULL for literals. */
+typedef struct {
+    int64_t len;
+    const char* data;
+    void* owner;
+} razor_string;
+
+/* Allocates a string of `len` bytes, which the caller fills in through `data`. */
+static inline razor_string razor_string_new(int64_t len, char** data) {
+    char* bytes = razor_alloc(len ? (size_t)len : 1);
+    *data = bytes;
+    return (razor_string){ len, bytes, bytes };
+}
+
+static inline razor_string razor_string_concat(razor_string a, razor_string b) {
+    char* data;
+    razor_string result = razor_string_new(a.len + b.len, &data);
+    memcpy(data, a.data, (size_t)a.len);
+    memcpy(data + a.len, b.data, (size_t)b.len);
+    return result;
+}
+
+/* Concatenates `count` strings at once, which interpolated string literals are built with. */
+static inline razor_string razor_string_join(int64_t count, const razor_string* parts) {
+    int64_t len = 0;
+    for (int64_t i = 0; i < count; i++) len += parts[i].len;
+
+    char* data;
+    razor_string result = razor_string_new(len, &data);
+    for (int64_t i = 0; i < count; i++) {
+        memcpy(data, parts[i].data, (size_t)parts[i].len);
+        data += parts[i].len;
+    }
+
+    return result;
+}
+
+static inline bool razor_string_eq(razor_string a, razor_string b) {
+    return a.len == b.len && memcmp(a.data, b.data, (size_t)a.len) == 0;
+}
+
+/* Compares the bytes of `a` and `b`, which orders UTF-8 by code points. */
+static inline int razor_string_compare(razor_string a, razor_string b) {
+    int result = memcmp(a.data, b.data, (size_t)(a.len < b.len ? a.len : b.len));
+    if (result) return result;
+    return (a.len > b.len) - (a.len < b.len);
+}
+
+/* Copies `s` into a NUL-terminated string for C code, which the caller frees. */
+static inline char* razor_string_to_c(razor_string s) {
+    char* data = malloc((size_t)s.len + 1);
+    if (!data) razor_abort("Out of memory.\n");
+
+    memcpy(data, s.data, (size_t)s.len);
+    data[s.len] = '\0';
+    return data;
+}
+
+/* Returns the bytes from `start` to `end` of `s` without copying them, the caller checks the
+ * bounds. */
+static inline razor_string razor_string_slice(razor_string s, int64_t start, int64_t end) {
+    if (s.owner) razor_retain(s.owner);
+    return (razor_string){ end - start, s.data + start, s.owner };
+}
+
+static inline razor_string razor_string_format(const char* format, ...) {
+    va_list args;
+    va_start(args, format);
+    int len = vsnprintf(NULL, 0, format, args);
+    va_end(args);
+
+    /* One more byte is allocated for the terminator `vsnprintf` writes. */
+    char* data = razor_alloc((size_t)len + 1);
+    va_start(args, format);
+    vsnprintf(data, (size_t)len + 1, format, args);
+    va_end(args);
+    return (razor_string){ len, data, data };
+}
+
+static inline razor_string razor_string_from_int(int64_t value) {
+    return razor_string_format("%lld", (long long)value);
+}
+
+static inline razor_string razor_string_from_uint(uint64_t value) {
+    return razor_string_format("%llu", (unsigned long long)value);
+}
+
+/* Uses the fewest significant digits which read back as `value`, a `float` if `f32` is set, in
+ * scientific notation only below `1e-4` and from `1e16` on. Integral values keep a `.0` to tell
+ * them apart from integers. */
+static inline razor_string razor_string_from_float(double value, bool f32) {
+    char buffer[48];
+    if (value != value || value - value != 0) {
+        snprintf(buffer, sizeof(buffer), "%g", value);
+        return razor_string_format("%s", buffer);
+    }
+
+    int precision = 1;
+    for (; precision < (f32 ? 9 : 17); precision++) {
+        snprintf(buffer, sizeof(buffer), "%.*e", precision - 1, value);
+        if (f32 ? strtof(buffer, NULL) == (float)value : strtod(buffer, NULL) == value) break;
+    }
+
+    snprintf(buffer, sizeof(buffer), "%.*e", precision - 1, value);
+    int exponent = atoi(strchr(buffer, 'e') + 1);
+    if (exponent >= -4 && exponent < 16) {
+        int decimals = precision - 1 - exponent;
+        snprintf(buffer, sizeof(buffer), "%.*f", decimals > 0 ? decimals : 0, value);
+    }
+
+    if (!strpbrk(buffer, ".e")) strcat(buffer, ".0");
+    return razor_string_format("%s", buffer);
+}
+
+static inline razor_string razor_string_from_bool(bool value) {
+    return value ? (razor_string){ 4, "true", NULL } : (razor_string){ 5, "false", NULL };
+}
+
+/* Writes `s` to `stream`, followed by a newline if `newline` is set. */
+static inline void razor_print(FILE* stream, razor_string s, bool newline) {
+    fwrite(s.data, 1, (size_t)s.len, stream);
+    if (newline) fputc('\n', stream);
+}
+
+/* Reports `message` along with the source location `at` and aborts. */
+__attribute__((noreturn)) static inline void razor_panic(razor_string message, const char* at) {
+    razor_abort("%s: %.*s\n", at, (int)message.len, message.data);
+}
+
+/* Reads a line of the standard input into `line` without its line ending, returns false once
+ * the input is exhausted. */
+static inline bool razor_read_line(razor_string* line) {
+    char* buffer = NULL;
+    size_t len = 0, cap = 0;
+    int c;
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (len == cap) {
+            cap = cap ? cap * 2 : 64;
+            buffer = realloc(buffer, cap);
+            if (!buffer) razor_abort("Out of memory.\n");
+        }
+
+        buffer[len++] = (char)c;
+    }
+
+    if (c == EOF && !len) {
+        *line = (razor_string){ 0, "", NULL };
+        return false;
+    }
+
+    if (len && buffer[len - 1] == '\r') len--;
+    char* data;
+    *line = razor_string_new((int64_t)len, &data);
+    if (len) memcpy(data, buffer, len);
+    free(buffer);
+    return true;
+}
@@ -0,0 +1,126 @@
+//! Strings and the operations on them.
+//!
+//! A string is a length and a pointer to its bytes along with the counted allocation holding
+//! them, literals point into static memory and have none. Operations call into the runtime
+//! header bundled with the compiler.
+
+use super::{c_operator, rc::is_borrowed, Codegen};
+use crate::{
+    checker::typed::{Expr, ExprKind, FloatTy, Type},
+    lexer::token::Operator,
+};
+
+impl Codegen {
+    /// Generates a string pointing at the bytes of the literal `value`.
+    pub(super) fn gen_string_literal(&mut self, value: &str) -> String {
+        let mut bytes = String::new();
+        for byte in value.bytes() {
+            match byte {
+                b'"' | b'\\' => bytes += &format!("\\{}", byte as char),
+                b' '..=b'~' => bytes.push(byte as char),
+                // Octal escapes end after three digits, unlike hexadecimal ones.
+                byte => bytes += &format!("\\{byte:03o}"),
+            }
+        }
+
+        format!(
+            "(({}){{ {}, \"{bytes}\", NULL }})",
+            self.c_type(&Type::String),
+            value.len()
+        )
+    }
+
+    /// Generates `left operator right` on strings, `+` concatenates them and comparisons compare
+    /// their bytes.
+    pub(super) fn gen_string_binary(
+        &mut self,
+        left: &Expr,
+        operator: Operator,
+        right: &Expr,
+        ty: &Type,
+    ) -> String {
+        let operation = |left: &str, right: &str| match operator {
+            Operator::Plus => format!("razor_string_concat({left}, {right})"),
+            Operator::Equal => format!("razor_string_eq({left}, {right})"),
+            Operator::NotEqual => format!("!razor_string_eq({left}, {right})"),
+            operator => format!(
+                "(razor_string_compare({left}, {right}) {} 0)",
+                c_operator(operator)
+            ),
+        };
+
+        let (left_code, right_code) = (self.gen_expr(left), self.gen_expr(right));
+        if is_borrowed(left) && is_borrowed(right) {
+            return operation(&left_code, &right_code);
+        }
+
+        // Owned operands are stored so they can be released after the operation.
+        let (left_temp, right_temp, result) =
+            (self.next_temp(), self.next_temp(), self.next_temp());
+        let mut drops = String::new();
+        for (expr, temp) in [(left, &left_temp), (right, &right_temp)] {
+            if !is_borrowed(expr) {
+                drops += &self.gen_drop(temp, &expr.ty).unwrap_or_default();
+            }
+        }
+
+        let string = self.c_type(&Type::String);
+        format!(
+            "({{ {string} {left_temp} = {left_code}, {right_temp} = {right_code}; \
+             {} {result} = {}; {drops} {result}; }})",
+            self.c_type(ty),
+            operation(&left_temp, &right_temp),
+        )
+    }
+
+    /// Generates an interpolated string literal, joining its parts in one allocation.
+    pub(super) fn gen_format(&mut self, parts: &[Expr]) -> String {
+        let (array, result) = (self.next_temp(), self.next_temp());
+        let (mut codes, mut drops) = (Vec::new(), String::new());
+        for (i, part) in parts.iter().enumerate() {
+            codes.push(self.gen_expr(part));
+            if !is_borrowed(part) && !matches!(part.kind, ExprKind::Literal(_)) {
+                let drop = self.gen_drop(&format!("{array}[{i}]"), &part.ty);
+                drops += &drop.unwrap_or_default();
+            }
+        }
+
+        let string = self.c_type(&Type::String);
+        format!(
+            "({{ {string} {array}[] = {{ {} }}; \
+             {string} {result} = razor_string_join({}, {array}); {drops} {result}; }})",
+            codes.join(", "),
+            parts.len(),
+        )
+    }
+
+    /// Converts the owned number, `bool` or optional of them `code` of type `from` into a new
+    /// string, strings are passed through.
+    pub(super) fn gen_to_string(&mut self, code: String, from: &Type) -> String {
+        self.c_type(&Type::String);
+        match from {
+            Type::String => code,
+            Type::Optional(inner) => {
+                let temp = self.next_temp();
+                let value = self.gen_to_string(format!("{temp}.value"), inner);
+                format!(
+                    "({{ {} {temp} = {code}; {temp}.some ? {value} : {}; }})",
+                    self.c_type(from),
+                    self.gen_string_literal("?"),
+                )
+            }
+            Type::Int(ty) if ty.signed() => format!("razor_string_from_int({code})"),
+            Type::Int(_) => format!("razor_string_from_uint({code})"),
+            Type::Float(ty) => {
+                format!("razor_string_from_float({code}, {})", *ty == FloatTy::F32)
+            }
+            Type::Bool => format!("razor_string_from_bool({code})"),
+            _ => unreachable!("Only printable values convert into strings."),
+        }
+    }
+
+    /// Returns the runtime library defining strings, which builds on the counted allocations.
+    pub(super) fn gen_string_runtime(&self) -> String {
+        format!("\n{}", include_str!("runtime.h"))
+    }
+}
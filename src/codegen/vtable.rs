@@ -0,0 +1,124 @@
+//! `dyn` values and the vtables they are dispatched through.
+//!
+//! A `dyn Trait` value is a pair of a counted reference to the value and a pointer to the vtable
+//! of its type. The vtable holds a function releasing the reference followed by a thunk for every
+//! method of the trait, which casts the data pointer back and calls the method of the type.
+
+use super::{c_ident, c_local, type_ident, Codegen};
+use crate::checker::typed::{Expr, Type};
+
+impl Codegen {
+    /// Generates the `typedef`s of the `dyn` values of every trait.
+    pub(super) fn gen_dyn_typedefs(&self) -> String {
+        let mut code = String::new();
+        for ident in self.traits.keys() {
+            let ident = c_ident(ident);
+            code += &format!(
+                "\ntypedef struct {ident}_vtable {ident}_vtable;\n\
+                 typedef struct {{ void* data; const {ident}_vtable* vtable; }} dyn_{ident};\n"
+            );
+        }
+
+        code
+    }
+
+    /// Generates the vtable layout of every trait and declares the vtables which are used.
+    pub(super) fn gen_vtable_structs(&mut self) -> String {
+        let mut code = String::new();
+        for (ident, methods) in self.traits.clone() {
+            let mut fields = "    void (*razor_drop)(void*);\n".to_owned();
+            for method in methods.iter() {
+                let args = method
+                    .args
+                    .iter()
+                    .map(|ty| ", ".to_owned() + &self.c_type(ty))
+                    .collect::<String>();
+
+                fields += &format!(
+                    "    {} (*{})(void*{args});\n",
+                    self.c_type(&method.ty),
+                    c_local(&method.ident)
+                );
+            }
+
+            code += &format!("\nstruct {0}_vtable {{\n{fields}}};\n", c_ident(&ident));
+        }
+
+        for (trait_, ty) in self.vtables.iter() {
+            let trait_ = c_ident(trait_);
+            code += &format!(
+                "static const {trait_}_vtable razor_vtable_{trait_}_{};\n",
+                type_ident(ty)
+            );
+        }
+
+        code
+    }
+
+    /// Generates the conversion of the reference `expr` into a `dyn` value of `trait_`.
+    pub(super) fn gen_dyn(&mut self, expr: &Expr, trait_: &str) -> String {
+        let Type::Reference(ty) = &expr.ty else {
+            unreachable!("The checker only turns references into dyn values.")
+        };
+
+        // The vtable releases the reference through its drop function.
+        self.drops.insert(expr.ty.clone());
+        self.vtables.insert((trait_.to_owned(), (**ty).clone()));
+
+        let trait_ = c_ident(trait_);
+        format!(
+            "(dyn_{trait_}){{ {}, &razor_vtable_{trait_}_{} }}",
+            self.gen_owned(expr),
+            type_ident(ty)
+        )
+    }
+
+    /// Generates the thunks and vtables of every type converted into a `dyn` value.
+    pub(super) fn gen_vtables(&mut self) -> String {
+        let mut code = String::new();
+        for (trait_, ty) in self.vtables.clone() {
+            let (c_trait, id) = (c_ident(&trait_), type_ident(&ty));
+            let prefix = format!("razor_{c_trait}_{id}");
+            let mut entries = vec![format!("{prefix}_drop")];
+            code += &format!(
+                "\nstatic void {prefix}_drop(void* self) {{\n    \
+                     razor_drop_ref_{id}(self);\n\
+                 }}\n"
+            );
+
+            for method in self.traits[&trait_].clone() {
+                let (mut params, mut args) =
+                    (vec!["void* self".to_owned()], vec!["self".to_owned()]);
+                for (i, ty) in method.args.iter().enumerate() {
+                    params.push(format!("{} _{i}", self.c_type(ty)));
+                    args.push(format!("_{i}"));
+                }
+
+                let call = format!(
+                    "{}({})",
+                    c_ident(&format!("{ty}.{}", method.ident)),
+                    args.join(", ")
+                );
+                let body = match method.ty {
+                    Type::Void => format!("{call};"),
+                    _ => format!("return {call};"),
+                };
+
+                let thunk = format!("{prefix}_{}", c_ident(&method.ident));
+                code += &format!(
+                    "\nstatic {} {thunk}({}) {{\n    {body}\n}}\n",
+                    self.c_type(&method.ty),
+                    params.join(", ")
+                );
+                entries.push(thunk);
+            }
+
+            code += &format!(
+                "\nstatic const {c_trait}_vtable razor_vtable_{c_trait}_{id} = {{ {} }};\n",
+                entries.join(", ")
+            );
+        }
+
+        code
+    }
+}
@@ -1,9 +1,16 @@
-use std::ptr;
+use crate::util::Spanned;
+use std::{ops::Range, ptr};
 
 pub trait Operator {
     fn precedence(&self) -> u32;
 }
 
+impl<O: Operator> Operator for Spanned<O> {
+    fn precedence(&self) -> u32 {
+        self.value.precedence()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ExprTree<T, O> {
     Value(T),
@@ -57,3 +64,14 @@ impl<T, O: Operator> ExprTree<T, O> {
         }
     }
 }
+
+impl<T, O> ExprTree<Spanned<T>, O> {
+    /// Returns the range from the start of the leftmost value to the end of the rightmost one.
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            ExprTree::Value(value) => value.range(),
+            ExprTree::Expression { left, right, .. } => left.range().start..right.range().end,
+            ExprTree::Enclosed(node) => node.range(),
+        }
+    }
+}
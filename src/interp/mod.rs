@@ -0,0 +1,832 @@
+//! A tree-walking interpreter running checked programs without a `C` compiler.
+//!
+//! It follows the semantics of the generated `C`, so a program prints the same output either way.
+//! Locals live in cells so that methods can assign to the fields of `self` through a [`Place`].
+//! `break`, `continue` and `return` unwind through [`Flow`] until the loop or function handling
+//! them. Panics, failed bounds checks and unwrapping empty optionals abort like in `C`, and so
+//! do divisions by zero, which `C` leaves undefined.
+
+mod value;
+
+use crate::{
+    checker::typed::{
+        Arm, Block, Builtin, Expr, ExprKind, Fn, IntTy, Item, Loop, Pattern, Program, Statement,
+        Type,
+    },
+    lexer::token::Operator,
+    parser::item::UnaryOperator,
+    util::{Lines, Span, Spanned},
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Write},
+    process,
+    rc::Rc,
+    thread,
+};
+pub use value::{format_float, round, saturate, wrap};
+use value::{Function, Place, Value};
+
+/// Programs recurse on the stack of the interpreter, which needs far more of it than `C`.
+const STACK_SIZE: usize = 1 << 30;
+
+/// Part of the stack kept free for the expressions evaluated between two calls, calls abort once
+/// they would use it.
+const STACK_RESERVE: usize = 1 << 26;
+
+/// Calls nested deeper than this abort instead of exhausting the memory, the stack of the
+/// generated `C` allows for less.
+pub const MAX_FRAMES: usize = 1 << 20;
+
+/// Runs the checked `program` read from `path`, `args` are passed to `main` after the path.
+/// Returns the exit code, programs calling into `C` are rejected before they run.
+pub fn run(
+    program: &Program,
+    source: &str,
+    path: &str,
+    args: Vec<String>,
+) -> Result<i32, Spanned<LoadError>> {
+    for item in program.items.iter() {
+        if let Item::Extern(e) = &item.value {
+            let error = LoadError::Extern(e.ident.value.clone());
+            return Err(error.span(e.ident.range()));
+        }
+    }
+
+    let main = program
+        .items
+        .iter()
+        .find_map(|item| match &item.value {
+            Item::Fn(f) if f.ident.value == "main" => Some(f),
+            _ => None,
+        })
+        .expect("The checker requires a \"main\".");
+
+    let takes_args = !main.args.is_empty();
+    Ok(thread::scope(|scope| {
+        let thread = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let interpreter = Interpreter::new(program, source, path);
+                let args = match takes_args {
+                    true => {
+                        let args = std::iter::once(path.to_owned())
+                            .chain(args)
+                            .map(|arg| Value::string(arg.into_bytes()))
+                            .collect();
+
+                        vec![Value::list(args)]
+                    }
+                    false => Vec::new(),
+                };
+
+                let code = match interpreter.call(main, None, args) {
+                    Value::Int(code) => wrap(code, IntTy::I32) as i32,
+                    _ => 0,
+                };
+
+                _ = io::stdout().flush();
+                code
+            })
+            .expect("Couldn't start the interpreter.");
+
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }))
+}
+
+#[derive(Debug, Clone)]
+pub enum LoadError {
+    /// An external function, which is implemented in `C`.
+    Extern(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Extern(ident) => write!(
+                f,
+                "The external function \"{ident}\" is implemented in C, so it can't be \
+                 interpreted."
+            ),
+        }
+    }
+}
+
+/// How control leaves a statement other than by falling through.
+enum Flow<'a> {
+    /// Breaks out of the loop at the given depth, `0` being the innermost one.
+    Break(usize),
+    /// Continues the loop at the given depth, `0` being the innermost one.
+    Continue(usize),
+    Return(Value<'a>),
+}
+
+type Eval<'a> = Result<Value<'a>, Flow<'a>>;
+
+/// The locals of a function call.
+#[derive(Default)]
+struct Frame<'a> {
+    locals: HashMap<&'a str, Rc<RefCell<Value<'a>>>>,
+    /// Where `self` lives inside of a method.
+    receiver: Option<Place<'a>>,
+}
+
+impl<'a> Frame<'a> {
+    /// Declares the local `ident`, replacing a previous one of the same name.
+    fn declare(&mut self, ident: &'a str, value: Value<'a>) {
+        self.locals.insert(ident, Rc::new(RefCell::new(value)));
+    }
+
+    fn get(&self, ident: &str) -> Value<'a> {
+        self.locals[ident].borrow().clone()
+    }
+}
+
+struct Interpreter<'a> {
+    /// Top-level functions and methods by their path.
+    fns: HashMap<&'a str, &'a Fn>,
+    /// Names of the fields of every struct in declaration order.
+    fields: HashMap<&'a str, Vec<&'a str>>,
+    path: &'a str,
+    lines: Lines,
+    /// Number of calls being run.
+    depth: Cell<usize>,
+    /// Address near the start of the stack of the interpreter, to measure how much is in use.
+    stack_base: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(program: &'a Program, source: &str, path: &'a str) -> Interpreter<'a> {
+        let mut interpreter = Interpreter {
+            fns: HashMap::new(),
+            fields: HashMap::new(),
+            path,
+            lines: Lines::new(source),
+            depth: Cell::new(0),
+            stack_base: 0,
+        };
+
+        interpreter.stack_base = &interpreter as *const _ as usize;
+
+        for item in program.items.iter() {
+            let methods = match &item.value {
+                Item::Fn(f) => {
+                    interpreter.fns.insert(&f.ident.value, f);
+                    continue;
+                }
+                Item::Struct(s) => {
+                    let fields = s.fields.iter().map(|(ident, _)| ident.as_str()).collect();
+                    interpreter.fields.insert(&s.ident.value, fields);
+                    &s.methods
+                }
+                Item::Enum(e) => &e.methods,
+                Item::Trait(_) | Item::Extern(_) => continue,
+            };
+
+            for method in methods.iter() {
+                interpreter.fns.insert(&method.ident.value, method);
+            }
+        }
+
+        interpreter
+    }
+
+    /// Calls `f` with `args`, methods get the place of their receiver.
+    fn call(&self, f: &'a Fn, receiver: Option<Place<'a>>, args: Vec<Value<'a>>) -> Value<'a> {
+        let mut frame = Frame {
+            receiver,
+            ..Default::default()
+        };
+
+        for ((ident, _), arg) in f.args.iter().zip(args) {
+            frame.declare(ident, arg);
+        }
+
+        self.body(&f.block, &mut frame)
+    }
+
+    /// Calls the function value `function` with `args`.
+    fn call_value(&self, function: Function<'a>, args: Vec<Value<'a>>) -> Value<'a> {
+        let (closure, captures) = match function {
+            Function::Item(ident) => return self.call_item(ident, args),
+            Function::Closure(closure, captures) => (closure, captures),
+        };
+
+        let mut frame = Frame::default();
+        for ((ident, _), value) in closure.captures.iter().zip(captures.iter()) {
+            frame.declare(ident, value.clone());
+        }
+
+        for ((ident, _), arg) in closure.f.args.iter().zip(args) {
+            frame.declare(ident, arg);
+        }
+
+        self.body(&closure.f.block, &mut frame)
+    }
+
+    /// Calls the top-level function `ident`.
+    fn call_item(&self, ident: &str, args: Vec<Value<'a>>) -> Value<'a> {
+        self.call(self.fns[ident], None, args)
+    }
+
+    /// Runs the body of a function, returning the value of its trailing expression or `return`.
+    /// Aborts like the virtual machine if calls nest too deep, before the stack overflows.
+    fn body(&self, block: &'a Block, frame: &mut Frame<'a>) -> Value<'a> {
+        let depth = self.depth.get();
+        let used = self.stack_base.abs_diff(&depth as *const _ as usize);
+        if depth == MAX_FRAMES || used > STACK_SIZE - STACK_RESERVE {
+            abort(b"Stack overflow.");
+        }
+
+        self.depth.set(depth + 1);
+        let value = match self.block(block, frame) {
+            Ok(value) | Err(Flow::Return(value)) => value,
+            Err(_) => unreachable!("The checker ensures loops are only exited inside of them."),
+        };
+
+        self.depth.set(depth);
+        value
+    }
+
+    fn block(&self, block: &'a Block, frame: &mut Frame<'a>) -> Eval<'a> {
+        for statement in block.statements.iter() {
+            self.statement(statement, frame)?;
+        }
+
+        match &block.trailing_expr {
+            Some(expr) => self.eval(expr, frame),
+            None => Ok(Value::Void),
+        }
+    }
+
+    fn statement(&self, statement: &'a Statement, frame: &mut Frame<'a>) -> Result<(), Flow<'a>> {
+        match statement {
+            Statement::VariableInit { ident, expr } => {
+                let value = self.eval(expr, frame)?;
+                frame.declare(ident, value);
+            }
+            Statement::Expr(expr) => _ = self.eval(expr, frame)?,
+            Statement::Assign {
+                place,
+                operator,
+                expr,
+            } => {
+                // The new value is evaluated first since it may read the old one.
+                let value = self.eval(expr, frame)?;
+                let target = self.place(place, frame)?;
+                let value = match operator {
+                    Some(operator) => target.read().binary(*operator, value, &place.ty),
+                    None => value,
+                };
+
+                target.write(value);
+            }
+            Statement::For { kind, block } => self.run_loop(kind, block, frame)?,
+            Statement::Break(depth) => return Err(Flow::Break(*depth)),
+            Statement::Continue(depth) => return Err(Flow::Continue(*depth)),
+            Statement::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval(expr, frame)?,
+                    None => Value::Void,
+                };
+
+                return Err(Flow::Return(value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_loop(
+        &self,
+        kind: &'a Loop,
+        block: &'a Block,
+        frame: &mut Frame<'a>,
+    ) -> Result<(), Flow<'a>> {
+        if let Loop::Range {
+            ident,
+            start,
+            end,
+            inclusive,
+        } = kind
+        {
+            // The bounds are evaluated once, the body can't affect the iteration.
+            let start = self.eval(start, frame)?.as_int();
+            let end = self.eval(end, frame)?.as_int() + *inclusive as i128;
+            for index in start..end {
+                frame.declare(ident, Value::Int(index));
+                if !self.iteration(block, frame)? {
+                    break;
+                }
+            }
+
+            return Ok(());
+        }
+
+        loop {
+            match kind {
+                Loop::Infinite => {}
+                Loop::While(condition) => {
+                    if !self.eval(condition, frame)?.as_bool() {
+                        break;
+                    }
+                }
+                Loop::Optional { ident, expr } => match self.eval(expr, frame)? {
+                    Value::Optional(Some(value)) => frame.declare(ident, *value),
+                    _ => break,
+                },
+                Loop::Range { .. } => unreachable!("Ranges are handled above."),
+            }
+
+            if !self.iteration(block, frame)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the body of a loop once, returns whether the loop goes on. Exiting an outer loop
+    /// passes on to it.
+    fn iteration(&self, block: &'a Block, frame: &mut Frame<'a>) -> Result<bool, Flow<'a>> {
+        match self.block(block, frame) {
+            Ok(_) | Err(Flow::Continue(0)) => Ok(true),
+            Err(Flow::Break(0)) => Ok(false),
+            Err(Flow::Break(depth)) => Err(Flow::Break(depth - 1)),
+            Err(Flow::Continue(depth)) => Err(Flow::Continue(depth - 1)),
+            Err(flow) => Err(flow),
+        }
+    }
+
+    fn eval(&self, expr: &'a Expr, frame: &mut Frame<'a>) -> Eval<'a> {
+        Ok(match &expr.kind {
+            ExprKind::Local(ident) => frame.get(ident),
+            ExprKind::Receiver => {
+                let receiver = frame.receiver.as_ref().expect("Only methods use self.");
+                match expr.ty {
+                    Type::Reference(_) => Value::Pointer(receiver.clone()),
+                    _ => receiver.read(),
+                }
+            }
+            // Only the field or element is copied out of its place.
+            ExprKind::Field { .. } | ExprKind::Index { .. } if expr.is_place() => {
+                self.place(expr, frame)?.read()
+            }
+            ExprKind::Field { expr: inner, ident } => {
+                let index = self.field(&inner.ty, ident);
+                match self.eval(inner, frame)? {
+                    Value::Reference(cell) => cell.borrow().items()[index].clone(),
+                    Value::Pointer(place) => place.child(index).read(),
+                    Value::Struct(mut fields) => fields.swap_remove(index),
+                    _ => unreachable!("Only structs have fields."),
+                }
+            }
+            ExprKind::MethodCall {
+                ident,
+                receiver,
+                args,
+            } => self.method_call(ident, receiver, args, frame)?,
+            ExprKind::Literal(literal) => Value::literal(literal, &expr.ty),
+            ExprKind::None => Value::Optional(None),
+            ExprKind::Struct { fields, .. } => Value::Struct(self.eval_all(fields, frame)?),
+            ExprKind::Variant { variant, args, .. } => {
+                Value::Variant(variant, self.eval_all(args, frame)?)
+            }
+            ExprKind::Call { ident, args } => {
+                let args = self.eval_all(args, frame)?;
+                self.call_item(ident, args)
+            }
+            ExprKind::Unary {
+                operator: UnaryOperator::Reference,
+                expr: inner,
+            } => Value::Reference(Rc::new(RefCell::new(self.eval(inner, frame)?))),
+            ExprKind::Unary {
+                operator,
+                expr: inner,
+            } => match (operator, self.eval(inner, frame)?) {
+                (UnaryOperator::Negate, Value::Int(value)) => {
+                    let Type::Int(ty) = expr.ty else {
+                        unreachable!("Integers have integer types.")
+                    };
+
+                    Value::Int(wrap(-value, ty))
+                }
+                (UnaryOperator::Negate, Value::Float(value)) => Value::Float(-value),
+                (UnaryOperator::Not, Value::Bool(value)) => Value::Bool(!value),
+                _ => unreachable!("The checker ensures operands fit their operator."),
+            },
+            ExprKind::Binary {
+                left,
+                operator: operator @ (Operator::And | Operator::Or),
+                right,
+            } => {
+                let left = self.eval(left, frame)?.as_bool();
+                match (operator, left) {
+                    (Operator::And, false) | (Operator::Or, true) => Value::Bool(left),
+                    _ => Value::Bool(self.eval(right, frame)?.as_bool()),
+                }
+            }
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let (ty, left) = (&left.ty, self.eval(left, frame)?);
+                let right = self.eval(right, frame)?;
+                left.binary(*operator, right, ty)
+            }
+            ExprKind::Cast(inner) => self.eval(inner, frame)?.cast(&inner.ty, &expr.ty),
+            ExprKind::CallValue { expr: callee, args } => {
+                let Value::Fn(function) = self.eval(callee, frame)? else {
+                    unreachable!("Only functions are called.")
+                };
+
+                let args = self.eval_all(args, frame)?;
+                self.call_value(function, args)
+            }
+            ExprKind::Function(ident) => Value::Fn(Function::Item(ident)),
+            ExprKind::Closure(closure) => {
+                let captures = closure
+                    .captures
+                    .iter()
+                    .map(|(ident, _)| frame.get(ident))
+                    .collect();
+
+                Value::Fn(Function::Closure(closure, captures))
+            }
+            ExprKind::Dyn(inner) => {
+                let (Value::Reference(cell), Type::Reference(ty)) =
+                    (self.eval(inner, frame)?, &inner.ty)
+                else {
+                    unreachable!("The checker only turns references into dyn values.")
+                };
+
+                Value::Dyn(cell, ty.to_string().into())
+            }
+            ExprKind::If {
+                condition,
+                block,
+                else_block,
+            } => match (self.eval(condition, frame)?.as_bool(), else_block) {
+                (true, _) => self.block(block, frame)?,
+                (false, Some(else_block)) => self.block(else_block, frame)?,
+                (false, None) => Value::Void,
+            },
+            ExprKind::Array(exprs) => Value::Array(self.eval_all(exprs, frame)?),
+            ExprKind::Repeat { expr: inner, len } => {
+                Value::Array(vec![self.eval(inner, frame)?; *len])
+            }
+            ExprKind::Index {
+                expr: inner,
+                index,
+                location,
+            } => {
+                let value = self.eval(inner, frame)?.deref();
+                let index = wrap(self.eval(index, frame)?.as_int(), IntTy::Int);
+                let index = self.check_index(index, value.len(), *location);
+                match value {
+                    Value::Array(mut items) => items.swap_remove(index),
+                    Value::List(cell) => cell.borrow().items()[index].clone(),
+                    Value::String(bytes) => Value::Int(bytes[index] as i128),
+                    _ => unreachable!("Only arrays, lists and strings are indexed."),
+                }
+            }
+            ExprKind::Slice {
+                expr: inner,
+                start,
+                end,
+                inclusive,
+                location,
+            } => {
+                let value = self.eval(inner, frame)?.deref();
+                let start = match start {
+                    Some(start) => wrap(self.eval(start, frame)?.as_int(), IntTy::Int),
+                    None => 0,
+                };
+
+                let len = value.len();
+                let end = match end {
+                    Some(end) => {
+                        wrap(self.eval(end, frame)?.as_int(), IntTy::Int) + *inclusive as i128
+                    }
+                    None => len as i128,
+                };
+
+                if start < 0 || start > end || end > len as i128 {
+                    let message = format!(
+                        "{}: Range {start}..{end} is out of bounds for length {len}.",
+                        self.location(*location)
+                    );
+                    abort(message.as_bytes());
+                }
+
+                let range = start as usize..end as usize;
+                match value {
+                    Value::String(bytes) => Value::string(&bytes[range]),
+                    Value::Array(items) => Value::list(items[range].to_vec()),
+                    Value::List(cell) => Value::list(cell.borrow().items()[range].to_vec()),
+                    _ => unreachable!("Only arrays, lists and strings are sliced."),
+                }
+            }
+            ExprKind::Len(inner) => Value::Int(self.eval(inner, frame)?.deref().len() as i128),
+            ExprKind::Format(parts) => {
+                let mut bytes = Vec::new();
+                for part in parts.iter() {
+                    bytes.extend_from_slice(self.eval(part, frame)?.as_bytes());
+                }
+
+                Value::string(bytes)
+            }
+            ExprKind::ToString(inner) => {
+                Value::string(self.eval(inner, frame)?.to_string(&inner.ty))
+            }
+            ExprKind::Builtin {
+                builtin,
+                args,
+                location,
+            } => self.builtin(*builtin, args, *location, frame)?,
+            ExprKind::Match { expr: inner, arms } => self.eval_match(inner, arms, frame)?,
+        })
+    }
+
+    fn eval_all(
+        &self,
+        exprs: &'a [Expr],
+        frame: &mut Frame<'a>,
+    ) -> Result<Vec<Value<'a>>, Flow<'a>> {
+        exprs.iter().map(|expr| self.eval(expr, frame)).collect()
+    }
+
+    /// Returns the place `expr` refers to, which the checker ensures it is.
+    fn place(&self, expr: &'a Expr, frame: &mut Frame<'a>) -> Result<Place<'a>, Flow<'a>> {
+        Ok(match &expr.kind {
+            ExprKind::Local(ident) => Place {
+                cell: frame.locals[ident.as_str()].clone(),
+                path: Vec::new(),
+            },
+            ExprKind::Receiver => frame.receiver.clone().expect("Only methods use self."),
+            ExprKind::Field { expr: inner, ident } => {
+                let index = self.field(&inner.ty, ident);
+                self.target(inner, frame)?.child(index)
+            }
+            ExprKind::Index {
+                expr: inner,
+                index,
+                location,
+            } => {
+                let index = wrap(self.eval(index, frame)?.as_int(), IntTy::Int);
+                let target = match inner.ty.deref() {
+                    Type::List(_) => {
+                        let Value::List(cell) = self.eval(inner, frame)?.deref() else {
+                            unreachable!("Lists have list values.")
+                        };
+
+                        Place {
+                            cell,
+                            path: Vec::new(),
+                        }
+                    }
+                    _ => self.target(inner, frame)?,
+                };
+
+                let len = target.read_len();
+                target.child(self.check_index(index, len, *location))
+            }
+            _ => unreachable!("The checker only assigns to places."),
+        })
+    }
+
+    /// Returns the place of the value `expr` refers to, which is behind it if it's a reference.
+    fn target(&self, expr: &'a Expr, frame: &mut Frame<'a>) -> Result<Place<'a>, Flow<'a>> {
+        if let Type::Reference(_) = expr.ty {
+            return Ok(match self.eval(expr, frame)? {
+                Value::Reference(cell) => Place {
+                    cell,
+                    path: Vec::new(),
+                },
+                Value::Pointer(place) => place,
+                _ => unreachable!("References have reference values."),
+            });
+        }
+
+        self.place(expr, frame)
+    }
+
+    /// Calls the method at `path` on `receiver`, which is passed as a place. Methods of `dyn`
+    /// values are looked up on the type behind them and lists have their methods built in.
+    fn method_call(
+        &self,
+        path: &'a str,
+        receiver: &'a Expr,
+        args: &'a [Expr],
+        frame: &mut Frame<'a>,
+    ) -> Eval<'a> {
+        let mut method = path.to_owned();
+        let place = match (&receiver.ty, receiver.is_place()) {
+            (Type::Dyn(_), _) | (Type::Reference(_), _)
+                if matches!(receiver.ty.deref(), Type::Dyn(_)) =>
+            {
+                let Value::Dyn(cell, ty) = self.eval(receiver, frame)?.deref() else {
+                    unreachable!("Dyn values have dyn types.")
+                };
+
+                let (_, ident) = path.rsplit_once('.').expect("Trait methods have a path.");
+                method = format!("{ty}.{ident}");
+                Place {
+                    cell,
+                    path: Vec::new(),
+                }
+            }
+            (Type::Reference(_), _) | (_, true) => self.target(receiver, frame)?,
+            (_, false) => Place::new(self.eval(receiver, frame)?),
+        };
+
+        let mut args = self.eval_all(args, frame)?;
+        if let Type::List(_) = receiver.ty.deref() {
+            let Value::List(cell) = place.read() else {
+                unreachable!("Lists have list values.")
+            };
+
+            let mut list = cell.borrow_mut();
+            return Ok(match path {
+                "push" => {
+                    list.items_mut().extend(args.pop());
+                    Value::Void
+                }
+                "pop" => Value::Optional(list.items_mut().pop().map(Box::new)),
+                _ => unreachable!("Lists only have the methods push and pop."),
+            });
+        }
+
+        Ok(self.call(self.fns[method.as_str()], Some(place), args))
+    }
+
+    fn builtin(
+        &self,
+        builtin: Builtin,
+        args: &'a [Expr],
+        location: usize,
+        frame: &mut Frame<'a>,
+    ) -> Eval<'a> {
+        match builtin {
+            Builtin::Print | Builtin::Println | Builtin::Eprintln => {
+                let mut bytes = match args.first() {
+                    Some(arg) => self.eval(arg, frame)?.as_bytes().to_vec(),
+                    None => Vec::new(),
+                };
+
+                if builtin != Builtin::Print {
+                    bytes.push(b'\n');
+                }
+
+                // Like `fwrite`, output to a closed pipe is ignored.
+                _ = match builtin {
+                    Builtin::Eprintln => io::stderr().write_all(&bytes),
+                    _ => io::stdout().write_all(&bytes),
+                };
+            }
+            Builtin::Panic => {
+                let message = match args.first() {
+                    Some(message) => self.eval(message, frame)?.as_bytes().to_vec(),
+                    None => b"Panicked.".to_vec(),
+                };
+
+                self.panic(&message, location);
+            }
+            Builtin::Assert => {
+                if !self.eval(&args[0], frame)?.as_bool() {
+                    let message = match args.get(1) {
+                        Some(message) => self.eval(message, frame)?.as_bytes().to_vec(),
+                        None => b"Assertion failed.".to_vec(),
+                    };
+
+                    self.panic(&message, location);
+                }
+            }
+            Builtin::ReadLine => {
+                let mut line = Vec::new();
+                if io::stdin().lock().read_until(b'\n', &mut line).unwrap_or(0) == 0 {
+                    return Ok(Value::Optional(None));
+                }
+
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+
+                return Ok(Value::Optional(Some(Box::new(Value::string(line)))));
+            }
+        }
+
+        Ok(Value::Void)
+    }
+
+    /// Runs the first arm whose pattern matches the value of `expr`.
+    fn eval_match(&self, expr: &'a Expr, arms: &'a [Arm], frame: &mut Frame<'a>) -> Eval<'a> {
+        let value = self.eval(expr, frame)?;
+        for arm in arms.iter() {
+            let mut bindings = Vec::new();
+            if matches(&arm.pattern, &value, &mut bindings) {
+                for (ident, value) in bindings {
+                    frame.declare(ident, value);
+                }
+
+                return self.block(&arm.block, frame);
+            }
+        }
+
+        unreachable!("The checker ensures matches are exhaustive.")
+    }
+
+    /// Returns the index of the field `ident` of the struct `ty` or the struct behind it.
+    fn field(&self, ty: &Type, ident: &str) -> usize {
+        let Type::Struct(name) = ty.deref() else {
+            unreachable!("Only structs have fields.")
+        };
+
+        self.fields[name.as_str()]
+            .iter()
+            .position(|field| *field == ident)
+            .expect("The checker ensures fields exist.")
+    }
+
+    /// Returns `index` as a `usize`, aborting if it's out of bounds for `len`.
+    fn check_index(&self, index: i128, len: usize, location: usize) -> usize {
+        if index < 0 || index >= len as i128 {
+            let message = format!(
+                "{}: Index {index} is out of bounds for length {len}.",
+                self.location(location)
+            );
+            abort(message.as_bytes());
+        }
+
+        index as usize
+    }
+
+    /// Reports `message` along with the source location of `offset` and aborts.
+    fn panic(&self, message: &[u8], offset: usize) -> ! {
+        let location = self.location(offset);
+        abort(&[location.as_bytes(), b": ", message].concat())
+    }
+
+    /// Returns the source location of `offset` like `path:1:5`.
+    fn location(&self, offset: usize) -> String {
+        let (line, column) = self.lines.locate(offset);
+        format!("{}:{line}:{column}", self.path)
+    }
+}
+
+/// Flushes the standard output, reports `message` and aborts like the generated `C` does.
+pub fn abort(message: &[u8]) -> ! {
+    _ = io::stdout().flush();
+    _ = io::stderr().write_all(&[message, b"\n"].concat());
+    process::abort()
+}
+
+/// Returns whether `pattern` matches `value`, collecting the values it binds.
+fn matches<'a>(
+    pattern: &'a Pattern,
+    value: &Value<'a>,
+    bindings: &mut Vec<(&'a str, Value<'a>)>,
+) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Binding(ident), value) => {
+            bindings.push((ident, value.clone()));
+            true
+        }
+        (Pattern::Literal(literal), value) => value.equals(&Value::literal(literal, &Type::Void)),
+        (Pattern::None, Value::Optional(value)) => value.is_none(),
+        (Pattern::Some(inner), Value::Optional(value)) => value
+            .as_ref()
+            .is_some_and(|value| matches(inner, value, bindings)),
+        (Pattern::Deref(inner), Value::Reference(cell)) => {
+            let value = cell.borrow().clone();
+            matches(inner, &value, bindings)
+        }
+        (
+            Pattern::Variant {
+                variant, fields, ..
+            },
+            Value::Variant(actual, values),
+        ) => {
+            variant == actual
+                && fields
+                    .iter()
+                    .zip(values.iter())
+                    .all(|(field, value)| matches(field, value, bindings))
+        }
+        (Pattern::Struct { fields, .. }, Value::Struct(values)) => fields
+            .iter()
+            .zip(values.iter())
+            .all(|(field, value)| matches(field, value, bindings)),
+        _ => unreachable!("The checker ensures patterns match their types."),
+    }
+}
@@ -0,0 +1,390 @@
+//! Values of the interpreter and the operations on them, which follow the generated `C`.
+//!
+//! Structs, enums and arrays are copied by value. References, lists and the environments of
+//! closures are shared, so they live in reference counted cells. Integers are kept in an `i128`
+//! wrapped to the width of their type after every operation.
+
+use super::abort;
+use crate::{
+    checker::typed::{Closure, FloatTy, IntTy, Literal, Type},
+    lexer::token::Operator,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Void,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    /// The bytes of a string, which aren't necessarily valid UTF-8 after slicing.
+    String(Rc<[u8]>),
+    Optional(Option<Box<Value<'a>>>),
+    /// A struct with its fields in declaration order.
+    Struct(Vec<Value<'a>>),
+    Variant(&'a str, Vec<Value<'a>>),
+    Reference(Rc<RefCell<Value<'a>>>),
+    /// A reference to the receiver of a method, which is a place rather than a cell of its own.
+    Pointer(Place<'a>),
+    /// A reference along with the name of its type, whose methods implement the trait.
+    Dyn(Rc<RefCell<Value<'a>>>, Rc<str>),
+    Array(Vec<Value<'a>>),
+    /// A list, which holds an array of its elements.
+    List(Rc<RefCell<Value<'a>>>),
+    Fn(Function<'a>),
+}
+
+/// A function value.
+#[derive(Debug, Clone)]
+pub enum Function<'a> {
+    /// A top-level function.
+    Item(&'a str),
+    /// A closure with the values of its captured locals.
+    Closure(&'a Closure, Rc<[Value<'a>]>),
+}
+
+/// A location holding a value, a path of field and element indices into a cell.
+#[derive(Debug, Clone)]
+pub struct Place<'a> {
+    pub cell: Rc<RefCell<Value<'a>>>,
+    pub path: Vec<usize>,
+}
+
+impl<'a> Place<'a> {
+    /// Returns a place holding `value` on its own.
+    pub fn new(value: Value<'a>) -> Place<'a> {
+        Place {
+            cell: Rc::new(RefCell::new(value)),
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns the place of the field or element `index` of the value at this place.
+    pub fn child(mut self, index: usize) -> Place<'a> {
+        self.path.push(index);
+        self
+    }
+
+    pub fn read(&self) -> Value<'a> {
+        let value = self.cell.borrow();
+        let mut value = &*value;
+        for index in self.path.iter() {
+            value = &value.items()[*index];
+        }
+
+        value.clone()
+    }
+
+    pub fn write(&self, new: Value<'a>) {
+        let mut value = self.cell.borrow_mut();
+        let mut value = &mut *value;
+        for index in self.path.iter() {
+            value = &mut value.items_mut()[*index];
+        }
+
+        *value = new;
+    }
+
+    /// Returns the length of the array or list at this place.
+    pub fn read_len(&self) -> usize {
+        self.read().len()
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Returns the value of `literal` whose type is `ty`, integer literals may be floats.
+    pub fn literal(literal: &Literal, ty: &Type) -> Value<'a> {
+        match (literal, ty) {
+            (Literal::Int(value), Type::Float(ty)) => Value::Float(round(*value as f64, *ty)),
+            (Literal::Int(value), _) => Value::Int(*value),
+            (Literal::Float(value), Type::Float(ty)) => Value::Float(round(*value, *ty)),
+            (Literal::Float(value), _) => Value::Float(*value),
+            (Literal::Bool(value), _) => Value::Bool(*value),
+            (Literal::String(value), _) => Value::String(value.as_bytes().into()),
+        }
+    }
+
+    pub fn string(bytes: impl Into<Rc<[u8]>>) -> Value<'a> {
+        Value::String(bytes.into())
+    }
+
+    /// Returns a new list holding `items`.
+    pub fn list(items: Vec<Value<'a>>) -> Value<'a> {
+        Value::List(Rc::new(RefCell::new(Value::Array(items))))
+    }
+
+    /// Returns the length of an array, list or string.
+    pub fn len(&self) -> usize {
+        match self {
+            Value::Array(items) => items.len(),
+            Value::List(cell) => cell.borrow().len(),
+            Value::String(bytes) => bytes.len(),
+            _ => unreachable!("Only arrays, lists and strings have a length."),
+        }
+    }
+
+    /// Returns the fields of a struct or the elements of an array.
+    pub fn items(&self) -> &[Value<'a>] {
+        match self {
+            Value::Struct(items) | Value::Array(items) => items,
+            _ => unreachable!("Only structs and arrays have places inside of them."),
+        }
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<Value<'a>> {
+        match self {
+            Value::Struct(items) | Value::Array(items) => items,
+            _ => unreachable!("Only structs and arrays have places inside of them."),
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            _ => unreachable!("The checker ensures conditions are booleans."),
+        }
+    }
+
+    pub fn as_int(&self) -> i128 {
+        match self {
+            Value::Int(value) => *value,
+            _ => unreachable!("The checker ensures indices and bounds are integers."),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &Rc<[u8]> {
+        match self {
+            Value::String(bytes) => bytes,
+            _ => unreachable!("The checker ensures printed values are strings."),
+        }
+    }
+
+    /// Returns the value behind a reference, other values are returned as they are.
+    pub fn deref(self) -> Value<'a> {
+        match self {
+            Value::Reference(cell) => cell.borrow().clone(),
+            Value::Pointer(place) => place.read(),
+            value => value,
+        }
+    }
+
+    /// Converts the value of type `from` into `to` like a cast in the generated `C`.
+    pub fn cast(self, from: &Type, to: &Type) -> Value<'a> {
+        if from == to {
+            return self;
+        }
+
+        match (self, from, to) {
+            (Value::Optional(value), Type::Optional(from), Type::Optional(to)) => {
+                Value::Optional(value.map(|value| Box::new(value.cast(from, to))))
+            }
+            (Value::Optional(value), Type::Optional(from), to) => match value {
+                Some(value) => value.cast(from, to),
+                None => abort(b"Unwrapped an empty optional."),
+            },
+            (value, from, Type::Optional(to)) => {
+                Value::Optional(Some(Box::new(value.cast(from, to))))
+            }
+            (Value::Array(items), _, Type::List(_)) => Value::list(items),
+            (value, from, Type::String) => Value::string(value.to_string(from)),
+            (Value::Int(value), _, Type::Int(ty)) => Value::Int(wrap(value, *ty)),
+            (Value::Int(value), Type::Int(_), Type::Float(FloatTy::F32)) => {
+                Value::Float(value as f32 as f64)
+            }
+            (Value::Int(value), _, Type::Float(_)) => Value::Float(value as f64),
+            (Value::Float(value), _, Type::Int(ty)) => Value::Int(saturate(value, *ty)),
+            (Value::Float(value), _, Type::Float(ty)) => Value::Float(round(value, *ty)),
+            (Value::Bool(value), _, Type::Int(_)) => Value::Int(value as i128),
+            (Value::Reference(cell), _, Type::Int(_)) => {
+                Value::Int(Rc::as_ptr(&cell) as usize as i128)
+            }
+            (value, _, _) => value,
+        }
+    }
+
+    /// Converts a number, `bool` or optional of them of type `ty` into the bytes of a string.
+    pub fn to_string(&self, ty: &Type) -> Vec<u8> {
+        match (self, ty) {
+            (Value::String(bytes), _) => bytes.to_vec(),
+            (Value::Optional(Some(value)), Type::Optional(ty)) => value.to_string(ty),
+            (Value::Optional(None), _) => b"?".to_vec(),
+            (Value::Int(value), _) => value.to_string().into_bytes(),
+            (Value::Float(value), Type::Float(ty)) => format_float(*value, *ty).into_bytes(),
+            (Value::Bool(value), _) => value.to_string().into_bytes(),
+            _ => unreachable!("Only printable values convert into strings."),
+        }
+    }
+
+    /// Returns the result of `self operator right`, both of type `ty`. `&&` and `||` are
+    /// evaluated lazily by the caller.
+    pub fn binary(self, operator: Operator, right: Value<'a>, ty: &Type) -> Value<'a> {
+        match (self, right) {
+            (Value::Int(left), Value::Int(right)) => {
+                let Type::Int(ty) = ty else {
+                    unreachable!("Integers have integer types.")
+                };
+
+                let value = match operator {
+                    Operator::Plus => left + right,
+                    Operator::Minus => left - right,
+                    Operator::Star => left.wrapping_mul(right),
+                    Operator::Slash | Operator::Percent if right == 0 => {
+                        abort(b"Division by zero.")
+                    }
+                    Operator::Slash => left / right,
+                    Operator::Percent => left % right,
+                    operator => return Value::Bool(compare(operator, left, right)),
+                };
+
+                Value::Int(wrap(value, *ty))
+            }
+            (Value::Float(left), Value::Float(right)) => {
+                let Type::Float(ty) = ty else {
+                    unreachable!("Floats have float types.")
+                };
+
+                let value = match operator {
+                    Operator::Plus => left + right,
+                    Operator::Minus => left - right,
+                    Operator::Star => left * right,
+                    Operator::Slash => left / right,
+                    operator => return Value::Bool(compare(operator, left, right)),
+                };
+
+                Value::Float(round(value, *ty))
+            }
+            (Value::String(left), Value::String(right)) => match operator {
+                Operator::Plus => Value::string([&*left, &*right].concat()),
+                operator => Value::Bool(compare(operator, left, right)),
+            },
+            (Value::Bool(left), Value::Bool(right)) => Value::Bool(compare(operator, left, right)),
+            _ => unreachable!("The checker ensures operands have the same type."),
+        }
+    }
+
+    /// Returns whether the value equals the literal pattern `other`.
+    pub fn equals(&self, other: &Value<'a>) -> bool {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+fn compare<T: PartialOrd>(operator: Operator, left: T, right: T) -> bool {
+    match operator {
+        Operator::Equal => left == right,
+        Operator::NotEqual => left != right,
+        Operator::Less => left < right,
+        Operator::LessEqual => left <= right,
+        Operator::Greater => left > right,
+        Operator::GreaterEqual => left >= right,
+        _ => unreachable!("Only comparisons remain."),
+    }
+}
+
+/// Wraps `value` into the range of `ty`, like the conversions of `C` on two's complement.
+pub fn wrap(value: i128, ty: IntTy) -> i128 {
+    let unused = 128 - ty.bits();
+    match ty.signed() {
+        true => (value << unused) >> unused,
+        false => value & ty.max(),
+    }
+}
+
+/// Converts `value` into `ty` like `razor_f2i`, saturating at the limits and turning `NaN`
+/// into zero.
+pub fn saturate(value: f64, ty: IntTy) -> i128 {
+    match ty {
+        IntTy::Int => value as i64 as i128,
+        IntTy::I8 => value as i8 as i128,
+        IntTy::I16 => value as i16 as i128,
+        IntTy::I32 => value as i32 as i128,
+        IntTy::Uint => value as u64 as i128,
+        IntTy::U8 => value as u8 as i128,
+        IntTy::U16 => value as u16 as i128,
+        IntTy::U32 => value as u32 as i128,
+    }
+}
+
+/// Rounds `value` to the precision of `ty`.
+pub fn round(value: f64, ty: FloatTy) -> f64 {
+    match ty {
+        FloatTy::Float => value,
+        FloatTy::F32 => value as f32 as f64,
+    }
+}
+
+/// Formats `value` of type `ty` like `razor_string_from_float`, with the fewest significant
+/// digits which read back as `value` in `ty` and a `.0` for integral values.
+pub fn format_float(value: f64, ty: FloatTy) -> String {
+    if value.is_nan() {
+        return match value.is_sign_negative() {
+            true => "-nan".to_owned(),
+            false => "nan".to_owned(),
+        };
+    }
+
+    if value.is_infinite() {
+        return match value < 0.0 {
+            true => "-inf".to_owned(),
+            false => "inf".to_owned(),
+        };
+    }
+
+    let (digits, reads_back): (usize, fn(&str, f64) -> bool) = match ty {
+        FloatTy::Float => (17, |formatted, value| formatted.parse() == Ok(value)),
+        FloatTy::F32 => (9, |formatted, value| formatted.parse() == Ok(value as f32)),
+    };
+
+    let mut formatted = String::new();
+    for precision in 1..=digits {
+        formatted = format_digits(value, precision);
+        if reads_back(&formatted, value) {
+            break;
+        }
+    }
+
+    if !formatted.contains(['.', 'e']) {
+        formatted += ".0";
+    }
+
+    formatted
+}
+
+/// Formats the finite `value` with `precision` significant digits, in scientific notation like
+/// `%.{precision}g` only if it's smaller than `1e-4` or at least `1e16`.
+fn format_digits(value: f64, precision: usize) -> String {
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent.");
+    let exponent = exponent.parse::<i32>().expect("The exponent is a number.");
+
+    if !(-4..16).contains(&exponent) {
+        let sign = match exponent < 0 {
+            true => '-',
+            false => '+',
+        };
+
+        return format!(
+            "{}e{sign}{:02}",
+            trim_zeros(mantissa),
+            exponent.unsigned_abs()
+        );
+    }
+
+    let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+    trim_zeros(&format!("{value:.decimals$}")).to_owned()
+}
+
+/// Removes the trailing zeros of a fraction, along with the point if nothing is left of it.
+fn trim_zeros(number: &str) -> &str {
+    match number.contains('.') {
+        true => number.trim_end_matches('0').trim_end_matches('.'),
+        false => number,
+    }
+}
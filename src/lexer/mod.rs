@@ -1,8 +1,8 @@
 pub mod token;
 
 use crate::util::{Span, Spanned};
-use std::iter::Peekable;
-use token::{Assignment, BraceKind, LiteralKind, Operator, Token};
+use std::{fmt, iter::Peekable, mem};
+use token::{Assignment, BraceKind, FormatPart, LiteralKind, Operator, Token};
 
 pub fn tokenize<I, B>(bytes: B) -> TokenIter<I>
 where
@@ -12,6 +12,7 @@ where
     TokenIter {
         bytes: bytes.into_iter().peekable(),
         index: 0,
+        range_dot: false,
     }
 }
 
@@ -19,16 +20,47 @@ where
 pub enum LexerError {
     UnexpectedCharacter(u8),
     NonUtf8Bytes,
+    UnterminatedString,
+    UnknownEscape(u8),
+    /// A `{` in a string literal without the `}` ending the interpolation.
+    UnclosedInterpolation,
+    /// A `}` in a string literal which doesn't end an interpolation.
+    UnopenedInterpolation,
+    EmptyInterpolation,
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexerError::UnexpectedCharacter(byte) => {
+                write!(f, "Unexpected character {:?}.", *byte as char)
+            }
+            LexerError::NonUtf8Bytes => write!(f, "String literal is not valid UTF-8."),
+            LexerError::UnterminatedString => write!(f, "String literal is missing its \"."),
+            LexerError::UnknownEscape(byte) => {
+                write!(f, "Unknown escape sequence \"\\{}\".", *byte as char)
+            }
+            LexerError::UnclosedInterpolation => {
+                write!(f, "Expected \"}}\" to end the interpolation.")
+            }
+            LexerError::UnopenedInterpolation => {
+                write!(f, "Unmatched \"}}\", write \"\\}}\" for a literal one.")
+            }
+            LexerError::EmptyInterpolation => write!(f, "Expected an expression to interpolate."),
+        }
+    }
 }
 
 pub struct TokenIter<I: Iterator<Item = u8>> {
     bytes: Peekable<I>,
     index: usize,
+    /// Set when a number was followed by `..`, the first `.` of which is already consumed.
+    range_dot: bool,
 }
 
 impl<I: Iterator<Item = u8>> TokenIter<I> {
     fn peek_byte(&mut self) -> Option<u8> {
-        self.bytes.peek().map(|byte| byte).copied()
+        self.bytes.peek().copied()
     }
 
     /// Returns the next byte in the byte iterator and adds to the index.
@@ -53,10 +85,150 @@ impl<I: Iterator<Item = u8>> TokenIter<I> {
         vec
     }
 
+    /// Returns `operator` if the next byte is `=` and consumes it, otherwise returns `token`.
+    fn next_if_eq(&mut self, token: Token, operator: Operator) -> Token {
+        if let Some(b'=') = self.peek_byte() {
+            _ = self.next_byte();
+            Token::Operator(operator)
+        } else {
+            token
+        }
+    }
+
+    /// Returns `operator` or its compound assignment if followed by `=`.
+    fn next_operator(&mut self, operator: Operator) -> Token {
+        if let Some(b'=') = self.peek_byte() {
+            _ = self.next_byte();
+            Token::CompoundAssignment(operator)
+        } else {
+            Token::Operator(operator)
+        }
+    }
+
+    /// Parses `..` or `..=` after the first `.`.
+    fn next_range(&mut self) -> Token {
+        _ = self.next_byte();
+        if let Some(b'=') = self.peek_byte() {
+            _ = self.next_byte();
+            return Token::Range { inclusive: true };
+        }
+
+        Token::Range { inclusive: false }
+    }
+
+    /// Parses a string literal after its opening `"`. Expressions between `{` and `}` are
+    /// interpolated, which turns the literal into a [`Token::Format`].
+    fn next_string(&mut self, start: usize) -> Result<Token, Spanned<LexerError>> {
+        // Lexing continues after an error so the rest of the literal isn't lexed as code.
+        let (mut parts, mut bytes, mut error) = (Vec::new(), Vec::new(), None);
+        loop {
+            let offset = self.index;
+            match self.next_byte() {
+                None => return Err(LexerError::UnterminatedString.span(start..self.index)),
+                Some(b'"') => break,
+                Some(b'\\') => match self.next_byte() {
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b'0') => bytes.push(b'\0'),
+                    Some(byte @ (b'\\' | b'"' | b'{' | b'}')) => bytes.push(byte),
+                    Some(byte) => {
+                        let unknown = LexerError::UnknownEscape(byte).span(offset..self.index);
+                        error.get_or_insert(unknown);
+                    }
+                    None => return Err(LexerError::UnterminatedString.span(start..self.index)),
+                },
+                Some(b'{') => {
+                    if !bytes.is_empty() {
+                        match into_string(mem::take(&mut bytes), start..self.index) {
+                            Ok(literal) => parts.push(FormatPart::Literal(literal)),
+                            Err(utf8) => _ = error.get_or_insert(utf8),
+                        }
+                    }
+
+                    match self.next_interpolation(offset) {
+                        Ok(tokens) => parts.push(FormatPart::Expr(tokens)),
+                        Err(interpolation) => _ = error.get_or_insert(interpolation),
+                    }
+                }
+                Some(b'}') => {
+                    let unopened = LexerError::UnopenedInterpolation.span(offset..self.index);
+                    error.get_or_insert(unopened);
+                }
+                Some(byte) => bytes.push(byte),
+            }
+        }
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        if parts.is_empty() {
+            return Ok(Token::Literal {
+                value: into_string(bytes, start..self.index)?,
+                kind: LiteralKind::String,
+            });
+        }
+
+        if !bytes.is_empty() {
+            parts.push(FormatPart::Literal(into_string(bytes, start..self.index)?));
+        }
+
+        Ok(Token::Format(parts))
+    }
+
+    /// Lexes the expression of an interpolation up to its `}`, `open` is the offset of the `{`.
+    /// Braces inside of it have to be balanced and it can't contain string literals.
+    fn next_interpolation(
+        &mut self,
+        open: usize,
+    ) -> Result<Vec<Spanned<Token>>, Spanned<LexerError>> {
+        let (start, mut bytes, mut depth) = (self.index, Vec::new(), 0);
+        loop {
+            match self.peek_byte() {
+                None | Some(b'"') => {
+                    return Err(LexerError::UnclosedInterpolation.span(open..self.index))
+                }
+                Some(b'}') if depth == 0 => break,
+                Some(b'{') => depth += 1,
+                Some(b'}') => depth -= 1,
+                Some(_) => {}
+            }
+
+            bytes.extend(self.next_byte());
+        }
+
+        // Skip the '}'
+        _ = self.next_byte();
+
+        let tokens = TokenIter {
+            bytes: bytes.into_iter().peekable(),
+            index: start,
+            range_dot: false,
+        }
+        .collect::<Result<Vec<_>, _>>()?;
+
+        if tokens.is_empty() {
+            return Err(LexerError::EmptyInterpolation.span(open..self.index));
+        }
+
+        Ok(tokens)
+    }
+
     /// Parses the next [`Token`].
-    fn next_token(&mut self, byte: u8) -> Result<Token, LexerError> {
+    fn next_token(&mut self, byte: u8, start: usize) -> Result<Token, Spanned<LexerError>> {
+        if byte == b'"' {
+            return self.next_string(start);
+        }
+
+        self.next_plain_token(byte)
+            .map_err(|error| error.span(start..self.index))
+    }
+
+    /// Parses the next [`Token`] which isn't a string literal.
+    fn next_plain_token(&mut self, byte: u8) -> Result<Token, LexerError> {
         match byte {
-            b'a'..=b'z' | b'A'..=b'Z' => {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
                 let bytes = self.collect_bytes(
                     vec![byte],
                     |byte| matches!(byte, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_'),
@@ -65,32 +237,29 @@ impl<I: Iterator<Item = u8>> TokenIter<I> {
                 // The bytes here can never be non UTF8 because of the checks above.
                 let string = unsafe { String::from_utf8_unchecked(bytes) };
 
+                if string == "_" {
+                    return Ok(Token::Underscore);
+                }
+
                 if let Ok(keyword) = string.parse() {
                     return Ok(Token::Keyword(keyword));
                 }
 
-                Ok(Token::Ident(string))
-            }
-            // TODO: Add support for escape characters.
-            b'"' => {
-                let bytes = self.collect_bytes(vec![], |byte| byte != b'"');
-                // Skip another '"'
-                _ = self.next_byte();
-
-                let Ok(value) = String::from_utf8(bytes) else {
-                    return Err(LexerError::NonUtf8Bytes);
-                };
+                if string == "true" || string == "false" {
+                    return Ok(Token::Literal {
+                        value: string,
+                        kind: LiteralKind::Bool,
+                    });
+                }
 
-                Ok(Token::Literal {
-                    value,
-                    kind: LiteralKind::String,
-                })
+                Ok(Token::Ident(string))
             }
             b'0'..=b'9' | b'.' | b'-' => {
                 // This checks if `byte` or the next byte is numeric.
                 // If so it proceeds to parse a number otherwise parses the other possible tokens.
                 match (byte, self.peek_byte()) {
                     (_, Some(b'0'..=b'9')) => {}
+                    (b'.', Some(b'.')) => return Ok(self.next_range()),
                     (b'.', _) => {
                         return Ok(Token::Dot);
                     }
@@ -99,25 +268,26 @@ impl<I: Iterator<Item = u8>> TokenIter<I> {
                             _ = self.next_byte();
                             Ok(Token::Arrow)
                         } else {
-                            Ok(Token::Operator(Operator::Minus))
+                            Ok(self.next_operator(Operator::Minus))
                         };
                     }
                     _ => {}
                 }
 
                 let mut dot = byte == b'.';
-                let bytes = self.collect_bytes(vec![byte], |byte| match byte {
-                    b'0'..=b'9' => true,
-                    b'.' => {
-                        if dot {
-                            return false;
-                        }
+                let mut bytes = self.collect_bytes(vec![byte], |byte| byte.is_ascii_digit());
+                if !dot && self.peek_byte() == Some(b'.') {
+                    _ = self.next_byte();
 
+                    // A number followed by `..` is the start of a range.
+                    if self.peek_byte() == Some(b'.') {
+                        self.range_dot = true;
+                    } else {
                         dot = true;
-                        true
+                        bytes.push(b'.');
+                        bytes = self.collect_bytes(bytes, |byte| byte.is_ascii_digit());
                     }
-                    _ => false,
-                });
+                }
 
                 // The bytes here can never be non UTF8 because of the checks above.
                 let value = unsafe { String::from_utf8_unchecked(bytes) };
@@ -178,12 +348,53 @@ impl<I: Iterator<Item = u8>> TokenIter<I> {
                 }
             }
             b';' => Ok(Token::SemiColon),
-            b'&' => Ok(Token::Ampersand),
+            b'&' => {
+                if let Some(b'&') = self.peek_byte() {
+                    _ = self.next_byte();
+                    Ok(Token::Operator(Operator::And))
+                } else {
+                    Ok(Token::Ampersand)
+                }
+            }
+            b'|' => {
+                if let Some(b'|') = self.peek_byte() {
+                    _ = self.next_byte();
+                    Ok(Token::Operator(Operator::Or))
+                } else {
+                    Err(LexerError::UnexpectedCharacter(byte))
+                }
+            }
             b',' => Ok(Token::Comma),
-            b'=' => Ok(Token::Eq),
-            b'+' => Ok(Token::Operator(Operator::Plus)),
-            b'*' => Ok(Token::Operator(Operator::Star)),
-            b'/' => Ok(Token::Operator(Operator::Slash)),
+            b'@' => {
+                let bytes = self.collect_bytes(
+                    Vec::new(),
+                    |byte| matches!(byte, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_'),
+                );
+
+                if bytes.is_empty() {
+                    return Err(LexerError::UnexpectedCharacter(byte));
+                }
+
+                // The bytes here can never be non UTF8 because of the checks above.
+                Ok(Token::Attribute(unsafe {
+                    String::from_utf8_unchecked(bytes)
+                }))
+            }
+            b'=' => {
+                if let Some(b'>') = self.peek_byte() {
+                    _ = self.next_byte();
+                    Ok(Token::FatArrow)
+                } else {
+                    Ok(self.next_if_eq(Token::Eq, Operator::Equal))
+                }
+            }
+            b'!' => Ok(self.next_if_eq(Token::Bang, Operator::NotEqual)),
+            b'<' => Ok(self.next_if_eq(Token::Operator(Operator::Less), Operator::LessEqual)),
+            b'>' => Ok(self.next_if_eq(Token::Operator(Operator::Greater), Operator::GreaterEqual)),
+            b'+' => Ok(self.next_operator(Operator::Plus)),
+            b'*' => Ok(self.next_operator(Operator::Star)),
+            b'/' => Ok(self.next_operator(Operator::Slash)),
+            b'%' => Ok(self.next_operator(Operator::Percent)),
             _ => Err(LexerError::UnexpectedCharacter(byte)),
         }
     }
@@ -193,6 +404,12 @@ impl<I: Iterator<Item = u8>> Iterator for TokenIter<I> {
     type Item = Result<Spanned<Token>, Spanned<LexerError>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.range_dot {
+            self.range_dot = false;
+            let start = self.index - 1;
+            return Some(Ok(self.next_range().span(start..self.index)));
+        }
+
         // Skip any whitespace.
         while self.peek_byte()?.is_ascii_whitespace() {
             _ = self.next_byte();
@@ -227,9 +444,104 @@ impl<I: Iterator<Item = u8>> Iterator for TokenIter<I> {
 
         let start = self.index;
         let byte = self.next_byte()?;
-        Some(match self.next_token(byte) {
-            Ok(token) => Ok(token.span(start..self.index)),
-            Err(error) => Err(error.span(start..self.index)),
-        })
+        Some(
+            self.next_token(byte, start)
+                .map(|token| token.span(start..self.index)),
+        )
+    }
+}
+
+/// Converts the bytes of a string literal spanning `range` into a [`String`].
+fn into_string(
+    bytes: Vec<u8>,
+    range: std::ops::Range<usize>,
+) -> Result<String, Spanned<LexerError>> {
+    String::from_utf8(bytes).map_err(|_| LexerError::NonUtf8Bytes.span(range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the errors lexing `source` with the part of it they span.
+    fn errors(source: &str) -> Vec<(String, &str)> {
+        tokenize(source.bytes())
+            .filter_map(Result::err)
+            .map(|error| (error.to_string(), &source[error.range()]))
+            .collect()
+    }
+
+    /// Lexes `source` into a single token.
+    fn token(source: &str) -> Token {
+        let tokens = tokenize(source.bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .expect("The source lexes.");
+
+        match <[_; 1]>::try_from(tokens) {
+            Ok([token]) => token.value,
+            Err(tokens) => panic!("Expected a single token, found {tokens:?}."),
+        }
+    }
+
+    #[test]
+    fn escapes_are_replaced() {
+        assert_eq!(
+            token(r#""\{\}\n\t\"\\""#),
+            Token::Literal {
+                value: "{}\n\t\"\\".to_owned(),
+                kind: LiteralKind::String
+            }
+        );
+    }
+
+    #[test]
+    fn interpolations_are_lexed() {
+        let Token::Format(parts) = token(r#""a {b.c} d""#) else {
+            panic!("Expected an interpolated string.");
+        };
+
+        let [FormatPart::Literal(start), FormatPart::Expr(tokens), FormatPart::Literal(end)] =
+            &parts[..]
+        else {
+            panic!("Expected a literal, an expression and a literal, found {parts:?}.");
+        };
+
+        let tokens = tokens.iter().map(|token| &token.value).collect::<Vec<_>>();
+        assert_eq!((start.as_str(), end.as_str()), ("a ", " d"));
+        assert_eq!(
+            tokens,
+            [
+                &Token::Ident("b".to_owned()),
+                &Token::Dot,
+                &Token::Ident("c".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn string_errors_are_reported() {
+        let cases = [
+            (r#""a\q""#, "Unknown escape sequence \"\\q\".", r"\q"),
+            (r#""abc"#, "String literal is missing its \".", r#""abc"#),
+            (
+                r#""a {b""#,
+                "Expected \"}\" to end the interpolation.",
+                "{b",
+            ),
+            (
+                r#""a } b""#,
+                "Unmatched \"}\", write \"\\}\" for a literal one.",
+                "}",
+            ),
+            (
+                r#""a {} b""#,
+                "Expected an expression to interpolate.",
+                "{}",
+            ),
+        ];
+
+        for (source, message, span) in cases {
+            assert_eq!(errors(source), [(message.to_owned(), span)], "{source}");
+        }
     }
 }
@@ -1,34 +1,132 @@
-use std::str::FromStr;
+use crate::{expr_tree, util::Spanned};
+use std::{fmt, str::FromStr};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Ident(String),
     Assignment(Assignment),
     Eq,
-    Brace { open: bool, kind: BraceKind },
+    /// `+=`, `-=`, `*=`, `/=` or `%=`
+    CompoundAssignment(Operator),
+    Brace {
+        open: bool,
+        kind: BraceKind,
+    },
     QuestionMark,
     SemiColon,
     Colon,
     Dot,
+    /// `..` or `..=`
+    Range {
+        inclusive: bool,
+    },
     Ampersand,
     Comma,
-    Literal { value: String, kind: LiteralKind },
+    Literal {
+        value: String,
+        kind: LiteralKind,
+    },
+    /// A string literal interpolating expressions like `"value = {node.value}"`.
+    Format(Vec<FormatPart>),
     Keyword(Keyword),
     Arrow,
+    /// `=>`
+    FatArrow,
+    /// `_`
+    Underscore,
+    Bang,
     Operator(Operator),
+    /// An attribute of the following item like `@no_mangle`.
+    Attribute(String),
 }
 
-#[derive(Debug, Clone)]
+/// A part of an interpolated string literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPart {
+    Literal(String),
+    /// The tokens between `{` and `}`, spanned in the source like any other token.
+    Expr(Vec<Spanned<Token>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Plus,
     Minus,
     Star,
     Slash,
+    Percent,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl Operator {
+    /// Returns whether the operator produces a `bool` out of two comparable values.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operator::Equal
+                | Operator::NotEqual
+                | Operator::Less
+                | Operator::LessEqual
+                | Operator::Greater
+                | Operator::GreaterEqual
+        )
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Operator::And | Operator::Or)
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Star => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+        };
+
+        write!(f, "{operator}")
+    }
+}
+
+impl expr_tree::Operator for Operator {
+    fn precedence(&self) -> u32 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => 3,
+            Operator::Plus | Operator::Minus => 4,
+            Operator::Star | Operator::Slash | Operator::Percent => 5,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Keyword {
     Struct,
+    Enum,
     Fn,
     For,
     Pub,
@@ -36,6 +134,14 @@ pub enum Keyword {
     Else,
     Get,
     As,
+    In,
+    Break,
+    Continue,
+    Return,
+    Match,
+    Trait,
+    Dyn,
+    Extern,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -47,6 +153,7 @@ impl FromStr for Keyword {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "struct" => Ok(Keyword::Struct),
+            "enum" => Ok(Keyword::Enum),
             "fn" => Ok(Keyword::Fn),
             "for" => Ok(Keyword::For),
             "pub" => Ok(Keyword::Pub),
@@ -54,25 +161,34 @@ impl FromStr for Keyword {
             "else" => Ok(Keyword::Else),
             "get" => Ok(Keyword::Get),
             "as" => Ok(Keyword::As),
+            "in" => Ok(Keyword::In),
+            "break" => Ok(Keyword::Break),
+            "continue" => Ok(Keyword::Continue),
+            "return" => Ok(Keyword::Return),
+            "match" => Ok(Keyword::Match),
+            "trait" => Ok(Keyword::Trait),
+            "dyn" => Ok(Keyword::Dyn),
+            "extern" => Ok(Keyword::Extern),
             _ => Err(UnknownKeywordError),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Assignment {
     Normal,
     Optional,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LiteralKind {
     String,
     Int,
     Float,
+    Bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BraceKind {
     Curly,
     Square,
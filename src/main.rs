@@ -1,13 +1,245 @@
+mod bytecode;
+mod checker;
 mod codegen;
 mod expr_tree;
+mod interp;
 mod lexer;
 mod parser;
 mod util;
 
-use std::{env, fs};
+use parser::ParseError;
+use std::{
+    env, fs,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::Path,
+    process::{self, Command, ExitStatus},
+};
 
 fn main() {
-    let path = env::args().nth(1).expect("Expected a path argument.");
+    let mut args = env::args().skip(1).peekable();
+    let command = args.next_if(|arg| arg == "build" || arg == "run" || arg == "emit");
+    let emit = command.as_deref() == Some("emit");
+    let (build, run) = (
+        command.is_some() && !emit,
+        command.as_deref() == Some("run"),
+    );
+    let (mut path, mut options, mut interpret) = (None, codegen::Options::default(), false);
+    let mut bytecode = false;
+    let (mut output, mut links) = (None, Vec::new());
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--leak-check" => options.leak_check = true,
+            "--release" => options.release = true,
+            "-g" => options.debug = true,
+            "--interp" if run => interpret = true,
+            "--bytecode" if command.is_some() => bytecode = true,
+            "--lib" if build && !run => options.library = true,
+            "-o" if build && !run => {
+                output = Some(args.next().expect("Expected a path after \"-o\"."))
+            }
+            "-l" | "-L" if build => {
+                let value = args.next().expect("Expected a library or directory.");
+                links.push(format!("{arg}{value}"));
+            }
+            _ if build && (arg.starts_with("-l") || arg.starts_with("-L")) => links.push(arg),
+            _ => {
+                path = Some(arg);
+                // The arguments after the path are passed on to the program.
+                if run {
+                    break;
+                }
+            }
+        }
+    }
+
+    let path = path.expect("Expected a path argument.");
+    // Bytecode files are recognized by their header, so they run like sources.
+    if bytecode || run && fs::read(&path).is_ok_and(|bytes| bytecode::format::is_bytecode(&bytes)) {
+        let Some(module) = compile_bytecode(&path) else {
+            process::exit(1);
+        };
+
+        if run {
+            process::exit(bytecode::vm::run(&module, args.collect()));
+        } else if build {
+            let output = output.unwrap_or_else(|| {
+                Path::new(&path)
+                    .with_extension("rzb")
+                    .to_string_lossy()
+                    .into_owned()
+            });
+
+            let bytes = bytecode::format::write(&module);
+            fs::write(output, bytes).expect("Couldn't write the bytecode file.");
+        } else {
+            print!("{module}");
+        }
+
+        return;
+    }
+
+    if interpret {
+        let Some((program, code)) = check(&path, false, false) else {
+            process::exit(1);
+        };
+
+        match interp::run(&program, &code, &path, args.collect()) {
+            Ok(status) => process::exit(status),
+            Err(error) => {
+                eprintln!("\nInterpreter Errors:\n");
+                eprintln!(
+                    "{}",
+                    util::map_spans([&error], &code, |str| format!("\x1b[41m{str}\x1b[0m"))
+                );
+                eprintln!("{}", error.value);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut flags = Vec::new();
+    if options.debug {
+        flags.push("-g".to_owned());
+    }
+
+    if options.release {
+        flags.push("-O2".to_owned());
+    }
+
+    flags.extend(links);
+
+    let Some(generated) = compile(&path, options, command.is_none()) else {
+        if build {
+            process::exit(1);
+        }
+
+        return;
+    };
+
+    if run {
+        // The executable is built into a directory of its own which is removed after it exits.
+        let dir = env::temp_dir().join(format!("razor-{}", process::id()));
+        fs::create_dir_all(&dir).expect("Couldn't create the build directory.");
+        let executable = dir.join("program").to_string_lossy().into_owned();
+        let status = build_output(&path, generated, Some(executable.clone()), &flags);
+        if !status.success() {
+            _ = fs::remove_dir_all(&dir);
+            process::exit(status.code().unwrap_or(1));
+        }
+
+        // Like the interpreter and the virtual machine, the program gets the source as its path.
+        let status = Command::new(&executable)
+            .arg0(&path)
+            .args(args)
+            .status()
+            .expect("Couldn't run the program.");
+
+        _ = fs::remove_dir_all(&dir);
+        // Like shells, a program killed by a signal exits with 128 plus the signal.
+        let signal = status.signal().map(|signal| 128 + signal);
+        process::exit(status.code().or(signal).unwrap_or(1));
+    }
+
+    if !build {
+        println!("{}", generated.code);
+        return;
+    }
+
+    let status = build_output(&path, generated, output, &flags);
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Writes the `C` code compiled from `path` next to `output` and builds it with `$CC`, passing
+/// `flags` on. Programs are linked into the executable `output`, libraries are compiled into
+/// `output.o` and get the header `output.h`. Returns the status of the compiler.
+fn build_output(
+    path: &str,
+    generated: codegen::Output,
+    output: Option<String>,
+    flags: &[String],
+) -> ExitStatus {
+    let stem = Path::new(path).with_extension("");
+    let output = output.unwrap_or_else(|| match stem.to_string_lossy() {
+        stem if stem == path => "a.out".to_owned(),
+        stem => stem.into_owned(),
+    });
+
+    let c_path = format!("{output}.c");
+    fs::write(&c_path, generated.code).expect("Couldn't write the C file.");
+
+    // Like `make`, `$CC` may hold flags after the compiler.
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let mut compiler = compiler.split_whitespace();
+    let mut command = Command::new(compiler.next().unwrap_or("cc"));
+    command.args(compiler).arg(&c_path);
+    match generated.header {
+        Some(header) => {
+            fs::write(format!("{output}.h"), header).expect("Couldn't write the header.");
+            command.arg("-c").arg("-o").arg(format!("{output}.o"));
+        }
+        None => _ = command.arg("-o").arg(&output),
+    }
+
+    command
+        .args(flags)
+        .status()
+        .expect("Couldn't run the C compiler.")
+}
+
+/// Compiles the source file at `path` into `C`, printing the errors if it fails. `verbose` also
+/// prints the tokens and items.
+fn compile(path: &str, mut options: codegen::Options, verbose: bool) -> Option<codegen::Output> {
+    let (program, code) = check(path, options.library, verbose)?;
+    options.path = path.to_owned();
+    match codegen::gen_c(program, &code, options) {
+        Ok(generated) => Some(generated),
+        Err(error) => {
+            eprintln!("\nCodegen Errors:\n");
+            eprintln!(
+                "{}",
+                util::map_spans([&error], &code, |str| format!("\x1b[41m{str}\x1b[0m"))
+            );
+            eprintln!("{}", error.value);
+            None
+        }
+    }
+}
+
+/// Compiles the source file at `path` into bytecode or loads it if it's a bytecode file,
+/// printing the errors if either fails.
+fn compile_bytecode(path: &str) -> Option<bytecode::Module> {
+    let bytes = fs::read(path).expect("Couldn't read the source file.");
+    if bytecode::format::is_bytecode(&bytes) {
+        return match bytecode::format::read(&bytes) {
+            Ok(module) => Some(module),
+            Err(error) => {
+                eprintln!("{error}");
+                None
+            }
+        };
+    }
+
+    let (program, code) = check(path, false, false)?;
+    match bytecode::compiler::compile(&program, &code, path) {
+        Ok(module) => Some(module),
+        Err(error) => {
+            eprintln!("\nBytecode Errors:\n");
+            eprintln!(
+                "{}",
+                util::map_spans([&error], &code, |str| format!("\x1b[41m{str}\x1b[0m"))
+            );
+            eprintln!("{}", error.value);
+            None
+        }
+    }
+}
+
+/// Lexes, parses and checks the source file at `path`, printing the errors if it fails. Returns
+/// the checked program along with the source. A `library` doesn't need a `main`, `verbose` also
+/// prints the tokens and items.
+fn check(path: &str, library: bool, verbose: bool) -> Option<(checker::typed::Program, String)> {
     let bytes = fs::read(path).expect("Couldn't read the source file.");
 
     let (mut tokens, mut errors) = (Vec::new(), Vec::new());
@@ -19,21 +251,25 @@ fn main() {
     }
 
     let code = String::from_utf8_lossy(&bytes);
+    // The parser still runs after lexer errors to report its errors as well.
+    let mut failed = !errors.is_empty();
     if !errors.is_empty() {
-        println!("\nLexer Errors:\n");
-        println!(
+        eprintln!("\nLexer Errors:\n");
+        eprintln!(
             "{}",
-            util::map_spans(&errors, &*code, |str| format!("\x1b[41m{str}\x1b[0m")).expect("xd")
+            util::map_spans(&errors, &code, |str| format!("\x1b[41m{str}\x1b[0m"))
         );
 
         for error in errors {
-            println!("{:?}", error.value);
+            eprintln!("{}", error.value);
         }
     }
 
-    println!("\nTokens:\n");
-    for (i, token) in tokens.iter().enumerate() {
-        println!("{i}: {:?}", token);
+    if verbose {
+        println!("\nTokens:\n");
+        for (i, token) in tokens.iter().enumerate() {
+            println!("{i}: {:?}", token);
+        }
     }
 
     let (mut items, mut errors) = (Vec::new(), Vec::new());
@@ -44,11 +280,58 @@ fn main() {
         }
     }
 
-    println!("\nItems:\n");
-    for item in items.iter() {
-        println!("{:?}", item);
+    failed |= !errors.is_empty();
+    if !errors.is_empty() {
+        eprintln!("\nParser Errors:\n");
+        for error in errors {
+            let ParseError::Lazy(message) = error.value;
+            eprintln!(
+                "{}",
+                util::map_spans([&message], &code, |str| format!("\x1b[41m{str}\x1b[0m"))
+            );
+            eprintln!("{}", *message);
+        }
+    }
+
+    if verbose {
+        println!("\nItems:\n");
+        for item in items.iter() {
+            println!("{:?}", item);
+        }
     }
 
-    let code = codegen::gen_c(items).expect("Couldn't generate code.");
-    println!("{code}");
+    // Items which failed to parse are missing, so the checker would report their uses as well.
+    if failed {
+        return None;
+    }
+
+    let (result, warnings) = checker::check(items, library);
+    if !warnings.is_empty() {
+        eprintln!("\nChecker Warnings:\n");
+        eprintln!(
+            "{}",
+            util::map_spans(&warnings, &code, |str| format!("\x1b[43m{str}\x1b[0m"))
+        );
+
+        for warning in warnings {
+            eprintln!("{}", warning.value);
+        }
+    }
+
+    match result {
+        Ok(program) => Some((program, code.into_owned())),
+        Err(errors) => {
+            eprintln!("\nChecker Errors:\n");
+            eprintln!(
+                "{}",
+                util::map_spans(&errors, &code, |str| format!("\x1b[41m{str}\x1b[0m"))
+            );
+
+            for error in errors {
+                eprintln!("{}", error.value);
+            }
+
+            None
+        }
+    }
 }
@@ -3,25 +3,113 @@ use crate::{
     lexer::token::{LiteralKind, Operator},
     util::Spanned,
 };
+use std::fmt;
+
+pub type Expr = ExprTree<Spanned<Value>, Spanned<Operator>>;
 
 #[derive(Debug, Clone)]
 pub struct Item {
     pub ident: Spanned<String>,
+    pub public: bool,
+    /// Attributes like `@no_mangle` written before the item.
+    pub attributes: Vec<Spanned<String>>,
     pub kind: ItemKind,
 }
 
 #[derive(Debug, Clone)]
 pub enum ItemKind {
     Fn {
+        /// Type parameters like `T` in `fn[T](value: T)`.
+        params: Vec<TypeParam>,
+        /// Whether the first argument is `self`.
+        receiver: bool,
         args: Vec<FnArg>,
         ty: Option<Ty>,
         block: Block,
     },
+    Struct {
+        params: Vec<TypeParam>,
+        fields: Vec<Field>,
+        /// Traits the struct conforms to.
+        traits: Vec<Spanned<String>>,
+        methods: Vec<Spanned<Item>>,
+    },
+    Enum {
+        variants: Vec<Variant>,
+        traits: Vec<Spanned<String>>,
+        methods: Vec<Spanned<Item>>,
+    },
+    Trait {
+        methods: Vec<Spanned<TraitMethod>>,
+    },
+    /// A function implemented in `C` like `extern "math.h" fn(x: float) -> float`.
+    Extern {
+        /// Header declaring the function, which is included instead of declaring it.
+        header: Option<Spanned<String>>,
+        receiver: bool,
+        args: Vec<FnArg>,
+        ty: Option<Ty>,
+    },
+}
+
+/// A type parameter like `T` or `T: Shape + Named`.
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub ident: Spanned<String>,
+    /// Traits the type arguments have to conform to.
+    pub bounds: Vec<Spanned<String>>,
+}
+
+/// A method declared by a trait, which only consists of its signature.
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub ident: Spanned<String>,
+    pub receiver: bool,
+    pub args: Vec<FnArg>,
+    pub ty: Option<Ty>,
+}
+
+/// A variant of an enum like `Circle(float)` or `Empty`.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub ident: Spanned<String>,
+    pub fields: Vec<Ty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub ident: Spanned<String>,
+    pub ty: Ty,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Private,
+    /// `pub get`, readable from anywhere but only writable by the struct's methods.
+    Get,
+    Public,
 }
 
+/// A type like `Node`, `&Node`, `&Node?`, `List[int]`, `dyn Shape`, `fn(int) -> int`, `[int; 4]`
+/// or `[int]`, the optional applies to the reference.
 #[derive(Debug, Clone)]
 pub struct Ty {
     pub ident: Spanned<String>,
+    /// Type arguments of a generic struct, the arguments of a function type or the element type
+    /// of an array or list.
+    pub args: Vec<Ty>,
+    /// Whether this is a function type, `ident` then spans the `fn` keyword.
+    pub function: bool,
+    /// Whether this is an array or list type, `ident` then spans the `[`.
+    pub array: bool,
+    /// Length of an array type, lists don't have one.
+    pub len: Option<usize>,
+    /// Return type of a function type, `void` if it's missing.
+    pub ret: Option<Box<Ty>>,
+    pub reference: bool,
+    /// Whether this is `dyn Trait`, any value conforming to the trait.
+    pub dynamic: bool,
     pub optional: bool,
 }
 
@@ -33,23 +121,196 @@ pub struct FnArg {
 
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub statements: Vec<Statement>,
-    pub trailing_expr: Option<ExprTree<Value, Operator>>,
+    pub statements: Vec<Spanned<Statement>>,
+    pub trailing_expr: Option<Box<Expr>>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     VariableInit {
         ident: Spanned<String>,
-        expr: ExprTree<Value, Operator>,
+        ty: Option<Ty>,
+        expr: Expr,
+    },
+    Expr(Expr),
+    /// `place = expr;` or a compound assignment like `place += expr;`.
+    Assign {
+        place: Expr,
+        operator: Option<Operator>,
+        expr: Expr,
+    },
+    For {
+        label: Option<Spanned<String>>,
+        kind: Loop,
+        block: Block,
+    },
+    Break(Option<Spanned<String>>),
+    Continue(Option<Spanned<String>>),
+    /// `return;` or `return expr;`
+    Return(Option<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Loop {
+    /// `for { }`
+    Infinite,
+    /// `for condition { }`
+    While(Expr),
+    /// `for ident in start..end { }`
+    Range {
+        ident: Spanned<String>,
+        start: Expr,
+        end: Expr,
+        inclusive: bool,
     },
+    /// `for ident ?= expr { }`, runs until `expr` has no value.
+    Optional { ident: Spanned<String>, expr: Expr },
 }
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Ident(Spanned<String>),
+    Ident(String),
     Literal {
-        value: Spanned<String>,
+        value: String,
         kind: LiteralKind,
     },
+    /// `?`, an optional without a value.
+    None,
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    /// A function call or a struct construction like `Node(value, next: ?)`.
+    Call {
+        expr: Box<Expr>,
+        args: Vec<Arg>,
+    },
+    Field {
+        expr: Box<Expr>,
+        ident: Spanned<String>,
+    },
+    /// `expr as Ty`
+    Cast {
+        expr: Box<Expr>,
+        ty: Ty,
+    },
+    /// `if condition { } else { }`, an `else if` is an `else` block holding only the nested `if`.
+    If {
+        condition: Box<Expr>,
+        block: Block,
+        else_block: Option<Block>,
+    },
+    /// `match expr { pattern => expr, ... }`
+    Match {
+        expr: Box<Expr>,
+        arms: Vec<Arm>,
+    },
+    /// `[a, b, c]`, an array literal.
+    Array(Vec<Expr>),
+    /// `[expr; len]`, an array holding `len` copies of `expr`.
+    Repeat {
+        expr: Box<Expr>,
+        len: usize,
+    },
+    /// `expr[index]`
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `expr[start..end]`, a list copied out of a range of an array or list, both bounds may be
+    /// left out.
+    Slice {
+        expr: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        inclusive: bool,
+    },
+    /// `fn(args) -> ty { }`, an anonymous function capturing the locals it uses.
+    Fn {
+        args: Vec<FnArg>,
+        ty: Option<Ty>,
+        block: Block,
+    },
+    /// `"value = {node.value}"`, a string literal interpolating expressions.
+    Format(Vec<FormatPart>),
+}
+
+/// A part of an interpolated string literal.
+#[derive(Debug, Clone)]
+pub enum FormatPart {
+    Literal(String),
+    Expr(Expr),
+}
+
+/// An arm of a `match`, a body written as an expression is a block holding only that expression.
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern: Spanned<Pattern>,
+    pub block: Block,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`, matches any value.
+    Wildcard,
+    /// Binds the matched value to a new local.
+    Binding(String),
+    Literal {
+        value: String,
+        kind: LiteralKind,
+    },
+    /// `?`, matches an optional without a value.
+    None,
+    /// `Enum.Variant` or `Enum.Variant(patterns)`
+    Variant {
+        ty: Spanned<String>,
+        variant: Spanned<String>,
+        fields: Vec<PatternArg>,
+    },
+    /// `Struct(patterns)`, fields are given by position or by name like in a construction.
+    Struct {
+        ident: Spanned<String>,
+        fields: Vec<PatternArg>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternArg {
+    /// Name of the field for named patterns like `next: ?`.
+    pub ident: Option<Spanned<String>>,
+    pub pattern: Spanned<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arg {
+    /// Name of the field for named arguments like `next: ?`.
+    pub ident: Option<Spanned<String>>,
+    pub expr: Expr,
+}
+
+impl Value {
+    /// Returns whether the value ends with a block and can be used as a statement without `;`.
+    pub fn is_block_like(&self) -> bool {
+        matches!(self, Value::If { .. } | Value::Match { .. })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+    /// `&expr`, moves the value to the heap.
+    Reference,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+            UnaryOperator::Reference => "&",
+        };
+
+        write!(f, "{operator}")
+    }
 }
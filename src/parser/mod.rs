@@ -1,13 +1,17 @@
 pub mod item;
 
 use crate::{
-    lexer::token::{Assignment, BraceKind, Keyword, Token},
+    expr_tree::ExprTree,
+    lexer::token::{self, Assignment, BraceKind, Keyword, LiteralKind, Operator, Token},
     util::{Span, Spanned},
 };
 use item::Item;
 use std::iter::Peekable;
 
-use self::item::{Block, FnArg, ItemKind, Ty};
+use self::item::{
+    Arg, Arm, Block, Expr, Field, FnArg, FormatPart, ItemKind, Loop, Pattern, PatternArg,
+    Statement, TraitMethod, Ty, TypeParam, UnaryOperator, Value, Variant, Visibility,
+};
 
 pub fn parse<I, T>(tokens: T) -> ItemIter<I>
 where
@@ -16,6 +20,7 @@ where
 {
     ItemIter {
         index: 0,
+        end: 0,
         tokens: tokens.into_iter().peekable(),
     }
 }
@@ -28,16 +33,19 @@ pub enum ParseError {
 
 pub struct ItemIter<I: Iterator<Item = Spanned<Token>>> {
     index: usize,
+    /// End of the last consumed token.
+    end: usize,
     tokens: Peekable<I>,
 }
 
 impl<I: Iterator<Item = Spanned<Token>>> ItemIter<I> {
-    fn peek_token<'a>(&'a mut self) -> Option<&'a Spanned<Token>> {
+    fn peek_token(&mut self) -> Option<&Spanned<Token>> {
         self.tokens.peek()
     }
 
     fn next_token(&mut self) -> Option<Spanned<Token>> {
         let token = self.tokens.next()?;
+        self.end = token.end;
         self.index = match self.peek_token() {
             Some(Spanned { start, .. }) => *start,
             None => token.end,
@@ -47,6 +55,27 @@ impl<I: Iterator<Item = Spanned<Token>>> ItemIter<I> {
     }
 
     fn next_ty(&mut self) -> Result<Ty, ParseError> {
+        let reference = self.peek_is(&Token::Ampersand);
+        if reference {
+            _ = self.next_token();
+        }
+
+        let dynamic = self.peek_is(&Token::Keyword(Keyword::Dyn));
+        if dynamic {
+            _ = self.next_token();
+        }
+
+        if self.peek_is(&Token::Keyword(Keyword::Fn)) {
+            return self.next_fn_ty(reference);
+        }
+
+        if self.peek_is(&Token::Brace {
+            open: true,
+            kind: BraceKind::Square,
+        }) {
+            return self.next_array_ty(reference);
+        }
+
         let ident = match self.next_token() {
             Some(Spanned {
                 start,
@@ -62,243 +91,318 @@ impl<I: Iterator<Item = Spanned<Token>>> ItemIter<I> {
             }
         };
 
-        let optional = if matches!(
-            self.peek_token(),
-            Some(Spanned {
-                value: Token::QuestionMark,
-                ..
-            })
-        ) {
+        let mut args = Vec::new();
+        if self.peek_is(&Token::Brace {
+            open: true,
+            kind: BraceKind::Square,
+        }) {
             _ = self.next_token();
-            true
-        } else {
-            false
-        };
-
-        Ok(Ty { ident, optional })
-    }
-
-    fn next_item(&mut self, token: Spanned<Token>) -> Result<Item, ParseError> {
-        match token {
-            Spanned {
-                start,
-                end,
-                value: Token::Ident(ident),
-            } => {
-                let ident = ident.span(start..end);
-                let Some(Spanned {
-                    end,
-                    value: Token::Assignment(Assignment::Normal),
-                    ..
-                }) = self.next_token()
-                else {
-                    return Err(ParseError::Lazy(
-                        "Expected \":=\".".to_owned().span(end..end + 1),
-                    ));
-                };
-
-                let Some(Spanned {
-                    end,
-                    value: Token::Keyword(Keyword::Fn),
-                    ..
-                }) = self.next_token()
-                else {
-                    return Err(ParseError::Lazy(
-                        "Expected \"fn\".".to_owned().span(end..end + 1),
-                    ));
-                };
-
-                let Some(Spanned {
-                    end,
-                    value:
-                        Token::Brace {
-                            open: true,
-                            kind: BraceKind::Smooth,
-                        },
-                    ..
-                }) = self.next_token()
-                else {
-                    return Err(ParseError::Lazy(
-                        "Expected \"(\".".to_owned().span(end..end + 1),
-                    ));
-                };
-
-                // Parse fn arguments
-                // --------------------------------------------@
-                let mut args = Vec::new();
-                loop {
-                    if let Some(Spanned {
+            loop {
+                args.push(self.next_ty()?);
+                match self.next_token() {
+                    Some(Spanned {
+                        value: Token::Comma,
+                        ..
+                    }) => {}
+                    Some(Spanned {
                         value:
                             Token::Brace {
                                 open: false,
-                                kind: BraceKind::Smooth,
+                                kind: BraceKind::Square,
                             },
                         ..
-                    }) = self.peek_token()
-                    {
-                        break;
+                    }) => break,
+                    _ => {
+                        return Err(ParseError::Lazy(
+                            "Expected \",\" or \"]\"."
+                                .to_owned()
+                                .span(self.end..self.end + 1),
+                        ))
                     }
+                }
+            }
+        }
 
-                    let ident = match self.next_token() {
-                        Some(Spanned {
-                            start,
-                            end,
-                            value: Token::Ident(ident),
-                        }) => {
-                            if !args.is_empty() {
-                                return Err(ParseError::Lazy(
-                                    "Expected \",\".".to_owned().span(start..end),
-                                ));
-                            }
+        Ok(Ty {
+            ident,
+            args,
+            function: false,
+            array: false,
+            len: None,
+            ret: None,
+            reference,
+            dynamic,
+            optional: self.next_optional(),
+        })
+    }
 
-                            ident.span(start..end)
-                        }
-                        Some(Spanned {
-                            start,
-                            end,
-                            value: Token::Comma,
-                        }) => {
-                            if args.is_empty() {
-                                return Err(ParseError::Lazy(
-                                    "Expected identifier or \")\".".to_owned().span(start..end),
-                                ));
-                            }
+    /// Parses an array type like `[int; 4]` or a list type like `[int]`.
+    fn next_array_ty(&mut self, reference: bool) -> Result<Ty, ParseError> {
+        let Some(Spanned { start, end, .. }) = self.next_token() else {
+            unreachable!("The caller peeked the \"[\".")
+        };
 
-                            match self.next_token() {
-                                Some(Spanned {
-                                    start,
-                                    end,
-                                    value: Token::Ident(ident),
-                                }) => ident.span(start..end),
-                                Some(Spanned {
-                                    value:
-                                        Token::Brace {
-                                            open: false,
-                                            kind: BraceKind::Smooth,
-                                        },
-                                    ..
-                                }) => break,
-                                _ => {
-                                    return Err(ParseError::Lazy(
-                                        "Expected identifier or \")\"."
-                                            .to_owned()
-                                            .span(end..end + 1),
-                                    ))
-                                }
-                            }
-                        }
-                        _ => {
-                            return Err(ParseError::Lazy(
-                                "Expected identifier or \")\"."
-                                    .to_owned()
-                                    .span(end..end + 1),
-                            ))
-                        }
-                    };
+        let ty = self.next_ty()?;
+        let len = match self.peek_is(&Token::SemiColon) {
+            true => {
+                _ = self.next_token();
+                Some(self.next_len()?)
+            }
+            false => None,
+        };
 
-                    let Some(Spanned {
-                        value: Token::Colon,
-                        ..
-                    }) = self.next_token()
-                    else {
-                        return Err(ParseError::Lazy(
-                            "Expected \":\".".to_owned().span(end..end + 1),
-                        ));
-                    };
+        self.expect_token(
+            Token::Brace {
+                open: false,
+                kind: BraceKind::Square,
+            },
+            "\"]\"",
+        )?;
 
-                    args.push(FnArg {
-                        ident,
-                        ty: self.next_ty()?,
-                    })
+        Ok(Ty {
+            ident: "[".to_owned().span(start..end),
+            args: vec![ty],
+            function: false,
+            array: true,
+            len,
+            ret: None,
+            reference,
+            dynamic: false,
+            optional: self.next_optional(),
+        })
+    }
+
+    /// Parses the integer literal giving the length of an array.
+    fn next_len(&mut self) -> Result<usize, ParseError> {
+        let token = self.next_token_or("an array length")?;
+        match &token.value {
+            Token::Literal {
+                value,
+                kind: LiteralKind::Int,
+            } => value.parse().map_err(|_| {
+                ParseError::Lazy(
+                    "Expected an array length."
+                        .to_owned()
+                        .span(token.start..token.end),
+                )
+            }),
+            _ => Err(ParseError::Lazy(
+                "Expected an array length."
+                    .to_owned()
+                    .span(token.start..token.end),
+            )),
+        }
+    }
+
+    /// Parses a function type like `fn(int, int) -> int`, whose return type takes any `?`.
+    fn next_fn_ty(&mut self, reference: bool) -> Result<Ty, ParseError> {
+        let Some(Spanned { start, end, .. }) = self.next_token() else {
+            unreachable!("The caller peeked the \"fn\".")
+        };
+
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Smooth,
+            },
+            "\"(\"",
+        )?;
+
+        let mut args = Vec::new();
+        if self.peek_is(&Token::Brace {
+            open: false,
+            kind: BraceKind::Smooth,
+        }) {
+            _ = self.next_token();
+        } else {
+            loop {
+                args.push(self.next_ty()?);
+                if !self.next_separator()? {
+                    break;
                 }
+            }
+        }
 
-                let Some(Spanned {
-                    end,
-                    value:
-                        Token::Brace {
-                            open: false,
-                            kind: BraceKind::Smooth,
-                        },
-                    ..
-                }) = self.next_token()
-                else {
-                    return Err(ParseError::Lazy(
-                        "Expected identifier or \")\"."
-                            .to_owned()
-                            .span(end..end + 1),
-                    ));
-                };
+        let ret = self.next_return_ty()?.map(Box::new);
+        let optional = ret.is_none() && self.next_optional();
+        Ok(Ty {
+            ident: "fn".to_owned().span(start..end),
+            args,
+            function: true,
+            array: false,
+            len: None,
+            ret,
+            reference,
+            dynamic: false,
+            optional,
+        })
+    }
 
-                let ty = if let Some(Spanned {
-                    value: Token::Arrow,
-                    ..
-                }) = self.peek_token()
-                {
-                    _ = self.next_token();
-                    Some(self.next_ty()?)
-                } else {
-                    None
-                };
+    /// Consumes a `?` following a type, returning whether there was one.
+    fn next_optional(&mut self) -> bool {
+        let optional = self.peek_is(&Token::QuestionMark);
+        if optional {
+            _ = self.next_token();
+        }
 
-                let Some(Spanned {
+        optional
+    }
+
+    /// Parses type parameters like `[T: Shape, U]` if there are any.
+    fn next_type_params(&mut self) -> Result<Vec<TypeParam>, ParseError> {
+        let mut params = Vec::new();
+        if !self.peek_is(&Token::Brace {
+            open: true,
+            kind: BraceKind::Square,
+        }) {
+            return Ok(params);
+        }
+
+        _ = self.next_token();
+        loop {
+            let ident = match self.next_token() {
+                Some(Spanned {
+                    start,
                     end,
-                    value:
-                        Token::Brace {
-                            open: true,
-                            kind: BraceKind::Curly,
-                        },
-                    ..
-                }) = self.next_token()
-                else {
+                    value: Token::Ident(ident),
+                }) => ident.span(start..end),
+                _ => {
                     return Err(ParseError::Lazy(
-                        "Expected \"{\".".to_owned().span(end..end + 1),
-                    ));
-                };
-
-                loop {
-                    if let Some(Spanned {
-                        value:
-                            Token::Brace {
-                                open: false,
-                                kind: BraceKind::Curly,
-                            },
-                        ..
-                    }) = self.peek_token()
-                    {
-                        break;
-                    }
+                        "Expected an identifier."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            };
 
-                    // TODO: Make this actually parse statements.
-                    // Right now it just ignores everything...
-                    self.next_token();
+            let bounds = match self.peek_is(&Token::Colon) {
+                true => {
+                    _ = self.next_token();
+                    self.next_traits()?
                 }
+                false => Vec::new(),
+            };
 
-                let Some(Spanned {
+            params.push(TypeParam { ident, bounds });
+            match self.next_token() {
+                Some(Spanned {
+                    value: Token::Comma,
+                    ..
+                }) => {}
+                Some(Spanned {
                     value:
                         Token::Brace {
                             open: false,
-                            kind: BraceKind::Curly,
+                            kind: BraceKind::Square,
                         },
                     ..
-                }) = self.next_token()
-                else {
+                }) => return Ok(params),
+                _ => {
                     return Err(ParseError::Lazy(
-                        "Expected a statement or \"}\"."
+                        "Expected \",\" or \"]\"."
                             .to_owned()
-                            .span(end..end + 1),
-                    ));
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses a list of traits like `Shape + Named`.
+    fn next_traits(&mut self) -> Result<Vec<Spanned<String>>, ParseError> {
+        let mut traits = Vec::new();
+        loop {
+            match self.next_token() {
+                Some(Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                }) => traits.push(ident.span(start..end)),
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected a trait.".to_owned().span(self.end..self.end + 1),
+                    ))
+                }
+            }
+
+            if !self.peek_is(&Token::Operator(Operator::Plus)) {
+                return Ok(traits);
+            }
+
+            _ = self.next_token();
+        }
+    }
+
+    /// Parses the traits a struct or enum conforms to if there are any.
+    fn next_conformance(&mut self) -> Result<Vec<Spanned<String>>, ParseError> {
+        match self.peek_is(&Token::Colon) {
+            true => {
+                _ = self.next_token();
+                self.next_traits()
+            }
+            false => Ok(Vec::new()),
+        }
+    }
+
+    fn next_item(&mut self, mut token: Spanned<Token>) -> Result<Item, ParseError> {
+        let mut attributes = Vec::new();
+        while let Token::Attribute(attribute) = token.value {
+            attributes.push(attribute.span(token.start..token.end));
+            token = self.next_token_or("an item")?;
+        }
+
+        let (public, token) = match token {
+            Spanned {
+                value: Token::Keyword(Keyword::Pub),
+                ..
+            } => (true, self.next_token_or("an identifier")?),
+            token => (false, token),
+        };
+
+        match token {
+            Spanned {
+                start,
+                end,
+                value: Token::Ident(ident),
+            } => {
+                let ident = ident.span(start..end);
+                self.expect_token(Token::Assignment(Assignment::Normal), "\":=\"")?;
+
+                let kind = match self.next_token() {
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::Fn),
+                        ..
+                    }) => self.next_fn()?,
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::Struct),
+                        ..
+                    }) => self.next_struct()?,
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::Enum),
+                        ..
+                    }) => self.next_enum()?,
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::Trait),
+                        ..
+                    }) => self.next_trait()?,
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::Extern),
+                        ..
+                    }) => self.next_extern()?,
+                    _ => {
+                        return Err(ParseError::Lazy(
+                            "Expected \"fn\", \"struct\", \"enum\", \"trait\" or \"extern\"."
+                                .to_owned()
+                                .span(self.end..self.end + 1),
+                        ))
+                    }
                 };
 
                 Ok(Item {
                     ident,
-                    kind: ItemKind::Fn {
-                        args,
-                        block: Block {
-                            statements: Vec::new(),
-                            trailing_expr: None,
-                        },
-                        ty,
-                    },
+                    public,
+                    attributes,
+                    kind,
                 })
             }
             Spanned { start, end, .. } => Err(ParseError::Lazy(
@@ -306,17 +410,1333 @@ impl<I: Iterator<Item = Spanned<Token>>> ItemIter<I> {
             )),
         }
     }
-}
 
-impl<I: Iterator<Item = Spanned<Token>>> Iterator for ItemIter<I> {
-    type Item = Result<Spanned<Item>, Spanned<ParseError>>;
+    /// Parses the rest of a function after the `fn` keyword.
+    fn next_fn(&mut self) -> Result<ItemKind, ParseError> {
+        let params = self.next_type_params()?;
+        let (receiver, args, ty) = self.next_signature()?;
+        let block = self.next_block()?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let start = self.index;
-        let token = self.next_token()?;
-        Some(match self.next_item(token) {
-            Ok(item) => Ok(item.span(start..self.index)),
-            Err(error) => Err(error.span(start..self.index)),
+        Ok(ItemKind::Fn {
+            params,
+            receiver,
+            args,
+            ty,
+            block,
+        })
+    }
+
+    /// Parses the rest of an external function after the `extern` keyword, an optional header
+    /// followed by the signature.
+    fn next_extern(&mut self) -> Result<ItemKind, ParseError> {
+        let header = match self.peek_token() {
+            Some(Spanned {
+                value:
+                    Token::Literal {
+                        kind: LiteralKind::String,
+                        ..
+                    },
+                ..
+            }) => match self.next_token() {
+                Some(Spanned {
+                    start,
+                    end,
+                    value: Token::Literal { value, .. },
+                }) => Some(value.span(start..end)),
+                _ => unreachable!("The token was just peeked."),
+            },
+            _ => None,
+        };
+
+        self.expect_token(Token::Keyword(Keyword::Fn), "\"fn\"")?;
+        let (receiver, args, ty) = self.next_signature()?;
+
+        Ok(ItemKind::Extern {
+            header,
+            receiver,
+            args,
+            ty,
         })
     }
+
+    /// Parses the arguments and return type of a function.
+    fn next_signature(&mut self) -> Result<(bool, Vec<FnArg>, Option<Ty>), ParseError> {
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Smooth,
+            },
+            "\"(\"",
+        )?;
+
+        let receiver = self.peek_is(&Token::Ident("self".to_owned()));
+        if receiver {
+            _ = self.next_token();
+            if self.peek_is(&Token::Comma) {
+                _ = self.next_token();
+            }
+        }
+
+        let args = self.next_fn_args()?;
+        Ok((receiver, args, self.next_return_ty()?))
+    }
+
+    /// Parses the return type following a `->` if there is one.
+    fn next_return_ty(&mut self) -> Result<Option<Ty>, ParseError> {
+        match self.peek_is(&Token::Arrow) {
+            true => {
+                _ = self.next_token();
+                self.next_ty().map(Some)
+            }
+            false => Ok(None),
+        }
+    }
+
+    /// Parses `ident: Ty` arguments until a closing `)`.
+    fn next_fn_args(&mut self) -> Result<Vec<FnArg>, ParseError> {
+        let mut args = Vec::new();
+        loop {
+            let ident = match self.next_token() {
+                Some(Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Smooth,
+                        },
+                    ..
+                }) => return Ok(args),
+                Some(Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                }) => ident.span(start..end),
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected identifier or \")\"."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            };
+
+            self.expect_token(Token::Colon, "\":\"")?;
+            args.push(FnArg {
+                ident,
+                ty: self.next_ty()?,
+            });
+
+            if !self.next_separator()? {
+                return Ok(args);
+            }
+        }
+    }
+
+    /// Consumes a `,` returning `true` or a `)` returning `false`.
+    fn next_separator(&mut self) -> Result<bool, ParseError> {
+        match self.next_token() {
+            Some(Spanned {
+                value: Token::Comma,
+                ..
+            }) => Ok(true),
+            Some(Spanned {
+                value:
+                    Token::Brace {
+                        open: false,
+                        kind: BraceKind::Smooth,
+                    },
+                ..
+            }) => Ok(false),
+            _ => Err(ParseError::Lazy(
+                "Expected \",\" or \")\"."
+                    .to_owned()
+                    .span(self.end..self.end + 1),
+            )),
+        }
+    }
+
+    /// Parses the rest of a struct after the `struct` keyword.
+    fn next_struct(&mut self) -> Result<ItemKind, ParseError> {
+        let params = self.next_type_params()?;
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Smooth,
+            },
+            "\"(\"",
+        )?;
+
+        let mut fields = Vec::new();
+        loop {
+            let mut token = self.next_token_or("a field or \")\"")?;
+            if let Token::Brace {
+                open: false,
+                kind: BraceKind::Smooth,
+            } = token.value
+            {
+                break;
+            }
+
+            let mut visibility = Visibility::Private;
+            if token.value == Token::Keyword(Keyword::Pub) {
+                visibility = Visibility::Public;
+                token = self.next_token_or("an identifier")?;
+                if token.value == Token::Keyword(Keyword::Get) {
+                    visibility = Visibility::Get;
+                    token = self.next_token_or("an identifier")?;
+                }
+            }
+
+            let Spanned {
+                start,
+                end,
+                value: Token::Ident(ident),
+            } = token
+            else {
+                return Err(ParseError::Lazy(
+                    "Expected an identifier.".to_owned().span(token.range()),
+                ));
+            };
+
+            self.expect_token(Token::Colon, "\":\"")?;
+            fields.push(Field {
+                ident: ident.span(start..end),
+                ty: self.next_ty()?,
+                visibility,
+            });
+
+            if !self.next_separator()? {
+                break;
+            }
+        }
+
+        Ok(ItemKind::Struct {
+            params,
+            fields,
+            traits: self.next_conformance()?,
+            methods: self.next_methods()?,
+        })
+    }
+
+    /// Parses the rest of an enum after the `enum` keyword.
+    fn next_enum(&mut self) -> Result<ItemKind, ParseError> {
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Smooth,
+            },
+            "\"(\"",
+        )?;
+
+        let mut variants = Vec::new();
+        loop {
+            let (ident, range) = match self.next_token_or("a variant or \")\"")? {
+                Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Smooth,
+                        },
+                    ..
+                } => break,
+                Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                } => (ident, start..end),
+                token => {
+                    return Err(ParseError::Lazy(
+                        "Expected an identifier.".to_owned().span(token.range()),
+                    ))
+                }
+            };
+
+            let mut fields = Vec::new();
+            if self.peek_is(&Token::Brace {
+                open: true,
+                kind: BraceKind::Smooth,
+            }) {
+                _ = self.next_token();
+                if self.peek_is(&Token::Brace {
+                    open: false,
+                    kind: BraceKind::Smooth,
+                }) {
+                    _ = self.next_token();
+                } else {
+                    loop {
+                        fields.push(self.next_ty()?);
+                        if !self.next_separator()? {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            variants.push(Variant {
+                ident: ident.span(range),
+                fields,
+            });
+
+            if !self.next_separator()? {
+                break;
+            }
+        }
+
+        Ok(ItemKind::Enum {
+            variants,
+            traits: self.next_conformance()?,
+            methods: self.next_methods()?,
+        })
+    }
+
+    /// Parses the rest of a trait after the `trait` keyword.
+    fn next_trait(&mut self) -> Result<ItemKind, ParseError> {
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Curly,
+            },
+            "\"{\"",
+        )?;
+
+        let mut methods = Vec::new();
+        loop {
+            let (ident, start) = match self.next_token_or("a method or \"}\"")? {
+                Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Curly,
+                        },
+                    ..
+                } => return Ok(ItemKind::Trait { methods }),
+                Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                } => (ident.span(start..end), start),
+                token => {
+                    return Err(ParseError::Lazy(
+                        "Expected an identifier.".to_owned().span(token.range()),
+                    ))
+                }
+            };
+
+            self.expect_token(Token::Assignment(Assignment::Normal), "\":=\"")?;
+            self.expect_token(Token::Keyword(Keyword::Fn), "\"fn\"")?;
+            let (receiver, args, ty) = self.next_signature()?;
+            let method = TraitMethod {
+                ident,
+                receiver,
+                args,
+                ty,
+            };
+
+            methods.push(method.span(start..self.end));
+        }
+    }
+
+    /// Parses the optional block of methods following a struct or enum.
+    fn next_methods(&mut self) -> Result<Vec<Spanned<Item>>, ParseError> {
+        let mut methods = Vec::new();
+        if !self.peek_is(&Token::Brace {
+            open: true,
+            kind: BraceKind::Curly,
+        }) {
+            return Ok(methods);
+        }
+
+        _ = self.next_token();
+        loop {
+            let token = self.next_token_or("a method or \"}\"")?;
+            if let Token::Brace {
+                open: false,
+                kind: BraceKind::Curly,
+            } = token.value
+            {
+                return Ok(methods);
+            }
+
+            let start = token.start;
+            let method = self.next_item(token)?;
+            methods.push(method.span(start..self.end));
+        }
+    }
+
+    /// Returns the next token or errors with `"Expected {what}."` if there are none left.
+    fn next_token_or(&mut self, what: &str) -> Result<Spanned<Token>, ParseError> {
+        self.next_token().ok_or_else(|| {
+            ParseError::Lazy(format!("Expected {what}.").span(self.end..self.end + 1))
+        })
+    }
+
+    /// Consumes the next token and errors with `"Expected {what}."` if it isn't `expected`.
+    fn expect_token(&mut self, expected: Token, what: &str) -> Result<Spanned<Token>, ParseError> {
+        match self.next_token() {
+            Some(token) if token.value == expected => Ok(token),
+            Some(Spanned { start, end, .. }) => Err(ParseError::Lazy(
+                format!("Expected {what}.").span(start..end),
+            )),
+            None => Err(ParseError::Lazy(
+                format!("Expected {what}.").span(self.end..self.end + 1),
+            )),
+        }
+    }
+
+    fn peek_is(&mut self, expected: &Token) -> bool {
+        matches!(self.peek_token(), Some(Spanned { value, .. }) if value == expected)
+    }
+
+    /// Parses a block delimited by curly braces.
+    fn next_block(&mut self) -> Result<Block, ParseError> {
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Curly,
+            },
+            "\"{\"",
+        )?;
+
+        let mut statements = Vec::new();
+        loop {
+            let token = self.next_token_or("a statement or \"}\"")?;
+            let start = token.start;
+            let statement = match token {
+                Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Curly,
+                        },
+                    ..
+                } => {
+                    return Ok(Block {
+                        statements,
+                        trailing_expr: None,
+                    })
+                }
+                Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                } if matches!(
+                    self.peek_token(),
+                    Some(Spanned {
+                        value: Token::Assignment(Assignment::Normal) | Token::Colon,
+                        ..
+                    })
+                ) =>
+                {
+                    self.next_ident_statement(ident.span(start..end))?
+                }
+                Spanned {
+                    value: Token::Keyword(Keyword::For),
+                    ..
+                } => self.next_for(None)?,
+                Spanned {
+                    value: Token::Keyword(keyword @ (Keyword::Break | Keyword::Continue)),
+                    ..
+                } => {
+                    let label = match self.peek_token() {
+                        Some(Spanned {
+                            value: Token::Ident(_),
+                            ..
+                        }) => {
+                            let Some(Spanned {
+                                start,
+                                end,
+                                value: Token::Ident(label),
+                            }) = self.next_token()
+                            else {
+                                unreachable!()
+                            };
+
+                            Some(label.span(start..end))
+                        }
+                        _ => None,
+                    };
+
+                    self.expect_token(Token::SemiColon, "\";\"")?;
+                    match keyword {
+                        Keyword::Break => Statement::Break(label),
+                        _ => Statement::Continue(label),
+                    }
+                }
+                Spanned {
+                    value: Token::Keyword(Keyword::Return),
+                    ..
+                } => {
+                    let token = self.next_token_or("an expression or \";\"")?;
+                    if token.value == Token::SemiColon {
+                        Statement::Return(None)
+                    } else {
+                        let expr = self.next_expr(token)?;
+                        self.expect_token(Token::SemiColon, "\";\"")?;
+                        Statement::Return(Some(expr))
+                    }
+                }
+                token => {
+                    let expr = self.next_expr(token)?;
+                    let operator = match self.peek_token() {
+                        Some(Spanned {
+                            value: Token::Eq, ..
+                        }) => Some(None),
+                        Some(Spanned {
+                            value: Token::CompoundAssignment(operator),
+                            ..
+                        }) => Some(Some(*operator)),
+                        _ => None,
+                    };
+
+                    if let Some(operator) = operator {
+                        _ = self.next_token();
+                        let token = self.next_token_or("an expression")?;
+                        let value = self.next_expr(token)?;
+                        self.expect_token(Token::SemiColon, "\";\"")?;
+                        statements.push(
+                            Statement::Assign {
+                                place: expr,
+                                operator,
+                                expr: value,
+                            }
+                            .span(start..self.end),
+                        );
+                        continue;
+                    }
+
+                    let block_like =
+                        matches!(&expr, ExprTree::Value(value) if value.is_block_like());
+                    if block_like
+                        && !self.peek_is(&Token::SemiColon)
+                        && !self.peek_is(&Token::Brace {
+                            open: false,
+                            kind: BraceKind::Curly,
+                        })
+                    {
+                        statements.push(Statement::Expr(expr).span(start..self.end));
+                        continue;
+                    }
+
+                    match self.next_token() {
+                        Some(Spanned {
+                            value: Token::SemiColon,
+                            ..
+                        }) => Statement::Expr(expr),
+                        Some(Spanned {
+                            value:
+                                Token::Brace {
+                                    open: false,
+                                    kind: BraceKind::Curly,
+                                },
+                            ..
+                        }) => {
+                            return Ok(Block {
+                                statements,
+                                trailing_expr: Some(Box::new(expr)),
+                            })
+                        }
+                        _ => {
+                            return Err(ParseError::Lazy(
+                                "Expected \";\" or \"}\"."
+                                    .to_owned()
+                                    .span(self.end..self.end + 1),
+                            ))
+                        }
+                    }
+                }
+            };
+
+            statements.push(statement.span(start..self.end));
+        }
+    }
+
+    /// Parses `ident := expr;`, `ident: Ty = expr;` or a loop labeled `ident` after the identifier.
+    fn next_ident_statement(&mut self, ident: Spanned<String>) -> Result<Statement, ParseError> {
+        let ty = match self.next_token() {
+            Some(Spanned {
+                value: Token::Colon,
+                ..
+            }) => {
+                if self.peek_is(&Token::Keyword(Keyword::For)) {
+                    _ = self.next_token();
+                    return self.next_for(Some(ident));
+                }
+
+                let ty = self.next_ty()?;
+                self.expect_token(Token::Eq, "\"=\"")?;
+                Some(ty)
+            }
+            _ => None,
+        };
+
+        let token = self.next_token_or("an expression")?;
+        let expr = self.next_expr(token)?;
+        self.expect_token(Token::SemiColon, "\";\"")?;
+
+        Ok(Statement::VariableInit { ident, ty, expr })
+    }
+
+    /// Parses the rest of a loop after the `for` keyword.
+    fn next_for(&mut self, label: Option<Spanned<String>>) -> Result<Statement, ParseError> {
+        let open_curly = Token::Brace {
+            open: true,
+            kind: BraceKind::Curly,
+        };
+
+        let kind = match self.peek_token() {
+            Some(Spanned { value, .. }) if *value == open_curly => Loop::Infinite,
+            Some(Spanned {
+                value: Token::Ident(_),
+                ..
+            }) => {
+                let Some(Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                }) = self.next_token()
+                else {
+                    unreachable!()
+                };
+
+                match self.peek_token() {
+                    Some(Spanned {
+                        value: Token::Keyword(Keyword::In),
+                        ..
+                    }) => {
+                        _ = self.next_token();
+                        let token = self.next_token_or("an expression")?;
+                        let range_start = self.next_expr(token)?;
+                        let inclusive = match self.next_token() {
+                            Some(Spanned {
+                                value: Token::Range { inclusive },
+                                ..
+                            }) => inclusive,
+                            _ => {
+                                return Err(ParseError::Lazy(
+                                    "Expected \"..\" or \"..=\"."
+                                        .to_owned()
+                                        .span(self.end..self.end + 1),
+                                ))
+                            }
+                        };
+
+                        let token = self.next_token_or("an expression")?;
+                        Loop::Range {
+                            ident: ident.span(start..end),
+                            start: range_start,
+                            end: self.next_expr(token)?,
+                            inclusive,
+                        }
+                    }
+                    Some(Spanned {
+                        value: Token::Assignment(Assignment::Optional),
+                        ..
+                    }) => {
+                        _ = self.next_token();
+                        let token = self.next_token_or("an expression")?;
+                        Loop::Optional {
+                            ident: ident.span(start..end),
+                            expr: self.next_expr(token)?,
+                        }
+                    }
+                    _ => Loop::While(self.next_expr(Token::Ident(ident).span(start..end))?),
+                }
+            }
+            _ => {
+                let token = self.next_token_or("a condition or \"{\"")?;
+                Loop::While(self.next_expr(token)?)
+            }
+        };
+
+        Ok(Statement::For {
+            label,
+            kind,
+            block: self.next_block()?,
+        })
+    }
+
+    /// Parses an expression made out of operands joined by binary operators.
+    fn next_expr(&mut self, token: Spanned<Token>) -> Result<Expr, ParseError> {
+        let mut expr = self.next_operand(token)?;
+        loop {
+            let (operator, right) = match self.peek_token() {
+                Some(Spanned {
+                    value: Token::Operator(_),
+                    ..
+                }) => {
+                    let Some(Spanned {
+                        start,
+                        end,
+                        value: Token::Operator(operator),
+                    }) = self.next_token()
+                    else {
+                        unreachable!()
+                    };
+
+                    let token = self.next_token_or("an expression")?;
+                    (operator.span(start..end), self.next_operand(token)?)
+                }
+                // The lexer reads `x -1` as an identifier followed by a negative literal,
+                // so split the literal back into a subtraction.
+                Some(Spanned {
+                    value:
+                        Token::Literal {
+                            value,
+                            kind: LiteralKind::Int | LiteralKind::Float,
+                        },
+                    ..
+                }) if value.starts_with('-') => {
+                    let Some(Spanned {
+                        start,
+                        end,
+                        value: Token::Literal { value, kind },
+                    }) = self.next_token()
+                    else {
+                        unreachable!()
+                    };
+
+                    let literal = Token::Literal {
+                        value: value[1..].to_owned(),
+                        kind,
+                    };
+                    (
+                        Operator::Minus.span(start..start + 1),
+                        self.next_operand(literal.span(start + 1..end))?,
+                    )
+                }
+                _ => break,
+            };
+
+            expr.append(operator, right);
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a unary expression optionally followed by casts.
+    fn next_operand(&mut self, token: Spanned<Token>) -> Result<Expr, ParseError> {
+        let start = token.start;
+        let mut expr = self.next_unary(token)?;
+        while self.peek_is(&Token::Keyword(Keyword::As)) {
+            _ = self.next_token();
+            let ty = self.next_ty()?;
+            expr = ExprTree::Value(
+                Value::Cast {
+                    expr: Box::new(expr),
+                    ty,
+                }
+                .span(start..self.end),
+            );
+        }
+
+        Ok(expr)
+    }
+
+    fn next_unary(&mut self, token: Spanned<Token>) -> Result<Expr, ParseError> {
+        let operator = match token.value {
+            Token::Operator(Operator::Minus) => UnaryOperator::Negate,
+            Token::Bang => UnaryOperator::Not,
+            Token::Ampersand => UnaryOperator::Reference,
+            _ => return self.next_postfix(token),
+        };
+
+        let start = token.start;
+        let token = self.next_token_or("an expression")?;
+        let expr = self.next_unary(token)?;
+
+        Ok(ExprTree::Value(
+            Value::Unary {
+                operator,
+                expr: Box::new(expr),
+            }
+            .span(start..self.end),
+        ))
+    }
+
+    /// Parses a primary expression followed by any calls or field accesses.
+    fn next_postfix(&mut self, token: Spanned<Token>) -> Result<Expr, ParseError> {
+        let start = token.start;
+        let mut expr = self.next_primary(token)?;
+
+        // Block-like expressions end statements, so `if c { } (x)` isn't a call.
+        if matches!(&expr, ExprTree::Value(value) if value.is_block_like()) {
+            return Ok(expr);
+        }
+
+        loop {
+            match self.peek_token() {
+                Some(Spanned {
+                    value:
+                        Token::Brace {
+                            open: true,
+                            kind: BraceKind::Smooth,
+                        },
+                    ..
+                }) => {
+                    _ = self.next_token();
+                    let args = self.next_args()?;
+                    expr = ExprTree::Value(
+                        Value::Call {
+                            expr: Box::new(expr),
+                            args,
+                        }
+                        .span(start..self.end),
+                    );
+                }
+                Some(Spanned {
+                    value: Token::Dot, ..
+                }) => {
+                    _ = self.next_token();
+                    let ident = match self.next_token() {
+                        Some(Spanned {
+                            start,
+                            end,
+                            value: Token::Ident(ident),
+                        }) => ident.span(start..end),
+                        _ => {
+                            return Err(ParseError::Lazy(
+                                "Expected an identifier."
+                                    .to_owned()
+                                    .span(self.end..self.end + 1),
+                            ))
+                        }
+                    };
+
+                    expr = ExprTree::Value(
+                        Value::Field {
+                            expr: Box::new(expr),
+                            ident,
+                        }
+                        .span(start..self.end),
+                    );
+                }
+                Some(Spanned {
+                    value:
+                        Token::Brace {
+                            open: true,
+                            kind: BraceKind::Square,
+                        },
+                    ..
+                }) => {
+                    _ = self.next_token();
+                    let value = self.next_index(expr)?;
+                    expr = ExprTree::Value(value.span(start..self.end));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// Parses the rest of `expr[index]` or `expr[start..end]` after the `[`.
+    fn next_index(&mut self, expr: Expr) -> Result<Value, ParseError> {
+        let close = Token::Brace {
+            open: false,
+            kind: BraceKind::Square,
+        };
+
+        let start = match self.peek_token() {
+            Some(Spanned {
+                value: Token::Range { .. },
+                ..
+            }) => None,
+            _ => {
+                let token = self.next_token_or("an expression")?;
+                Some(Box::new(self.next_expr(token)?))
+            }
+        };
+
+        let (inclusive, start) = match (self.next_token(), start) {
+            (
+                Some(Spanned {
+                    value: Token::Range { inclusive },
+                    ..
+                }),
+                start,
+            ) => (inclusive, start),
+            (Some(Spanned { value, .. }), Some(index)) if value == close => {
+                return Ok(Value::Index {
+                    expr: Box::new(expr),
+                    index,
+                })
+            }
+            _ => {
+                return Err(ParseError::Lazy(
+                    "Expected \"]\" or \"..\"."
+                        .to_owned()
+                        .span(self.end..self.end + 1),
+                ))
+            }
+        };
+
+        // `..=` needs an end, `..` may leave it out to slice until the end.
+        let end = match !inclusive && self.peek_is(&close) {
+            true => None,
+            false => {
+                let token = self.next_token_or("an expression")?;
+                Some(Box::new(self.next_expr(token)?))
+            }
+        };
+
+        self.expect_token(close, "\"]\"")?;
+        Ok(Value::Slice {
+            expr: Box::new(expr),
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn next_primary(&mut self, token: Spanned<Token>) -> Result<Expr, ParseError> {
+        match token {
+            Spanned {
+                start,
+                end,
+                value: Token::Ident(ident),
+            } => Ok(ExprTree::Value(Value::Ident(ident).span(start..end))),
+            Spanned {
+                start,
+                end,
+                value: Token::Literal { value, kind },
+            } => Ok(ExprTree::Value(
+                Value::Literal { value, kind }.span(start..end),
+            )),
+            Spanned {
+                start,
+                end,
+                value: Token::Format(parts),
+            } => Ok(ExprTree::Value(
+                Value::Format(next_format(parts)?).span(start..end),
+            )),
+            Spanned {
+                value:
+                    Token::Brace {
+                        open: true,
+                        kind: BraceKind::Smooth,
+                    },
+                ..
+            } => {
+                let token = self.next_token_or("an expression")?;
+                let expr = self.next_expr(token)?;
+                self.expect_token(
+                    Token::Brace {
+                        open: false,
+                        kind: BraceKind::Smooth,
+                    },
+                    "\")\"",
+                )?;
+
+                Ok(ExprTree::new_enclosed(expr))
+            }
+            Spanned {
+                start,
+                value:
+                    Token::Brace {
+                        open: true,
+                        kind: BraceKind::Square,
+                    },
+                ..
+            } => {
+                let value = self.next_array()?;
+                Ok(ExprTree::Value(value.span(start..self.end)))
+            }
+            Spanned {
+                start,
+                end,
+                value: Token::QuestionMark,
+            } => Ok(ExprTree::Value(Value::None.span(start..end))),
+            Spanned {
+                start,
+                value: Token::Keyword(Keyword::If),
+                ..
+            } => self.next_if(start),
+            Spanned {
+                start,
+                value: Token::Keyword(Keyword::Match),
+                ..
+            } => self.next_match(start),
+            Spanned {
+                start,
+                value: Token::Keyword(Keyword::Fn),
+                ..
+            } => {
+                self.expect_token(
+                    Token::Brace {
+                        open: true,
+                        kind: BraceKind::Smooth,
+                    },
+                    "\"(\"",
+                )?;
+
+                let args = self.next_fn_args()?;
+                let ty = self.next_return_ty()?;
+                let block = self.next_block()?;
+
+                Ok(ExprTree::Value(
+                    Value::Fn { args, ty, block }.span(start..self.end),
+                ))
+            }
+            Spanned { start, end, .. } => Err(ParseError::Lazy(
+                "Expected an expression.".to_owned().span(start..end),
+            )),
+        }
+    }
+
+    /// Parses the rest of an array literal like `[a, b, c]` or `[expr; len]` after the `[`.
+    fn next_array(&mut self) -> Result<Value, ParseError> {
+        let close = Token::Brace {
+            open: false,
+            kind: BraceKind::Square,
+        };
+
+        let mut exprs = Vec::new();
+        if self.peek_is(&close) {
+            _ = self.next_token();
+            return Ok(Value::Array(exprs));
+        }
+
+        loop {
+            let token = self.next_token_or("an expression")?;
+            exprs.push(self.next_expr(token)?);
+            match self.next_token() {
+                Some(Spanned {
+                    value: Token::Comma,
+                    ..
+                }) => {}
+                Some(Spanned {
+                    value: Token::SemiColon,
+                    ..
+                }) if exprs.len() == 1 => {
+                    let len = self.next_len()?;
+                    self.expect_token(close, "\"]\"")?;
+                    return Ok(Value::Repeat {
+                        expr: Box::new(exprs.remove(0)),
+                        len,
+                    });
+                }
+                Some(Spanned { value, .. }) if value == close => return Ok(Value::Array(exprs)),
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected \",\" or \"]\"."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses the rest of an `if` expression after the `if` keyword.
+    fn next_if(&mut self, start: usize) -> Result<Expr, ParseError> {
+        let token = self.next_token_or("a condition")?;
+        let condition = self.next_expr(token)?;
+        let block = self.next_block()?;
+
+        let else_block = if self.peek_is(&Token::Keyword(Keyword::Else)) {
+            _ = self.next_token();
+            if self.peek_is(&Token::Keyword(Keyword::If)) {
+                let Some(Spanned { start, .. }) = self.next_token() else {
+                    unreachable!()
+                };
+
+                Some(Block {
+                    statements: Vec::new(),
+                    trailing_expr: Some(Box::new(self.next_if(start)?)),
+                })
+            } else {
+                Some(self.next_block()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(ExprTree::Value(
+            Value::If {
+                condition: Box::new(condition),
+                block,
+                else_block,
+            }
+            .span(start..self.end),
+        ))
+    }
+
+    /// Parses the rest of a `match` expression after the `match` keyword.
+    fn next_match(&mut self, start: usize) -> Result<Expr, ParseError> {
+        let token = self.next_token_or("an expression")?;
+        let expr = self.next_expr(token)?;
+        self.expect_token(
+            Token::Brace {
+                open: true,
+                kind: BraceKind::Curly,
+            },
+            "\"{\"",
+        )?;
+
+        let close_curly = Token::Brace {
+            open: false,
+            kind: BraceKind::Curly,
+        };
+
+        let mut arms = Vec::new();
+        loop {
+            let token = self.next_token_or("a pattern or \"}\"")?;
+            if token.value == close_curly {
+                break;
+            }
+
+            let pattern = self.next_pattern(token)?;
+            self.expect_token(Token::FatArrow, "\"=>\"")?;
+
+            // Arms with a block body don't need a `,`.
+            if self.peek_is(&Token::Brace {
+                open: true,
+                kind: BraceKind::Curly,
+            }) {
+                let block = self.next_block()?;
+                arms.push(Arm { pattern, block });
+                if self.peek_is(&Token::Comma) {
+                    _ = self.next_token();
+                }
+
+                continue;
+            }
+
+            let token = self.next_token_or("an expression")?;
+            let block = Block {
+                statements: Vec::new(),
+                trailing_expr: Some(Box::new(self.next_expr(token)?)),
+            };
+
+            arms.push(Arm { pattern, block });
+            match self.next_token() {
+                Some(Spanned {
+                    value: Token::Comma,
+                    ..
+                }) => {}
+                Some(Spanned { value, .. }) if value == close_curly => break,
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected \",\" or \"}\"."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            }
+        }
+
+        Ok(ExprTree::Value(
+            Value::Match {
+                expr: Box::new(expr),
+                arms,
+            }
+            .span(start..self.end),
+        ))
+    }
+
+    fn next_pattern(&mut self, token: Spanned<Token>) -> Result<Spanned<Pattern>, ParseError> {
+        let Spanned { start, end, value } = token;
+        let pattern = match value {
+            Token::Underscore => Pattern::Wildcard,
+            Token::QuestionMark => Pattern::None,
+            Token::Literal { value, kind } => Pattern::Literal { value, kind },
+            Token::Ident(ident) if self.peek_is(&Token::Dot) => {
+                _ = self.next_token();
+                let variant = match self.next_token() {
+                    Some(Spanned {
+                        start,
+                        end,
+                        value: Token::Ident(variant),
+                    }) => variant.span(start..end),
+                    _ => {
+                        return Err(ParseError::Lazy(
+                            "Expected an identifier."
+                                .to_owned()
+                                .span(self.end..self.end + 1),
+                        ))
+                    }
+                };
+
+                let fields = match self.peek_is(&Token::Brace {
+                    open: true,
+                    kind: BraceKind::Smooth,
+                }) {
+                    true => {
+                        _ = self.next_token();
+                        self.next_pattern_args()?
+                    }
+                    false => Vec::new(),
+                };
+
+                Pattern::Variant {
+                    ty: ident.span(start..end),
+                    variant,
+                    fields,
+                }
+            }
+            Token::Ident(ident)
+                if self.peek_is(&Token::Brace {
+                    open: true,
+                    kind: BraceKind::Smooth,
+                }) =>
+            {
+                _ = self.next_token();
+                Pattern::Struct {
+                    ident: ident.span(start..end),
+                    fields: self.next_pattern_args()?,
+                }
+            }
+            Token::Ident(ident) => Pattern::Binding(ident),
+            _ => {
+                return Err(ParseError::Lazy(
+                    "Expected a pattern.".to_owned().span(start..end),
+                ))
+            }
+        };
+
+        Ok(pattern.span(start..self.end))
+    }
+
+    /// Parses comma separated patterns until a closing `)`.
+    fn next_pattern_args(&mut self) -> Result<Vec<PatternArg>, ParseError> {
+        let mut args = Vec::new();
+        loop {
+            let mut token = self.next_token_or("a pattern or \")\"")?;
+            if let Token::Brace {
+                open: false,
+                kind: BraceKind::Smooth,
+            } = token.value
+            {
+                return Ok(args);
+            }
+
+            let ident = match token {
+                Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                } if self.peek_is(&Token::Colon) => {
+                    _ = self.next_token();
+                    token = self.next_token_or("a pattern")?;
+                    Some(ident.span(start..end))
+                }
+                _ => None,
+            };
+
+            args.push(PatternArg {
+                ident,
+                pattern: self.next_pattern(token)?,
+            });
+            match self.next_token() {
+                Some(Spanned {
+                    value: Token::Comma,
+                    ..
+                }) => {}
+                Some(Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Smooth,
+                        },
+                    ..
+                }) => return Ok(args),
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected \",\" or \")\"."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses comma separated expressions until a closing `)`.
+    fn next_args(&mut self) -> Result<Vec<Arg>, ParseError> {
+        let mut args = Vec::new();
+        loop {
+            let mut token = self.next_token_or("an expression or \")\"")?;
+            if let Token::Brace {
+                open: false,
+                kind: BraceKind::Smooth,
+            } = token.value
+            {
+                return Ok(args);
+            }
+
+            let ident = match token {
+                Spanned {
+                    start,
+                    end,
+                    value: Token::Ident(ident),
+                } if self.peek_is(&Token::Colon) => {
+                    _ = self.next_token();
+                    token = self.next_token_or("an expression")?;
+                    Some(ident.span(start..end))
+                }
+                _ => None,
+            };
+
+            args.push(Arg {
+                ident,
+                expr: self.next_expr(token)?,
+            });
+            match self.next_token() {
+                Some(Spanned {
+                    value: Token::Comma,
+                    ..
+                }) => {}
+                Some(Spanned {
+                    value:
+                        Token::Brace {
+                            open: false,
+                            kind: BraceKind::Smooth,
+                        },
+                    ..
+                }) => return Ok(args),
+                _ => {
+                    return Err(ParseError::Lazy(
+                        "Expected \",\" or \")\"."
+                            .to_owned()
+                            .span(self.end..self.end + 1),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Spanned<Token>>> Iterator for ItemIter<I> {
+    type Item = Result<Spanned<Item>, Spanned<ParseError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.index;
+        let token = self.next_token()?;
+        Some(match self.next_item(token) {
+            Ok(item) => Ok(item.span(start..self.index)),
+            Err(error) => Err(error.span(start..self.index)),
+        })
+    }
+}
+
+/// Parses the expressions interpolated into a string literal, each of which has to use up all of
+/// its tokens.
+fn next_format(parts: Vec<token::FormatPart>) -> Result<Vec<FormatPart>, ParseError> {
+    let mut parsed = Vec::new();
+    for part in parts {
+        parsed.push(match part {
+            token::FormatPart::Literal(literal) => FormatPart::Literal(literal),
+            token::FormatPart::Expr(tokens) => {
+                let mut parser = parse(tokens);
+                let token = parser.next_token_or("an expression")?;
+                let expr = parser.next_expr(token)?;
+                if let Some(Spanned { start, end, .. }) = parser.next_token() {
+                    return Err(ParseError::Lazy(
+                        "Expected \"}\".".to_owned().span(start..end),
+                    ));
+                }
+
+                FormatPart::Expr(expr)
+            }
+        });
+    }
+
+    Ok(parsed)
 }
@@ -3,7 +3,7 @@ use std::{
     ops::{Deref, DerefMut, Range},
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Spanned<T> {
     pub start: usize,
     pub end: usize,
@@ -43,22 +43,66 @@ pub trait Span: Sized {
 
 impl<T> Span for T {}
 
-pub fn map_spans<'a, T, I, F>(
-    spans: impl IntoIterator<IntoIter = I>,
+/// Offsets at which the lines of a source start, to turn offsets into lines and columns.
+#[derive(Debug, Clone, Default)]
+pub struct Lines(Vec<usize>);
+
+impl Lines {
+    pub fn new(source: &str) -> Lines {
+        let starts = source.match_indices('\n').map(|(i, _)| i + 1);
+        Lines(std::iter::once(0).chain(starts).collect())
+    }
+
+    /// Returns the line and column of `offset`, both counting from 1.
+    pub fn locate(&self, offset: usize) -> (usize, usize) {
+        let line = self.0.partition_point(|&start| start <= offset);
+        (line, offset - self.0[line - 1] + 1)
+    }
+}
+
+/// Highlights the `spans` of `str` with `f`. Spans overlapping an earlier span, like an error
+/// nested in another error, or not falling on characters of `str` are left out.
+pub fn map_spans<'a, T: 'a>(
+    spans: impl IntoIterator<Item = &'a Spanned<T>>,
     str: &str,
-    mut f: F,
-) -> Option<String>
-where
-    T: 'a,
-    I: DoubleEndedIterator<Item = &'a Spanned<T>>,
-    F: FnMut(&str) -> String,
-{
-    let (res, index) = spans
-        .into_iter()
-        .try_fold((String::new(), 0), |(acc, index), span| {
-            let (prev, fmt) = (str.get(index..span.start)?, str.get(span.range())?);
-            Some((format!("{acc}{prev}{}", f(fmt)), span.end))
-        })?;
-
-    Some(format!("{res}{}", str.get(index..)?))
+    mut f: impl FnMut(&str) -> String,
+) -> String {
+    let (mut res, mut index) = (String::new(), 0);
+    for span in spans {
+        if span.start < index {
+            continue;
+        }
+
+        let (Some(prev), Some(fmt)) = (str.get(index..span.start), str.get(span.range())) else {
+            continue;
+        };
+
+        res.push_str(prev);
+        res.push_str(&f(fmt));
+        index = span.end;
+    }
+
+    res.push_str(&str[index..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_spans_are_left_out() {
+        let spans = [().span(0..10), ().span(7..8), ().span(11..12)];
+        let mapped = map_spans(&spans, "if a { b } c", |str| format!("[{str}]"));
+        assert_eq!(mapped, "[if a { b }] [c]");
+    }
+
+    #[test]
+    fn spans_outside_the_source_are_left_out() {
+        let spans = [().span(0..2), ().span(5..40)];
+        assert_eq!(
+            map_spans(&spans, "ab cd", |str| format!("[{str}]")),
+            "[ab] cd"
+        );
+    }
 }
@@ -0,0 +1,132 @@
+//! Runs the examples and the samples with every backend, which have to print the output and exit
+//! with the code expected in `tests/expected`. Bytecode also runs after a round trip through a
+//! file.
+
+use std::{
+    env, fs,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+/// Arguments running a program with the generated `C`, the interpreter and the virtual machine.
+const BACKENDS: [&[&str]; 3] = [&["run"], &["run", "--interp"], &["run", "--bytecode"]];
+
+#[derive(Debug, PartialEq)]
+struct Run {
+    stdout: String,
+    stderr: String,
+    code: i32,
+}
+
+/// Runs the program at `path` relative to the crate with the backend selected by `args`.
+fn run(args: &[&str], path: &Path) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_razor"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .expect("Couldn't run razor.");
+
+    // The interpreter and the virtual machine abort themselves, while `run` passes on the code of
+    // the aborted executable like a shell.
+    let signal = output.status.signal().map(|signal| 128 + signal);
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        code: output.status.code().or(signal).unwrap_or(1),
+    }
+}
+
+/// Returns the programs in `dir` relative to the crate.
+fn programs(dir: &str) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join(dir))
+        .expect("Couldn't read the directory.")
+        .map(|entry| entry.expect("Couldn't read the directory.").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "rzr"))
+        .map(|path| Path::new(dir).join(path.file_name().expect("Programs are files.")))
+        .collect::<Vec<_>>();
+
+    paths.sort();
+    paths
+}
+
+/// Returns the exit code and the output expected of the program at `path`. The expectation starts
+/// with a line holding the code, the output follows it as is.
+fn expected(path: &Path) -> (i32, String) {
+    let stem = path.file_stem().expect("Programs are files.");
+    let expected = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/expected")
+        .join(stem)
+        .with_extension("out");
+
+    let expected = fs::read_to_string(&expected)
+        .unwrap_or_else(|_| panic!("{} has no expected output.", path.display()));
+
+    let (code, stdout) = expected.split_once('\n').unwrap_or((&expected, ""));
+    let code = code
+        .strip_prefix("exit ")
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_else(|| {
+            panic!(
+                "The expectation of {} starts with \"exit\".",
+                path.display()
+            )
+        });
+
+    (code, stdout.to_owned())
+}
+
+#[test]
+fn backends_run_as_expected() {
+    let dir = env::temp_dir().join(format!("razor-test-{}", process::id()));
+    fs::create_dir_all(&dir).expect("Couldn't create the build directory.");
+    for path in programs("examples")
+        .into_iter()
+        .chain(programs("tests/samples"))
+    {
+        let (code, stdout) = expected(&path);
+        let first = run(BACKENDS[0], &path);
+        assert_eq!(
+            (first.code, &first.stdout),
+            (code, &stdout),
+            "\"razor run\" doesn't run {} as expected.\n{}",
+            path.display(),
+            first.stderr
+        );
+
+        for args in BACKENDS[1..].iter() {
+            assert_eq!(
+                run(args, &path),
+                first,
+                "\"razor {}\" differs from \"razor run\" on {}.",
+                args.join(" "),
+                path.display()
+            );
+        }
+
+        let module = dir.join(
+            path.with_extension("rzb")
+                .file_name()
+                .expect("Programs are files."),
+        );
+        let module = module.to_str().expect("The path is UTF-8.");
+        let build = run(&["build", "--bytecode", "-o", module], &path);
+        assert_eq!(
+            build.code,
+            0,
+            "Couldn't build {}.\n{}",
+            path.display(),
+            build.stderr
+        );
+        assert_eq!(
+            run(&["run"], Path::new(module)),
+            first,
+            "The bytecode file of {} differs from \"razor run\".",
+            path.display()
+        );
+    }
+
+    _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,127 @@
+//! Checks the `C` generated for libraries and for debugging, which the backends don't cover.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// Returns a new directory named after `test`, which the test builds in.
+fn dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("razor-{test}-{}", process::id()));
+    fs::create_dir_all(&dir).expect("Couldn't create the build directory.");
+    dir
+}
+
+/// Runs `razor` with `args` in `dir` and returns its standard output, panicking if it fails.
+fn razor(args: &[&str], dir: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_razor"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("Couldn't run razor.");
+
+    assert!(
+        output.status.success(),
+        "\"razor {}\" failed.\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn libraries_link_with_c() {
+    let dir = dir("lib");
+    fs::write(
+        dir.join("geometry.rzr"),
+        "Point := struct (
+            pub x: int,
+            pub y: int,
+        )
+
+        pub add := fn (a: int, b: int) -> int {
+            return a + b;
+        }
+
+        pub origin := fn () -> Point {
+            return Point(x: 3, y: 4);
+        }
+
+        pub find := fn (points: int, x: int) -> int? {
+            if x < points {
+                return x;
+            }
+
+            return ?;
+        }
+        ",
+    )
+    .expect("Couldn't write the library.");
+
+    fs::write(
+        dir.join("host.c"),
+        "#include \"lib.h\"\n\
+         \n\
+         int main(void) {\n\
+         \x20   Point point = origin();\n\
+         \x20   optional_int found = find(2, 1);\n\
+         \x20   optional_int missing = find(2, 5);\n\
+         \x20   if (!found.some || missing.some) {\n\
+         \x20       return 1;\n\
+         \x20   }\n\
+         \x20   return (int)add(point.x, point.y + found.value);\n\
+         }\n",
+    )
+    .expect("Couldn't write the host.");
+
+    razor(&["build", "--lib", "geometry.rzr", "-o", "lib"], &dir);
+    let status = Command::new("cc")
+        .current_dir(&dir)
+        .args(["-Wall", "-Werror", "host.c", "lib.o", "-o", "host"])
+        .status()
+        .expect("Couldn't run the C compiler.");
+    assert!(
+        status.success(),
+        "The host doesn't compile with the header."
+    );
+
+    let status = Command::new(dir.join("host"))
+        .status()
+        .expect("Couldn't run the host.");
+    assert_eq!(status.code(), Some(8));
+
+    _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn debug_info_refers_to_the_source() {
+    let dir = dir("line");
+    let name = "a \"quoted\" \\ name.rzr";
+    fs::write(
+        dir.join(name),
+        "main := fn () -> int {\n    println(\"Hello\");\n    return 0;\n}\n",
+    )
+    .expect("Couldn't write the program.");
+
+    let code = razor(&["emit", "-g", name], &dir);
+    let path = "\"a \\\"quoted\\\" \\\\ name.rzr\"";
+    assert!(
+        code.contains(&format!("#line 2 {path}\n")),
+        "The println has no line directive.\n{code}"
+    );
+    assert!(
+        code.contains(&format!("#line 3 {path}\n")),
+        "The return has no line directive.\n{code}"
+    );
+
+    // The compiler has to accept the directives too.
+    razor(&["build", "-g", name, "-o", "program"], &dir);
+    let output = Command::new(dir.join("program"))
+        .output()
+        .expect("Couldn't run the program.");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hello\n");
+
+    _ = fs::remove_dir_all(&dir);
+}